@@ -0,0 +1,200 @@
+#![feature(test)]
+
+extern crate test;
+
+use asm::lex::{
+	keyword,
+	BranchInstruction,
+	CsrInstruction,
+	CsriInstruction,
+	InstToken,
+	Lexer,
+	LoadInstruction,
+	MdrInstruction,
+	RegToken,
+	RriInstruction,
+	RrrInstruction,
+	StoreInstruction,
+	TokenType,
+};
+use test::{black_box, Bencher};
+
+/// Identifiers as they appear in source code, mostly keywords in varying
+/// case along with a few labels
+const IDENTIFIERS: [&str; 16] = [
+	"addi", "ADD", "sw", "lw", "bne", "mulhsu", "csrrw", "jal", "t0", "a0", "sp", "ra", "zero",
+	"r31", "loop", "function",
+];
+
+/// Source code consisting of many instructions, mixing register names,
+/// ABI aliases, and mnemonic casing
+fn instruction_heavy_source() -> String {
+	let lines = [
+		"\taddi r1, r0, 5",
+		"\tADD t0, a0, a1",
+		"\tsw [sp + 4], ra",
+		"\tlw s0, [sp + 4]",
+		"\tbne t1, zero, loop",
+		"\tmulhsu a2, a3, a4",
+		"\tcsrrw r5, r6, 0xC00",
+		"\tjal ra, function",
+	];
+
+	let mut source = "#SECTION .text\n".to_string();
+	for _ in 0..1000 {
+		for line in lines {
+			source.push_str(line);
+			source.push('\n');
+		}
+	}
+
+	source
+}
+
+#[bench]
+fn lex_instructions(b: &mut Bencher) {
+	let source = instruction_heavy_source();
+
+	b.iter(|| Lexer::new("bench.asm", &source).count());
+}
+
+#[bench]
+fn keyword_table_lookup(b: &mut Bencher) {
+	b.iter(|| IDENTIFIERS.iter().filter_map(|id| keyword(black_box(id))).count());
+}
+
+#[bench]
+fn keyword_match(b: &mut Bencher) {
+	b.iter(|| IDENTIFIERS.iter().filter_map(|id| match_keyword(black_box(id))).count());
+}
+
+/// The keyword matcher the lexer used before it switched to a table lookup,
+/// kept to compare the two
+fn match_keyword(id: &str) -> Option<TokenType<'static>> {
+	match &id.to_lowercase()[..] {
+		"addi" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Addi))),
+		"andi" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Andi))),
+		"ori" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Ori))),
+		"xori" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Xori))),
+		"lsli" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Lsli))),
+		"lsri" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Lsri))),
+		"asri" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Asri))),
+		"slti" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Slti))),
+		"sltiu" => Some(TokenType::Inst(InstToken::Rri(RriInstruction::Sltiu))),
+		"add" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Add))),
+		"sub" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Sub))),
+		"and" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::And))),
+		"or" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Or))),
+		"xor" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Xor))),
+		"lsl" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Lsl))),
+		"lsr" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Lsr))),
+		"asr" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Asr))),
+		"slt" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Slt))),
+		"sltu" => Some(TokenType::Inst(InstToken::Rrr(RrrInstruction::Sltu))),
+		"lw" => Some(TokenType::Inst(InstToken::Load(LoadInstruction::Lw))),
+		"lh" => Some(TokenType::Inst(InstToken::Load(LoadInstruction::Lh))),
+		"lhu" => Some(TokenType::Inst(InstToken::Load(LoadInstruction::Lhu))),
+		"lb" => Some(TokenType::Inst(InstToken::Load(LoadInstruction::Lb))),
+		"lbu" => Some(TokenType::Inst(InstToken::Load(LoadInstruction::Lbu))),
+		"sw" => Some(TokenType::Inst(InstToken::Store(StoreInstruction::Sw))),
+		"sh" => Some(TokenType::Inst(InstToken::Store(StoreInstruction::Sh))),
+		"sb" => Some(TokenType::Inst(InstToken::Store(StoreInstruction::Sb))),
+		"lui" => Some(TokenType::Inst(InstToken::Lui)),
+		"auipc" => Some(TokenType::Inst(InstToken::Auipc)),
+		"beq" => Some(TokenType::Inst(InstToken::Branch(BranchInstruction::Beq))),
+		"bne" => Some(TokenType::Inst(InstToken::Branch(BranchInstruction::Bne))),
+		"blt" => Some(TokenType::Inst(InstToken::Branch(BranchInstruction::Blt))),
+		"bltu" => Some(TokenType::Inst(InstToken::Branch(BranchInstruction::Bltu))),
+		"bge" => Some(TokenType::Inst(InstToken::Branch(BranchInstruction::Bge))),
+		"bgeu" => Some(TokenType::Inst(InstToken::Branch(BranchInstruction::Bgeu))),
+		"jal" => Some(TokenType::Inst(InstToken::Jal)),
+		"jalr" => Some(TokenType::Inst(InstToken::Jalr)),
+		"ecall" => Some(TokenType::Inst(InstToken::Ecall)),
+		"ebreak" => Some(TokenType::Inst(InstToken::Ebreak)),
+		"fence" => Some(TokenType::Inst(InstToken::Fence)),
+		"fence.tso" => Some(TokenType::Inst(InstToken::FenceTso)),
+		"fence.i" => Some(TokenType::Inst(InstToken::Fencei)),
+		"csrrw" => Some(TokenType::Inst(InstToken::Csr(CsrInstruction::Csrrw))),
+		"csrrs" => Some(TokenType::Inst(InstToken::Csr(CsrInstruction::Csrrs))),
+		"csrrc" => Some(TokenType::Inst(InstToken::Csr(CsrInstruction::Csrrc))),
+		"csrrwi" => Some(TokenType::Inst(InstToken::Csri(CsriInstruction::Csrrwi))),
+		"csrrsi" => Some(TokenType::Inst(InstToken::Csri(CsriInstruction::Csrrsi))),
+		"csrrci" => Some(TokenType::Inst(InstToken::Csri(CsriInstruction::Csrrci))),
+		"mul" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mul))),
+		"mulh" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mulh))),
+		"mulhu" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mulhu))),
+		"mulhsu" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mulhsu))),
+		"div" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Div))),
+		"divu" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Divu))),
+		"rem" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Rem))),
+		"remu" => Some(TokenType::Inst(InstToken::Mdr(MdrInstruction::Remu))),
+
+		"r0" => Some(TokenType::Reg(RegToken::R0)),
+		"r1" => Some(TokenType::Reg(RegToken::R1)),
+		"r2" => Some(TokenType::Reg(RegToken::R2)),
+		"r3" => Some(TokenType::Reg(RegToken::R3)),
+		"r4" => Some(TokenType::Reg(RegToken::R4)),
+		"r5" => Some(TokenType::Reg(RegToken::R5)),
+		"r6" => Some(TokenType::Reg(RegToken::R6)),
+		"r7" => Some(TokenType::Reg(RegToken::R7)),
+		"r8" => Some(TokenType::Reg(RegToken::R8)),
+		"r9" => Some(TokenType::Reg(RegToken::R9)),
+		"r10" => Some(TokenType::Reg(RegToken::R10)),
+		"r11" => Some(TokenType::Reg(RegToken::R11)),
+		"r12" => Some(TokenType::Reg(RegToken::R12)),
+		"r13" => Some(TokenType::Reg(RegToken::R13)),
+		"r14" => Some(TokenType::Reg(RegToken::R14)),
+		"r15" => Some(TokenType::Reg(RegToken::R15)),
+		"r16" => Some(TokenType::Reg(RegToken::R16)),
+		"r17" => Some(TokenType::Reg(RegToken::R17)),
+		"r18" => Some(TokenType::Reg(RegToken::R18)),
+		"r19" => Some(TokenType::Reg(RegToken::R19)),
+		"r20" => Some(TokenType::Reg(RegToken::R20)),
+		"r21" => Some(TokenType::Reg(RegToken::R21)),
+		"r22" => Some(TokenType::Reg(RegToken::R22)),
+		"r23" => Some(TokenType::Reg(RegToken::R23)),
+		"r24" => Some(TokenType::Reg(RegToken::R24)),
+		"r25" => Some(TokenType::Reg(RegToken::R25)),
+		"r26" => Some(TokenType::Reg(RegToken::R26)),
+		"r27" => Some(TokenType::Reg(RegToken::R27)),
+		"r28" => Some(TokenType::Reg(RegToken::R28)),
+		"r29" => Some(TokenType::Reg(RegToken::R29)),
+		"r30" => Some(TokenType::Reg(RegToken::R30)),
+		"r31" => Some(TokenType::Reg(RegToken::R31)),
+
+		"zero" => Some(TokenType::Reg(RegToken::R0)),
+		"ra" => Some(TokenType::Reg(RegToken::R1)),
+		"sp" => Some(TokenType::Reg(RegToken::R2)),
+		"gp" => Some(TokenType::Reg(RegToken::R3)),
+		"tp" => Some(TokenType::Reg(RegToken::R4)),
+		"fp" => Some(TokenType::Reg(RegToken::R8)),
+		"a0" => Some(TokenType::Reg(RegToken::R10)),
+		"a1" => Some(TokenType::Reg(RegToken::R11)),
+		"a2" => Some(TokenType::Reg(RegToken::R12)),
+		"a3" => Some(TokenType::Reg(RegToken::R13)),
+		"a4" => Some(TokenType::Reg(RegToken::R14)),
+		"a5" => Some(TokenType::Reg(RegToken::R15)),
+		"a6" => Some(TokenType::Reg(RegToken::R16)),
+		"a7" => Some(TokenType::Reg(RegToken::R17)),
+		"s0" => Some(TokenType::Reg(RegToken::R8)),
+		"s1" => Some(TokenType::Reg(RegToken::R9)),
+		"s2" => Some(TokenType::Reg(RegToken::R18)),
+		"s3" => Some(TokenType::Reg(RegToken::R19)),
+		"s4" => Some(TokenType::Reg(RegToken::R20)),
+		"s5" => Some(TokenType::Reg(RegToken::R21)),
+		"s6" => Some(TokenType::Reg(RegToken::R22)),
+		"s7" => Some(TokenType::Reg(RegToken::R23)),
+		"s8" => Some(TokenType::Reg(RegToken::R24)),
+		"s9" => Some(TokenType::Reg(RegToken::R25)),
+		"s10" => Some(TokenType::Reg(RegToken::R26)),
+		"s11" => Some(TokenType::Reg(RegToken::R27)),
+		"t0" => Some(TokenType::Reg(RegToken::R5)),
+		"t1" => Some(TokenType::Reg(RegToken::R6)),
+		"t2" => Some(TokenType::Reg(RegToken::R7)),
+		"t3" => Some(TokenType::Reg(RegToken::R28)),
+		"t4" => Some(TokenType::Reg(RegToken::R29)),
+		"t5" => Some(TokenType::Reg(RegToken::R30)),
+		"t6" => Some(TokenType::Reg(RegToken::R31)),
+		_ => None,
+	}
+}
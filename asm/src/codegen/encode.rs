@@ -0,0 +1,361 @@
+//! [`Generator`] functions to encode [`Instruction`]s into machine code
+
+use super::Generator;
+use crate::error::CodegenError;
+use crate::lex::RegToken;
+use crate::parse::ast::{Address, Instruction, OffsetOperator, OrderingTarget};
+
+const OP_LOAD: u32 = 0b000_0011;
+const OP_MISC_MEM: u32 = 0b000_1111;
+const OP_IMM: u32 = 0b001_0011;
+const OP_AUIPC: u32 = 0b001_0111;
+const OP_STORE: u32 = 0b010_0011;
+const OP_OP: u32 = 0b011_0011;
+const OP_LUI: u32 = 0b011_0111;
+const OP_BRANCH: u32 = 0b110_0011;
+const OP_JALR: u32 = 0b110_0111;
+const OP_JAL: u32 = 0b110_1111;
+const OP_SYSTEM: u32 = 0b111_0011;
+
+const FUNCT7_ALT: u32 = 0b010_0000;
+const FUNCT7_MULDIV: u32 = 0b000_0001;
+
+impl<'s> Generator<'s> {
+	/// Encode a single [`Instruction`] into its 32-bit machine code
+	/// representation
+	///
+	/// Immediates are truncated to the width of the field they are encoded
+	/// into
+	///
+	/// Every [`Instruction`] is matched explicitly, without a wildcard arm,
+	/// so that new instructions can not be added without an encoding
+	pub fn encode(&self, inst: &Instruction<'s>) -> Result<u32, CodegenError> {
+		let word = match inst {
+			Instruction::Addi { dest, src, imm } => {
+				i_type(self.value(imm)?, *src, 0b000, *dest, OP_IMM)
+			},
+			Instruction::Slti { dest, src, imm } => {
+				i_type(self.value(imm)?, *src, 0b010, *dest, OP_IMM)
+			},
+			Instruction::Sltiu { dest, src, imm } => {
+				i_type(self.value(imm)?, *src, 0b011, *dest, OP_IMM)
+			},
+			Instruction::Andi { dest, src, imm } => {
+				i_type(self.value(imm)?, *src, 0b111, *dest, OP_IMM)
+			},
+			Instruction::Ori { dest, src, imm } => {
+				i_type(self.value(imm)?, *src, 0b110, *dest, OP_IMM)
+			},
+			Instruction::Xori { dest, src, imm } => {
+				i_type(self.value(imm)?, *src, 0b100, *dest, OP_IMM)
+			},
+			Instruction::Lsli { dest, src, imm } => {
+				i_type(self.value(imm)? & 0x1F, *src, 0b001, *dest, OP_IMM)
+			},
+			Instruction::Lsri { dest, src, imm } => {
+				i_type(self.value(imm)? & 0x1F, *src, 0b101, *dest, OP_IMM)
+			},
+			Instruction::Asri { dest, src, imm } => {
+				let shamt = (self.value(imm)? & 0x1F) | ((FUNCT7_ALT as i32) << 5);
+				i_type(shamt, *src, 0b101, *dest, OP_IMM)
+			},
+
+			Instruction::Add { dest, src1, src2 } => r_type(0, *src2, *src1, 0b000, *dest, OP_OP),
+			Instruction::Sub { dest, src1, src2 } => {
+				r_type(FUNCT7_ALT, *src2, *src1, 0b000, *dest, OP_OP)
+			},
+			Instruction::Lsl { dest, src1, src2 } => r_type(0, *src2, *src1, 0b001, *dest, OP_OP),
+			Instruction::Slt { dest, src1, src2 } => r_type(0, *src2, *src1, 0b010, *dest, OP_OP),
+			Instruction::Sltu { dest, src1, src2 } => r_type(0, *src2, *src1, 0b011, *dest, OP_OP),
+			Instruction::Xor { dest, src1, src2 } => r_type(0, *src2, *src1, 0b100, *dest, OP_OP),
+			Instruction::Lsr { dest, src1, src2 } => r_type(0, *src2, *src1, 0b101, *dest, OP_OP),
+			Instruction::Asr { dest, src1, src2 } => {
+				r_type(FUNCT7_ALT, *src2, *src1, 0b101, *dest, OP_OP)
+			},
+			Instruction::Or { dest, src1, src2 } => r_type(0, *src2, *src1, 0b110, *dest, OP_OP),
+			Instruction::And { dest, src1, src2 } => r_type(0, *src2, *src1, 0b111, *dest, OP_OP),
+
+			Instruction::Lui { dest, imm } => u_type(self.value(imm)?, *dest, OP_LUI),
+			Instruction::Auipc { dest, imm } => u_type(self.value(imm)?, *dest, OP_AUIPC),
+
+			Instruction::Jal { dest, offset } => j_type(self.value(offset)?, *dest, OP_JAL),
+			Instruction::Jalr { dest, base, offset } => {
+				i_type(self.value(offset)?, *base, 0b000, *dest, OP_JALR)
+			},
+
+			Instruction::Beq { src1, src2, offset } => {
+				b_type(self.value(offset)?, *src2, *src1, 0b000, OP_BRANCH)
+			},
+			Instruction::Bne { src1, src2, offset } => {
+				b_type(self.value(offset)?, *src2, *src1, 0b001, OP_BRANCH)
+			},
+			Instruction::Blt { src1, src2, offset } => {
+				b_type(self.value(offset)?, *src2, *src1, 0b100, OP_BRANCH)
+			},
+			Instruction::Bge { src1, src2, offset } => {
+				b_type(self.value(offset)?, *src2, *src1, 0b101, OP_BRANCH)
+			},
+			Instruction::Bltu { src1, src2, offset } => {
+				b_type(self.value(offset)?, *src2, *src1, 0b110, OP_BRANCH)
+			},
+			Instruction::Bgeu { src1, src2, offset } => {
+				b_type(self.value(offset)?, *src2, *src1, 0b111, OP_BRANCH)
+			},
+
+			Instruction::Lb { dest, addr } => {
+				i_type(self.address_offset(addr)?, addr.base, 0b000, *dest, OP_LOAD)
+			},
+			Instruction::Lh { dest, addr } => {
+				i_type(self.address_offset(addr)?, addr.base, 0b001, *dest, OP_LOAD)
+			},
+			Instruction::Lw { dest, addr } => {
+				i_type(self.address_offset(addr)?, addr.base, 0b010, *dest, OP_LOAD)
+			},
+			Instruction::Lbu { dest, addr } => {
+				i_type(self.address_offset(addr)?, addr.base, 0b100, *dest, OP_LOAD)
+			},
+			Instruction::Lhu { dest, addr } => {
+				i_type(self.address_offset(addr)?, addr.base, 0b101, *dest, OP_LOAD)
+			},
+
+			Instruction::Sb { dest, src } => {
+				s_type(self.address_offset(dest)?, *src, dest.base, 0b000, OP_STORE)
+			},
+			Instruction::Sh { dest, src } => {
+				s_type(self.address_offset(dest)?, *src, dest.base, 0b001, OP_STORE)
+			},
+			Instruction::Sw { dest, src } => {
+				s_type(self.address_offset(dest)?, *src, dest.base, 0b010, OP_STORE)
+			},
+
+			Instruction::Fence { pred, succ } => fence(0b0000, *pred, *succ),
+			Instruction::FenceTso { pred, succ } => fence(0b1000, *pred, *succ),
+
+			Instruction::Ecall => i_type(0, RegToken::R0, 0b000, RegToken::R0, OP_SYSTEM),
+			Instruction::Ebreak => i_type(1, RegToken::R0, 0b000, RegToken::R0, OP_SYSTEM),
+
+			Instruction::Fencei => i_type(0, RegToken::R0, 0b001, RegToken::R0, OP_MISC_MEM),
+
+			Instruction::Csrrw { dest, src, target } => {
+				i_type(self.value(target)?, *src, 0b001, *dest, OP_SYSTEM)
+			},
+			Instruction::Csrrs { dest, src, target } => {
+				i_type(self.value(target)?, *src, 0b010, *dest, OP_SYSTEM)
+			},
+			Instruction::Csrrc { dest, src, target } => {
+				i_type(self.value(target)?, *src, 0b011, *dest, OP_SYSTEM)
+			},
+			Instruction::Csrrwi { dest, src, target } => {
+				csri_type(self.value(target)?, self.value(src)?, 0b101, *dest)
+			},
+			Instruction::Csrrsi { dest, src, target } => {
+				csri_type(self.value(target)?, self.value(src)?, 0b110, *dest)
+			},
+			Instruction::Csrrci { dest, src, target } => {
+				csri_type(self.value(target)?, self.value(src)?, 0b111, *dest)
+			},
+
+			Instruction::Mul { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b000, *dest, OP_OP)
+			},
+			Instruction::Mulh { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b001, *dest, OP_OP)
+			},
+			Instruction::Mulhsu { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b010, *dest, OP_OP)
+			},
+			Instruction::Mulhu { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b011, *dest, OP_OP)
+			},
+			Instruction::Div { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b100, *dest, OP_OP)
+			},
+			Instruction::Divu { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b101, *dest, OP_OP)
+			},
+			Instruction::Rem { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b110, *dest, OP_OP)
+			},
+			Instruction::Remu { dest, src1, src2 } => {
+				r_type(FUNCT7_MULDIV, *src2, *src1, 0b111, *dest, OP_OP)
+			},
+		};
+
+		Ok(word)
+	}
+
+	/// Get the signed offset of an [`Address`], or 0 if it has none
+	fn address_offset(&self, addr: &Address<'s>) -> Result<i32, CodegenError> {
+		match &addr.offset {
+			Some(offset) => {
+				let value = self.value(&offset.offset)?;
+
+				match offset.op {
+					OffsetOperator::Plus => Ok(value),
+					OffsetOperator::Minus => Ok(value.wrapping_neg()),
+				}
+			},
+			None => Ok(0),
+		}
+	}
+}
+
+/// Describe a 32-bit instruction word by splitting it into the fields of its
+/// RISC-V instruction format, based on its opcode
+///
+/// The word is shown in binary with the fields separated by underscores,
+/// followed by the value of every field from the opcode up. Immediates are
+/// shown as the value they encode rather than as their scattered bits, and
+/// words with an unknown opcode are shown as plain binary
+///
+/// ```rust
+/// use asm::codegen::describe_encoding;
+///
+/// assert_eq!(
+///     describe_encoding(0x003100B3),
+///     "0b0000000_00011_00010_000_00001_0110011  (opcode=0b0110011, rd=1, funct3=0b000, rs1=2, \
+///      rs2=3, funct7=0b0000000)"
+/// );
+/// ```
+pub fn describe_encoding(word: u32) -> String {
+	let bits = |hi: u32, lo: u32| (word >> lo) & ((1 << (hi - lo + 1)) - 1);
+	let opcode = bits(6, 0);
+	let rd = format!("rd={}", bits(11, 7));
+	let funct3 = format!("funct3={:#05b}", bits(14, 12));
+	let rs1 = format!("rs1={}", bits(19, 15));
+	let rs2 = format!("rs2={}", bits(24, 20));
+
+	let (widths, fields): (&[usize], Vec<String>) = match opcode {
+		OP_OP => {
+			let funct7 = format!("funct7={:#09b}", bits(31, 25));
+			(&[7, 5, 5, 3, 5, 7], vec![rd, funct3, rs1, rs2, funct7])
+		},
+		OP_LOAD | OP_MISC_MEM | OP_IMM | OP_JALR | OP_SYSTEM => {
+			let imm = format!("imm={}", word as i32 >> 20);
+			(&[12, 5, 3, 5, 7], vec![rd, funct3, rs1, imm])
+		},
+		OP_STORE => {
+			let imm = ((word as i32 >> 25) << 5) | bits(11, 7) as i32;
+			(&[7, 5, 5, 3, 5, 7], vec![funct3, rs1, rs2, format!("imm={}", imm)])
+		},
+		OP_BRANCH => {
+			let imm = ((word as i32 >> 31) << 12)
+				| (bits(7, 7) << 11 | bits(30, 25) << 5 | bits(11, 8) << 1) as i32;
+			(&[7, 5, 5, 3, 5, 7], vec![funct3, rs1, rs2, format!("imm={}", imm)])
+		},
+		OP_LUI | OP_AUIPC => (&[20, 5, 7], vec![rd, format!("imm={:#07x}", bits(31, 12))]),
+		OP_JAL => {
+			let imm = ((word as i32 >> 31) << 20)
+				| (bits(19, 12) << 12 | bits(20, 20) << 11 | bits(30, 21) << 1) as i32;
+			(&[20, 5, 7], vec![rd, format!("imm={}", imm)])
+		},
+		_ => return format!("{:#034b}", word),
+	};
+
+	let mut hi = 32;
+	let groups: Vec<String> = widths
+		.iter()
+		.map(|&width| {
+			hi -= width;
+			format!("{:0width$b}", word >> hi & ((1u64 << width) - 1) as u32, width = width)
+		})
+		.collect();
+
+	let fields: Vec<String> =
+		std::iter::once(format!("opcode={:#09b}", opcode)).chain(fields).collect();
+
+	format!("0b{}  ({})", groups.join("_"), fields.join(", "))
+}
+
+/// The 5 bit register specifier of a [`RegToken`]
+fn reg(r: RegToken) -> u32 { r.index() as u32 }
+
+fn r_type(
+	funct7: u32,
+	rs2: RegToken,
+	rs1: RegToken,
+	funct3: u32,
+	rd: RegToken,
+	opcode: u32,
+) -> u32 {
+	(funct7 << 25) | (reg(rs2) << 20) | (reg(rs1) << 15) | (funct3 << 12) | (reg(rd) << 7) | opcode
+}
+
+fn i_type(imm: i32, rs1: RegToken, funct3: u32, rd: RegToken, opcode: u32) -> u32 {
+	((imm as u32 & 0xFFF) << 20) | (reg(rs1) << 15) | (funct3 << 12) | (reg(rd) << 7) | opcode
+}
+
+fn s_type(imm: i32, rs2: RegToken, rs1: RegToken, funct3: u32, opcode: u32) -> u32 {
+	let imm = imm as u32;
+
+	(((imm >> 5) & 0x7F) << 25)
+		| (reg(rs2) << 20)
+		| (reg(rs1) << 15)
+		| (funct3 << 12)
+		| ((imm & 0x1F) << 7)
+		| opcode
+}
+
+fn b_type(imm: i32, rs2: RegToken, rs1: RegToken, funct3: u32, opcode: u32) -> u32 {
+	let imm = imm as u32;
+
+	(((imm >> 12) & 0x1) << 31)
+		| (((imm >> 5) & 0x3F) << 25)
+		| (reg(rs2) << 20)
+		| (reg(rs1) << 15)
+		| (funct3 << 12)
+		| (((imm >> 1) & 0xF) << 8)
+		| (((imm >> 11) & 0x1) << 7)
+		| opcode
+}
+
+/// Encode a U-type instruction, `imm` holds the upper 20 bits of the value
+fn u_type(imm: i32, rd: RegToken, opcode: u32) -> u32 {
+	((imm as u32 & 0xF_FFFF) << 12) | (reg(rd) << 7) | opcode
+}
+
+fn j_type(imm: i32, rd: RegToken, opcode: u32) -> u32 {
+	let imm = imm as u32;
+
+	(((imm >> 20) & 0x1) << 31)
+		| (((imm >> 1) & 0x3FF) << 21)
+		| (((imm >> 11) & 0x1) << 20)
+		| (((imm >> 12) & 0xFF) << 12)
+		| (reg(rd) << 7)
+		| opcode
+}
+
+/// Encode a CSR immediate instruction, the immediate is stored in the `rs1`
+/// field
+fn csri_type(csr: i32, uimm: i32, funct3: u32, rd: RegToken) -> u32 {
+	((csr as u32 & 0xFFF) << 20)
+		| ((uimm as u32 & 0x1F) << 15)
+		| (funct3 << 12)
+		| (reg(rd) << 7)
+		| OP_SYSTEM
+}
+
+/// Encode a fence instruction with the given fence mode
+fn fence(fm: u32, pred: OrderingTarget, succ: OrderingTarget) -> u32 {
+	(fm << 28) | (ordering_bits(pred) << 24) | (ordering_bits(succ) << 20) | OP_MISC_MEM
+}
+
+/// Convert an [`OrderingTarget`] into the 4 bit `IORW` field of a fence
+fn ordering_bits(target: OrderingTarget) -> u32 {
+	let mut bits = 0;
+
+	if target.contains(OrderingTarget::I) {
+		bits |= 0b1000;
+	}
+	if target.contains(OrderingTarget::O) {
+		bits |= 0b0100;
+	}
+	if target.contains(OrderingTarget::R) {
+		bits |= 0b0010;
+	}
+	if target.contains(OrderingTarget::W) {
+		bits |= 0b0001;
+	}
+
+	bits
+}
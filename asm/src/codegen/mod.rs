@@ -0,0 +1,553 @@
+//! # Code Generator
+//!
+//! The code generator is responsible for converting a folded AST (see
+//! [`ast`](crate::parse::ast)) into an executable [`Image`]
+//!
+//! Every [`Section`](crate::parse::ast::Section) in the AST produces a
+//! [`wpibin`](crate::wpibin) section of the same name. Sections are laid out
+//! one after the other starting at the base address (0 by default), each
+//! aligned to a word boundary. Execution starts at the first `.text` section
+//!
+//! Instructions may only be placed in `.text` sections, `.data` and `.bss`
+//! sections can only hold data. `.bss` sections are uninitialized, so they
+//! can only reserve space
+//!
+//! Every label is recorded in the symbol table of the image under its
+//! qualified name (eg. `_start.nested`)
+//!
+//! References to symbols declared with `#EXTERN` are encoded as if their
+//! value were 0 and recorded as [`Relocation`]s in the image. Only branch and
+//! `jal` offsets consisting of a single external symbol, and `#WORDS` values
+//! consisting of a single external symbol can be relocated
+//!
+//! ### Usage
+//! ```rust
+//! use std::fs::File;
+//! use std::io::Read;
+//! use std::path::PathBuf;
+//!
+//! use asm::codegen::Generator;
+//! use asm::error::Error;
+//! use asm::fold::Folder;
+//! use asm::lex::{Lexer, Token};
+//! use asm::parse::Parser;
+//!
+//! fn generator_example() -> Result<(), Error> {
+//!     let src_file_name = "/foo/bar/baz.asm";
+//!     let src_file_path = PathBuf::from(&src_file_name);
+//!
+//!     let mut file = File::open(src_file_path)?;
+//!     let mut contents = String::new();
+//!     file.read_to_string(&mut contents)?;
+//!
+//!     let lexer = Lexer::new(&src_file_name, &contents);
+//!     let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+//!
+//!     let mut parser = Parser::new(&src_file_name, &tokens);
+//!     let mut ast_root = parser.parse()?;
+//!
+//!     let mut folder = Folder::new(&src_file_name);
+//!     folder.fold(&mut ast_root)?;
+//!
+//!     let generator = Generator::new(&src_file_name);
+//!     let image = generator.generate(&ast_root)?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+mod encode;
+
+use std::collections::HashSet;
+
+pub use encode::describe_encoding;
+
+use crate::error::{CodegenError, Error, LocationInfo};
+use crate::include_path;
+use crate::lex::{unescape, Token, TokenType};
+use crate::parse::ast::{
+	BitField,
+	Directive,
+	Immediate,
+	Instruction,
+	Line,
+	Literal,
+	Root,
+	Statement,
+};
+use crate::wpibin::{
+	Endian,
+	Image,
+	Relocation,
+	RelocationKind,
+	Section,
+	SectionFlags,
+	SourceLocation,
+	Symbol,
+};
+
+/// Main code generator type
+///
+/// Provides a namespace for all code generation functions
+///
+/// ### Lifetimes
+///  - `'s`: The lifetime of the reference to the source code string
+#[derive(Clone, Debug)]
+pub struct Generator<'s> {
+	/// The name of the file being generated (used for error messages)
+	source_file: &'s str,
+	/// The address the first section will be loaded at
+	base_addr:   u32,
+	/// Whether to record the source line of every instruction
+	source_map:  bool,
+	/// The byte order of halves and words in data directives
+	endian:      Endian,
+}
+
+/// The output of the section currently being generated
+struct SectionOutput<'a, 's> {
+	/// The name of the section
+	name:        &'s str,
+	/// The address the section will be loaded at
+	addr:        u32,
+	/// The bytes generated so far
+	data:        Vec<u8>,
+	/// The names of all symbols declared with `#EXTERN`
+	externs:     &'a HashSet<&'s str>,
+	/// The relocations of all sections generated so far
+	relocations: &'a mut Vec<Relocation>,
+	/// The symbols of all sections generated so far
+	symbols:     &'a mut Vec<Symbol>,
+	/// The source locations of all instructions generated so far, [`None`]
+	/// if no source map is generated
+	source_map:  Option<&'a mut Vec<SourceLocation>>,
+	/// The names of the labeled blocks enclosing the current line
+	scope:       Vec<&'s str>,
+	/// The errors recovered from so far, [`None`] if generation stops at
+	/// the first error
+	errors:      Option<&'a mut Vec<CodegenError>>,
+}
+
+impl<'a, 's> SectionOutput<'a, 's> {
+	/// Get the name of the external symbol an [`Immediate`] consists of, if
+	/// any
+	fn extern_symbol(&self, imm: &Immediate<'s>) -> Option<&'s str> {
+		match imm.rpn_tokens[..] {
+			[Token { t: TokenType::Identifier(id), .. }] if self.externs.contains(id) => Some(id),
+			_ => None,
+		}
+	}
+
+	/// Record an error if errors are being recovered from, else return it
+	fn recover(&mut self, err: CodegenError) -> Result<(), CodegenError> {
+		match &mut self.errors {
+			Some(errors) => {
+				errors.push(err);
+				Ok(())
+			},
+			None => Err(err),
+		}
+	}
+
+	/// Record a relocation for the next word of data
+	fn relocate(&mut self, symbol: &str, kind: RelocationKind) {
+		self.relocations.push(Relocation {
+			addr: self.addr + self.data.len() as u32,
+			symbol: symbol.to_string(),
+			kind,
+		});
+	}
+}
+
+impl<'s> Generator<'s> {
+	/// Create a new generator given a source file name
+	pub fn new(source_file: &'s str) -> Self {
+		Self { source_file, base_addr: 0, source_map: false, endian: Endian::default() }
+	}
+
+	/// Set the address the first section will be loaded at
+	///
+	/// This should match the base address the AST was
+	/// [folded](crate::fold::Folder::with_base_addr) with
+	pub fn with_base_addr(mut self, base_addr: u32) -> Self {
+		self.base_addr = base_addr;
+		self
+	}
+
+	/// Record the source file and line of every instruction in the
+	/// [source map](Image::source_map) of the generated image
+	pub fn with_source_map(mut self, source_map: bool) -> Self {
+		self.source_map = source_map;
+		self
+	}
+
+	/// Set the byte order of the halves and words of data directives
+	///
+	/// Instructions are always encoded little-endian
+	pub fn with_endian(mut self, endian: Endian) -> Self {
+		self.endian = endian;
+		self
+	}
+
+	/// Generate an executable [`Image`] from the given AST [`Root`]
+	///
+	/// All immediates in the AST should already be folded
+	pub fn generate(&self, root: &Root<'s>) -> Result<Image, Error> {
+		self.generate_image(root, None)
+	}
+
+	/// Generate an executable [`Image`] from the given AST [`Root`],
+	/// continuing past errors in single statements
+	///
+	/// An instruction that can not be generated is replaced by an `ebreak`,
+	/// any other statement is left out
+	///
+	/// Returns the image along with every error that was recovered from, in
+	/// the order they were found
+	pub fn generate_partial(&self, root: &Root<'s>) -> Result<(Image, Vec<CodegenError>), Error> {
+		let mut errors = vec![];
+		let image = self.generate_image(root, Some(&mut errors))?;
+
+		Ok((image, errors))
+	}
+
+	/// Generate an executable [`Image`], recording the errors recovered from
+	/// in `errors` if it is not [`None`]
+	fn generate_image(
+		&self,
+		root: &Root<'s>,
+		mut errors: Option<&mut Vec<CodegenError>>,
+	) -> Result<Image, Error> {
+		let mut image = Image { endian: self.endian, base: self.base_addr, ..Default::default() };
+		let mut entry = None;
+		let mut addr = self.base_addr;
+
+		let mut externs = HashSet::new();
+		for section in &root.sections {
+			collect_externs(&section.lines, &mut externs);
+		}
+
+		for section in &root.sections {
+			let mut out = SectionOutput {
+				name: section.name,
+				addr,
+				data: Vec::new(),
+				externs: &externs,
+				relocations: &mut image.relocations,
+				symbols: &mut image.symbols,
+				source_map: self.source_map.then_some(&mut image.source_map),
+				scope: Vec::new(),
+				errors: errors.as_deref_mut(),
+			};
+			self.generate_lines(&section.lines, &mut out)?;
+			let data = out.data;
+
+			if entry.is_none() && section.name.eq_ignore_ascii_case(".text") {
+				entry = Some(addr);
+			}
+
+			image.sections.push(Section {
+				name: section.name.to_string(),
+				flags: SectionFlags::from_name(section.name),
+				addr,
+				data,
+			});
+
+			let size = image.sections.last().unwrap().data.len() as u32;
+			addr = (addr + size + 3) & !3;
+		}
+
+		image.entry = entry.unwrap_or(self.base_addr);
+
+		Ok(image)
+	}
+
+	/// Append the bytes of a list of [`Line`]s to the output
+	fn generate_lines(
+		&self,
+		lines: &[Line<'s>],
+		out: &mut SectionOutput<'_, 's>,
+	) -> Result<(), CodegenError> {
+		for line in lines {
+			if let Some(Statement::Directive(dir)) = &line.statement
+				&& dir.initializes_data()
+				&& SectionFlags::from_name(out.name).uninit
+			{
+				out.recover(CodegenError::DataInUninitSection {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(&line.start)),
+					section:  out.name.to_string(),
+				})?;
+			}
+
+			match &line.statement {
+				Some(Statement::LabeledBlock(block)) => {
+					out.scope.push(block.label);
+					out.symbols.push(Symbol {
+						name: out.scope.join("."),
+						addr: out.addr + out.data.len() as u32,
+					});
+
+					self.generate_lines(&block.lines, out)?;
+					out.scope.pop();
+				},
+				Some(Statement::Directive(Directive::IncludeBin { path })) => {
+					let path = unescape(path);
+					match std::fs::read(include_path(self.source_file, &path)) {
+						Ok(bytes) => out.data.extend_from_slice(&bytes),
+						Err(err) => {
+							out.recover(CodegenError::IncludeFailed {
+								src_file: self.source_file.to_string(),
+								location: Box::new(LocationInfo::from(&line.start)),
+								path: path.to_string(),
+								err,
+							})?
+						},
+					}
+				},
+				Some(Statement::Directive(dir)) => {
+					if let Err(err) = self.generate_directive(dir, out) {
+						out.recover(err)?;
+					}
+				},
+				Some(Statement::Instruction(_)) if !out.name.eq_ignore_ascii_case(".text") => {
+					out.recover(CodegenError::InstructionOutsideText {
+						src_file: self.source_file.to_string(),
+						location: Box::new(LocationInfo::from(&line.start)),
+						section:  out.name.to_string(),
+					})?;
+
+					let word = self.encode(&Instruction::Ebreak)?;
+					out.data.extend_from_slice(&word.to_le_bytes());
+				},
+				Some(Statement::Instruction(inst)) => {
+					if let Some(source_map) = &mut out.source_map {
+						source_map.push(SourceLocation {
+							addr: out.addr + out.data.len() as u32,
+							file: line.start.included_file.unwrap_or(self.source_file).to_string(),
+							line: line.start.line as u32,
+						});
+					}
+
+					let encoded = match relocated_offset(inst, out) {
+						Some((symbol, kind, inst)) => {
+							out.relocate(symbol, kind);
+							self.encode(&inst)
+						},
+						None => self.encode(inst),
+					};
+					let word = match encoded {
+						Ok(word) => word,
+						Err(err) => {
+							out.recover(err)?;
+							self.encode(&Instruction::Ebreak)?
+						},
+					};
+
+					out.data.extend_from_slice(&word.to_le_bytes());
+				},
+				Some(Statement::MacroInvocation(invocation)) => {
+					out.recover(CodegenError::UnexpandedMacro {
+						src_file: self.source_file.to_string(),
+						id:       invocation.id.to_string(),
+					})?;
+				},
+				// Conditionals have already been replaced by their taken branch
+				// while folding
+				Some(
+					Statement::MacroDefinition(_)
+					| Statement::LocalLabel(_)
+					| Statement::Conditional(_),
+				)
+				| None => (),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Append the bytes of a [`Directive`] to the output
+	fn generate_directive(
+		&self,
+		dir: &Directive<'s>,
+		out: &mut SectionOutput<'_, 's>,
+	) -> Result<(), CodegenError> {
+		match dir {
+			Directive::Bytes(lits) => self.generate_data(lits, 1, out),
+			Directive::Halves(lits) => self.generate_data(lits, 2, out),
+			Directive::Words(lits) => self.generate_data(lits, 4, out),
+			Directive::Bits(fields) => self.generate_bits(fields, &mut out.data),
+			Directive::ResBytes(lits) => self.generate_reserved(lits, 1, &mut out.data),
+			Directive::ResHalves(lits) => self.generate_reserved(lits, 2, &mut out.data),
+			Directive::ResWords(lits) => self.generate_reserved(lits, 4, &mut out.data),
+			Directive::Align { boundary } => {
+				let addr = out.addr + out.data.len() as u32;
+				let padding = addr.wrapping_neg() & (self.value(boundary)? as u32 - 1);
+
+				out.data.resize(out.data.len() + padding as usize, 0);
+				Ok(())
+			},
+			// The folder already checked that the address does not lie
+			// before the current one
+			Directive::Org { addr } => {
+				let padding =
+					(self.value(addr)? as u32).wrapping_sub(out.addr + out.data.len() as u32);
+
+				out.data.resize(out.data.len() + padding as usize, 0);
+				Ok(())
+			},
+			// Included files are read by `generate_lines` as they need the
+			// location of the directive for errors
+			Directive::Const(_)
+			| Directive::IncludeBin { .. }
+			| Directive::Extern { .. }
+			| Directive::Global { .. }
+			| Directive::Weak { .. } => Ok(()),
+		}
+	}
+
+	/// Append a list of [`Literal`]s as values of `width` bytes in the target
+	/// byte order
+	///
+	/// Strings are emitted as one value per character, or as their UTF-8
+	/// bytes when `width` is 1
+	///
+	/// Values are packed directly after any preceding data without padding,
+	/// even if they are not aligned to `width`, use `#ALIGN` to align them
+	fn generate_data(
+		&self,
+		lits: &[Literal<'s>],
+		width: usize,
+		out: &mut SectionOutput<'_, 's>,
+	) -> Result<(), CodegenError> {
+		for lit in lits {
+			match lit {
+				Literal::Immediate(imm) => {
+					let value = match out.extern_symbol(imm) {
+						Some(symbol) if width == 4 => {
+							out.relocate(symbol, RelocationKind::Absolute);
+							0
+						},
+						_ => self.value(imm)?,
+					};
+
+					out.data.extend(self.endian.bytes(value as u32, width));
+				},
+				Literal::Char(c, _) => out.data.extend(self.endian.bytes(*c as u32, width)),
+				Literal::String(s, _) if width == 1 => {
+					out.data.extend_from_slice(unescape(s).as_bytes())
+				},
+				Literal::String(s, _) => {
+					for c in unescape(s).chars() {
+						out.data.extend(self.endian.bytes(c as u32, width));
+					}
+				},
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Append the values of a list of [`BitField`]s packed most significant
+	/// bit first, padding the last byte with zero bits
+	///
+	/// The folder already checked that every value fits in its width
+	fn generate_bits(
+		&self,
+		fields: &[BitField<'s>],
+		data: &mut Vec<u8>,
+	) -> Result<(), CodegenError> {
+		let mut acc = 0u64;
+		let mut bits = 0;
+
+		for field in fields {
+			let width = self.value(&field.width)? as u32;
+			let value = self.value(&field.value)? as u32 as u64 & ((1 << width) - 1);
+
+			acc = (acc << width) | value;
+			bits += width;
+
+			while bits >= 8 {
+				bits -= 8;
+				data.push((acc >> bits) as u8);
+			}
+		}
+
+		if bits > 0 {
+			data.push((acc << (8 - bits)) as u8);
+		}
+
+		Ok(())
+	}
+
+	/// Append `count * width` zero bytes for every count in a list of
+	/// [`Literal`]s
+	fn generate_reserved(
+		&self,
+		lits: &[Literal<'s>],
+		width: usize,
+		data: &mut Vec<u8>,
+	) -> Result<(), CodegenError> {
+		for lit in lits {
+			let count = match lit {
+				Literal::Immediate(imm) => self.value(imm)? as u32 as usize,
+				Literal::Char(c, _) => *c as usize,
+				Literal::String(s, _) => unescape(s).chars().count(),
+			};
+
+			data.resize(data.len() + count * width, 0);
+		}
+
+		Ok(())
+	}
+
+	/// Get the value of a folded [`Immediate`]
+	fn value(&self, imm: &Immediate<'s>) -> Result<i32, CodegenError> {
+		imm.value().ok_or_else(|| {
+			CodegenError::UnresolvedImmediate {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(&imm.rpn_tokens[0])),
+			}
+		})
+	}
+}
+
+/// Recursively collect the names of all symbols declared with `#EXTERN` in a
+/// list of [`Line`]s
+fn collect_externs<'s>(lines: &[Line<'s>], externs: &mut HashSet<&'s str>) {
+	for line in lines {
+		match &line.statement {
+			Some(Statement::LabeledBlock(block)) => collect_externs(&block.lines, externs),
+			Some(Statement::Directive(Directive::Extern { id })) => {
+				externs.insert(*id);
+			},
+			_ => (),
+		}
+	}
+}
+
+/// If the offset of a branch or `jal` instruction is a single external
+/// symbol, get the symbol, the kind of relocation needed, and a copy of the
+/// instruction with its offset set to 0
+fn relocated_offset<'s>(
+	inst: &Instruction<'s>,
+	out: &SectionOutput<'_, 's>,
+) -> Option<(&'s str, RelocationKind, Instruction<'s>)> {
+	let mut inst = inst.clone();
+
+	let (offset, kind) = match &mut inst {
+		Instruction::Jal { offset, .. } => (offset, RelocationKind::Jal),
+		Instruction::Beq { offset, .. }
+		| Instruction::Bne { offset, .. }
+		| Instruction::Blt { offset, .. }
+		| Instruction::Bltu { offset, .. }
+		| Instruction::Bge { offset, .. }
+		| Instruction::Bgeu { offset, .. } => (offset, RelocationKind::Branch),
+		_ => return None,
+	};
+
+	let symbol = out.extern_symbol(offset)?;
+	offset.rpn_tokens = vec![Token { t: TokenType::LitNum(0), ..offset.rpn_tokens[0] }];
+
+	Some((symbol, kind, inst))
+}
@@ -0,0 +1,110 @@
+#![allow(missing_docs)]
+
+use std::fmt::{Display, Formatter};
+
+use ansi_term::Colour::{Blue, Red, White};
+
+use super::print::{make_info_body, make_info_header};
+use super::LocationInfo;
+
+/// An error produced by the [`Generator`](crate::codegen::Generator)
+#[derive(Debug)]
+pub enum CodegenError {
+	UnresolvedImmediate {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	UnexpandedMacro {
+		src_file: String,
+		id:       String,
+	},
+	IncludeFailed {
+		src_file: String,
+		location: Box<LocationInfo>,
+		path:     String,
+		err:      std::io::Error,
+	},
+	InstructionOutsideText {
+		src_file: String,
+		location: Box<LocationInfo>,
+		section:  String,
+	},
+	DataInUninitSection {
+		src_file: String,
+		location: Box<LocationInfo>,
+		section:  String,
+	},
+}
+
+impl Display for CodegenError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let repr = match self {
+			Self::UnresolvedImmediate { src_file, location } => {
+				let mut pretty_err = make_info_header(
+					"immediate could not be resolved to a value",
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::IncludeFailed { src_file, location, path, err } => {
+				let mut pretty_err = make_info_header(
+					&format!("could not include `{}`: {}", path, err),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::InstructionOutsideText { src_file, location, section } => {
+				let mut pretty_err = make_info_header(
+					&format!("instructions can not be placed in the `{}` section", section),
+					src_file,
+					location,
+				);
+
+				pretty_err
+					.push_str(&make_info_body(Some("move it to a `.text` section"), location));
+
+				pretty_err
+			},
+			Self::DataInUninitSection { src_file, location, section } => {
+				let mut pretty_err = make_info_header(
+					&format!("initialized data can not be placed in the `{}` section", section),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(
+					Some("move it to a `.data` section, or reserve space instead"),
+					location,
+				));
+
+				pretty_err
+			},
+			Self::UnexpandedMacro { src_file, id } => {
+				let header = format!(
+					"macro `{}` can not be emitted as macro expansion is not supported yet",
+					id
+				);
+
+				format!(
+					"{} {}\n  {} {}\n",
+					Red.bold().paint("error:"),
+					White.bold().paint(header),
+					Blue.bold().paint("-->"),
+					src_file
+				)
+			},
+		};
+
+		write!(f, "{}", repr)
+	}
+}
+
+impl std::error::Error for CodegenError {}
@@ -0,0 +1,112 @@
+use std::fmt::{Display, Formatter};
+
+use super::print::{make_info_body, make_info_header, make_warning_header};
+use super::LocationInfo;
+
+/// How severe a [`Diagnostic`] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	/// Reported without stopping assembly
+	Warning,
+	/// Stops assembly
+	Error,
+}
+
+/// A single message about a location in the source code, independent of the
+/// pass that produced it
+///
+/// [`LexError`](super::LexError)s, [`ParseError`](super::ParseError)s
+/// (including errors in macro definitions), and [`Warning`](super::Warning)s
+/// can all be converted into a diagnostic, and are printed by rendering the
+/// diagnostic they convert into
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+	/// Whether this is an error or a warning
+	pub severity:  Severity,
+	/// The name of the file the diagnostic was reported for
+	pub src_file:  String,
+	/// A one-line description of the problem
+	pub message:   String,
+	/// Where the problem is
+	pub primary:   LocationInfo,
+	/// A note shown above the source code at `primary`
+	pub label:     Option<String>,
+	/// The source code to underline instead of just `primary`, eg. the whole
+	/// instruction an unexpected operand is part of
+	pub context:   Option<LocationInfo>,
+	/// Other relevant locations, each shown below `primary` with a note
+	pub secondary: Vec<(String, LocationInfo)>,
+}
+
+impl Diagnostic {
+	/// Create a diagnostic with only a message and a primary location
+	pub(super) fn new(
+		severity: Severity,
+		src_file: &str,
+		message: impl Into<String>,
+		primary: LocationInfo,
+	) -> Self {
+		Self {
+			severity,
+			src_file: src_file.to_string(),
+			message: message.into(),
+			primary,
+			label: None,
+			context: None,
+			secondary: vec![],
+		}
+	}
+
+	/// Create an error with only a message and a primary location
+	pub(super) fn error(src_file: &str, message: impl Into<String>, primary: LocationInfo) -> Self {
+		Self::new(Severity::Error, src_file, message, primary)
+	}
+
+	/// Set the note shown above the source code at the primary location
+	pub(super) fn with_label(mut self, label: impl Into<String>) -> Self {
+		self.label = Some(label.into());
+		self
+	}
+
+	/// Set the source code to underline instead of the primary location
+	pub(super) fn with_context(mut self, context: Option<LocationInfo>) -> Self {
+		self.context = context;
+		self
+	}
+
+	/// Add a secondary location with a note
+	pub(super) fn with_secondary(
+		mut self,
+		label: impl Into<String>,
+		location: LocationInfo,
+	) -> Self {
+		self.secondary.push((label.into(), location));
+		self
+	}
+
+	/// The file the primary location is in, which differs from `src_file`
+	/// if it is in an included file
+	pub fn file(&self) -> &str { self.primary.file.as_deref().unwrap_or(&self.src_file) }
+
+	/// The line and column of the primary location
+	pub fn position(&self) -> (usize, usize) { (self.primary.line, self.primary.col) }
+}
+
+impl Display for Diagnostic {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let mut repr = match self.severity {
+			Severity::Error => make_info_header(&self.message, &self.src_file, &self.primary),
+			Severity::Warning => make_warning_header(&self.message, &self.src_file, &self.primary),
+		};
+
+		repr.push_str(&make_info_body(
+			self.label.as_deref(),
+			self.context.as_ref().unwrap_or(&self.primary),
+		));
+		for (label, location) in &self.secondary {
+			repr.push_str(&make_info_body(Some(label), location));
+		}
+
+		write!(f, "{}", repr)
+	}
+}
@@ -0,0 +1,356 @@
+#![allow(missing_docs)]
+
+use std::fmt::{Display, Formatter};
+
+use super::print::{make_info_body, make_info_header};
+use super::LocationInfo;
+
+/// An error produced by the [`Folder`](crate::fold::Folder)
+#[derive(Debug)]
+pub enum FoldError {
+	UndefinedIdentifier {
+		src_file: String,
+		location: Box<LocationInfo>,
+		id:       String,
+	},
+	/// A constant declared more than once, where not every declaration uses
+	/// `#SET`
+	ConstantRedefinition {
+		src_file: String,
+		location: Box<LocationInfo>,
+		id:       String,
+	},
+	UndefinedLocalLabel {
+		src_file: String,
+		location: Box<LocationInfo>,
+		label:    String,
+	},
+	InvalidExpression {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	Overflow {
+		src_file: String,
+		location: Box<LocationInfo>,
+		op:       String,
+	},
+	DivisionByZero {
+		src_file: String,
+		location: Box<LocationInfo>,
+		op:       String,
+	},
+	ImmediateOutOfRange {
+		src_file: String,
+		location: Box<LocationInfo>,
+		value:    i64,
+		min:      i64,
+		max:      i64,
+	},
+	/// A branch whose offset does not fit in the 13 bit signed offset of a
+	/// B-type instruction
+	BranchOutOfRange {
+		src_file: String,
+		location: Box<LocationInfo>,
+		offset:   i64,
+	},
+	/// A branch or jump whose offset is odd, which can not be encoded
+	MisalignedBranch {
+		src_file: String,
+		location: Box<LocationInfo>,
+		offset:   i64,
+	},
+	/// The target of a CSR instruction names a CSR that does not exist
+	UnknownCsr {
+		src_file:   String,
+		location:   Box<LocationInfo>,
+		name:       String,
+		/// The known CSR name closest to `name`, if any is close enough
+		suggestion: Option<String>,
+	},
+	DotOutsideSection {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	UnresolvedSize {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	UnresolvedCondition {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	DataOutOfRange {
+		src_file: String,
+		location: Box<LocationInfo>,
+		value:    i64,
+		bits:     u32,
+	},
+	CharOutOfRange {
+		src_file: String,
+		location: Box<LocationInfo>,
+		value:    char,
+		bits:     u32,
+	},
+	InvalidAlignment {
+		src_file: String,
+		location: Box<LocationInfo>,
+		boundary: i32,
+	},
+	BackwardOrg {
+		src_file: String,
+		location: Box<LocationInfo>,
+		addr:     u32,
+		current:  u32,
+	},
+	IncludeFailed {
+		src_file: String,
+		location: Box<LocationInfo>,
+		path:     String,
+		err:      std::io::Error,
+	},
+	/// A statement that would extend past the end of the 32 bit address
+	/// space
+	AddressOverflow {
+		src_file: String,
+		location: Box<LocationInfo>,
+		addr:     u32,
+		size:     u64,
+	},
+}
+
+impl Display for FoldError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let repr = match self {
+			Self::UndefinedIdentifier { src_file, location, id } => {
+				let mut pretty_err = make_info_header(
+					&format!("use of undefined identifier `{}`", id),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::ConstantRedefinition { src_file, location, id } => {
+				let mut pretty_err = make_info_header(
+					&format!("constant `{}` is defined more than once", id),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(
+					Some("use `#SET` for a value that can change"),
+					location,
+				));
+
+				pretty_err
+			},
+			Self::UndefinedLocalLabel { src_file, location, label } => {
+				let mut pretty_err = make_info_header(
+					&format!("local label `{}` is not defined", label),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::InvalidExpression { src_file, location } => {
+				let mut pretty_err =
+					make_info_header("invalid immediate expression", src_file, location);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::Overflow { src_file, location, op } => {
+				let mut pretty_err = make_info_header(
+					&format!("arithmetic overflow while evaluating `{}`", op),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::DivisionByZero { src_file, location, op } => {
+				let mut pretty_err = make_info_header(
+					&format!("division by zero while evaluating `{}`", op),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::ImmediateOutOfRange { src_file, location, value, min, max } => {
+				let mut pretty_err = make_info_header(
+					&format!(
+						"immediate `{}` is out of range, expected a value in {}..={}",
+						value, min, max
+					),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::BranchOutOfRange { src_file, location, offset } => {
+				let mut pretty_err = make_info_header(
+					&format!("branch target out of range: {} bytes, max \u{b1}4096", offset),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(
+					Some("use `jal` to reach targets further away"),
+					location,
+				));
+
+				pretty_err
+			},
+			Self::MisalignedBranch { src_file, location, offset } => {
+				let mut pretty_err = make_info_header(
+					&format!(
+						"branch target is misaligned: {} bytes is not a multiple of 2",
+						offset
+					),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::UnknownCsr { src_file, location, name, suggestion } => {
+				let mut pretty_err =
+					make_info_header(&format!("unknown CSR `{}`", name), src_file, location);
+
+				let label = suggestion.as_ref().map(|csr| format!("did you mean `{}`?", csr));
+				pretty_err.push_str(&make_info_body(label.as_deref(), location));
+
+				pretty_err
+			},
+			Self::DotOutsideSection { src_file, location } => {
+				let mut pretty_err = make_info_header(
+					"the current address `.` can only be used inside a section",
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::UnresolvedSize { src_file, location } => {
+				let mut pretty_err = make_info_header(
+					"the size of a directive can not depend on labels declared after it",
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::UnresolvedCondition { src_file, location } => {
+				let mut pretty_err = make_info_header(
+					"the condition of an `#IF` can only depend on constants declared in the \
+					 preamble",
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::DataOutOfRange { src_file, location, value, bits } => {
+				let mut pretty_err = make_info_header(
+					&format!("value `{}` does not fit in {} bits", value, bits),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::InvalidAlignment { src_file, location, boundary } => {
+				let mut pretty_err = make_info_header(
+					&format!("alignment `{}` is not a power of two", boundary),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::BackwardOrg { src_file, location, addr, current } => {
+				let mut pretty_err = make_info_header(
+					&format!(
+						"`#ORG` address {:#010x} lies before the current address {:#010x}",
+						addr, current
+					),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::AddressOverflow { src_file, location, addr, size } => {
+				let mut pretty_err = make_info_header(
+					&format!(
+						"{} bytes at {:#010x} do not fit in the 32 bit address space",
+						size, addr
+					),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::IncludeFailed { src_file, location, path, err } => {
+				let mut pretty_err = make_info_header(
+					&format!("could not include `{}`: {}", path, err),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+			Self::CharOutOfRange { src_file, location, value, bits } => {
+				let mut pretty_err = make_info_header(
+					&format!(
+						"character {:?} (U+{:04X}) does not fit in {} bits",
+						value, *value as u32, bits
+					),
+					src_file,
+					location,
+				);
+
+				pretty_err.push_str(&make_info_body(None, location));
+
+				pretty_err
+			},
+		};
+
+		write!(f, "{}", repr)
+	}
+}
+
+impl std::error::Error for FoldError {}
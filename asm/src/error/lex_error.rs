@@ -2,8 +2,8 @@
 
 use std::fmt::{Display, Formatter};
 
-use super::print::{make_info_body, make_info_header};
-use super::LocationInfo;
+use super::{Diagnostic, LocationInfo};
+use crate::lex::{Extension, Isa};
 
 /// An error produced by the [`Lexer`](crate::lex::Lexer)
 #[derive(Debug)]
@@ -20,7 +20,8 @@ pub enum LexError {
 		col:      usize,
 		src_line: String,
 		found:    char,
-		expected: char,
+		/// Every symbol that would have been valid instead
+		expected: Vec<char>,
 	},
 	RawUnexpectedSymbol {
 		src_file: String,
@@ -51,75 +52,123 @@ pub enum LexError {
 		src_line: String,
 		dir:      String,
 	},
+	UnsupportedInstruction {
+		src_file:    String,
+		line:        usize,
+		col:         usize,
+		span:        usize,
+		src_line:    String,
+		instruction: String,
+		extension:   Extension,
+		isa:         Isa,
+	},
+	IncludeFailed {
+		src_file: String,
+		line:     usize,
+		col:      usize,
+		span:     usize,
+		src_line: String,
+		path:     String,
+		err:      std::io::Error,
+	},
+	IncludeCycle {
+		src_file: String,
+		line:     usize,
+		col:      usize,
+		span:     usize,
+		src_line: String,
+		path:     String,
+	},
 }
 
-impl Display for LexError {
-	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		let repr = match self {
-			Self::UnexpectedEof { src_file, line, col, src_line } => {
+impl From<&LexError> for Diagnostic {
+	fn from(value: &LexError) -> Self {
+		match value {
+			LexError::UnexpectedEof { src_file, line, col, src_line } => {
 				let location = LocationInfo::new(*line, *col, 1, src_line);
-				let mut pretty_err =
-					make_info_header("unexpected end-of-file", src_file, &location);
-
-				pretty_err.push_str(&make_info_body(None, &location));
 
-				pretty_err
+				Self::error(src_file, "unexpected end-of-file", location)
 			},
-			Self::UnexpectedSymbol { src_file, line, col, src_line, found, expected } => {
+			LexError::UnexpectedSymbol { src_file, line, col, src_line, found, expected } => {
 				let location = LocationInfo::new(*line, *col, 1, src_line);
-				let mut pretty_err = make_info_header(
-					&format!("found unexpected symbol `{:?}`, expected `{:?}`", found, expected),
-					src_file,
-					&location,
-				);
-
-				pretty_err.push_str(&make_info_body(None, &location));
+				let expected: Vec<String> = expected.iter().map(|c| format!("`{:?}`", c)).collect();
 
-				pretty_err
+				Self::error(
+					src_file,
+					format!(
+						"found unexpected symbol `{:?}`, expected {}",
+						found,
+						expected.join(" or ")
+					),
+					location,
+				)
 			},
-			Self::RawUnexpectedSymbol { src_file, line, col, src_line, found } => {
+			LexError::RawUnexpectedSymbol { src_file, line, col, src_line, found } => {
 				let location = LocationInfo::new(*line, *col, 1, src_line);
-				let mut pretty_err = make_info_header(
-					&format!("found unexpected symbol `{:?}`", found),
-					src_file,
-					&location,
-				);
-
-				pretty_err.push_str(&make_info_body(None, &location));
 
-				pretty_err
+				Self::error(src_file, format!("found unexpected symbol `{:?}`", found), location)
 			},
-			Self::InvalidNumber { src_file, line, col, span, src_line } => {
+			LexError::InvalidNumber { src_file, line, col, span, src_line } => {
 				let location = LocationInfo::new(*line, *col, *span, src_line);
-				let mut pretty_err = make_info_header("invalid number", src_file, &location);
 
-				pretty_err.push_str(&make_info_body(None, &location));
-
-				pretty_err
+				Self::error(src_file, "invalid number", location)
 			},
-			Self::InvalidEscape { src_file, line, col, span, src_line } => {
+			LexError::InvalidEscape { src_file, line, col, span, src_line } => {
 				let location = LocationInfo::new(*line, *col, *span, src_line);
-				let mut pretty_err =
-					make_info_header("invalid escape sequence", src_file, &location);
 
-				pretty_err.push_str(&make_info_body(None, &location));
+				Self::error(src_file, "invalid escape sequence", location)
+			},
+			LexError::InvalidDirective { src_file, line, col, span, src_line, dir } => {
+				let location = LocationInfo::new(*line, *col, *span, src_line);
 
-				pretty_err
+				Self::error(src_file, format!("invalid directive `{:?}`", dir), location)
 			},
-			Self::InvalidDirective { src_file, line, col, span, src_line, dir } => {
+			LexError::UnsupportedInstruction {
+				src_file,
+				line,
+				col,
+				span,
+				src_line,
+				instruction,
+				extension,
+				isa,
+			} => {
 				let location = LocationInfo::new(*line, *col, *span, src_line);
-				let mut pretty_err = make_info_header(
-					&format!("invalid directive `{:?}`", dir),
+
+				Self::error(
 					src_file,
-					&location,
-				);
+					format!(
+						"instruction `{}` requires the {} extension, which is not enabled in {}",
+						instruction, extension, isa
+					),
+					location,
+				)
+			},
+			LexError::IncludeFailed { src_file, line, col, span, src_line, path, err } => {
+				let location = LocationInfo::new(*line, *col, *span, src_line);
 
-				pretty_err.push_str(&make_info_body(None, &location));
+				Self::error(src_file, format!("could not include `{}`: {}", path, err), location)
+			},
+			LexError::IncludeCycle { src_file, line, col, span, src_line, path } => {
+				let location = LocationInfo::new(*line, *col, *span, src_line);
 
-				pretty_err
+				Self::error(src_file, format!("`{}` is included recursively", path), location)
+					.with_label(
+						"the file is already being included by this file or one that includes it",
+					)
 			},
-		};
+		}
+	}
+}
 
-		write!(f, "{}", repr)
+impl From<LexError> for Diagnostic {
+	fn from(value: LexError) -> Self { Self::from(&value) }
+}
+
+impl Display for LexError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", Diagnostic::from(self))
 	}
 }
+
+impl std::error::Error for LexError {}
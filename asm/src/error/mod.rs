@@ -2,12 +2,20 @@
 
 use std::fmt::{Display, Formatter};
 
+mod codegen_error;
+mod diagnostic;
+mod fold_error;
 mod lex_error;
 mod parse_error;
 mod print;
+mod warning;
 
+pub use codegen_error::CodegenError;
+pub use diagnostic::{Diagnostic, Severity};
+pub use fold_error::FoldError;
 pub use lex_error::LexError;
 pub use parse_error::ParseError;
+pub use warning::Warning;
 
 use crate::lex::Token;
 
@@ -20,32 +28,65 @@ pub enum Error {
 	Lex(LexError),
 	/// An error produced by the [`Parser`](crate::parse::Parser)
 	Parse(ParseError),
+	/// An error produced by the [`Folder`](crate::fold::Folder)
+	Fold(FoldError),
+	/// An error produced by the [`Generator`](crate::codegen::Generator)
+	Codegen(CodegenError),
+	/// The amount of warnings reported while warnings are treated as errors,
+	/// see [`AsmOptions::warnings_as_errors`](crate::AsmOptions::warnings_as_errors)
+	DeniedWarnings(usize),
+	/// Every error reported while assembling past errors, see
+	/// [`AsmOptions::keep_going`](crate::AsmOptions::keep_going)
+	Collected(Vec<Error>),
 }
 
 /// Information on where exactly an error occured, can be generated from
 /// Lexer tokens
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct LocationInfo {
 	line:     usize,
 	col:      usize,
 	span:     usize,
+	/// The number of lines spanned, starting at `line`
+	lines:    usize,
+	/// Every line spanned
 	src_line: String,
+	/// The name of the included file the location is in, [`None`] if it is
+	/// in the file the error was reported for
+	file:     Option<String>,
 }
 
 impl<'s> From<&Token<'s>> for LocationInfo {
 	fn from(value: &Token<'s>) -> Self {
-		Self {
-			line:     value.line,
-			col:      value.col,
-			span:     value.span,
-			src_line: value.source_line.to_string(),
-		}
+		let mut location = Self::new(value.line, value.col, value.span, value.source_line);
+		location.file = value.included_file.map(str::to_string);
+
+		location
 	}
 }
 
 impl LocationInfo {
 	fn new(line: usize, col: usize, span: usize, src_line: &str) -> Self {
-		Self { line, col, span, src_line: src_line.to_string() }
+		let lines = src_line.trim_end_matches('\n').lines().count().max(1);
+
+		Self { line, col, span, lines, src_line: src_line.to_string(), file: None }
+	}
+
+	/// Create a location covering every column from the start of `first` up
+	/// to the end of `last`
+	///
+	/// Falls back to only covering `last` if the tokens are on different
+	/// lines
+	pub(crate) fn spanning(first: &Token, last: &Token) -> Self {
+		if first.line != last.line || first.col > last.col {
+			return Self::from(last);
+		}
+
+		let mut location =
+			Self::new(first.line, first.col, last.col + last.span - first.col, first.source_line);
+		location.file = first.included_file.map(str::to_string);
+
+		location
 	}
 }
 
@@ -55,6 +96,31 @@ impl Display for Error {
 			Self::Io(err) => write!(f, "{}", err),
 			Self::Lex(err) => write!(f, "{}", err),
 			Self::Parse(err) => write!(f, "{}", err),
+			Self::Fold(err) => write!(f, "{}", err),
+			Self::Codegen(err) => write!(f, "{}", err),
+			Self::DeniedWarnings(1) => write!(f, "aborting due to 1 warning treated as an error"),
+			Self::DeniedWarnings(n) => {
+				write!(f, "aborting due to {} warnings treated as errors", n)
+			},
+			Self::Collected(errors) => {
+				for err in errors {
+					writeln!(f, "{}", err)?;
+				}
+
+				match errors.len() {
+					1 => write!(f, "aborting due to 1 previous error"),
+					n => write!(f, "aborting due to {} previous errors", n),
+				}
+			},
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(err) => Some(err),
+			_ => None,
 		}
 	}
 }
@@ -70,3 +136,11 @@ impl From<LexError> for Error {
 impl From<ParseError> for Error {
 	fn from(value: ParseError) -> Self { Self::Parse(value) }
 }
+
+impl From<FoldError> for Error {
+	fn from(value: FoldError) -> Self { Self::Fold(value) }
+}
+
+impl From<CodegenError> for Error {
+	fn from(value: CodegenError) -> Self { Self::Codegen(value) }
+}
@@ -2,8 +2,7 @@
 
 use std::fmt::{Display, Formatter};
 
-use super::print::{make_info_body, make_info_header};
-use super::LocationInfo;
+use super::{Diagnostic, LocationInfo};
 
 #[derive(Debug)]
 pub enum ParseError {
@@ -12,10 +11,13 @@ pub enum ParseError {
 		location: Box<LocationInfo>,
 	},
 	UnexpectedToken {
-		src_file: String,
-		location: Box<LocationInfo>,
-		found:    String,
-		expected: String,
+		src_file:  String,
+		location:  Box<LocationInfo>,
+		found:     String,
+		expected:  String,
+		/// The statement containing the unexpected token, up to and
+		/// including that token, underlined instead of `location` if present
+		statement: Option<Box<LocationInfo>>,
 	},
 	UnclosedDelimiter {
 		src_file:       String,
@@ -24,81 +26,222 @@ pub enum ParseError {
 		close_location: Box<LocationInfo>,
 		open_location:  Box<LocationInfo>,
 	},
+	/// Opening delimiters that are never closed, `location` points at the
+	/// first one and `count` is the amount of unclosed delimiters of its type
+	UnclosedDelimiters {
+		src_file:  String,
+		location:  Box<LocationInfo>,
+		delimiter: String,
+		count:     usize,
+	},
 	UnmatchedCloseParenthesis {
 		src_file: String,
 		location: Box<LocationInfo>,
 	},
+	UnclosedConditional {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	UnmatchedConditional {
+		src_file:  String,
+		location:  Box<LocationInfo>,
+		directive: String,
+	},
+	/// An `#INCLUDE` directive in source code that is not read from a file,
+	/// see [`Includes`](crate::lex::Includes)
+	UnexpandedInclude {
+		src_file: String,
+		location: Box<LocationInfo>,
+	},
+	/// A labeled block, conditional, or parenthesis nested deeper than the
+	/// [maximum depth](crate::parse::Parser::with_max_depth)
+	NestingTooDeep {
+		src_file: String,
+		location: Box<LocationInfo>,
+		limit:    usize,
+	},
+	/// A store instruction with its operands in the order of a load, ie.
+	/// the register before the address
+	SwappedOperands {
+		src_file:    String,
+		location:    Box<LocationInfo>,
+		instruction: String,
+		register:    String,
+		address:     String,
+	},
+	/// An instruction whose line ends before all of its operands are given,
+	/// `location` points at the instruction
+	MissingOperands {
+		src_file:    String,
+		location:    Box<LocationInfo>,
+		instruction: String,
+		/// The kind of operands the instruction takes, eg. `register operands`
+		kind:        String,
+		expected:    usize,
+		found:       usize,
+	},
 	InvalidOrderingSpecifier {
 		src_file: String,
 		location: Box<LocationInfo>,
 		spec:     String,
 	},
+	AmbiguousMacroRules {
+		src_file:   String,
+		location:   Box<LocationInfo>,
+		macro_name: String,
+	},
+	/// A macro defined more than once
+	///
+	/// Later definitions never replace earlier ones, as that would silently
+	/// change the meaning of every invocation between them
+	MacroRedefinition {
+		src_file: String,
+		location: Box<LocationInfo>,
+		/// The name of the macro in its first definition
+		original: Box<LocationInfo>,
+		id:       String,
+	},
+	/// A label, constant, or macro declared with the name of an instruction
+	/// or register
+	ReservedName {
+		src_file: String,
+		location: Box<LocationInfo>,
+		name:     String,
+		/// What the name is reserved for, eg. `a register`
+		reserved: String,
+		/// What was being declared, eg. `label`
+		declared: String,
+	},
 }
 
-impl Display for ParseError {
-	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		let repr = match self {
-			Self::UnexpectedEof { src_file, location } => {
-				let mut pretty_err = make_info_header("unexpected end-of-file", src_file, location);
-
-				pretty_err.push_str(&make_info_body(None, location));
-
-				pretty_err
+impl From<&ParseError> for Diagnostic {
+	fn from(value: &ParseError) -> Self {
+		match value {
+			ParseError::UnexpectedEof { src_file, location } => {
+				Self::error(src_file, "unexpected end-of-file", (**location).clone())
 			},
-			Self::UnexpectedToken { src_file, location, found, expected } => {
-				let mut pretty_err = make_info_header(
-					&format!("found unexpected token `{}`, expected `{}`", found, expected),
+			ParseError::UnexpectedToken { src_file, location, found, expected, statement } => {
+				Self::error(
 					src_file,
-					location,
-				);
-
-				pretty_err.push_str(&make_info_body(None, location));
-
-				pretty_err
+					format!("found unexpected token `{}`, expected {}", found, expected),
+					(**location).clone(),
+				)
+				.with_context(statement.as_deref().cloned())
 			},
-			Self::UnclosedDelimiter {
+			ParseError::UnclosedDelimiter {
 				src_file,
 				delim_type,
 				found,
 				close_location,
 				open_location,
 			} => {
-				let mut pretty_err = make_info_header(
-					&format!("found unexpected token `{}`, expected closing {}", found, delim_type),
+				Self::error(
 					src_file,
-					close_location,
-				);
-
-				pretty_err.push_str(&make_info_body(None, close_location));
-
-				pretty_err.push_str(&make_info_body(
-					Some(&format!("unclosed {}", delim_type)),
-					open_location,
-				));
-
-				pretty_err
+					format!("found unexpected token `{}`, expected closing {}", found, delim_type),
+					(**close_location).clone(),
+				)
+				.with_secondary(format!("unclosed {}", delim_type), (**open_location).clone())
 			},
-			Self::UnmatchedCloseParenthesis { src_file, location } => {
-				let mut pretty_err =
-					make_info_header("unmatched closing parenthesis", src_file, location);
-
-				pretty_err.push_str(&make_info_body(None, location));
-
-				pretty_err
+			ParseError::UnclosedDelimiters { src_file, location, delimiter, count } => {
+				Self::error(
+					src_file,
+					format!("{} unclosed `{}`", count, delimiter),
+					(**location).clone(),
+				)
+				.with_label("first unclosed here")
+			},
+			ParseError::UnmatchedCloseParenthesis { src_file, location } => {
+				Self::error(src_file, "unmatched closing parenthesis", (**location).clone())
+			},
+			ParseError::UnclosedConditional { src_file, location } => {
+				Self::error(src_file, "`#IF` is never closed by an `#ENDIF`", (**location).clone())
 			},
-			Self::InvalidOrderingSpecifier { src_file, location, spec } => {
-				let mut pretty_err = make_info_header(
-					&format!("invalid ordering specifier `{:?}`", spec),
+			ParseError::UnmatchedConditional { src_file, location, directive } => {
+				Self::error(
 					src_file,
-					location,
-				);
-
-				pretty_err.push_str(&make_info_body(None, location));
-
-				pretty_err
+					format!("found `{}` without a matching `#IF`", directive),
+					(**location).clone(),
+				)
+			},
+			ParseError::UnexpandedInclude { src_file, location } => {
+				Self::error(src_file, "files can not be included here", (**location).clone())
+					.with_label("`#INCLUDE` can only be used when assembling a file")
+			},
+			ParseError::NestingTooDeep { src_file, location, limit } => {
+				Self::error(
+					src_file,
+					format!("nesting exceeds the maximum depth of {}", limit),
+					(**location).clone(),
+				)
+			},
+			ParseError::SwappedOperands { src_file, location, instruction, register, address } => {
+				Self::error(
+					src_file,
+					format!("`{}` takes the address before the register", instruction),
+					(**location).clone(),
+				)
+				.with_label(format!("try `{} {}, {}`", instruction, address, register))
+			},
+			ParseError::MissingOperands {
+				src_file,
+				location,
+				instruction,
+				kind,
+				expected,
+				found,
+			} => {
+				Self::error(
+					src_file,
+					format!("`{}` expects {} {}, found {}", instruction, expected, kind, found),
+					(**location).clone(),
+				)
 			},
-		};
+			ParseError::InvalidOrderingSpecifier { src_file, location, spec } => {
+				Self::error(
+					src_file,
+					format!("invalid ordering specifier `{:?}`", spec),
+					(**location).clone(),
+				)
+			},
+			ParseError::AmbiguousMacroRules { src_file, location, macro_name } => {
+				Self::error(
+					src_file,
+					format!(
+						"macro `{}` has multiple rules matching the same arguments",
+						macro_name
+					),
+					(**location).clone(),
+				)
+			},
+			ParseError::MacroRedefinition { src_file, location, original, id } => {
+				Self::error(
+					src_file,
+					format!("macro `{}` is defined more than once", id),
+					(**location).clone(),
+				)
+				.with_label("redefined here")
+				.with_secondary(format!("`{}` was first defined here", id), (**original).clone())
+			},
+			ParseError::ReservedName { src_file, location, name, reserved, declared } => {
+				Self::error(
+					src_file,
+					format!("`{}` is {} and can not be the name of a {}", name, reserved, declared),
+					(**location).clone(),
+				)
+				.with_label(format!("rename the {}, eg. to `{}_`", declared, name))
+			},
+		}
+	}
+}
+
+impl From<ParseError> for Diagnostic {
+	fn from(value: ParseError) -> Self { Self::from(&value) }
+}
 
-		write!(f, "{}", repr)
+impl Display for ParseError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", Diagnostic::from(self))
 	}
 }
+
+impl std::error::Error for ParseError {}
@@ -1,15 +1,31 @@
-use ansi_term::Colour::{Blue, Red, White};
+use ansi_term::ANSIString;
+use ansi_term::Colour::{Blue, Red, White, Yellow};
 
 use super::LocationInfo;
 
 /// Format error message and file information into a header
 pub(crate) fn make_info_header(header: &str, src_file: &str, loc_info: &LocationInfo) -> String {
+	make_header(Red.bold().paint("error:"), header, src_file, loc_info)
+}
+
+/// Format warning message and file information into a header
+pub(super) fn make_warning_header(header: &str, src_file: &str, loc_info: &LocationInfo) -> String {
+	make_header(Yellow.bold().paint("warning:"), header, src_file, loc_info)
+}
+
+fn make_header(
+	severity: ANSIString<'static>,
+	header: &str,
+	src_file: &str,
+	loc_info: &LocationInfo,
+) -> String {
 	// Header line
-	// `error: {msg}`
-	let mut repr = format!("{} {}\n", Red.bold().paint("error:"), White.bold().paint(header));
+	// `{severity} {msg}`
+	let mut repr = format!("{} {}\n", severity, White.bold().paint(header));
 
 	// File arrow
 	// `--> {src_file}`
+	let src_file = loc_info.file.as_deref().unwrap_or(src_file);
 	repr.push_str(&format!(
 		"  {} {}:{}:{}\n",
 		Blue.bold().paint("-->"),
@@ -32,6 +48,11 @@ pub(super) fn make_info_body(header: Option<&str>, loc_info: &LocationInfo) -> S
 	let mut repr =
 		if let Some(h) = header { format!("{}\n", White.bold().paint(h)) } else { String::new() };
 
+	if loc_info.lines > 1 {
+		repr.push_str(&make_multi_line_body(loc_info));
+		return repr;
+	}
+
 	let line_len = format!("{}", loc_info.line).len();
 
 	repr.push_str(&format!("{}{}\n", " ".repeat(line_len + 1), Blue.bold().paint("|")));
@@ -50,11 +71,67 @@ pub(super) fn make_info_body(header: Option<&str>, loc_info: &LocationInfo) -> S
 	for _ in 1..loc_info.col {
 		repr.push(' ');
 	}
-	for _ in 0..loc_info.span {
+
+	// Spans continuing past the end of the line are cut off, but always
+	// show at least one caret
+	let line_rest =
+		loc_info.src_line.trim_end().len().saturating_sub(loc_info.col.saturating_sub(1));
+	let span = loc_info.span.min(line_rest).max(1);
+	for _ in 0..span {
 		repr.push_str(&format!("{}", Red.bold().paint("^")));
 	}
+	if span < loc_info.span {
+		repr.push_str(&format!("{}", Red.bold().paint("...")));
+	}
 
 	repr.push('\n');
 
 	repr
 }
+
+/// Format the location of a span covering multiple lines, bracketing every
+/// line from the start to the end of the span
+///
+/// ```txt
+///     |
+/// {l} |   {first_line}
+///     |  ______^
+/// {l} | | {middle_line}
+/// {l} | | {last_line}
+///     | |____^
+/// ```
+fn make_multi_line_body(loc_info: &LocationInfo) -> String {
+	let last_line = loc_info.line + loc_info.lines - 1;
+	let line_len = format!("{}", last_line).len();
+	let gutter = format!("{}{}", " ".repeat(line_len + 1), Blue.bold().paint("|"));
+
+	let mut repr = format!("{}\n", gutter);
+
+	for (i, src_line) in loc_info.src_line.lines().enumerate() {
+		let bracket = if i == 0 { " ".to_string() } else { Red.bold().paint("|").to_string() };
+		repr.push_str(&format!(
+			"{} {} {} {}\n",
+			Blue.bold().paint(format!("{:>width$}", loc_info.line + i, width = line_len)),
+			Blue.bold().paint("|"),
+			bracket,
+			src_line.trim_end(),
+		));
+
+		// Start of the span
+		// `  ______^`
+		if i == 0 {
+			let underline = format!("{}^", "_".repeat(loc_info.col));
+			repr.push_str(&format!("{}  {}\n", gutter, Red.bold().paint(underline)));
+		}
+	}
+
+	// End of the span, pointing at its last character
+	// `|____^`
+	let span_end = (loc_info.col - 1 + loc_info.span).min(loc_info.src_line.len());
+	let last_line_start = loc_info.src_line[..span_end].rfind('\n').map_or(0, |i| i + 1);
+	let end_col = span_end.saturating_sub(last_line_start).max(1);
+	let underline = format!("|{}^", "_".repeat(end_col));
+	repr.push_str(&format!("{} {}\n", gutter, Red.bold().paint(underline)));
+
+	repr
+}
@@ -0,0 +1,71 @@
+#![allow(missing_docs)]
+
+use std::fmt::{Display, Formatter};
+
+use super::{Diagnostic, LocationInfo, Severity};
+
+/// A warning produced by the [`Linter`](crate::lint::Linter) or the
+/// [`Folder`](crate::fold::Folder)
+///
+/// Unlike errors, warnings do not stop assembly
+#[derive(Debug)]
+pub enum Warning {
+	UnreachableCode { src_file: String, location: Box<LocationInfo> },
+	UndefinedWeakSymbol { src_file: String, location: Box<LocationInfo>, id: String },
+	DeadWrite { src_file: String, location: Box<LocationInfo>, reg: String },
+}
+
+impl Warning {
+	/// The name used to allow this kind of warning
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::UnreachableCode { .. } => "unreachable-code",
+			Self::UndefinedWeakSymbol { .. } => "undefined-weak-symbol",
+			Self::DeadWrite { .. } => "dead-write",
+		}
+	}
+}
+
+impl From<&Warning> for Diagnostic {
+	fn from(value: &Warning) -> Self {
+		match value {
+			Warning::UnreachableCode { src_file, location } => {
+				Self::new(
+					Severity::Warning,
+					src_file,
+					"unreachable instruction",
+					(**location).clone(),
+				)
+				.with_label("this instruction follows an unconditional jump and has no label")
+			},
+			Warning::UndefinedWeakSymbol { src_file, location, id } => {
+				Self::new(
+					Severity::Warning,
+					src_file,
+					format!("weak symbol `{}` is never declared", id),
+					(**location).clone(),
+				)
+				.with_label("resolves to address 0")
+			},
+			Warning::DeadWrite { src_file, location, reg } => {
+				Self::new(
+					Severity::Warning,
+					src_file,
+					format!("value written to `{}` is never read", reg),
+					(**location).clone(),
+				)
+				.with_label("it is overwritten by a later instruction first")
+			},
+		}
+	}
+}
+
+impl From<Warning> for Diagnostic {
+	fn from(value: Warning) -> Self { Self::from(&value) }
+}
+
+impl Display for Warning {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", Diagnostic::from(self))
+	}
+}
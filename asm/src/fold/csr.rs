@@ -0,0 +1,84 @@
+//! Names of control and status registers
+//!
+//! The target of a CSR instruction can be written as the name of a CSR
+//! instead of its address, eg. `csrrs r1, r0, cycle`. Constants and labels
+//! take precedence over CSR names
+
+/// Every known CSR name along with its address
+pub const CSRS: [(&str, u16); 29] = [
+	// Unprivileged counters
+	("cycle", 0xC00),
+	("time", 0xC01),
+	("instret", 0xC02),
+	("cycleh", 0xC80),
+	("timeh", 0xC81),
+	("instreth", 0xC82),
+	// Machine information
+	("mvendorid", 0xF11),
+	("marchid", 0xF12),
+	("mimpid", 0xF13),
+	("mhartid", 0xF14),
+	// Machine trap setup
+	("mstatus", 0x300),
+	("misa", 0x301),
+	("medeleg", 0x302),
+	("mideleg", 0x303),
+	("mie", 0x304),
+	("mtvec", 0x305),
+	("mcounteren", 0x306),
+	("mstatush", 0x310),
+	// Machine trap handling
+	("mscratch", 0x340),
+	("mepc", 0x341),
+	("mcause", 0x342),
+	("mtval", 0x343),
+	("mip", 0x344),
+	// Machine counters
+	("mcycle", 0xB00),
+	("minstret", 0xB02),
+	("mcycleh", 0xB80),
+	("minstreth", 0xB82),
+	("mcountinhibit", 0x320),
+	// Debug
+	("dscratch0", 0x7B2),
+];
+
+/// The maximum edit distance between an unknown name and a known one for
+/// the known one to be suggested
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Get the address of the CSR with the given name
+pub fn csr_address(name: &str) -> Option<u16> {
+	CSRS.iter().find(|(csr, _)| *csr == name).map(|&(_, addr)| addr)
+}
+
+/// Get the known CSR name closest to an unknown one, if any is close enough
+/// to likely be a typo of it
+pub(super) fn suggest_csr(name: &str) -> Option<&'static str> {
+	CSRS.iter()
+		.map(|&(csr, _)| (edit_distance(name, csr), csr))
+		.filter(|&(distance, _)| distance <= MAX_SUGGESTION_DISTANCE)
+		.min_by_key(|&(distance, _)| distance)
+		.map(|(_, csr)| csr)
+}
+
+/// Get the Levenshtein distance between two strings, the least amount of
+/// inserted, removed, or replaced characters needed to turn one into the
+/// other
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+	for (i, ca) in a.chars().enumerate() {
+		let mut curr = vec![i + 1];
+
+		for (j, &cb) in b.iter().enumerate() {
+			let replace = prev[j] + (ca != cb) as usize;
+			curr.push(replace.min(prev[j + 1] + 1).min(curr[j] + 1));
+		}
+
+		prev = curr;
+	}
+
+	prev[b.len()]
+}
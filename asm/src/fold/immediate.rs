@@ -0,0 +1,249 @@
+//! [`Folder`] functions to evaluate [`Immediate`] expressions
+
+use super::Folder;
+use crate::error::{FoldError, LocationInfo};
+use crate::lex::{OpToken, Token, TokenType};
+use crate::parse::ast::Immediate;
+
+/// A value on the evaluation stack
+#[derive(Clone, Copy, Debug)]
+enum Value {
+	/// A regular number
+	Num(i32),
+	/// The two branches of a ternary expression, produced by the `:` operator
+	/// and consumed by the `?` operator
+	Branches(i32, i32),
+}
+
+impl<'s> Folder<'s> {
+	/// Evaluate an [`Immediate`] and replace its tokens with a single numeric
+	/// literal
+	///
+	/// The new literal spans all tokens of the original expression
+	pub(super) fn fold_immediate(&self, imm: &mut Immediate<'s>) -> Result<(), FoldError> {
+		let first = match imm.rpn_tokens.first() {
+			Some(t) => *t,
+			None => return Ok(()),
+		};
+
+		if let Some(value) = self.evaluate(imm)? {
+			// Immediates can not span multiple lines so all tokens share the
+			// same source line
+			let start = imm.rpn_tokens.iter().map(|t| t.col).min().unwrap_or(first.col);
+			let end = imm.rpn_tokens.iter().map(|t| t.col + t.span).max().unwrap_or(first.col);
+
+			imm.rpn_tokens =
+				vec![Token { t: TokenType::LitNum(value), col: start, span: end - start, ..first }];
+		}
+
+		Ok(())
+	}
+
+	/// Evaluate an [`Immediate`] in reverse polish notation
+	///
+	/// Returns [`None`] if the immediate references an identifier whose value
+	/// is not known yet
+	fn evaluate(&self, imm: &Immediate<'s>) -> Result<Option<i32>, FoldError> {
+		let mut stack: Vec<Value> = vec![];
+
+		for token in &imm.rpn_tokens {
+			match token.t {
+				TokenType::LitNum(n) => stack.push(Value::Num(n)),
+				TokenType::LitChar(c) => stack.push(Value::Num(c as i32)),
+				TokenType::Identifier(id) => {
+					if self.deferred.contains(id) {
+						return Ok(None);
+					}
+					if self.weak.contains(id) {
+						self.reference_weak(token);
+					}
+
+					match self.consts.get(id) {
+						Some(value) => stack.push(Value::Num(*value)),
+						None => {
+							return Err(FoldError::UndefinedIdentifier {
+								src_file: self.source_file.to_string(),
+								location: Box::new(LocationInfo::from(token)),
+								id:       id.to_string(),
+							});
+						},
+					}
+				},
+				TokenType::LocalLabelBackward(_) | TokenType::LocalLabelForward(_) => {
+					match self.local_label_address(token)? {
+						Some(addr) => stack.push(Value::Num(addr)),
+						None => return Ok(None),
+					}
+				},
+				TokenType::SymDot => {
+					match self.location {
+						Some(addr) => stack.push(Value::Num(addr as i32)),
+						None => {
+							return Err(FoldError::DotOutsideSection {
+								src_file: self.source_file.to_string(),
+								location: Box::new(LocationInfo::from(token)),
+							});
+						},
+					}
+				},
+				TokenType::Op(op) => {
+					let value = self.apply_operator(op, token, &mut stack)?;
+					stack.push(value);
+				},
+				_ => return Err(self.invalid_expression(token)),
+			}
+		}
+
+		match stack[..] {
+			[Value::Num(n)] => Ok(Some(n)),
+			// Unwrap is safe as the immediate contains at least one token if
+			// the stack isn't empty
+			_ => Err(self.invalid_expression(imm.rpn_tokens.last().unwrap())),
+		}
+	}
+
+	/// Get the address of the local label referenced by a token
+	///
+	/// Backward references resolve to the closest definition before the
+	/// statement being folded, forward references to the closest one after
+	/// it. Returns [`None`] if the definition has not been folded yet
+	pub(super) fn local_label_address(&self, token: &Token<'s>) -> Result<Option<i32>, FoldError> {
+		let (before, after) = self.local_labels.split_at(self.local_index);
+
+		let definition = match token.t {
+			TokenType::LocalLabelBackward(label) => before.iter().rev().find(|(l, _)| *l == label),
+			TokenType::LocalLabelForward(label) => after.iter().find(|(l, _)| *l == label),
+			_ => unreachable!(),
+		};
+
+		match definition {
+			Some((_, addr)) => Ok(*addr),
+			None => {
+				Err(FoldError::UndefinedLocalLabel {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(token)),
+					label:    token.t.to_string(),
+				})
+			},
+		}
+	}
+
+	/// Apply an operator to the top value(s) of the evaluation stack and
+	/// return the result
+	fn apply_operator(
+		&self,
+		op: OpToken,
+		token: &Token<'s>,
+		stack: &mut Vec<Value>,
+	) -> Result<Value, FoldError> {
+		let overflow = || {
+			FoldError::Overflow {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(token)),
+				op:       op.to_string(),
+			}
+		};
+
+		match op {
+			OpToken::UnaryMinus => {
+				let a = self.pop_num(token, stack)?;
+				a.checked_neg().map(Value::Num).ok_or_else(overflow)
+			},
+			OpToken::Exclamation => {
+				let a = self.pop_num(token, stack)?;
+				Ok(Value::Num((a == 0) as i32))
+			},
+			OpToken::BitNot => {
+				let a = self.pop_num(token, stack)?;
+				Ok(Value::Num(!a))
+			},
+			// The low part is sign-extended when added, so the high part is
+			// rounded up whenever bit 11 is set to compensate
+			OpToken::Hi => {
+				let a = self.pop_num(token, stack)?;
+				Ok(Value::Num(((a as u32).wrapping_add(0x800) >> 12) as i32))
+			},
+			OpToken::Lo => {
+				let a = self.pop_num(token, stack)?;
+				Ok(Value::Num(a << 20 >> 20))
+			},
+			OpToken::Colon => {
+				let b = self.pop_num(token, stack)?;
+				let a = self.pop_num(token, stack)?;
+				Ok(Value::Branches(a, b))
+			},
+			OpToken::Question => {
+				let branches = stack.pop();
+				let cond = self.pop_num(token, stack)?;
+
+				match branches {
+					Some(Value::Branches(a, b)) => Ok(Value::Num(if cond != 0 { a } else { b })),
+					_ => Err(self.invalid_expression(token)),
+				}
+			},
+			OpToken::Slash | OpToken::Percent if matches!(stack.last(), Some(Value::Num(0))) => {
+				Err(FoldError::DivisionByZero {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(token)),
+					op:       op.to_string(),
+				})
+			},
+			_ => {
+				let b = self.pop_num(token, stack)?;
+				let a = self.pop_num(token, stack)?;
+				apply_binary_operator(op, a, b).map(Value::Num).ok_or_else(overflow)
+			},
+		}
+	}
+
+	/// Pop a single number off the evaluation stack
+	fn pop_num(&self, token: &Token<'s>, stack: &mut Vec<Value>) -> Result<i32, FoldError> {
+		match stack.pop() {
+			Some(Value::Num(n)) => Ok(n),
+			_ => Err(self.invalid_expression(token)),
+		}
+	}
+
+	/// Make an [`FoldError::InvalidExpression`] pointing at the given token
+	fn invalid_expression(&self, token: &Token<'s>) -> FoldError {
+		FoldError::InvalidExpression {
+			src_file: self.source_file.to_string(),
+			location: Box::new(LocationInfo::from(token)),
+		}
+	}
+}
+
+/// Apply a binary operator to 2 numbers
+///
+/// Returns [`None`] if the operation overflows, division by zero should be
+/// checked beforehand
+fn apply_binary_operator(op: OpToken, a: i32, b: i32) -> Option<i32> {
+	match op {
+		OpToken::Plus => a.checked_add(b),
+		OpToken::Minus => a.checked_sub(b),
+		OpToken::Star => a.checked_mul(b),
+		OpToken::Slash => a.checked_div(b),
+		OpToken::Percent => a.checked_rem(b),
+		OpToken::LogicOr => Some((a != 0 || b != 0) as i32),
+		OpToken::LogicXor => Some(((a != 0) ^ (b != 0)) as i32),
+		OpToken::LogicAnd => Some((a != 0 && b != 0) as i32),
+		OpToken::BitOr => Some(a | b),
+		OpToken::BitXor => Some(a ^ b),
+		OpToken::BitAnd => Some(a & b),
+		OpToken::Eq => Some((a == b) as i32),
+		OpToken::Neq => Some((a != b) as i32),
+		OpToken::Lt => Some((a < b) as i32),
+		OpToken::Lte => Some((a <= b) as i32),
+		OpToken::Gt => Some((a > b) as i32),
+		OpToken::Gte => Some((a >= b) as i32),
+		OpToken::Lsl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+		OpToken::Lsr => {
+			u32::try_from(b).ok().and_then(|b| (a as u32).checked_shr(b)).map(|v| v as i32)
+		},
+		OpToken::Asr => u32::try_from(b).ok().and_then(|b| a.checked_shr(b)),
+
+		// The immediate parser only produces arithmetic/logic operators, and
+		// all unary and ternary operators are handled separately
+		_ => unreachable!(),
+	}
+}
@@ -0,0 +1,227 @@
+//! [`Folder`] functions to fold and validate [`Instruction`]s
+
+use super::csr::{csr_address, suggest_csr};
+use super::Folder;
+use crate::error::{FoldError, LocationInfo};
+use crate::lex::{Token, TokenType};
+use crate::parse::ast::{Address, Immediate, Instruction, OffsetOperator};
+
+impl<'s> Folder<'s> {
+	/// Fold all immediates in an [`Instruction`] and check that they fit in
+	/// their respective instruction fields
+	pub(super) fn fold_instruction(&self, inst: &mut Instruction<'s>) -> Result<(), FoldError> {
+		match inst {
+			Instruction::Addi { imm, .. }
+			| Instruction::Slti { imm, .. }
+			| Instruction::Sltiu { imm, .. }
+			| Instruction::Andi { imm, .. }
+			| Instruction::Ori { imm, .. }
+			| Instruction::Xori { imm, .. } => {
+				self.fold_immediate(imm)?;
+
+				// The immediate gets sign extended from 12 bits
+				self.check_range(imm, -0x800, 0x7FF)
+			},
+			Instruction::Lsli { imm, .. }
+			| Instruction::Lsri { imm, .. }
+			| Instruction::Asri { imm, .. } => {
+				self.fold_immediate(imm)?;
+
+				// Only the lower 5 bits are used as the shift amount
+				self.check_range(imm, 0, 31)
+			},
+			Instruction::Lui { imm, .. } => {
+				self.fold_immediate(imm)?;
+
+				// The immediate becomes the upper 20 bits of the destination register
+				self.check_range(imm, 0, 0xF_FFFF)
+			},
+			Instruction::Auipc { imm, .. } => {
+				self.fold_immediate(imm)?;
+
+				// The immediate becomes the upper 20 bits of a signed offset from the PC
+				self.check_range(imm, -0x8_0000, 0x7_FFFF)
+			},
+			// Jumps can reach +-1MiB
+			Instruction::Jal { offset, .. } => {
+				self.fold_pc_relative(offset)?;
+				self.check_range(offset, -0x10_0000, 0xF_FFFF)?;
+				self.check_aligned(offset)
+			},
+			Instruction::Beq { offset, .. }
+			| Instruction::Bne { offset, .. }
+			| Instruction::Blt { offset, .. }
+			| Instruction::Bltu { offset, .. }
+			| Instruction::Bge { offset, .. }
+			| Instruction::Bgeu { offset, .. } => {
+				self.fold_pc_relative(offset)?;
+				self.check_branch_offset(offset)
+			},
+			// Jumps to registers, loads, and stores all have a 12 bit signed offset
+			Instruction::Jalr { offset, .. } => {
+				self.fold_immediate(offset)?;
+				self.check_range(offset, -0x800, 0x7FF)
+			},
+			Instruction::Lb { addr, .. }
+			| Instruction::Lbu { addr, .. }
+			| Instruction::Lh { addr, .. }
+			| Instruction::Lhu { addr, .. }
+			| Instruction::Lw { addr, .. } => self.fold_address(addr),
+			Instruction::Sb { dest, .. }
+			| Instruction::Sh { dest, .. }
+			| Instruction::Sw { dest, .. } => self.fold_address(dest),
+			Instruction::Csrrw { target, .. }
+			| Instruction::Csrrs { target, .. }
+			| Instruction::Csrrc { target, .. } => self.fold_csr_target(target),
+			Instruction::Csrrwi { src, target, .. }
+			| Instruction::Csrrsi { src, target, .. }
+			| Instruction::Csrrci { src, target, .. } => {
+				self.fold_immediate(src)?;
+
+				// The source immediate gets zero-extended from 5 bits
+				self.check_range(src, 0, 31)?;
+				self.fold_csr_target(target)
+			},
+			Instruction::Add { .. }
+			| Instruction::Slt { .. }
+			| Instruction::Sltu { .. }
+			| Instruction::And { .. }
+			| Instruction::Or { .. }
+			| Instruction::Xor { .. }
+			| Instruction::Lsl { .. }
+			| Instruction::Lsr { .. }
+			| Instruction::Asr { .. }
+			| Instruction::Sub { .. }
+			| Instruction::Mul { .. }
+			| Instruction::Mulh { .. }
+			| Instruction::Mulhu { .. }
+			| Instruction::Mulhsu { .. }
+			| Instruction::Div { .. }
+			| Instruction::Divu { .. }
+			| Instruction::Rem { .. }
+			| Instruction::Remu { .. }
+			| Instruction::Fence { .. }
+			| Instruction::FenceTso { .. }
+			| Instruction::Ecall
+			| Instruction::Ebreak
+			| Instruction::Fencei => Ok(()),
+		}
+	}
+
+	/// Fold the target of a CSR instruction and check that it is a valid CSR
+	/// address
+	///
+	/// A target consisting of a single identifier that is not a constant or
+	/// label gets resolved as the name of a CSR, see [`CSRS`](super::CSRS)
+	fn fold_csr_target(&self, target: &mut Immediate<'s>) -> Result<(), FoldError> {
+		if let [token] = target.rpn_tokens[..]
+			&& let TokenType::Identifier(id) = token.t
+			&& !self.consts.contains_key(id)
+			&& !self.deferred.contains(id)
+			&& !self.weak.contains(id)
+		{
+			let addr = csr_address(id).ok_or_else(|| {
+				FoldError::UnknownCsr {
+					src_file:   self.source_file.to_string(),
+					location:   Box::new(LocationInfo::from(&token)),
+					name:       id.to_string(),
+					suggestion: suggest_csr(id).map(str::to_string),
+				}
+			})?;
+
+			target.rpn_tokens = vec![Token { t: TokenType::LitNum(addr as i32), ..token }];
+		}
+
+		self.fold_immediate(target)?;
+
+		// CSR addresses are 12 bit unsigned values
+		self.check_range(target, 0, 0xFFF)
+	}
+
+	/// Fold the offset of a branch or jump instruction
+	///
+	/// A bare label or local label reference gets resolved to its distance
+	/// from the current instruction, any other immediate is taken to be an
+	/// offset already
+	fn fold_pc_relative(&self, offset: &mut Immediate<'s>) -> Result<(), FoldError> {
+		match offset.rpn_tokens[..] {
+			[token @ Token { t: TokenType::Identifier(id), .. }] if self.labels.contains(id) => {
+				if self.weak.contains(id) {
+					self.reference_weak(&token);
+				}
+				if let Some(addr) = self.consts.get(id) && !self.deferred.contains(id) {
+					let distance = addr.wrapping_sub(self.current_address() as i32);
+					offset.rpn_tokens = vec![Token { t: TokenType::LitNum(distance), ..token }];
+				}
+
+				Ok(())
+			},
+			[
+				token @ Token {
+					t: TokenType::LocalLabelBackward(_) | TokenType::LocalLabelForward(_),
+					..
+				},
+			] => {
+				if let Some(addr) = self.local_label_address(&token)? {
+					let distance = addr.wrapping_sub(self.current_address() as i32);
+					offset.rpn_tokens = vec![Token { t: TokenType::LitNum(distance), ..token }];
+				}
+
+				Ok(())
+			},
+			_ => self.fold_immediate(offset),
+		}
+	}
+
+	/// Check that the folded offset of a branch instruction can be encoded
+	///
+	/// Branches can reach +-4KiB, and as B-type instructions do not encode
+	/// the lowest bit of their offset it must be a multiple of 2
+	fn check_branch_offset(&self, offset: &Immediate<'s>) -> Result<(), FoldError> {
+		let Some(value) = offset.value() else { return Ok(()) };
+
+		if !(-0x1000..=0xFFF).contains(&value) {
+			return Err(FoldError::BranchOutOfRange {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(&offset.rpn_tokens[0])),
+				offset:   value as i64,
+			});
+		}
+
+		self.check_aligned(offset)
+	}
+
+	/// Check that the folded offset of a branch or jump instruction is a
+	/// multiple of 2, as B-type and J-type instructions do not encode its
+	/// lowest bit
+	fn check_aligned(&self, offset: &Immediate<'s>) -> Result<(), FoldError> {
+		match offset.value() {
+			Some(value) if value % 2 != 0 => {
+				Err(FoldError::MisalignedBranch {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(&offset.rpn_tokens[0])),
+					offset:   value as i64,
+				})
+			},
+			_ => Ok(()),
+		}
+	}
+
+	/// Fold the offset of an [`Address`], if it has one, and check that it
+	/// fits in 12 bits
+	///
+	/// A subtracted offset gets negated, so its range is shifted by one
+	fn fold_address(&self, addr: &mut Address<'s>) -> Result<(), FoldError> {
+		match &mut addr.offset {
+			Some(offset) => {
+				self.fold_immediate(&mut offset.offset)?;
+
+				match offset.op {
+					OffsetOperator::Plus => self.check_range(&offset.offset, -0x800, 0x7FF),
+					OffsetOperator::Minus => self.check_range(&offset.offset, -0x7FF, 0x800),
+				}
+			},
+			None => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,804 @@
+//! # Constant Folder
+//!
+//! The folder is responsible for evaluating the [`Immediate`] expressions in
+//! the AST (see [`ast`](crate::parse::ast)) down to a single numeric literal,
+//! and checking that the resulting values fit in the fields they will
+//! eventually be encoded into
+//!
+//! Constants declared with a `#CONST` directive can be used by any immediate
+//! that follows their declaration. Constants declared with `#SET` can be
+//! declared again later on, every immediate uses the value of the closest
+//! preceding declaration. While folding, the folder keeps track of
+//! the address of every statement so that labels and the current address `.`
+//! can be resolved. Sections are laid out one after the other starting at
+//! the base address (0 by default), each aligned to a word boundary
+//!
+//! A label used as the offset of a branch or `jal` instruction resolves to
+//! the distance from that instruction to the label, anywhere else it
+//! resolves to the address of the label
+//!
+//! The target of a CSR instruction can also be the name of a CSR, see
+//! [`CSRS`], which resolves to its address
+//!
+//! Before anything else gets folded, every `#IF` block is replaced by the
+//! lines of its taken branch. Its condition can only use constants declared
+//! in the preamble or passed to [`Folder::with_constant`]
+//!
+//! Labels can be referenced before they are declared, so folding happens in
+//! two passes. The first pass folds everything that does not depend on a
+//! label declared later on, after which the second pass resolves the
+//! remaining immediates using the address of every label
+//!
+//! Labels are [local](SymbolBinding::Local) unless they are named by a
+//! `#GLOBAL` or `#WEAK` directive. A weak label that is never declared
+//! resolves to address 0, and every reference to it is reported as a
+//! [`Warning`] instead of an error
+//!
+//! ### Usage
+//! ```rust
+//! use std::fs::File;
+//! use std::io::Read;
+//! use std::path::PathBuf;
+//!
+//! use asm::error::Error;
+//! use asm::fold::Folder;
+//! use asm::lex::{Lexer, Token};
+//! use asm::parse::Parser;
+//!
+//! fn folder_example() -> Result<(), Error> {
+//!     let src_file_name = "/foo/bar/baz.asm";
+//!     let src_file_path = PathBuf::from(&src_file_name);
+//!
+//!     let mut file = File::open(src_file_path)?;
+//!     let mut contents = String::new();
+//!     file.read_to_string(&mut contents)?;
+//!
+//!     let lexer = Lexer::new(&src_file_name, &contents);
+//!     let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+//!
+//!     let mut parser = Parser::new(&src_file_name, &tokens);
+//!     let mut ast_root = parser.parse()?;
+//!
+//!     let mut folder = Folder::new(&src_file_name);
+//!     folder.fold(&mut ast_root)?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+mod csr;
+mod immediate;
+mod instruction;
+
+pub use csr::{csr_address, CSRS};
+
+use crate::error::{Error, FoldError, LocationInfo, Warning};
+use crate::include_path;
+use crate::lex::{unescape, Token, TokenType};
+use crate::parse::ast::{
+	ConstDirective,
+	Directive,
+	Immediate,
+	Instruction,
+	Line,
+	Literal,
+	PreambleStatement,
+	Root,
+	Statement,
+};
+
+/// Main folder type
+///
+/// Wraps all internal state during folding and provides a namespace for all
+/// folder-related functions
+///
+/// ### Lifetimes
+///  - `'s`: The lifetime of the reference to the source code string, needed as the names of
+///    constants and labels are references into it
+#[derive(Clone, Debug)]
+pub struct Folder<'s> {
+	/// The name of the file being folded (used for error messages)
+	source_file: &'s str,
+
+	/// The values of all constants and labels declared so far
+	consts:       HashMap<&'s str, i32>,
+	/// The names of all constants declared so far in the current pass, and
+	/// whether they were declared with `#SET`
+	declared:     HashMap<&'s str, bool>,
+	/// Identifiers whose value can not be known until labels are resolved
+	deferred:     HashSet<&'s str>,
+	/// The names of all labels
+	labels:       HashSet<&'s str>,
+	/// The bindings of all labels named by a `#GLOBAL` or `#WEAK` directive
+	bindings:     HashMap<&'s str, SymbolBinding>,
+	/// Weak labels that are never declared
+	weak:         HashSet<&'s str>,
+	/// The first reference to every weak label that is never declared
+	weak_refs:    RefCell<Vec<Token<'s>>>,
+	/// All local labels in order of definition, along with their address
+	/// once it is known
+	local_labels: Vec<(u32, Option<i32>)>,
+	/// The amount of local labels defined before the statement being folded
+	local_index:  usize,
+	/// The address of the statement being folded, [`None`] outside of
+	/// sections
+	location:     Option<u32>,
+	/// The address the first section will be loaded at
+	base_addr:    u32,
+
+	/// The name of the section being folded
+	section: &'s str,
+	/// The labels of all [`LabeledBlock`]s enclosing the statement being
+	/// folded
+	///
+	/// [`LabeledBlock`]: crate::parse::ast::LabeledBlock
+	scope:   Vec<&'s str>,
+	/// All labels declared so far, in order of declaration
+	symbols: Vec<Symbol>,
+	/// All symbols declared with `#EXTERN`, in order of declaration
+	externs: Vec<&'s str>,
+
+	/// Whether to keep folding after an error, see [`Folder::fold_partial`]
+	keep_going: bool,
+}
+
+/// A label declared in the source, see [`Folder::symbols`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+	/// The name of the label, prefixed by the names of all enclosing labels
+	/// separated by dots (eg. `_start.nested`)
+	pub name:    String,
+	/// The address of the label
+	pub addr:    u32,
+	/// The amount of bytes in the block the label is attached to, including
+	/// any nested blocks
+	pub size:    u32,
+	/// The name of the section the label is declared in
+	pub section: String,
+	/// The binding of the label
+	pub binding: SymbolBinding,
+}
+
+/// The visibility of a label outside of its source file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolBinding {
+	/// Only visible inside the source file
+	#[default]
+	Local,
+	/// Visible to other files, set by a `#GLOBAL` directive
+	Global,
+	/// Visible to other files but allowed to be undeclared, set by a `#WEAK`
+	/// directive
+	Weak,
+}
+
+impl<'s> Folder<'s> {
+	/// Create a new folder given a source file name
+	pub fn new(source_file: &'s str) -> Self {
+		Self {
+			source_file,
+			consts: HashMap::new(),
+			declared: HashMap::new(),
+			deferred: HashSet::new(),
+			labels: HashSet::new(),
+			bindings: HashMap::new(),
+			weak: HashSet::new(),
+			weak_refs: RefCell::new(vec![]),
+			local_labels: vec![],
+			local_index: 0,
+			location: None,
+			base_addr: 0,
+			section: "",
+			scope: vec![],
+			symbols: vec![],
+			externs: vec![],
+			keep_going: false,
+		}
+	}
+
+	/// Set the address the first section will be loaded at
+	pub fn with_base_addr(mut self, base_addr: u32) -> Self {
+		self.base_addr = base_addr;
+		self
+	}
+
+	/// Declare a constant as if it were defined by a `#CONST` directive at
+	/// the very start of the source
+	///
+	/// A `#CONST` directive in the source with the same name takes
+	/// precedence, it overrides the given value from that directive onwards
+	pub fn with_constant(mut self, id: &'s str, value: i32) -> Self {
+		self.define(id, value);
+		self
+	}
+
+	/// Get all labels declared in the last folded AST, in order of
+	/// declaration
+	pub fn symbols(&self) -> &[Symbol] { &self.symbols }
+
+	/// Get all symbols declared with `#EXTERN` in the last folded AST, in
+	/// order of declaration
+	pub fn externs(&self) -> &[&'s str] { &self.externs }
+
+	/// Get the warnings produced while folding the last AST, one for every
+	/// weak label that is referenced but never declared
+	pub fn warnings(&self) -> Vec<Warning> {
+		self.weak_refs
+			.borrow()
+			.iter()
+			.map(|token| {
+				Warning::UndefinedWeakSymbol {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(token)),
+					id:       token.t.to_string(),
+				}
+			})
+			.collect()
+	}
+
+	/// Fold all immediates in the given AST [`Root`] in place
+	pub fn fold(&mut self, root: &mut Root<'s>) -> Result<(), Error> {
+		self.keep_going = false;
+		self.fold_root(root, &mut vec![])
+	}
+
+	/// Fold all immediates in the given AST [`Root`] in place, continuing
+	/// past errors in instructions and data
+	///
+	/// An instruction that fails to fold is replaced by an `ebreak`, and
+	/// every value of a `#BYTES`, `#HALVES`, or `#WORDS` directive that
+	/// fails to fold is replaced by 0. Neither changes the size of the
+	/// statement, so the addresses of all later statements stay the same
+	///
+	/// Returns every error that was recovered from, in the order they were
+	/// found. Any other error is returned like in [`fold`](Self::fold)
+	pub fn fold_partial(&mut self, root: &mut Root<'s>) -> Result<Vec<FoldError>, Error> {
+		self.keep_going = true;
+		let mut errors = vec![];
+		let folded = self.fold_root(root, &mut errors);
+		self.keep_going = false;
+
+		folded.map(|_| errors)
+	}
+
+	/// Fold all immediates in the given AST [`Root`] in place, recording the
+	/// errors recovered from in `errors`
+	fn fold_root(&mut self, root: &mut Root<'s>, errors: &mut Vec<FoldError>) -> Result<(), Error> {
+		self.resolve_conditionals(root)?;
+
+		self.local_labels.clear();
+		self.weak_refs.borrow_mut().clear();
+		self.externs.clear();
+		for section in &root.sections {
+			self.collect_labels(&section.lines);
+		}
+
+		// Undeclared weak labels are treated as labels at address 0
+		let weak: Vec<&'s str> = self
+			.bindings
+			.iter()
+			.filter(|&(id, &binding)| binding == SymbolBinding::Weak && !self.labels.contains(id))
+			.map(|(id, _)| *id)
+			.collect();
+		for id in weak {
+			self.labels.insert(id);
+			self.weak.insert(id);
+			self.define(id, 0);
+		}
+
+		// The first pass declares every label, the second pass can then
+		// resolve any references to labels declared later on
+		for _ in 0..2 {
+			self.location = None;
+			self.local_index = 0;
+			self.symbols.clear();
+			self.declared.clear();
+			for line in &mut root.preamble {
+				if let Some(PreambleStatement::ConstDirective(const_dir)) = &mut line.statement {
+					self.fold_const_directive(const_dir, &line.start)?;
+				}
+			}
+
+			let mut addr = self.base_addr;
+			for section in &mut root.sections {
+				self.location = Some(addr);
+				self.section = section.name;
+				self.fold_lines(&mut section.lines, errors)?;
+
+				// Sections start on a word boundary, a section ending in the
+				// last word of the address space leaves no room for any
+				// statement after it
+				addr = self.current_address().checked_add(3).map_or(u32::MAX, |end| end & !3);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Replace every [`Conditional`] in the sections of the given AST by the
+	/// lines of its taken branch
+	///
+	/// Conditions are folded before any other immediate, so they can only use
+	/// constants declared in the preamble or through
+	/// [`with_constant`](Self::with_constant), and never labels
+	///
+	/// [`Conditional`]: crate::parse::ast::Conditional
+	fn resolve_conditionals(&self, root: &mut Root<'s>) -> Result<(), FoldError> {
+		// Evaluate the conditions with a scratch folder, so the preamble
+		// constants are declared without affecting the actual passes
+		let mut scratch = self.clone();
+		scratch.declared.clear();
+		for section in &root.sections {
+			scratch.collect_labels(&section.lines);
+		}
+		for line in &mut root.preamble {
+			if let Some(PreambleStatement::ConstDirective(const_dir)) = &mut line.statement {
+				scratch.fold_const_directive(const_dir, &line.start)?;
+			}
+		}
+
+		for section in &mut root.sections {
+			scratch.resolve_conditional_lines(&mut section.lines)?;
+		}
+
+		Ok(())
+	}
+
+	/// Recursively replace every [`Conditional`] in a list of [`Line`]s by
+	/// the lines of its taken branch
+	///
+	/// [`Conditional`]: crate::parse::ast::Conditional
+	fn resolve_conditional_lines(&mut self, lines: &mut Vec<Line<'s>>) -> Result<(), FoldError> {
+		for line in std::mem::take(lines) {
+			match line.statement {
+				Some(Statement::Conditional(mut cond)) => {
+					self.fold_immediate(&mut cond.condition)?;
+
+					let mut taken = match cond.condition.value() {
+						Some(0) => cond.else_lines,
+						Some(_) => cond.then_lines,
+						None => {
+							return Err(FoldError::UnresolvedCondition {
+								src_file: self.source_file.to_string(),
+								location: Box::new(LocationInfo::from(&line.start)),
+							});
+						},
+					};
+
+					self.resolve_conditional_lines(&mut taken)?;
+					lines.extend(taken);
+				},
+				Some(Statement::LabeledBlock(mut block)) => {
+					self.resolve_conditional_lines(&mut block.lines)?;
+					lines.push(Line { statement: Some(Statement::LabeledBlock(block)), ..line });
+				},
+				_ => lines.push(line),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Recursively collect the labels of all [`LabeledBlock`]s in a list of
+	/// [`Line`]s and mark them as deferred
+	///
+	/// External symbols are also marked as deferred, but are never defined,
+	/// so references to them are left for the code generator to relocate
+	///
+	/// [`LabeledBlock`]: crate::parse::ast::LabeledBlock
+	fn collect_labels(&mut self, lines: &[Line<'s>]) {
+		for line in lines {
+			match &line.statement {
+				Some(Statement::LabeledBlock(block)) => {
+					self.labels.insert(block.label);
+					self.deferred.insert(block.label);
+					self.collect_labels(&block.lines);
+				},
+				Some(Statement::Conditional(cond)) => {
+					self.collect_labels(&cond.then_lines);
+					self.collect_labels(&cond.else_lines);
+				},
+				Some(Statement::Directive(Directive::Extern { id })) => {
+					self.deferred.insert(id);
+					if !self.externs.contains(id) {
+						self.externs.push(id);
+					}
+				},
+				Some(Statement::Directive(Directive::Global { id })) => {
+					self.bindings.insert(id, SymbolBinding::Global);
+				},
+				Some(Statement::Directive(Directive::Weak { id })) => {
+					self.bindings.insert(id, SymbolBinding::Weak);
+				},
+				Some(Statement::LocalLabel(label)) => self.local_labels.push((*label, None)),
+				_ => (),
+			}
+		}
+	}
+
+	/// Fold all immediates in a list of [`Line`]s
+	///
+	/// When [keeping going](Self::fold_partial) errors in instructions and
+	/// data are recorded in `errors`, and the statement is poisoned
+	fn fold_lines(
+		&mut self,
+		lines: &mut [Line<'s>],
+		errors: &mut Vec<FoldError>,
+	) -> Result<(), FoldError> {
+		for line in lines {
+			match &mut line.statement {
+				Some(Statement::LabeledBlock(block)) => {
+					self.define(block.label, self.current_address() as i32);
+
+					self.scope.push(block.label);
+					let start = self.current_address();
+					let index = self.symbols.len();
+					self.symbols.push(Symbol {
+						name:    self.scope.join("."),
+						addr:    start,
+						size:    0,
+						section: self.section.to_string(),
+						binding: self.bindings.get(block.label).copied().unwrap_or_default(),
+					});
+
+					self.fold_lines(&mut block.lines, errors)?;
+					self.symbols[index].size = self.current_address().wrapping_sub(start);
+					self.scope.pop();
+				},
+				Some(Statement::LocalLabel(_)) => {
+					self.local_labels[self.local_index].1 = Some(self.current_address() as i32);
+					self.local_index += 1;
+				},
+				Some(Statement::Directive(dir)) => {
+					match self.fold_directive(dir, &line.start) {
+						Err(err) if self.keep_going && poison_data(dir) => errors.push(err),
+						folded => folded?,
+					}
+					self.advance(self.directive_size(dir, &line.start)?, &line.start)?;
+				},
+				Some(Statement::Instruction(inst)) => {
+					match self.fold_instruction(inst) {
+						Err(err) if self.keep_going => {
+							errors.push(err);
+							*inst = Instruction::Ebreak;
+						},
+						folded => folded?,
+					}
+					self.advance(inst.size_bytes() as u64, &line.start)?;
+				},
+				// Macros only contain raw tokens until they get expanded, and
+				// conditionals have already been resolved
+				Some(
+					Statement::MacroDefinition(_)
+					| Statement::MacroInvocation(_)
+					| Statement::Conditional(_),
+				)
+				| None => (),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Fold all immediates in a [`Directive`]
+	fn fold_directive(
+		&mut self,
+		dir: &mut Directive<'s>,
+		start: &Token<'s>,
+	) -> Result<(), FoldError> {
+		match dir {
+			Directive::Bytes(data) => self.fold_data(data, 8),
+			Directive::Halves(data) => self.fold_data(data, 16),
+			Directive::Words(data) => self.fold_data(data, 32),
+			Directive::Bits(fields) => {
+				for field in fields {
+					self.fold_immediate(&mut field.width)?;
+					self.check_range(&field.width, 1, 32)?;
+					self.fold_immediate(&mut field.value)?;
+
+					// Like other data, values may be either signed or unsigned
+					if let Some(width) = field.width.value()
+						&& let Some(value) = field.value.value()
+					{
+						let value = value as i64;
+						if value < -(1i64 << (width - 1)) || value >= 1i64 << width {
+							return Err(FoldError::DataOutOfRange {
+								src_file: self.source_file.to_string(),
+								location: Box::new(LocationInfo::from(&field.value.rpn_tokens[0])),
+								value,
+								bits: width as u32,
+							});
+						}
+					}
+				}
+
+				Ok(())
+			},
+			Directive::ResBytes(data) | Directive::ResHalves(data) | Directive::ResWords(data) => {
+				for lit in data {
+					if let Literal::Immediate(imm) = lit {
+						self.fold_immediate(imm)?;
+						self.check_range(imm, 0, i32::MAX as i64)?;
+					}
+				}
+
+				Ok(())
+			},
+			Directive::Const(const_dir) => self.fold_const_directive(const_dir, start),
+			Directive::Align { boundary } => {
+				self.fold_immediate(boundary)?;
+
+				match boundary.value() {
+					Some(value) if value <= 0 || !(value as u32).is_power_of_two() => {
+						Err(FoldError::InvalidAlignment {
+							src_file: self.source_file.to_string(),
+							location: Box::new(LocationInfo::from(&boundary.rpn_tokens[0])),
+							boundary: value,
+						})
+					},
+					_ => Ok(()),
+				}
+			},
+			Directive::Org { addr } => {
+				self.fold_immediate(addr)?;
+
+				match addr.value() {
+					Some(value) if (value as u32) < self.current_address() => {
+						Err(FoldError::BackwardOrg {
+							src_file: self.source_file.to_string(),
+							location: Box::new(LocationInfo::from(&addr.rpn_tokens[0])),
+							addr:     value as u32,
+							current:  self.current_address(),
+						})
+					},
+					_ => Ok(()),
+				}
+			},
+			Directive::IncludeBin { .. }
+			| Directive::Extern { .. }
+			| Directive::Global { .. }
+			| Directive::Weak { .. } => Ok(()),
+		}
+	}
+
+	/// Fold all immediates in the data of a `#BYTES`, `#HALVES`, or `#WORDS`
+	/// directive, and check that every value fits in `bits` bits
+	///
+	/// Values may be either signed or unsigned, so a byte can hold anything
+	/// in `-128..=255`. Strings in byte directives are encoded as UTF-8 and
+	/// so always fit, wider directives hold one character per value
+	fn fold_data(&mut self, data: &mut [Literal<'s>], bits: u32) -> Result<(), FoldError> {
+		let min = -(1i64 << (bits - 1));
+		let max = (1i64 << bits) - 1;
+		let fits = |value: i64| (min..=max).contains(&value);
+
+		for lit in data {
+			match lit {
+				Literal::Immediate(imm) => {
+					self.fold_immediate(imm)?;
+
+					if let Some(value) = imm.value() && !fits(value as i64) {
+						return Err(FoldError::DataOutOfRange {
+							src_file: self.source_file.to_string(),
+							location: Box::new(LocationInfo::from(&imm.rpn_tokens[0])),
+							value: value as i64,
+							bits,
+						});
+					}
+				},
+				Literal::String(s, token) if bits > 8 => {
+					if let Some(c) = unescape(s).chars().find(|c| !fits(*c as i64)) {
+						return Err(self.char_out_of_range(c, bits, token));
+					}
+				},
+				Literal::Char(c, token) if !fits(*c as i64) => {
+					return Err(self.char_out_of_range(*c, bits, token));
+				},
+				Literal::String(..) | Literal::Char(..) => (),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Make an error for a character of the given literal that does not fit
+	/// in `bits` bits
+	fn char_out_of_range(&self, value: char, bits: u32, token: &Token<'s>) -> FoldError {
+		FoldError::CharOutOfRange {
+			src_file: self.source_file.to_string(),
+			location: Box::new(LocationInfo::from(token)),
+			value,
+			bits,
+		}
+	}
+
+	/// Fold the value of a [`ConstDirective`] and declare the constant
+	///
+	/// String constants can not be used in immediates and so are not declared.
+	/// Errors if the constant was declared before, unless both declarations
+	/// use `#SET`
+	fn fold_const_directive(
+		&mut self,
+		const_dir: &mut ConstDirective<'s>,
+		start: &Token<'s>,
+	) -> Result<(), FoldError> {
+		let previous = self.declared.insert(const_dir.id, const_dir.reassignable);
+		if let Some(reassignable) = previous && !(reassignable && const_dir.reassignable) {
+			return Err(FoldError::ConstantRedefinition {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(start)),
+				id:       const_dir.id.to_string(),
+			});
+		}
+
+		match &mut const_dir.value {
+			Literal::Immediate(imm) => {
+				self.fold_immediate(imm)?;
+
+				match imm.value() {
+					Some(value) => self.define(const_dir.id, value),
+					None => {
+						self.deferred.insert(const_dir.id);
+					},
+				};
+			},
+			Literal::Char(c, _) => self.define(const_dir.id, *c as i32),
+			Literal::String(..) => (),
+		}
+
+		Ok(())
+	}
+
+	/// Declare the value of a constant or label
+	fn define(&mut self, id: &'s str, value: i32) {
+		self.deferred.remove(id);
+		self.consts.insert(id, value);
+	}
+
+	/// Remember a reference to a weak label that is never declared, only the
+	/// first reference to every label is kept
+	fn reference_weak(&self, token: &Token<'s>) {
+		let mut weak_refs = self.weak_refs.borrow_mut();
+
+		if !weak_refs.iter().any(|r| r.t == token.t) {
+			weak_refs.push(*token);
+		}
+	}
+
+	/// Get the address of the statement being folded
+	///
+	/// Only valid while folding a section
+	fn current_address(&self) -> u32 { self.location.unwrap_or(0) }
+
+	/// Move the location counter forward by `size` bytes, past the statement
+	/// starting at `start`
+	///
+	/// Errors if the statement does not end within the address space
+	fn advance(&mut self, size: u64, start: &Token<'s>) -> Result<(), FoldError> {
+		if let Some(location) = self.location {
+			let end = location as u64 + size;
+			if end > u32::MAX as u64 {
+				return Err(FoldError::AddressOverflow {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(start)),
+					addr: location,
+					size,
+				});
+			}
+
+			self.location = Some(end as u32);
+		}
+
+		Ok(())
+	}
+
+	/// Get the amount of bytes a folded [`Directive`] will be encoded into
+	///
+	/// Errors if a reservation count or alignment could not be folded yet,
+	/// or if an included file can not be read
+	fn directive_size(&self, dir: &Directive<'s>, start: &Token<'s>) -> Result<u64, FoldError> {
+		if let Some(size) = dir.size_bytes() {
+			return Ok(size);
+		}
+
+		if let Directive::IncludeBin { path } = dir {
+			let path = unescape(path);
+			let metadata = std::fs::metadata(include_path(self.source_file, &path));
+
+			return metadata.map(|m| m.len()).map_err(|err| {
+				FoldError::IncludeFailed {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(start)),
+					path: path.to_string(),
+					err,
+				}
+			});
+		}
+
+		if let Directive::Align { boundary } = dir && let Some(boundary) = boundary.value() {
+			return Ok((self.current_address().wrapping_neg() & (boundary as u32 - 1)) as u64);
+		}
+
+		if let Directive::Org { addr } = dir && let Some(addr) = addr.value() {
+			return Ok((addr as u32).wrapping_sub(self.current_address()) as u64);
+		}
+
+		let unresolved = match dir {
+			Directive::ResBytes(lits) | Directive::ResHalves(lits) | Directive::ResWords(lits) => {
+				lits.iter().find_map(|lit| {
+					match lit {
+						Literal::Immediate(imm) if imm.value().is_none() => Some(imm),
+						_ => None,
+					}
+				})
+			},
+			Directive::Bits(fields) => {
+				fields.iter().map(|field| &field.width).find(|width| width.value().is_none())
+			},
+			Directive::Align { boundary } => Some(boundary),
+			Directive::Org { addr } => Some(addr),
+			_ => None,
+		};
+
+		// Only unresolved reservation counts, bit field widths, alignments, or
+		// addresses can make the size unknown
+		let imm = unresolved.unwrap();
+
+		Err(FoldError::UnresolvedSize {
+			src_file: self.source_file.to_string(),
+			location: Box::new(LocationInfo::from(&imm.rpn_tokens[0])),
+		})
+	}
+
+	/// Check that a folded [`Immediate`] lies within the given range
+	///
+	/// Immediates that could not be folded yet are not checked
+	fn check_range(&self, imm: &Immediate<'s>, min: i64, max: i64) -> Result<(), FoldError> {
+		match imm.value() {
+			Some(value) if !(min..=max).contains(&(value as i64)) => {
+				Err(FoldError::ImmediateOutOfRange {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(&imm.rpn_tokens[0])),
+					value: value as i64,
+					min,
+					max,
+				})
+			},
+			_ => Ok(()),
+		}
+	}
+}
+
+/// Replace every value of a `#BYTES`, `#HALVES`, or `#WORDS` directive by 0
+/// without changing its size
+///
+/// Returns false if the directive is not a data directive, or if it
+/// contains a string whose characters take up a value each
+fn poison_data(dir: &mut Directive) -> bool {
+	let (data, bits) = match dir {
+		Directive::Bytes(data) => (data, 8),
+		Directive::Halves(data) => (data, 16),
+		Directive::Words(data) => (data, 32),
+		_ => return false,
+	};
+	if bits > 8 && data.iter().any(|lit| matches!(lit, Literal::String(..))) {
+		return false;
+	}
+
+	for lit in data {
+		match lit {
+			Literal::Immediate(imm) => {
+				let token = Token { t: TokenType::LitNum(0), ..imm.rpn_tokens[0] };
+				imm.rpn_tokens = vec![token];
+			},
+			Literal::Char(c, _) => *c = '\0',
+			// Strings in byte directives always fit
+			Literal::String(..) => (),
+		}
+	}
+
+	true
+}
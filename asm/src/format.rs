@@ -0,0 +1,140 @@
+//! # Formatter
+//!
+//! Rewrites source code in a canonical layout, similar to what `rustfmt`
+//! does for Rust code:
+//!  - Labeled blocks and macro definitions are indented by one tab per level
+//!  - Mnemonics are padded to [`MNEMONIC_WIDTH`] so the operands of instructions line up
+//!  - Commas are followed by a single space and never preceded by one, any other whitespace between
+//!    two tokens is reduced to a single space
+//!  - Comments are separated from any code before them by a single space, and their text from the
+//!    `;` by a single space
+//!  - Consecutive blank lines are merged into one, blank lines at the start and end of the file are
+//!    removed
+//!
+//! Formatting works on the [`Token`]s of a file rather than on its AST, so
+//! every token is written exactly like it appears in the source code, eg.
+//! numbers keep their [`Radix`](crate::lex::Radix), and macro definitions
+//! are formatted like any other code
+//!
+//! Formatting is idempotent, formatting an already formatted file does not
+//! change it
+
+use crate::error::Error;
+use crate::lex::{Includes, Isa, Lexer, Token, TokenType};
+use crate::parse::Parser;
+
+/// The width mnemonics are padded to, so that the operands of all
+/// instructions start in the same column
+pub const MNEMONIC_WIDTH: usize = 8;
+
+/// Format the contents of the file named `src_file`
+///
+/// Only files that can be parsed are formatted, for any other file the
+/// error that prevented parsing it is returned. Included files are read to
+/// check this, but are not formatted themselves
+pub fn format(src_file: &str, source: &str) -> Result<String, Error> {
+	let includes = Includes::load(src_file, source, Isa::default())?;
+	Parser::from_stream(src_file, includes.expand(Lexer::new(src_file, source))).parse()?;
+
+	let tokens: Vec<Token> = Lexer::new(src_file, source).collect::<Result<_, _>>()?;
+
+	let mut formatted = String::new();
+	let mut depth: usize = 0;
+	// Starts out true so blank lines at the start of the file are removed
+	let mut prev_blank = true;
+
+	for line in tokens.split(|token| token.t == TokenType::SymNewline) {
+		if line.is_empty() {
+			if !prev_blank {
+				formatted.push('\n');
+			}
+			prev_blank = true;
+
+			continue;
+		}
+		prev_blank = false;
+
+		// Closing braces are indented like the line that opened them
+		let closing = line.iter().take_while(|token| token.t == TokenType::SymRightBrace).count();
+		for _ in 0..depth.saturating_sub(closing) {
+			formatted.push('\t');
+		}
+
+		format_line(line, &mut formatted);
+		formatted.push('\n');
+
+		for token in line {
+			match token.t {
+				TokenType::SymLeftBrace => depth += 1,
+				TokenType::SymRightBrace => depth = depth.saturating_sub(1),
+				_ => (),
+			}
+		}
+	}
+
+	let len = formatted.trim_end_matches('\n').len();
+	formatted.truncate(len);
+	if !formatted.is_empty() {
+		formatted.push('\n');
+	}
+
+	Ok(formatted)
+}
+
+/// Format the tokens of a single line, without its indentation and newline
+fn format_line(line: &[Token], formatted: &mut String) {
+	for (i, token) in line.iter().enumerate() {
+		let next = line.get(i + 1);
+
+		match token.t {
+			TokenType::Comment(comment) => formatted.push_str(&format_comment(comment)),
+			// Mnemonics are only padded if any operands follow them
+			TokenType::Inst(_) if i == 0 && next.is_some_and(|next| !is_comment(next)) => {
+				formatted.push_str(&format!("{:<1$}", token.raw(), MNEMONIC_WIDTH - 1));
+			},
+			_ => formatted.push_str(token.raw()),
+		}
+
+		if let Some(next) = next {
+			formatted.push_str(separator(token, next));
+		}
+	}
+}
+
+/// Get the whitespace that should separate two consecutive tokens on the
+/// same line
+///
+/// Tokens that were written without any whitespace between them stay that
+/// way, eg. the `!` of a macro invocation. This includes operators directly
+/// following a comma, like the repetition in `$( $i:ident ),+`
+fn separator(prev: &Token, next: &Token) -> &'static str {
+	let adjacent = prev.line == next.line && prev.col + prev.span == next.col;
+
+	match (prev.t, next.t) {
+		(_, TokenType::SymComma) => "",
+		(TokenType::SymComma, TokenType::Op(_)) if adjacent => "",
+		(TokenType::SymComma, _) | (_, TokenType::Comment(_)) => " ",
+		(TokenType::Inst(_), _) => " ",
+		_ if adjacent => "",
+		_ => " ",
+	}
+}
+
+/// Normalize the spacing of a comment
+///
+/// Text directly following the `;` gets separated from it by a space, so
+/// `;comment` becomes `; comment`, but separator lines like `;----` are left
+/// as is
+fn format_comment(comment: &str) -> String {
+	let comment = comment.trim_end();
+	let text = comment.trim_start_matches(';');
+	let markers = &comment[..comment.len() - text.len()];
+
+	match text.chars().next() {
+		Some(c) if c.is_alphanumeric() => format!("{} {}", markers, text),
+		_ => comment.to_string(),
+	}
+}
+
+/// Check if a token is a comment
+fn is_comment(token: &Token) -> bool { matches!(token.t, TokenType::Comment(_)) }
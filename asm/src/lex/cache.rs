@@ -0,0 +1,298 @@
+//! On-disk cache of lexed [`Token`]s
+//!
+//! Lexing a large file on every assembly is wasted work if the file has not
+//! changed, so the tokens of a file can be stored in a compact cache file and
+//! read back later
+//!
+//! Tokens borrow from the source code, so any strings they contain are stored
+//! as byte ranges into the source instead of being copied. A cache entry is
+//! keyed by the path and modification time of the source file, as well as the
+//! length and hash of its contents, any change to the source invalidates it.
+//! The entry is also keyed by the instruction set the source was lexed for,
+//! as it decides which instructions are rejected. All multi-byte values are
+//! stored in little-endian order
+//!
+//! ```text
+//! header =
+//!     magic:       "WPIT"
+//!     version:     u8
+//!     mtime_secs:  u64
+//!     mtime_nanos: u32
+//!     path_len:    u16
+//!     path:        [u8; path_len]
+//!     source_len:  u32
+//!     source_hash: u64
+//!     isa:         u8 (bit 0: M, bit 1: Zicsr)
+//!     token_count: u32
+//!
+//! token =
+//!     kind:        u8
+//!     payload:     (depends on kind)
+//!     line:        u32
+//!     col:         u32
+//!     span:        u32
+//!     source_line: range
+//!
+//! range =
+//!     start: u32
+//!     len:   u32
+//! ```
+//!
+//! Instructions, registers, directives, and operators are stored as their
+//! index into [`InstToken::ALL`], [`RegToken::ALL`], [`DirToken::ALL`], and
+//! [`OpToken::ALL`] respectively
+
+use std::hash::Hasher;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use super::identifier::FnvHasher;
+use super::{DirToken, InstToken, Isa, OpToken, RegToken, Token, TokenType};
+
+/// The magic bytes every token cache file starts with
+const MAGIC: &[u8; 4] = b"WPIT";
+
+/// The version of the cache format, caches of any other version are ignored
+const VERSION: u8 = 3;
+
+/// Read the tokens of `source`, lexed for the instruction set `isa`, from the
+/// cache at `cache_path`
+///
+/// Returns [`None`] if there is no cache, if it cannot be read, or if it was
+/// written for a different path, a different version of the source file, or
+/// a different instruction set
+pub fn read_cache<'s>(
+	source_path: &Path,
+	source: &'s str,
+	isa: Isa,
+	cache_path: &Path,
+) -> Option<Vec<Token<'s>>> {
+	let bytes = std::fs::read(cache_path).ok()?;
+	let mut reader = Reader { bytes: &bytes, pos: 0 };
+
+	if reader.take(MAGIC.len())? != MAGIC || reader.read_u8()? != VERSION {
+		return None;
+	}
+
+	let header = cache_header(source_path, source, isa)?;
+	if reader.take(header.len())? != header {
+		return None;
+	}
+
+	let token_count = reader.read_u32()?;
+
+	let mut tokens = Vec::new();
+	for _ in 0..token_count {
+		let t = read_token_type(&mut reader, source)?;
+		let line = reader.read_u32()? as usize;
+		let col = reader.read_u32()? as usize;
+		let span = reader.read_u32()? as usize;
+		let source_line = reader.read_range(source)?;
+
+		tokens.push(Token { t, line, col, span, included_file: None, source_line });
+	}
+
+	// Trailing bytes mean the cache is corrupted
+	(reader.pos == bytes.len()).then_some(tokens)
+}
+
+/// Write the tokens of `source`, lexed for the instruction set `isa`, to a
+/// cache at `cache_path`
+///
+/// All strings contained in the tokens must be slices of `source`, as is the
+/// case for any tokens produced by a [`Lexer`](super::Lexer) over `source`
+pub fn write_cache(
+	source_path: &Path,
+	source: &str,
+	isa: Isa,
+	tokens: &[Token],
+	cache_path: &Path,
+) -> std::io::Result<()> {
+	let header = cache_header(source_path, source, isa).ok_or_else(|| {
+		std::io::Error::new(std::io::ErrorKind::InvalidInput, "source file cannot be cached")
+	})?;
+
+	let mut bytes = Vec::new();
+
+	bytes.extend_from_slice(MAGIC);
+	bytes.push(VERSION);
+	bytes.extend_from_slice(&header);
+	bytes.extend_from_slice(&(tokens.len() as u32).to_le_bytes());
+
+	for token in tokens {
+		write_token_type(&mut bytes, source, token.t);
+		bytes.extend_from_slice(&(token.line as u32).to_le_bytes());
+		bytes.extend_from_slice(&(token.col as u32).to_le_bytes());
+		bytes.extend_from_slice(&(token.span as u32).to_le_bytes());
+		write_range(&mut bytes, source, token.source_line);
+	}
+
+	std::fs::write(cache_path, bytes)
+}
+
+/// Build the part of the header identifying the source file and the
+/// instruction set it was lexed for
+///
+/// Returns [`None`] if the modification time of the file cannot be read
+fn cache_header(source_path: &Path, source: &str, isa: Isa) -> Option<Vec<u8>> {
+	let mtime = std::fs::metadata(source_path).ok()?.modified().ok()?;
+	let mtime = mtime.duration_since(UNIX_EPOCH).ok()?;
+	let path = source_path.to_str()?;
+
+	let mut hasher = FnvHasher::default();
+	hasher.write(source.as_bytes());
+
+	let mut header = Vec::new();
+	header.extend_from_slice(&mtime.as_secs().to_le_bytes());
+	header.extend_from_slice(&mtime.subsec_nanos().to_le_bytes());
+	header.extend_from_slice(&(path.len() as u16).to_le_bytes());
+	header.extend_from_slice(path.as_bytes());
+	header.extend_from_slice(&(source.len() as u32).to_le_bytes());
+	header.extend_from_slice(&hasher.finish().to_le_bytes());
+	header.push(isa.m as u8 | (isa.zicsr as u8) << 1);
+
+	Some(header)
+}
+
+/// Serialize a [`TokenType`] as its kind followed by its payload
+fn write_token_type(bytes: &mut Vec<u8>, source: &str, t: TokenType) {
+	let index = |i: Option<usize>| i.unwrap() as u8; // Unwrap is safe as ALL contains every variant
+
+	match t {
+		TokenType::Inst(inst) => {
+			bytes.push(0);
+			bytes.push(index(InstToken::ALL.iter().position(|&i| i == inst)));
+		},
+		TokenType::Reg(reg) => {
+			bytes.push(1);
+			bytes.push(reg.index());
+		},
+		TokenType::Dir(dir) => {
+			bytes.push(2);
+			bytes.push(index(DirToken::ALL.iter().position(|&d| d == dir)));
+		},
+		TokenType::LitStr(s) => {
+			bytes.push(3);
+			write_range(bytes, source, s);
+		},
+		TokenType::LitChar(c) => {
+			bytes.push(4);
+			bytes.extend_from_slice(&(c as u32).to_le_bytes());
+		},
+		TokenType::LitNum(n) => {
+			bytes.push(5);
+			bytes.extend_from_slice(&n.to_le_bytes());
+		},
+		TokenType::Section(s) => {
+			bytes.push(6);
+			write_range(bytes, source, s);
+		},
+		TokenType::Identifier(s) => {
+			bytes.push(7);
+			write_range(bytes, source, s);
+		},
+		TokenType::LocalLabel(l) => {
+			bytes.push(8);
+			bytes.extend_from_slice(&l.to_le_bytes());
+		},
+		TokenType::LocalLabelBackward(l) => {
+			bytes.push(9);
+			bytes.extend_from_slice(&l.to_le_bytes());
+		},
+		TokenType::LocalLabelForward(l) => {
+			bytes.push(10);
+			bytes.extend_from_slice(&l.to_le_bytes());
+		},
+		TokenType::SymComma => bytes.push(11),
+		TokenType::SymNewline => bytes.push(12),
+		TokenType::SymLeftParen => bytes.push(13),
+		TokenType::SymRightParen => bytes.push(14),
+		TokenType::SymLeftBracket => bytes.push(15),
+		TokenType::SymRightBracket => bytes.push(16),
+		TokenType::SymLeftBrace => bytes.push(17),
+		TokenType::SymRightBrace => bytes.push(18),
+		TokenType::SymFatArrow => bytes.push(19),
+		TokenType::SymDot => bytes.push(20),
+		TokenType::Op(op) => {
+			bytes.push(21);
+			bytes.push(index(OpToken::ALL.iter().position(|&o| o == op)));
+		},
+		TokenType::Comment(s) => {
+			bytes.push(22);
+			write_range(bytes, source, s);
+		},
+	}
+}
+
+/// Deserialize a [`TokenType`] written by [`write_token_type`]
+fn read_token_type<'s>(reader: &mut Reader, source: &'s str) -> Option<TokenType<'s>> {
+	let t = match reader.read_u8()? {
+		0 => TokenType::Inst(*InstToken::ALL.get(reader.read_u8()? as usize)?),
+		1 => TokenType::Reg(RegToken::try_from(reader.read_u8()?).ok()?),
+		2 => TokenType::Dir(*DirToken::ALL.get(reader.read_u8()? as usize)?),
+		3 => TokenType::LitStr(reader.read_range(source)?),
+		4 => TokenType::LitChar(char::from_u32(reader.read_u32()?)?),
+		5 => TokenType::LitNum(reader.read_u32()? as i32),
+		6 => TokenType::Section(reader.read_range(source)?),
+		7 => TokenType::Identifier(reader.read_range(source)?),
+		8 => TokenType::LocalLabel(reader.read_u32()?),
+		9 => TokenType::LocalLabelBackward(reader.read_u32()?),
+		10 => TokenType::LocalLabelForward(reader.read_u32()?),
+		11 => TokenType::SymComma,
+		12 => TokenType::SymNewline,
+		13 => TokenType::SymLeftParen,
+		14 => TokenType::SymRightParen,
+		15 => TokenType::SymLeftBracket,
+		16 => TokenType::SymRightBracket,
+		17 => TokenType::SymLeftBrace,
+		18 => TokenType::SymRightBrace,
+		19 => TokenType::SymFatArrow,
+		20 => TokenType::SymDot,
+		21 => TokenType::Op(*OpToken::ALL.get(reader.read_u8()? as usize)?),
+		22 => TokenType::Comment(reader.read_range(source)?),
+		_ => return None,
+	};
+
+	Some(t)
+}
+
+/// Serialize a slice of `source` as its byte range
+fn write_range(bytes: &mut Vec<u8>, source: &str, s: &str) {
+	let start = s.as_ptr() as usize - source.as_ptr() as usize;
+
+	bytes.extend_from_slice(&(start as u32).to_le_bytes());
+	bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+}
+
+/// Cursor over the bytes of a cache file
+struct Reader<'b> {
+	bytes: &'b [u8],
+	pos:   usize,
+}
+
+impl<'b> Reader<'b> {
+	/// Take the next `len` bytes
+	fn take(&mut self, len: usize) -> Option<&'b [u8]> {
+		let end = self.pos.checked_add(len)?;
+		let slice = self.bytes.get(self.pos..end)?;
+		self.pos = end;
+
+		Some(slice)
+	}
+
+	fn read_u8(&mut self) -> Option<u8> { Some(self.take(1)?[0]) }
+
+	fn read_u32(&mut self) -> Option<u32> {
+		let bytes = self.take(4)?;
+		Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+
+	/// Read a byte range written by [`write_range`] and get the slice of
+	/// `source` it refers to
+	fn read_range<'s>(&mut self, source: &'s str) -> Option<&'s str> {
+		let start = self.read_u32()? as usize;
+		let len = self.read_u32()? as usize;
+
+		source.get(start..start.checked_add(len)?)
+	}
+}
@@ -5,178 +5,156 @@
 //!  - Registers ([`RegToken`])
 //!  - Directives ([`DirToken`])
 //!  - Section Names ([`TokenType::Section`])
-//!  - Labels and LabelDefines ([`TokenType::Label`], [`TokenType::LocalLabel`],
-//!    [`TokenType::LabelDefine`], [`TokenType::LocalLabelDefine`])
+//!  - The current address ([`TokenType::SymDot`])
+//!  - Identifiers naming labels, constants, and macros ([`TokenType::Identifier`])
+//!
+//! Instructions and registers are looked up in a table of keywords that is
+//! only built once, everything else is matched by hand
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::OnceLock;
 
 use super::token::{DirToken, InstToken, RegToken};
-use super::{
-	BranchInstruction,
-	CsrInstruction,
-	CsriInstruction,
-	Lexer,
-	LoadInstruction,
-	MdrInstruction,
-	RegularDirective,
-	RriInstruction,
-	RrrInstruction,
-	StoreInstruction,
-	Token,
-	TokenType,
-};
+use super::{Extension, Lexer, RegularDirective, Token, TokenType};
 use crate::error::LexError;
 
+/// The ABI names of registers, along with the register they alias
+const ABI_ALIASES: [(&str, RegToken); 33] = [
+	("zero", RegToken::R0),
+	("ra", RegToken::R1),
+	("sp", RegToken::R2),
+	("gp", RegToken::R3),
+	("tp", RegToken::R4),
+	("fp", RegToken::R8),
+	("a0", RegToken::R10),
+	("a1", RegToken::R11),
+	("a2", RegToken::R12),
+	("a3", RegToken::R13),
+	("a4", RegToken::R14),
+	("a5", RegToken::R15),
+	("a6", RegToken::R16),
+	("a7", RegToken::R17),
+	("s0", RegToken::R8),
+	("s1", RegToken::R9),
+	("s2", RegToken::R18),
+	("s3", RegToken::R19),
+	("s4", RegToken::R20),
+	("s5", RegToken::R21),
+	("s6", RegToken::R22),
+	("s7", RegToken::R23),
+	("s8", RegToken::R24),
+	("s9", RegToken::R25),
+	("s10", RegToken::R26),
+	("s11", RegToken::R27),
+	("t0", RegToken::R5),
+	("t1", RegToken::R6),
+	("t2", RegToken::R7),
+	("t3", RegToken::R28),
+	("t4", RegToken::R29),
+	("t5", RegToken::R30),
+	("t6", RegToken::R31),
+];
+
+/// The table of keywords, see [`keywords`]
+type KeywordTable = HashMap<String, TokenType<'static>, BuildHasherDefault<FnvHasher>>;
+
+/// A [FNV-1a](https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function)
+/// hasher
+///
+/// Keywords are short and not attacker controlled, so this is a lot faster
+/// than the default hasher without any downsides
+pub(super) struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+	fn default() -> Self { Self(0xCBF2_9CE4_8422_2325) }
+}
+
+impl Hasher for FnvHasher {
+	fn finish(&self) -> u64 { self.0 }
+
+	fn write(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+		}
+	}
+}
+
+/// Get the table mapping every (lowercase) instruction mnemonic, register
+/// name, and register alias to its token
+fn keywords() -> &'static KeywordTable {
+	static KEYWORDS: OnceLock<KeywordTable> = OnceLock::new();
+
+	KEYWORDS.get_or_init(|| {
+		let instructions = InstToken::ALL.iter().map(|&i| (i.to_string(), TokenType::Inst(i)));
+		let registers = RegToken::ALL.iter().map(|&r| (r.to_string(), TokenType::Reg(r)));
+		let aliases = ABI_ALIASES.iter().map(|&(name, r)| (name.to_string(), TokenType::Reg(r)));
+
+		instructions.chain(registers).chain(aliases).collect()
+	})
+}
+
+/// Get the token of the instruction or register named by an identifier, if
+/// it names one
+///
+/// Matching is case insensitive
+pub fn keyword(id: &str) -> Option<TokenType<'static>> {
+	keywords().get(&to_lowercase(id)[..]).copied()
+}
+
+/// Lowercase an identifier, only allocating if it is not lowercase already
+fn to_lowercase(id: &str) -> Cow<'_, str> {
+	if id.bytes().all(|b| b.is_ascii() && !b.is_ascii_uppercase()) {
+		Cow::Borrowed(id)
+	} else {
+		Cow::Owned(id.to_lowercase())
+	}
+}
+
 impl<'s> Lexer<'s> {
+	/// Check that an instruction token is part of the enabled instruction
+	/// set, other tokens are always accepted
+	pub(super) fn check_isa(&self, token: Token<'s>) -> Result<Token<'s>, LexError> {
+		let extension = match token.t {
+			TokenType::Inst(inst) => Extension::of(inst),
+			_ => None,
+		};
+
+		match extension {
+			Some(ext) if !self.isa.has(ext) => {
+				Err(LexError::UnsupportedInstruction {
+					src_file:    self.source_file.to_string(),
+					line:        token.line,
+					col:         token.col,
+					span:        token.span,
+					src_line:    token.source_line.to_string(),
+					instruction: token.t.to_string(),
+					extension:   ext,
+					isa:         self.isa,
+				})
+			},
+			_ => Ok(token),
+		}
+	}
+
 	/// Attempt to match an identifier to an instruction, register, section
 	/// name, or directive, or return a new label if a match is not found
+	///
+	/// Matching is case insensitive
 	pub(super) fn match_identifier(&mut self, id: &'s str) -> Result<Token<'s>, LexError> {
-		match &id.to_lowercase()[..] {
-			"addi" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Addi)))),
-			"andi" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Andi)))),
-			"ori" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Ori)))),
-			"xori" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Xori)))),
-			"lsli" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Lsli)))),
-			"lsri" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Lsri)))),
-			"asri" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Asri)))),
-			"slti" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Slti)))),
-			"sltiu" => Ok(self.make_token(TokenType::Inst(InstToken::Rri(RriInstruction::Sltiu)))),
-			"add" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Add)))),
-			"sub" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Sub)))),
-			"and" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::And)))),
-			"or" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Or)))),
-			"xor" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Xor)))),
-			"lsl" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Lsl)))),
-			"lsr" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Lsr)))),
-			"asr" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Asr)))),
-			"slt" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Slt)))),
-			"sltu" => Ok(self.make_token(TokenType::Inst(InstToken::Rrr(RrrInstruction::Sltu)))),
-			"lw" => Ok(self.make_token(TokenType::Inst(InstToken::Load(LoadInstruction::Lw)))),
-			"lh" => Ok(self.make_token(TokenType::Inst(InstToken::Load(LoadInstruction::Lh)))),
-			"lhu" => Ok(self.make_token(TokenType::Inst(InstToken::Load(LoadInstruction::Lhu)))),
-			"lb" => Ok(self.make_token(TokenType::Inst(InstToken::Load(LoadInstruction::Lb)))),
-			"lbu" => Ok(self.make_token(TokenType::Inst(InstToken::Load(LoadInstruction::Lbu)))),
-			"sw" => Ok(self.make_token(TokenType::Inst(InstToken::Store(StoreInstruction::Sw)))),
-			"sh" => Ok(self.make_token(TokenType::Inst(InstToken::Store(StoreInstruction::Sh)))),
-			"sb" => Ok(self.make_token(TokenType::Inst(InstToken::Store(StoreInstruction::Sb)))),
-			"lui" => Ok(self.make_token(TokenType::Inst(InstToken::Lui))),
-			"auipc" => Ok(self.make_token(TokenType::Inst(InstToken::Auipc))),
-			"beq" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Branch(BranchInstruction::Beq))))
-			},
-			"bne" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Branch(BranchInstruction::Bne))))
-			},
-			"blt" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Branch(BranchInstruction::Blt))))
-			},
-			"bltu" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Branch(BranchInstruction::Bltu))))
-			},
-			"bge" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Branch(BranchInstruction::Bge))))
-			},
-			"bgeu" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Branch(BranchInstruction::Bgeu))))
-			},
-			"jal" => Ok(self.make_token(TokenType::Inst(InstToken::Jal))),
-			"jalr" => Ok(self.make_token(TokenType::Inst(InstToken::Jalr))),
-			"ecall" => Ok(self.make_token(TokenType::Inst(InstToken::Ecall))),
-			"ebreak" => Ok(self.make_token(TokenType::Inst(InstToken::Ebreak))),
-			"fence" => Ok(self.make_token(TokenType::Inst(InstToken::Fence))),
-			"fence.tso" => Ok(self.make_token(TokenType::Inst(InstToken::FenceTso))),
-			"fence.i" => Ok(self.make_token(TokenType::Inst(InstToken::Fencei))),
-			"csrrw" => Ok(self.make_token(TokenType::Inst(InstToken::Csr(CsrInstruction::Csrrw)))),
-			"csrrs" => Ok(self.make_token(TokenType::Inst(InstToken::Csr(CsrInstruction::Csrrs)))),
-			"csrrc" => Ok(self.make_token(TokenType::Inst(InstToken::Csr(CsrInstruction::Csrrc)))),
-			"csrrwi" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Csri(CsriInstruction::Csrrwi))))
-			},
-			"csrrsi" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Csri(CsriInstruction::Csrrsi))))
-			},
-			"csrrci" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Csri(CsriInstruction::Csrrci))))
-			},
-			"mul" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mul)))),
-			"mulh" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mulh)))),
-			"mulhu" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mulhu)))),
-			"mulhsu" => {
-				Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Mulhsu))))
-			},
-			"div" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Div)))),
-			"divu" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Divu)))),
-			"rem" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Rem)))),
-			"remu" => Ok(self.make_token(TokenType::Inst(InstToken::Mdr(MdrInstruction::Remu)))),
-
-			"r0" => Ok(self.make_token(TokenType::Reg(RegToken::R0))),
-			"r1" => Ok(self.make_token(TokenType::Reg(RegToken::R1))),
-			"r2" => Ok(self.make_token(TokenType::Reg(RegToken::R2))),
-			"r3" => Ok(self.make_token(TokenType::Reg(RegToken::R3))),
-			"r4" => Ok(self.make_token(TokenType::Reg(RegToken::R4))),
-			"r5" => Ok(self.make_token(TokenType::Reg(RegToken::R5))),
-			"r6" => Ok(self.make_token(TokenType::Reg(RegToken::R6))),
-			"r7" => Ok(self.make_token(TokenType::Reg(RegToken::R7))),
-			"r8" => Ok(self.make_token(TokenType::Reg(RegToken::R8))),
-			"r9" => Ok(self.make_token(TokenType::Reg(RegToken::R9))),
-			"r10" => Ok(self.make_token(TokenType::Reg(RegToken::R10))),
-			"r11" => Ok(self.make_token(TokenType::Reg(RegToken::R11))),
-			"r12" => Ok(self.make_token(TokenType::Reg(RegToken::R12))),
-			"r13" => Ok(self.make_token(TokenType::Reg(RegToken::R13))),
-			"r14" => Ok(self.make_token(TokenType::Reg(RegToken::R14))),
-			"r15" => Ok(self.make_token(TokenType::Reg(RegToken::R15))),
-			"r16" => Ok(self.make_token(TokenType::Reg(RegToken::R16))),
-			"r17" => Ok(self.make_token(TokenType::Reg(RegToken::R17))),
-			"r18" => Ok(self.make_token(TokenType::Reg(RegToken::R18))),
-			"r19" => Ok(self.make_token(TokenType::Reg(RegToken::R19))),
-			"r20" => Ok(self.make_token(TokenType::Reg(RegToken::R20))),
-			"r21" => Ok(self.make_token(TokenType::Reg(RegToken::R21))),
-			"r22" => Ok(self.make_token(TokenType::Reg(RegToken::R22))),
-			"r23" => Ok(self.make_token(TokenType::Reg(RegToken::R23))),
-			"r24" => Ok(self.make_token(TokenType::Reg(RegToken::R24))),
-			"r25" => Ok(self.make_token(TokenType::Reg(RegToken::R25))),
-			"r26" => Ok(self.make_token(TokenType::Reg(RegToken::R26))),
-			"r27" => Ok(self.make_token(TokenType::Reg(RegToken::R27))),
-			"r28" => Ok(self.make_token(TokenType::Reg(RegToken::R28))),
-			"r29" => Ok(self.make_token(TokenType::Reg(RegToken::R29))),
-			"r30" => Ok(self.make_token(TokenType::Reg(RegToken::R30))),
-			"r31" => Ok(self.make_token(TokenType::Reg(RegToken::R31))),
-
-			"zero" => Ok(self.make_token(TokenType::Reg(RegToken::R0))),
-			"ra" => Ok(self.make_token(TokenType::Reg(RegToken::R1))),
-			"sp" => Ok(self.make_token(TokenType::Reg(RegToken::R2))),
-			"gp" => Ok(self.make_token(TokenType::Reg(RegToken::R3))),
-			"tp" => Ok(self.make_token(TokenType::Reg(RegToken::R4))),
-			"fp" => Ok(self.make_token(TokenType::Reg(RegToken::R8))),
-			"a0" => Ok(self.make_token(TokenType::Reg(RegToken::R10))),
-			"a1" => Ok(self.make_token(TokenType::Reg(RegToken::R11))),
-			"a2" => Ok(self.make_token(TokenType::Reg(RegToken::R12))),
-			"a3" => Ok(self.make_token(TokenType::Reg(RegToken::R13))),
-			"a4" => Ok(self.make_token(TokenType::Reg(RegToken::R14))),
-			"a5" => Ok(self.make_token(TokenType::Reg(RegToken::R15))),
-			"a6" => Ok(self.make_token(TokenType::Reg(RegToken::R16))),
-			"a7" => Ok(self.make_token(TokenType::Reg(RegToken::R17))),
-			"s0" => Ok(self.make_token(TokenType::Reg(RegToken::R8))),
-			"s1" => Ok(self.make_token(TokenType::Reg(RegToken::R9))),
-			"s2" => Ok(self.make_token(TokenType::Reg(RegToken::R18))),
-			"s3" => Ok(self.make_token(TokenType::Reg(RegToken::R19))),
-			"s4" => Ok(self.make_token(TokenType::Reg(RegToken::R20))),
-			"s5" => Ok(self.make_token(TokenType::Reg(RegToken::R21))),
-			"s6" => Ok(self.make_token(TokenType::Reg(RegToken::R22))),
-			"s7" => Ok(self.make_token(TokenType::Reg(RegToken::R23))),
-			"s8" => Ok(self.make_token(TokenType::Reg(RegToken::R24))),
-			"s9" => Ok(self.make_token(TokenType::Reg(RegToken::R25))),
-			"s10" => Ok(self.make_token(TokenType::Reg(RegToken::R26))),
-			"s11" => Ok(self.make_token(TokenType::Reg(RegToken::R27))),
-			"t0" => Ok(self.make_token(TokenType::Reg(RegToken::R5))),
-			"t1" => Ok(self.make_token(TokenType::Reg(RegToken::R6))),
-			"t2" => Ok(self.make_token(TokenType::Reg(RegToken::R7))),
-			"t3" => Ok(self.make_token(TokenType::Reg(RegToken::R28))),
-			"t4" => Ok(self.make_token(TokenType::Reg(RegToken::R29))),
-			"t5" => Ok(self.make_token(TokenType::Reg(RegToken::R30))),
-			"t6" => Ok(self.make_token(TokenType::Reg(RegToken::R31))),
+		if let Some(keyword) = keyword(id) {
+			return Ok(self.make_token(keyword));
+		}
 
+		match &to_lowercase(id)[..] {
 			d if d.starts_with('#') => {
 				match d {
 					"#section" => Ok(self.make_token(TokenType::Dir(DirToken::Section))),
+					"#if" => Ok(self.make_token(TokenType::Dir(DirToken::If))),
+					"#else" => Ok(self.make_token(TokenType::Dir(DirToken::Else))),
+					"#endif" => Ok(self.make_token(TokenType::Dir(DirToken::Endif))),
+					"#include" => Ok(self.make_token(TokenType::Dir(DirToken::Include))),
 					"#bytes" => {
 						Ok(self
 							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Bytes))))
@@ -190,6 +168,10 @@ impl<'s> Lexer<'s> {
 						Ok(self
 							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Words))))
 					},
+					"#bits" => {
+						Ok(self
+							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Bits))))
+					},
 					"#res_bytes" => {
 						Ok(self.make_token(TokenType::Dir(DirToken::Regular(
 							RegularDirective::ResBytes,
@@ -209,6 +191,37 @@ impl<'s> Lexer<'s> {
 						Ok(self
 							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Const))))
 					},
+					"#set" => {
+						Ok(self
+							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Set))))
+					},
+					"#include_bin" => {
+						Ok(self.make_token(TokenType::Dir(DirToken::Regular(
+							RegularDirective::IncludeBin,
+						))))
+					},
+					"#extern" => {
+						Ok(self.make_token(TokenType::Dir(DirToken::Regular(
+							RegularDirective::Extern,
+						))))
+					},
+					"#global" => {
+						Ok(self.make_token(TokenType::Dir(DirToken::Regular(
+							RegularDirective::Global,
+						))))
+					},
+					"#weak" => {
+						Ok(self
+							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Weak))))
+					},
+					"#align" => {
+						Ok(self
+							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Align))))
+					},
+					"#org" => {
+						Ok(self
+							.make_token(TokenType::Dir(DirToken::Regular(RegularDirective::Org))))
+					},
 					_ => {
 						Err(LexError::InvalidDirective {
 							src_file: self.source_file.to_string(),
@@ -226,6 +239,8 @@ impl<'s> Lexer<'s> {
 			".data" => Ok(self.make_token(TokenType::Section(id))),
 			".bss" => Ok(self.make_token(TokenType::Section(id))),
 
+			"." => Ok(self.make_token(TokenType::SymDot)),
+
 			_ => Ok(self.make_token(TokenType::Identifier(id))),
 		}
 	}
@@ -0,0 +1,180 @@
+//! Textual inclusion of other source files
+//!
+//! An `#INCLUDE "file"` directive is replaced by the tokens of the file it
+//! names, as if the contents of that file were written in its place. Unlike
+//! [`#INCLUDE_BIN`](super::RegularDirective::IncludeBin), which embeds the raw
+//! bytes of a file, the included file is assembled as part of the including
+//! file and shares its namespace
+//!
+//! Paths are relative to the directory of the including file
+//!
+//! [`Token`]s borrow from the source code they were lexed from, so every
+//! included file is read up front by [`Includes::load`], after which
+//! [`Includes::expand`] splices their tokens into the token stream of the
+//! including file
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::{DirToken, Isa, Lexer, Token, TokenType};
+use crate::error::{Error, LexError, LocationInfo, ParseError};
+use crate::include_path;
+
+/// The contents of every file included by a source file, directly or
+/// through other included files
+#[derive(Debug, Default)]
+pub struct Includes {
+	/// The name and contents of every included file, by canonical path
+	files: HashMap<PathBuf, (String, String)>,
+	/// The instruction set whose instructions are recognised
+	isa:   Isa,
+}
+
+impl Includes {
+	/// Read every file included by the source code of the file named
+	/// `source_file`
+	///
+	/// Errors if an included file can not be read, or if a file includes
+	/// itself, directly or through other included files
+	pub fn load(source_file: &str, source: &str, isa: Isa) -> Result<Self, Error> {
+		let mut includes = Self { files: HashMap::new(), isa };
+
+		let mut stack = vec![canonical(Path::new(source_file))];
+		includes.load_file(source_file, source, &mut stack)?;
+
+		Ok(includes)
+	}
+
+	/// Read the files included by a single file, and recursively the files
+	/// included by those
+	///
+	/// `stack` holds the canonical paths of the files currently being
+	/// loaded, starting at the file being assembled
+	fn load_file(
+		&mut self,
+		source_file: &str,
+		source: &str,
+		stack: &mut Vec<PathBuf>,
+	) -> Result<(), Error> {
+		// Lexical errors are reported once the file gets assembled
+		let (tokens, _) =
+			Lexer::new(source_file, source).with_isa(self.isa).tokenize_with_recovery();
+
+		for pair in tokens.windows(2) {
+			let [directive, path_token] = pair else { unreachable!() };
+			if directive.t != TokenType::Dir(DirToken::Include) {
+				continue;
+			}
+
+			let TokenType::LitStr(path) = path_token.t else {
+				return Err(ParseError::UnexpectedToken {
+					src_file:  source_file.to_string(),
+					location:  Box::new(LocationInfo::from(path_token)),
+					found:     path_token.t.to_string(),
+					expected:  "string literal".to_string(),
+					statement: None,
+				}
+				.into());
+			};
+
+			let resolved = include_path(source_file, path);
+			let key = canonical(&resolved);
+			if stack.contains(&key) {
+				return Err(LexError::IncludeCycle {
+					src_file: source_file.to_string(),
+					line:     path_token.line,
+					col:      path_token.col,
+					span:     path_token.span,
+					src_line: path_token.source_line.to_string(),
+					path:     path.to_string(),
+				}
+				.into());
+			}
+			// Any cycle through a loaded file would have been found while
+			// loading it
+			if self.files.contains_key(&key) {
+				continue;
+			}
+
+			let contents = std::fs::read_to_string(&resolved).map_err(|err| {
+				LexError::IncludeFailed {
+					src_file: source_file.to_string(),
+					line: path_token.line,
+					col: path_token.col,
+					span: path_token.span,
+					src_line: path_token.source_line.to_string(),
+					path: path.to_string(),
+					err,
+				}
+			})?;
+			let name = resolved.to_string_lossy().to_string();
+
+			stack.push(key.clone());
+			self.load_file(&name, &contents, stack)?;
+			stack.pop();
+
+			self.files.insert(key, (name, contents));
+		}
+
+		Ok(())
+	}
+
+	/// Replace every `#INCLUDE` directive produced by a lexer by the tokens
+	/// of the file it includes
+	///
+	/// The lexer must lex the same source code these includes were
+	/// [loaded](Self::load) for
+	pub fn expand<'s>(&'s self, lexer: Lexer<'s>) -> Expand<'s> {
+		Expand { includes: self, lexers: vec![lexer] }
+	}
+}
+
+/// An iterator over the tokens of a file with all of its includes expanded,
+/// see [`Includes::expand`]
+pub struct Expand<'s> {
+	/// The included files
+	includes: &'s Includes,
+	/// The lexers of the file being assembled and every file being included,
+	/// the innermost include last
+	lexers:   Vec<Lexer<'s>>,
+}
+
+impl<'s> Iterator for Expand<'s> {
+	type Item = Result<Token<'s>, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let lexer = self.lexers.last_mut()?;
+
+			match Iterator::next(lexer) {
+				Some(Ok(token)) if token.t == TokenType::Dir(DirToken::Include) => {
+					let source_file = lexer.source_file;
+					let keep_going = lexer.keep_going;
+					// The path was checked while loading
+					let path = match Iterator::next(lexer) {
+						Some(Ok(Token { t: TokenType::LitStr(path), .. })) => path,
+						_ => unreachable!(),
+					};
+
+					let key = canonical(&include_path(source_file, path));
+					let (name, contents) = &self.includes.files[&key];
+					self.lexers.push(
+						Lexer::new(name, contents)
+							.with_isa(self.includes.isa)
+							.with_keep_going(keep_going)
+							.included(),
+					);
+				},
+				None => {
+					self.lexers.pop();
+				},
+				token => return token,
+			}
+		}
+	}
+}
+
+/// Get the canonical form of a path, or the path itself if it does not exist
+fn canonical(path: &Path) -> PathBuf {
+	std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,109 @@
+//! Instruction set profiles, controlling which instructions the lexer accepts
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use super::InstToken;
+
+/// The extensions on top of the RV32I base instruction set that may be
+/// enabled or disabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Extension {
+	/// Integer multiplication and division
+	M,
+	/// Control and status register instructions
+	Zicsr,
+}
+
+impl Extension {
+	/// Get the extension an instruction belongs to, or [`None`] if it is part
+	/// of the base instruction set
+	pub fn of(inst: InstToken) -> Option<Self> {
+		match inst {
+			InstToken::Mdr(_) => Some(Self::M),
+			InstToken::Csr(_) | InstToken::Csri(_) => Some(Self::Zicsr),
+			_ => None,
+		}
+	}
+}
+
+impl Display for Extension {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::M => write!(f, "M"),
+			Self::Zicsr => write!(f, "Zicsr"),
+		}
+	}
+}
+
+/// The instruction set the assembler targets, ie. the RV32I base and a set
+/// of enabled [`Extension`]s
+///
+/// Parsed from and displayed as a RISC-V ISA string, such as `rv32i`,
+/// `rv32im`, or `rv32im_zicsr`. Defaults to enabling every extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Isa {
+	/// Whether the [`M`](Extension::M) extension is enabled
+	pub m:     bool,
+	/// Whether the [`Zicsr`](Extension::Zicsr) extension is enabled
+	pub zicsr: bool,
+}
+
+impl Default for Isa {
+	fn default() -> Self { Self { m: true, zicsr: true } }
+}
+
+impl Isa {
+	/// The bare RV32I base instruction set, without any extensions
+	pub const RV32I: Self = Self { m: false, zicsr: false };
+
+	/// Check whether an extension is enabled
+	pub fn has(&self, ext: Extension) -> bool {
+		match ext {
+			Extension::M => self.m,
+			Extension::Zicsr => self.zicsr,
+		}
+	}
+
+	/// Check whether an instruction may be used
+	pub fn supports(&self, inst: InstToken) -> bool {
+		Extension::of(inst).map_or(true, |e| self.has(e))
+	}
+}
+
+impl FromStr for Isa {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let lower = s.to_lowercase();
+		let invalid =
+			|| format!("invalid ISA '{}', expected eg. rv32i, rv32im, or rv32im_zicsr", s);
+
+		let rest = lower.strip_prefix("rv32i").ok_or_else(invalid)?;
+		let (m, rest) = match rest.strip_prefix('m') {
+			Some(rest) => (true, rest),
+			None => (false, rest),
+		};
+		let zicsr = match rest {
+			"" => false,
+			"_zicsr" => true,
+			_ => return Err(invalid()),
+		};
+
+		Ok(Self { m, zicsr })
+	}
+}
+
+impl Display for Isa {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "rv32i")?;
+		if self.m {
+			write!(f, "m")?;
+		}
+		if self.zicsr {
+			write!(f, "_zicsr")?;
+		}
+
+		Ok(())
+	}
+}
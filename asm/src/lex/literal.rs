@@ -4,9 +4,13 @@
 //!  - characters (for [`LitChar`](crate::lex::TokenType::LitChar))
 //!  - string (for [`LitStr`](crate::lex::TokenType::LitStr))
 //!  - numbers (for [`LitNum`](crate::lex::TokenType::LitNum))
+//!  - local labels (for [`LocalLabel`](crate::lex::TokenType::LocalLabel),
+//!    [`LocalLabelBackward`](crate::lex::TokenType::LocalLabelBackward), and
+//!    [`LocalLabelForward`](crate::lex::TokenType::LocalLabelForward))
 
 use super::Lexer;
 use crate::error::LexError;
+use crate::lex::TokenType;
 
 impl<'s> Lexer<'s> {
 	/// Convert a string with a 2 character escape code into its corresponding character
@@ -85,7 +89,7 @@ impl<'s> Lexer<'s> {
 					col:      self.col + 3,
 					src_line: self.get_curr_line().to_string(),
 					found:    close,
-					expected: '\'',
+					expected: vec!['\''],
 				});
 			}
 
@@ -114,7 +118,7 @@ impl<'s> Lexer<'s> {
 				col:      self.col + 2,
 				src_line: self.get_curr_line().to_string(),
 				found:    close,
-				expected: '\'',
+				expected: vec!['\''],
 			});
 		}
 
@@ -185,58 +189,111 @@ impl<'s> Lexer<'s> {
 	///
 	/// Can make decimal, hex, octal, or binary numbers depending on the
 	/// supplied predicate function
-	pub(super) fn try_take_number(&mut self) -> Result<i32, LexError> {
-		let raw = match self.take_while(|c| {
+	///
+	/// Also makes local labels, a decimal number followed by a `:` at the
+	/// start of a line defines a local label, and a decimal number followed
+	/// by `b` or `f` references one
+	pub(super) fn try_take_number(&mut self) -> Result<TokenType<'s>, LexError> {
+		let raw = self.take_while(|c| {
 			c.is_ascii_hexdigit() || c == 'x' || c == 'X' || c == 'o' || c == 'O' || c == '_'
-		}) {
-			Ok(n) => n,
-			Err(e) => return Err(e),
-		};
+		});
 
-		let raw = raw.replace('_', "");
+		if let Some(num) = parse_number(raw) {
+			let starts_line = self.source[self.prev_nl..self.start].trim().is_empty();
 
-		let num = if raw.starts_with("0x") {
-			i32::from_str_radix(raw.trim_start_matches("0x"), 16).map_err(|_| {
-				LexError::InvalidNumber {
-					src_file: self.source_file.to_string(),
-					line:     self.line,
-					col:      self.col,
-					span:     raw.len(),
-					src_line: self.get_curr_line().to_string(),
-				}
-			})
-		} else if raw.starts_with("0o") {
-			i32::from_str_radix(raw.trim_start_matches("0o"), 8).map_err(|_| {
-				LexError::InvalidNumber {
-					src_file: self.source_file.to_string(),
-					line:     self.line,
-					col:      self.col,
-					span:     raw.len(),
-					src_line: self.get_curr_line().to_string(),
-				}
-			})
-		} else if raw.starts_with("0b") {
-			i32::from_str_radix(raw.trim_start_matches("0b"), 2).map_err(|_| {
-				LexError::InvalidNumber {
-					src_file: self.source_file.to_string(),
-					line:     self.line,
-					col:      self.col,
-					span:     raw.len(),
-					src_line: self.get_curr_line().to_string(),
-				}
-			})
-		} else {
-			raw.parse::<i32>().map_err(|_| {
-				LexError::InvalidNumber {
-					src_file: self.source_file.to_string(),
-					line:     self.line,
-					col:      self.col,
-					span:     raw.len(),
-					src_line: self.get_curr_line().to_string(),
-				}
-			})
-		};
+			if starts_line && self.peek() == Some(&':') && let Ok(label) = raw.parse() {
+				// Unwrap is safe as peek is Some
+				self.next().unwrap();
+				return Ok(TokenType::LocalLabel(label));
+			}
+
+			return Ok(TokenType::LitNum(num));
+		}
+
+		let (label, direction) = raw.split_at(raw.len() - 1);
+		match (label.parse(), direction) {
+			(Ok(label), "b") => return Ok(TokenType::LocalLabelBackward(label)),
+			(Ok(label), "f") => return Ok(TokenType::LocalLabelForward(label)),
+			_ => (),
+		}
+
+		Err(LexError::InvalidNumber {
+			src_file: self.source_file.to_string(),
+			line:     self.line,
+			col:      self.col,
+			span:     raw.len(),
+			src_line: self.get_curr_line().to_string(),
+		})
+	}
+}
+
+/// The base a numeric literal was written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum Radix {
+	Binary,
+	Octal,
+	Decimal,
+	Hex,
+}
 
-		num
+impl Radix {
+	/// Get the radix of a number as written in the source, based on its
+	/// prefix
+	pub fn of(raw: &str) -> Self {
+		match raw.get(..2) {
+			Some("0x") => Self::Hex,
+			Some("0o") => Self::Octal,
+			Some("0b") => Self::Binary,
+			_ => Self::Decimal,
+		}
 	}
 }
+
+/// Parse a decimal, hex (`0x`), octal (`0o`), or binary (`0b`) number,
+/// optionally containing `_` separators
+///
+/// Returns [`None`] if the number is invalid or does not fit in an [`i32`]
+pub fn parse_number(raw: &str) -> Option<i32> {
+	let raw = raw.replace('_', "");
+
+	let num = if let Some(hex) = raw.strip_prefix("0x") {
+		i32::from_str_radix(hex, 16)
+	} else if let Some(oct) = raw.strip_prefix("0o") {
+		i32::from_str_radix(oct, 8)
+	} else if let Some(bin) = raw.strip_prefix("0b") {
+		i32::from_str_radix(bin, 2)
+	} else {
+		raw.parse::<i32>()
+	};
+
+	num.ok()
+}
+
+/// Replace the escape sequences in a string literal by the characters they
+/// represent
+///
+/// String literals are stored unescaped by the [`Lexer`], so this needs to
+/// be done before their bytes can be emitted
+pub(crate) fn unescape(s: &str) -> String {
+	let mut unescaped = String::with_capacity(s.len());
+	let mut chars = s.chars();
+
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			unescaped.push(c);
+			continue;
+		}
+
+		match chars.next() {
+			Some('n') => unescaped.push('\n'),
+			Some('r') => unescaped.push('\r'),
+			Some('t') => unescaped.push('\t'),
+			Some('0') => unescaped.push('\0'),
+			Some(other) => unescaped.push(other),
+			None => unescaped.push('\\'),
+		}
+	}
+
+	unescaped
+}
@@ -30,12 +30,24 @@
 //! ```
 
 use std::iter::Peekable;
+use std::path::Path;
 use std::str::Chars;
 
+mod cache;
 mod identifier;
+mod include;
+mod isa;
 mod literal;
+mod reader;
 mod token;
 
+pub use cache::{read_cache, write_cache};
+pub use identifier::keyword;
+pub use include::{Expand, Includes};
+pub use isa::{Extension, Isa};
+pub(crate) use literal::unescape;
+pub use literal::{parse_number, Radix};
+pub use reader::{ChunkedLexer, SourceChunk};
 pub use token::*;
 
 use crate::error::{Error, LexError};
@@ -70,12 +82,30 @@ pub struct Lexer<'s> {
 
 	/// The index of the previous newline character (used for errors)
 	prev_nl: usize,
+
+	/// The instruction set whose instructions are recognised
+	isa: Isa,
+
+	/// Whether the source code was included by another file, see
+	/// [`Token::included_file`]
+	included: bool,
+
+	/// Whether to keep lexing after an error, see
+	/// [`with_keep_going`](Self::with_keep_going)
+	keep_going: bool,
 }
 
 impl<'s> Iterator for Lexer<'s> {
 	type Item = Result<Token<'s>, Error>;
 
-	fn next(&mut self) -> Option<Self::Item> { self.lex_token() }
+	fn next(&mut self) -> Option<Self::Item> {
+		let token = self.lex_token();
+		if self.keep_going && let Some(Err(_)) = token {
+			self.skip_to_whitespace();
+		}
+
+		token
+	}
 }
 
 impl<'s> Lexer<'s> {
@@ -91,9 +121,97 @@ impl<'s> Lexer<'s> {
 			line: 1,
 			col: 1,
 			prev_nl: 0,
+			isa: Isa::default(),
+			included: false,
+			keep_going: false,
 		}
 	}
 
+	/// Only accept the instructions of the given instruction set, using an
+	/// instruction from a disabled extension produces a
+	/// [`LexError::UnsupportedInstruction`]
+	pub fn with_isa(mut self, isa: Isa) -> Self {
+		self.isa = isa;
+		self
+	}
+
+	/// Keep iterating after an error, skipping ahead to the next whitespace
+	/// or newline like [`tokenize_with_recovery`](Self::tokenize_with_recovery)
+	/// does
+	///
+	/// Errors are still returned, a consumer such as a
+	/// [partial](crate::parse::Parser::parse_partial) parse can then record
+	/// them and continue with the tokens after them
+	pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+		self.keep_going = keep_going;
+		self
+	}
+
+	/// Mark the source code as included by another file, so that the
+	/// [`Token`]s it produces remember the name of their file
+	pub(crate) fn included(mut self) -> Self {
+		self.included = true;
+		self
+	}
+
+	/// Lex the entire source code, recovering from any lexical errors
+	///
+	/// Whenever an error is found it gets recorded, after which the lexer
+	/// skips ahead to the next whitespace or newline and continues lexing.
+	/// This allows collecting all valid tokens and all errors in one go,
+	/// whereas iterating over the lexer stops at the first error
+	pub fn tokenize_with_recovery(mut self) -> (Vec<Token<'s>>, Vec<LexError>) {
+		let mut tokens = vec![];
+		let mut errors = vec![];
+
+		while let Some(result) = self.lex_token() {
+			match result {
+				Ok(token) => tokens.push(token),
+				Err(Error::Lex(err)) => {
+					errors.push(err);
+					self.skip_to_whitespace();
+				},
+				Err(_) => unreachable!(),
+			}
+		}
+
+		(tokens, errors)
+	}
+
+	/// Lex the entire source code, reusing the tokens stored in the cache at
+	/// `cache_path` if it is still valid
+	///
+	/// The source file name is used as the path of the source file. If the
+	/// cache is missing or outdated the source is lexed as usual and the cache
+	/// is rewritten, failing to write the cache is only reported as a warning
+	pub fn tokenize_cached(self, cache_path: &Path) -> Result<Vec<Token<'s>>, Error> {
+		let source_path = Path::new(self.source_file);
+
+		if let Some(tokens) = read_cache(source_path, self.source, self.isa, cache_path) {
+			return Ok(tokens);
+		}
+
+		let (source, isa) = (self.source, self.isa);
+		let tokens = self.collect::<Result<Vec<Token>, Error>>()?;
+
+		if let Err(e) = write_cache(source_path, source, isa, &tokens, cache_path) {
+			warn!("failed to write token cache '{}': {}", cache_path.display(), e);
+		}
+
+		Ok(tokens)
+	}
+
+	/// Skip all characters up to the next whitespace or newline, and update
+	/// the column to match
+	fn skip_to_whitespace(&mut self) {
+		while let Some(c) = self.peek() && !matches!(c, ' ' | '\t' | '\n') {
+			self.next().unwrap(); // Unwrap is safe as peek is some
+		}
+
+		// Columns start at 1
+		self.col = self.idx - self.prev_nl + 1;
+	}
+
 	/// Peek at the next [`char`]
 	///
 	/// Returns [`None`] if no characters are left
@@ -108,11 +226,14 @@ impl<'s> Lexer<'s> {
 	}
 
 	/// Get a reference to the current working line of source code
+	///
+	/// If the current line is the last one and it has no trailing newline,
+	/// the rest of the source is returned
 	fn get_curr_line(&self) -> &'s str {
-		let next_nl =
-			self.source[self.prev_nl..].find('\n').unwrap_or(self.source.len()) + self.prev_nl;
-
-		&self.source[self.prev_nl..=next_nl]
+		match self.source[self.prev_nl..].find('\n') {
+			Some(offset) => &self.source[self.prev_nl..=self.prev_nl + offset],
+			None => &self.source[self.prev_nl..],
+		}
 	}
 
 	/// Make a [`Token`] given the [`Lexer`]s current state and a [`TokenType`]
@@ -122,6 +243,7 @@ impl<'s> Lexer<'s> {
 			line: self.line,
 			col: self.col,
 			span: self.idx - self.start,
+			included_file: self.included.then_some(self.source_file),
 			source_line: self.get_curr_line(),
 		}
 	}
@@ -131,45 +253,18 @@ impl<'s> Lexer<'s> {
 	/// Returns the slice of characters that satisfied the predicate, from the
 	/// start of the current token up to, and including, the last character
 	/// that satisfied the predicate
-	fn take_while<F>(&mut self, pred: F) -> Result<&'s str, LexError>
+	///
+	/// The end of the source code ends the slice like any other character
+	/// that does not satisfy the predicate would
+	fn take_while<F>(&mut self, pred: F) -> &'s str
 	where
 		F: Fn(char) -> bool,
 	{
-		// Return early if the immediately following character is None
-		let mut peek = match self.peek() {
-			Some(p) => *p,
-			None => {
-				return Err(LexError::UnexpectedEof {
-					src_file: self.source_file.to_string(),
-					line:     self.line,
-					col:      self.col,
-					src_line: self.get_curr_line().to_string(),
-				});
-			},
-		};
-
-		// Columns start at 1
-		let mut i = 1;
-		while pred(peek) {
-			// Unwrap is safe as the previous iteration of the loop assures
-			// there is a character
-			self.next().unwrap();
-
-			if self.idx >= self.len {
-				return Err(LexError::UnexpectedEof {
-					src_file: self.source_file.to_string(),
-					line:     self.line,
-					col:      self.col + i,
-					src_line: self.get_curr_line().to_string(),
-				});
-			}
-
-			// Unwrap is safe as idx < len
-			peek = *self.peek().unwrap();
-			i += 1;
+		while let Some(&peek) = self.peek() && pred(peek) {
+			self.next();
 		}
 
-		Ok(&self.source[self.start..self.idx])
+		&self.source[self.start..self.idx]
 	}
 
 	/// Consume any available whitespace characters, updating the [`Lexer`]s
@@ -219,10 +314,7 @@ impl<'s> Lexer<'s> {
 				Ok(token)
 			},
 			';' => {
-				let comment = match self.take_while(|c| c != '\n') {
-					Ok(cmt) => cmt,
-					Err(e) => return Some(Err(e.into())),
-				};
+				let comment = self.take_while(|c| c != '\n');
 
 				Ok(self.make_token(TokenType::Comment(comment)))
 			},
@@ -267,19 +359,46 @@ impl<'s> Lexer<'s> {
 			'-' => Ok(self.make_token(TokenType::Op(OpToken::Minus))),
 			'*' => Ok(self.make_token(TokenType::Op(OpToken::Star))),
 			'/' => Ok(self.make_token(TokenType::Op(OpToken::Slash))),
-			'%' => Ok(self.make_token(TokenType::Op(OpToken::Percent))),
+			'%' => {
+				// `%hi(` and `%lo(` start a relocation operator, anything else is
+				// the remainder operator
+				let ahead: String = self.source_iter.clone().take(3).collect();
+				let op = match &ahead.to_lowercase()[..] {
+					"hi(" => Some(OpToken::Hi),
+					"lo(" => Some(OpToken::Lo),
+					_ => None,
+				};
+
+				match op {
+					Some(op) => {
+						self.next().unwrap(); // Unwraps are safe as ahead has 3 characters
+						self.next().unwrap();
+						Ok(self.make_token(TokenType::Op(op)))
+					},
+					None => Ok(self.make_token(TokenType::Op(OpToken::Percent))),
+				}
+			},
 			'=' => {
-				match self.next()? {
-					'=' => Ok(self.make_token(TokenType::Op(OpToken::Eq))),
-					'>' => Ok(self.make_token(TokenType::SymFatArrow)),
-					c => {
+				// Errors point at the symbol after the `=`
+				match self.next() {
+					Some('=') => Ok(self.make_token(TokenType::Op(OpToken::Eq))),
+					Some('>') => Ok(self.make_token(TokenType::SymFatArrow)),
+					Some(c) => {
 						Err(LexError::UnexpectedSymbol {
 							src_file: self.source_file.to_string(),
 							line:     self.line,
-							col:      self.col,
+							col:      self.col + 1,
 							src_line: self.get_curr_line().to_string(),
 							found:    c,
-							expected: '=',
+							expected: vec!['=', '>'],
+						})
+					},
+					None => {
+						Err(LexError::UnexpectedEof {
+							src_file: self.source_file.to_string(),
+							line:     self.line,
+							col:      self.col + 1,
+							src_line: self.get_curr_line().to_string(),
 						})
 					},
 				}
@@ -341,23 +460,40 @@ impl<'s> Lexer<'s> {
 					Err(e) => return Some(Err(e.into())),
 				};
 
-				Ok(self.make_token(TokenType::LitStr(raw)))
+				let mut token = self.make_token(TokenType::LitStr(raw));
+
+				// Strings can span multiple lines, in which case the next
+				// token starts on the last of them
+				if let Some(offset) = raw.rfind('\n') {
+					let first_line_start = self.prev_nl;
+
+					self.line += raw.matches('\n').count();
+					// + 1 for the opening quote, + 1 to skip the newline
+					self.prev_nl = self.start + offset + 2;
+					// Columns start at 1
+					self.col = self.idx - self.prev_nl + 1;
+
+					let last_line_end = self.prev_nl + self.get_curr_line().len();
+					token.source_line = &self.source[first_line_start..last_line_end];
+
+					return Some(Ok(token));
+				}
+
+				Ok(token)
 			},
 			n if n.is_ascii_digit() => {
-				let num = match self.try_take_number() {
-					Ok(n) => n,
+				match self.try_take_number() {
+					Ok(t) => Ok(self.make_token(t)),
 					Err(e) => return Some(Err(e.into())),
-				};
-
-				Ok(self.make_token(TokenType::LitNum(num)))
+				}
 			},
 			c if unicode_ident::is_xid_start(c) || c == '#' || c == '_' || c == '.' => {
-				let raw = match self.take_while(|c| unicode_ident::is_xid_continue(c) || c == '_') {
-					Ok(id) => id,
-					Err(e) => return Some(Err(e.into())),
-				};
+				// Dots are allowed within identifiers so mnemonics like `fence.tso` lex as a
+				// single token
+				let raw =
+					self.take_while(|c| unicode_ident::is_xid_continue(c) || c == '_' || c == '.');
 
-				self.match_identifier(raw)
+				self.match_identifier(raw).and_then(|token| self.check_isa(token))
 			},
 			c => {
 				Err(LexError::RawUnexpectedSymbol {
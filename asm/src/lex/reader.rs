@@ -0,0 +1,168 @@
+//! Lexing source code read from a [`Read`]er
+//!
+//! [`Token`]s borrow from the source code they were lexed from, so a
+//! [`Lexer`] needs the entire file in memory. For very large (generated)
+//! files [`Lexer::new_from_reader`] instead reads the source code in
+//! [`SourceChunk`]s of whole lines, which own their source code and can be
+//! lexed one at a time
+//!
+//! A chunk holds a single line, unless a string literal spans multiple lines
+//! in which case it holds all of them. The tokens of a chunk have the same
+//! type, line, column, and span as if the entire file was lexed at once
+
+use std::io::{BufRead, BufReader, Read};
+
+use super::{Isa, Lexer, Token};
+use crate::error::Error;
+
+impl<'s> Lexer<'s> {
+	/// Create a lexer reading source code from `reader` one chunk of lines
+	/// at a time
+	///
+	/// A chunk holds a single line, unless a string literal spans multiple
+	/// lines in which case it holds all of them. The tokens of a chunk have
+	/// the same type, line, column, and span as if the entire source code
+	/// was lexed at once by [`Lexer::new`]
+	pub fn new_from_reader<R: Read>(source_file: &str, reader: R) -> ChunkedLexer<R> {
+		ChunkedLexer {
+			source_file: source_file.to_string(),
+			reader:      BufReader::new(reader),
+			line:        1,
+			isa:         Isa::default(),
+		}
+	}
+}
+
+/// An iterator over the [`SourceChunk`]s of a reader, see
+/// [`Lexer::new_from_reader`]
+pub struct ChunkedLexer<R> {
+	/// The name of the file being read (used for error messages)
+	source_file: String,
+	/// The reader the source code is read from
+	reader:      BufReader<R>,
+	/// The line number of the next chunk
+	line:        usize,
+	/// The instruction set whose instructions are recognised
+	isa:         Isa,
+}
+
+impl<R: Read> ChunkedLexer<R> {
+	/// Only accept the instructions of the given instruction set, see
+	/// [`Lexer::with_isa`]
+	pub fn with_isa(mut self, isa: Isa) -> Self {
+		self.isa = isa;
+		self
+	}
+
+	/// Read the next line, including its newline
+	///
+	/// Returns `false` at the end of the reader
+	fn read_line(&mut self, source: &mut String) -> Result<bool, Error> {
+		Ok(self.reader.read_line(source)? > 0)
+	}
+}
+
+impl<R: Read> Iterator for ChunkedLexer<R> {
+	type Item = Result<SourceChunk, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let mut chunk = SourceChunk {
+			source_file: self.source_file.clone(),
+			source:      String::new(),
+			first_line:  self.line,
+			isa:         self.isa,
+		};
+
+		match self.read_line(&mut chunk.source) {
+			Ok(true) => (),
+			Ok(false) => return None,
+			Err(err) => return Some(Err(err)),
+		}
+
+		// A string literal that is still open at the end of the chunk
+		// continues on the next line, only the newly read line needs to be
+		// scanned to find out whether it gets closed
+		let mut open = string_open_after(&chunk.source, false);
+		while open {
+			let len = chunk.source.len();
+
+			match self.read_line(&mut chunk.source) {
+				Ok(true) => open = string_open_after(&chunk.source[len..], true),
+				Ok(false) => break,
+				Err(err) => return Some(Err(err)),
+			}
+		}
+
+		self.line += chunk.source.matches('\n').count();
+
+		Some(Ok(chunk))
+	}
+}
+
+/// Check whether a string literal is open at the end of a line, given
+/// whether one was open at its start
+///
+/// Comments and character literals are skipped, and escaped quotes do not
+/// close a string, the same way the [`Lexer`] handles them
+fn string_open_after(line: &str, mut open: bool) -> bool {
+	let mut chars = line.chars();
+	let mut prev = '\n';
+
+	while let Some(c) = chars.next() {
+		if open {
+			open = !(c == '"' && prev != '\\');
+			prev = c;
+			continue;
+		}
+
+		match c {
+			';' => break,
+			'"' => {
+				open = true;
+				prev = ' ';
+			},
+			'\'' => {
+				if chars.next() == Some('\\') {
+					chars.next();
+				}
+				chars.next();
+			},
+			_ => (),
+		}
+	}
+
+	open
+}
+
+/// One or more whole lines of source code, read by a [`ChunkedLexer`]
+#[derive(Clone, Debug)]
+pub struct SourceChunk {
+	/// The name of the file the chunk was read from
+	source_file: String,
+	/// The source code of the chunk, including its last newline
+	source:      String,
+	/// The line number of the first line of the chunk
+	first_line:  usize,
+	/// The instruction set whose instructions are recognised
+	isa:         Isa,
+}
+
+impl SourceChunk {
+	/// Get the source code of the chunk
+	pub fn source(&self) -> &str { &self.source }
+
+	/// Get the line number of the first line of the chunk
+	pub fn first_line(&self) -> usize { self.first_line }
+
+	/// Create a lexer over the source code of the chunk, producing tokens
+	/// with the line numbers they have in the whole file
+	pub fn lexer(&self) -> Lexer<'_> {
+		let mut lexer = Lexer::new(&self.source_file, &self.source).with_isa(self.isa);
+		lexer.line = self.first_line;
+
+		lexer
+	}
+
+	/// Lex the entire chunk
+	pub fn tokens(&self) -> Result<Vec<Token<'_>>, Error> { self.lexer().collect() }
+}
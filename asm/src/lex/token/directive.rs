@@ -3,35 +3,87 @@ use std::fmt::{Display, Formatter, Result};
 
 /// A tokentype to identify directives
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(missing_docs)]
 pub enum DirToken {
 	Section,
+	If,
+	Else,
+	Endif,
+	Include,
 	Regular(RegularDirective),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(missing_docs)]
 pub enum RegularDirective {
 	Bytes,
 	Halves,
 	Words,
+	Bits,
 	ResBytes,
 	ResHalves,
 	ResWords,
 	Const,
+	Set,
+	IncludeBin,
+	Extern,
+	Global,
+	Weak,
+	Align,
+	Org,
+}
+
+impl DirToken {
+	/// All directives
+	pub const ALL: [Self; 20] = [
+		Self::Section,
+		Self::If,
+		Self::Else,
+		Self::Endif,
+		Self::Regular(RegularDirective::Bytes),
+		Self::Regular(RegularDirective::Halves),
+		Self::Regular(RegularDirective::Words),
+		Self::Regular(RegularDirective::Bits),
+		Self::Regular(RegularDirective::ResBytes),
+		Self::Regular(RegularDirective::ResHalves),
+		Self::Regular(RegularDirective::ResWords),
+		Self::Regular(RegularDirective::Const),
+		Self::Regular(RegularDirective::Set),
+		Self::Regular(RegularDirective::IncludeBin),
+		Self::Regular(RegularDirective::Extern),
+		Self::Regular(RegularDirective::Global),
+		Self::Regular(RegularDirective::Weak),
+		Self::Regular(RegularDirective::Align),
+		Self::Regular(RegularDirective::Org),
+		Self::Include,
+	];
 }
 
 impl Display for DirToken {
 	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
 		match self {
 			Self::Section => write!(f, "#SECTION"),
+			Self::If => write!(f, "#IF"),
+			Self::Else => write!(f, "#ELSE"),
+			Self::Endif => write!(f, "#ENDIF"),
+			Self::Include => write!(f, "#INCLUDE"),
 			Self::Regular(RegularDirective::Bytes) => write!(f, "#BYTES"),
 			Self::Regular(RegularDirective::Halves) => write!(f, "#HALVES"),
 			Self::Regular(RegularDirective::Words) => write!(f, "#WORDS"),
+			Self::Regular(RegularDirective::Bits) => write!(f, "#BITS"),
 			Self::Regular(RegularDirective::ResBytes) => write!(f, "#RES_BYTES"),
 			Self::Regular(RegularDirective::ResHalves) => write!(f, "#RES_HALVES"),
 			Self::Regular(RegularDirective::ResWords) => write!(f, "#RES_WORDS"),
 			Self::Regular(RegularDirective::Const) => write!(f, "#CONST"),
+			Self::Regular(RegularDirective::Set) => write!(f, "#SET"),
+			Self::Regular(RegularDirective::IncludeBin) => write!(f, "#INCLUDE_BIN"),
+			Self::Regular(RegularDirective::Extern) => write!(f, "#EXTERN"),
+			Self::Regular(RegularDirective::Global) => write!(f, "#GLOBAL"),
+			Self::Regular(RegularDirective::Weak) => write!(f, "#WEAK"),
+			Self::Regular(RegularDirective::Align) => write!(f, "#ALIGN"),
+			Self::Regular(RegularDirective::Org) => write!(f, "#ORG"),
 		}
 	}
 }
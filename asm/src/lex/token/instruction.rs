@@ -5,7 +5,8 @@
 use std::fmt::{Display, Formatter, Result};
 
 /// A tokentype to identify instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum InstToken {
 	Rri(RriInstruction),
 	Rrr(RrrInstruction),
@@ -27,7 +28,8 @@ pub enum InstToken {
 }
 
 /// Instructions taking 2 registers and an immediate as arguments
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum RriInstruction {
 	Addi,
 	Andi,
@@ -41,7 +43,8 @@ pub enum RriInstruction {
 }
 
 /// Instructions taking 3 registers as arguments
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum RrrInstruction {
 	Add,
 	Sub,
@@ -56,7 +59,8 @@ pub enum RrrInstruction {
 }
 
 /// Branch instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BranchInstruction {
 	Beq,
 	Bne,
@@ -67,7 +71,8 @@ pub enum BranchInstruction {
 }
 
 /// Memory load instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LoadInstruction {
 	Lw,
 	Lh,
@@ -77,7 +82,8 @@ pub enum LoadInstruction {
 }
 
 /// Memory store instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum StoreInstruction {
 	Sw,
 	Sh,
@@ -85,7 +91,8 @@ pub enum StoreInstruction {
 }
 
 /// CSR instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CsrInstruction {
 	Csrrw,
 	Csrrs,
@@ -93,7 +100,8 @@ pub enum CsrInstruction {
 }
 
 /// CSR immediate instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CsriInstruction {
 	Csrrwi,
 	Csrrsi,
@@ -101,7 +109,8 @@ pub enum CsriInstruction {
 }
 
 /// Multiply, divide, or remainder instructions
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MdrInstruction {
 	Mul,
 	Mulh,
@@ -113,6 +122,68 @@ pub enum MdrInstruction {
 	Remu,
 }
 
+impl InstToken {
+	/// All instructions
+	pub const ALL: [Self; 56] = [
+		Self::Rri(RriInstruction::Addi),
+		Self::Rri(RriInstruction::Andi),
+		Self::Rri(RriInstruction::Ori),
+		Self::Rri(RriInstruction::Xori),
+		Self::Rri(RriInstruction::Lsli),
+		Self::Rri(RriInstruction::Lsri),
+		Self::Rri(RriInstruction::Asri),
+		Self::Rri(RriInstruction::Slti),
+		Self::Rri(RriInstruction::Sltiu),
+		Self::Rrr(RrrInstruction::Add),
+		Self::Rrr(RrrInstruction::Sub),
+		Self::Rrr(RrrInstruction::And),
+		Self::Rrr(RrrInstruction::Or),
+		Self::Rrr(RrrInstruction::Xor),
+		Self::Rrr(RrrInstruction::Lsl),
+		Self::Rrr(RrrInstruction::Lsr),
+		Self::Rrr(RrrInstruction::Asr),
+		Self::Rrr(RrrInstruction::Slt),
+		Self::Rrr(RrrInstruction::Sltu),
+		Self::Load(LoadInstruction::Lw),
+		Self::Load(LoadInstruction::Lh),
+		Self::Load(LoadInstruction::Lhu),
+		Self::Load(LoadInstruction::Lb),
+		Self::Load(LoadInstruction::Lbu),
+		Self::Store(StoreInstruction::Sw),
+		Self::Store(StoreInstruction::Sh),
+		Self::Store(StoreInstruction::Sb),
+		Self::Lui,
+		Self::Auipc,
+		Self::Branch(BranchInstruction::Beq),
+		Self::Branch(BranchInstruction::Bne),
+		Self::Branch(BranchInstruction::Blt),
+		Self::Branch(BranchInstruction::Bltu),
+		Self::Branch(BranchInstruction::Bge),
+		Self::Branch(BranchInstruction::Bgeu),
+		Self::Jal,
+		Self::Jalr,
+		Self::Ecall,
+		Self::Ebreak,
+		Self::Fence,
+		Self::FenceTso,
+		Self::Fencei,
+		Self::Csr(CsrInstruction::Csrrw),
+		Self::Csr(CsrInstruction::Csrrs),
+		Self::Csr(CsrInstruction::Csrrc),
+		Self::Csri(CsriInstruction::Csrrwi),
+		Self::Csri(CsriInstruction::Csrrsi),
+		Self::Csri(CsriInstruction::Csrrci),
+		Self::Mdr(MdrInstruction::Mul),
+		Self::Mdr(MdrInstruction::Mulh),
+		Self::Mdr(MdrInstruction::Mulhu),
+		Self::Mdr(MdrInstruction::Mulhsu),
+		Self::Mdr(MdrInstruction::Div),
+		Self::Mdr(MdrInstruction::Divu),
+		Self::Mdr(MdrInstruction::Rem),
+		Self::Mdr(MdrInstruction::Remu),
+	];
+}
+
 impl Display for InstToken {
 	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
 		match self {
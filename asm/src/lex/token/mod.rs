@@ -23,6 +23,7 @@ pub use register::*;
 ///  - `'s`: The lifetime of the reference to the source code string, needed to store any potential
 ///    string references in identifiers
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum TokenType<'s> {
 	/// An instruction (see also [`InstToken`])
 	Inst(InstToken),
@@ -45,6 +46,13 @@ pub enum TokenType<'s> {
 	/// An identifier
 	Identifier(&'s str),
 
+	/// A local label definition (`1:`)
+	LocalLabel(u32),
+	/// A reference to the closest preceding local label (`1b`)
+	LocalLabelBackward(u32),
+	/// A reference to the closest following local label (`1f`)
+	LocalLabelForward(u32),
+
 	/// `,`
 	SymComma,
 	/// `\n`
@@ -63,6 +71,8 @@ pub enum TokenType<'s> {
 	SymRightBrace,
 	/// `=>`
 	SymFatArrow,
+	/// `.`, the address of the current statement
+	SymDot,
 
 	/// An operator (see also [`OpToken`])
 	Op(OpToken),
@@ -71,6 +81,17 @@ pub enum TokenType<'s> {
 	Comment(&'s str),
 }
 
+impl<'s> TokenType<'s> {
+	/// Check if this token is of the same kind as another token, ignoring any
+	/// value it carries
+	///
+	/// Where `==` only considers `5` equal to `5`, any two numeric literals
+	/// match as patterns
+	pub fn matches_pattern(&self, other: &TokenType) -> bool {
+		std::mem::discriminant(self) == std::mem::discriminant(other)
+	}
+}
+
 impl<'s> Debug for TokenType<'s> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		let t = 10;
@@ -91,6 +112,14 @@ impl<'s> Debug for TokenType<'s> {
 
 			Self::Identifier(i) => write!(f, "{:<t$} {:<v$}", "IDENTIFIER", i),
 
+			Self::LocalLabel(l) => write!(f, "{:<t$} {:<v$}", "LOCAL_LABEL", format!("{}:", l)),
+			Self::LocalLabelBackward(l) => {
+				write!(f, "{:<t$} {:<v$}", "LOCAL_REF", format!("{}b", l))
+			},
+			Self::LocalLabelForward(l) => {
+				write!(f, "{:<t$} {:<v$}", "LOCAL_REF", format!("{}f", l))
+			},
+
 			Self::SymComma => write!(f, "{:<t$} {:<v$}", "SYMBOL", ","),
 			Self::SymNewline => write!(f, "{:<t$} {:<v$}", "SYMBOL", "\\n"),
 			Self::SymLeftParen => write!(f, "{:<t$} {:<v$}", "SYMBOL", "("),
@@ -100,6 +129,7 @@ impl<'s> Debug for TokenType<'s> {
 			Self::SymLeftBrace => write!(f, "{:<t$} {:<v$}", "SYMBOL", "{{"),
 			Self::SymRightBrace => write!(f, "{:<t$} {:<v$}", "SYMBOL", "}}"),
 			Self::SymFatArrow => write!(f, "{:<t$} {:<v$}", "SYMBOL", "=>"),
+			Self::SymDot => write!(f, "{:<t$} {:<v$}", "SYMBOL", "."),
 
 			Self::Op(op) => write!(f, "{:<t$} {:<v$}", "OPERATOR", op),
 
@@ -125,6 +155,10 @@ impl<'s> Display for TokenType<'s> {
 
 			Self::Identifier(i) => write!(f, "{}", i),
 
+			Self::LocalLabel(l) => write!(f, "{}:", l),
+			Self::LocalLabelBackward(l) => write!(f, "{}b", l),
+			Self::LocalLabelForward(l) => write!(f, "{}f", l),
+
 			Self::SymComma => write!(f, "COMMA"),
 			Self::SymNewline => write!(f, "NEWLINE"),
 			Self::SymLeftParen => write!(f, "("),
@@ -134,6 +168,7 @@ impl<'s> Display for TokenType<'s> {
 			Self::SymLeftBrace => write!(f, "{{"),
 			Self::SymRightBrace => write!(f, "}}"),
 			Self::SymFatArrow => write!(f, "=>"),
+			Self::SymDot => write!(f, "."),
 
 			Self::Op(o) => write!(f, "{}", o),
 
@@ -150,29 +185,75 @@ impl<'s> Display for TokenType<'s> {
 /// ### Lifetimes
 ///  - `'s`: The lifetime of the reference to the source code string, needed to keep a reference to
 ///    the source line for this token and to store any potential references in its [`TokenType`]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Token<'s> {
 	/// The type of this token
-	pub t:           TokenType<'s>,
+	pub t:             TokenType<'s>,
 	/// The line number of this token
-	pub line:        usize,
+	pub line:          usize,
 	/// The column number of this token
-	pub col:         usize,
-	/// The length (in characters) of this token
-	pub span:        usize,
-	/// The line of source code containing this token
-	pub source_line: &'s str,
+	pub col:           usize,
+	/// The length (in characters) of this token, including any newlines if
+	/// it spans multiple lines
+	pub span:          usize,
+	/// The name of the file this token was [included](crate::lex::Includes)
+	/// from, [`None`] if it is part of the file being assembled
+	pub included_file: Option<&'s str>,
+	/// The line of source code containing this token, or all lines it spans
+	/// if it spans multiple
+	#[cfg_attr(feature = "serde", serde(skip))]
+	pub source_line:   &'s str,
+}
+
+impl<'s> Token<'s> {
+	/// Get the source code this token was lexed from
+	///
+	/// Useful to recover how a literal was written, eg. the
+	/// [`Radix`](super::Radix) of a [`LitNum`](TokenType::LitNum)
+	pub fn raw(&self) -> &'s str {
+		let (start, end) = self.source_range();
+
+		&self.source_line[start..end]
+	}
+
+	/// Get the byte range of this token in its source line
+	///
+	/// The range is clamped to the line and to character boundaries, so
+	/// slicing the line with it never panics, even for tokens that start or
+	/// end past the end of the line
+	fn source_range(&self) -> (usize, usize) {
+		let line = self.source_line;
+		let floor = |mut idx: usize| {
+			idx = idx.min(line.len());
+			while !line.is_char_boundary(idx) {
+				idx -= 1;
+			}
+			idx
+		};
+
+		let start = floor(self.col.saturating_sub(1)); // Columns start at 1
+		let end = floor(start.saturating_add(self.span));
+
+		(start, end)
+	}
 }
 
 impl<'s> Display for Token<'s> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		let start = self.col - 1; // Columns start at 1
+		// Tokens spanning multiple lines are cut off at the end of their first
+		// line
+		let first_line_end = self.source_line.find('\n').map_or(self.source_line.len(), |i| i + 1);
+		let (start, end) = self.source_range();
+		let (start, end) = (start.min(first_line_end), end.min(first_line_end));
+		let continuation = if start + self.span > end { "..." } else { "" };
+
 		let left_arm = &self.source_line[..start].trim_start();
-		let center = &self.source_line[start..start + self.span];
-		let right_arm = &self.source_line[start + self.span..];
+		let center = &self.source_line[start..end];
+		let right_arm = &self.source_line[end..first_line_end];
 
 		let t = format!("{:?}", self.t);
-		let annotated_src = format!("{}```{}```{}", left_arm, center, right_arm);
+		let annotated_src = format!("{}```{}```{}{}", left_arm, center, continuation, right_arm);
 
 		write!(f, "[{:0>3}:{:0>3}]: {:<42} {:?}", self.line, self.col, t, annotated_src)
 	}
@@ -4,6 +4,7 @@ use std::fmt::{Display, Formatter, Result};
 
 /// A tokentype to identify operators
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(missing_docs)]
 pub enum OpToken {
 	/// `+`
@@ -56,6 +57,11 @@ pub enum OpToken {
 	Lsr,
 	/// `>>>`
 	Asr,
+	/// `%hi`, the upper 20 bits of a value as loaded by `lui`, rounded so
+	/// that adding the [`%lo`](Self::Lo) part gives back the original value
+	Hi,
+	/// `%lo`, the sign-extended lower 12 bits of a value as added by `addi`
+	Lo,
 
 	// These will never be produced by the lexer and exist purely to simplify
 	// parsing immediate expressions
@@ -86,6 +92,8 @@ impl Display for OpToken {
 			Self::Lsl => write!(f, "<<"),
 			Self::Lsr => write!(f, ">>"),
 			Self::Asr => write!(f, ">>>"),
+			Self::Hi => write!(f, "%hi"),
+			Self::Lo => write!(f, "%lo"),
 			Self::Plus => write!(f, "+"),
 			Self::Minus => write!(f, "-"),
 			Self::Star => write!(f, "*"),
@@ -100,6 +108,39 @@ impl Display for OpToken {
 }
 
 impl OpToken {
+	/// All operators
+	pub const ALL: [Self; 29] = [
+		Self::Plus,
+		Self::Minus,
+		Self::Star,
+		Self::Slash,
+		Self::Percent,
+		Self::Question,
+		Self::Colon,
+		Self::Dollar,
+		Self::LogicOr,
+		Self::LogicXor,
+		Self::LogicAnd,
+		Self::Exclamation,
+		Self::BitOr,
+		Self::BitXor,
+		Self::BitAnd,
+		Self::BitNot,
+		Self::Eq,
+		Self::Neq,
+		Self::Lt,
+		Self::Lte,
+		Self::Gt,
+		Self::Gte,
+		Self::Lsl,
+		Self::Lsr,
+		Self::Asr,
+		Self::Hi,
+		Self::Lo,
+		Self::UnaryMinus,
+		Self::LeftParen,
+	];
+
 	/// Check if this token is an arithmetic/logic operator
 	pub(crate) fn is_al_operator(&self) -> bool {
 		matches!(
@@ -116,13 +157,14 @@ impl OpToken {
 				| Self::Plus | Self::Minus
 				| Self::Star | Self::Slash
 				| Self::Percent | Self::Exclamation
-				| Self::BitNot
+				| Self::BitNot | Self::Hi
+				| Self::Lo
 		)
 	}
 
 	/// Check if this token is right associative or not
 	pub(crate) fn is_right_associative(&self) -> bool {
-		matches!(self, Self::UnaryMinus | Self::Question | Self::Colon)
+		matches!(self, Self::UnaryMinus | Self::Hi | Self::Lo | Self::Question | Self::Colon)
 	}
 
 	/// Get the precedence of this token
@@ -155,6 +197,8 @@ impl OpToken {
 			Self::Exclamation => 12,
 			Self::BitNot => 12,
 			Self::UnaryMinus => 12,
+			Self::Hi => 12,
+			Self::Lo => 12,
 
 			_ => unreachable!(),
 		}
@@ -3,7 +3,10 @@
 use std::fmt::{Display, Formatter, Result};
 
 /// A tokentype to identify registers
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+///
+/// Registers are ordered by their index
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(missing_docs)]
 pub enum RegToken {
 	R0,
@@ -40,6 +43,82 @@ pub enum RegToken {
 	R31,
 }
 
+impl RegToken {
+	/// All registers, indexed by their register number
+	pub(crate) const ALL: [Self; 32] = [
+		Self::R0,
+		Self::R1,
+		Self::R2,
+		Self::R3,
+		Self::R4,
+		Self::R5,
+		Self::R6,
+		Self::R7,
+		Self::R8,
+		Self::R9,
+		Self::R10,
+		Self::R11,
+		Self::R12,
+		Self::R13,
+		Self::R14,
+		Self::R15,
+		Self::R16,
+		Self::R17,
+		Self::R18,
+		Self::R19,
+		Self::R20,
+		Self::R21,
+		Self::R22,
+		Self::R23,
+		Self::R24,
+		Self::R25,
+		Self::R26,
+		Self::R27,
+		Self::R28,
+		Self::R29,
+		Self::R30,
+		Self::R31,
+	];
+
+	/// Get the number of this register (0-31), as used in instruction
+	/// encodings
+	pub fn index(&self) -> u8 { *self as u8 }
+
+	/// Get the canonical ABI name of this register (eg. `a0` for `r10`)
+	///
+	/// `r8` may be written as both `s0` and `fp`, it is named `s0`
+	pub fn abi_name(&self) -> &'static str { ABI_NAMES[self.index() as usize] }
+
+	/// Display this register using its [ABI name](Self::abi_name) instead
+	/// of its number
+	pub fn abi(&self) -> AbiName { AbiName(*self) }
+}
+
+/// The canonical ABI names of all registers, indexed by register number
+const ABI_NAMES: [&str; 32] = [
+	"zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+	"a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+	"t5", "t6",
+];
+
+/// Displays a [`RegToken`] by its ABI name, see [`RegToken::abi`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbiName(RegToken);
+
+impl Display for AbiName {
+	fn fmt(&self, f: &mut Formatter<'_>) -> Result { write!(f, "{}", self.0.abi_name()) }
+}
+
+/// Convert a register number into its register, numbers of 32 and up are
+/// returned as the error
+impl TryFrom<u8> for RegToken {
+	type Error = u8;
+
+	fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+		Self::ALL.get(value as usize).copied().ok_or(value)
+	}
+}
+
 impl Display for RegToken {
 	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
 		match self {
@@ -4,53 +4,477 @@
 //! can be executed by the simulator <br>
 //! This is achieved using the following steps:
 //!  - Lexing: The [`Lexer`] converts the raw text of the input file into a series of abstracted
-//!    [`Token`]s that more conveniently represent their underlying data
-//!  - Parsing: The [`Parser`] converts the stream of [`Token`]s generated by the [`Lexer`] into a
-//!    structured representation called an Abstract Syntax Tree (AST) (see [parse::ast])
+//!    [`Token`](lex::Token)s that more conveniently represent their underlying data
+//!  - Parsing: The [`Parser`] converts the stream of [`Token`](lex::Token)s generated by the
+//!    [`Lexer`] into a structured representation called an Abstract Syntax Tree (AST) (see
+//!    [parse::ast])
+//!  - Linting: The [`Linter`] reports likely mistakes in the AST as warnings, which do not stop
+//!    assembly
+//!  - Folding: The [`Folder`] evaluates all immediate expressions in the AST into single numeric
+//!    literals, and checks that they fit in the fields they will be encoded into
+//!  - Code generation: The [`Generator`] encodes the folded AST into a [`wpibin`] image that can be
+//!    loaded by the simulator
 
 #![warn(missing_docs)]
 #![feature(let_chains)]
-#![feature(assert_matches)]
 
 #[macro_use]
 extern crate log;
 
+use std::cell::Cell;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+pub mod codegen;
 pub mod error;
+pub mod fold;
+pub mod format;
 pub mod lex;
+pub mod lint;
+pub mod map;
 pub mod parse;
+pub mod wpibin;
 
+use codegen::Generator;
 use error::Error;
-use lex::{Lexer, Token};
-use parse::Parser;
+use fold::{Folder, Symbol};
+use lex::{Includes, Isa, Lexer};
+use lint::Linter;
+use parse::{Parser, TrailingCommas};
+use wpibin::{Endian, Image};
 
+use crate::parse::ast::{Line, Root, Statement};
 use crate::parse::Node;
 
+/// Options controlling how a file gets assembled
+#[derive(Clone, Debug, Default)]
+pub struct AsmOptions {
+	/// The address the binary will be loaded at, all addresses are computed
+	/// relative to it
+	pub base_addr:          u32,
+	/// Constants declared outside of the source file, see
+	/// [`Folder::with_constant`]
+	pub defines:            Vec<(String, i32)>,
+	/// The names of warnings that should not be reported, see
+	/// [`Linter::allow`]
+	pub allowed:            Vec<String>,
+	/// The instruction set to accept, see [`Lexer::with_isa`]
+	pub isa:                Isa,
+	/// Whether to fail with [`Error::DeniedWarnings`] if any warning that is
+	/// not allowed gets reported
+	pub warnings_as_errors: bool,
+	/// Whether to include a source map in the binary, see
+	/// [`Generator::with_source_map`]
+	pub source_map:         bool,
+	/// Where to write a [map file](map) listing the size of every section
+	/// and label, no map file is written if [`None`]
+	pub map:                Option<PathBuf>,
+	/// Whether to print the encoding of every instruction to stdout, see
+	/// [`describe_encoding`](codegen::describe_encoding)
+	pub dump_encoding:      bool,
+	/// Where trailing commas are allowed, see
+	/// [`Parser::with_trailing_commas`]
+	pub trailing_commas:    TrailingCommas,
+	/// Whether to continue past errors and report all of them at once as an
+	/// [`Error::Collected`]
+	///
+	/// [`assemble`] still writes the binary, with every statement that
+	/// failed to assemble poisoned, see [`Parser::parse_partial`],
+	/// [`Folder::fold_partial`], and [`Generator::generate_partial`]
+	pub keep_going:         bool,
+	/// The byte order of halves and words in data directives, see
+	/// [`Generator::with_endian`]
+	pub endian:             Endian,
+}
+
+/// How long each assembler pass took on a single file
+///
+/// Lexing and parsing are interleaved, so the parsing time excludes the time
+/// spent lexing the tokens it consumed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+	/// Time spent lexing
+	pub lex:        Duration,
+	/// Time spent parsing
+	pub parse:      Duration,
+	/// Time spent linting
+	pub lint:       Duration,
+	/// Time spent folding
+	pub fold:       Duration,
+	/// Time spent generating code, zero if no code was generated
+	pub codegen:    Duration,
+	/// The amount of tokens produced by the lexer
+	pub tokens:     usize,
+	/// The amount of statements in the AST
+	pub statements: usize,
+}
+
+/// The symbols declared in a single file, see [`symbols`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+	/// The address of every label, sorted by address
+	pub symbols: Vec<Symbol>,
+	/// The names of all symbols declared with `#EXTERN`, in order of
+	/// declaration
+	pub externs: Vec<String>,
+}
+
 /// Assemble a file at the given input path into a binary, and write it to the
 /// file given by the output path
 ///
+/// Returns how long each pass took, which is also logged at the info level
+///
 /// See the [module level documentation](self) for more info
-pub fn assemble(input_path: &Path, _output_path: &Path) -> Result<(), Error> {
+pub fn assemble(
+	input_path: &Path,
+	output_path: &Path,
+	options: &AsmOptions,
+) -> Result<Timings, Error> {
+	let src_file = input_path.to_string_lossy().to_string();
+	let contents = read_source(input_path)?;
+	let includes = Includes::load(&src_file, &contents, options.isa)?;
+
+	let mut assembled = run_pipeline(&src_file, &contents, Some(&includes), true, options)?;
+	// Unwrap is safe as code was generated
+	let image = assembled.image.take().unwrap();
+	std::fs::write(output_path, image.to_bytes())?;
+
+	if let Some(map_path) = &options.map {
+		info!("Writing map file {}", map_path.display());
+		std::fs::write(map_path, map::render(&image, &assembled.symbols))?;
+	}
+
+	// The partial output is written before reporting errors
+	assembled.check_errors()?;
+
+	Ok(assembled.timings)
+}
+
+/// Assemble source code into the bytes of a [`wpibin`] file without touching
+/// the filesystem, `name` is only used in error messages
+///
+/// Returns the folded AST of the source code along with the bytes that
+/// [`assemble`] would have written. The AST may borrow the names of
+/// constants declared in the options, so these have to outlive it as well
+///
+/// Other files can not be [included](Includes), as that would require
+/// reading them
+pub fn assemble_to_vec<'s>(
+	name: &'s str,
+	contents: &'s str,
+	options: &'s AsmOptions,
+) -> Result<(Root<'s>, Vec<u8>), Error> {
+	let mut assembled = run_pipeline(name, contents, None, true, options)?;
+	assembled.check_errors()?;
+	// Unwrap is safe as code was generated
+	let bytes = assembled.image.unwrap().to_bytes();
+
+	Ok((assembled.root, bytes))
+}
+
+/// Run all assembler passes on the file at the given input path without
+/// emitting a binary
+///
+/// Useful to verify that a file assembles cleanly. Assembling always
+/// [keeps going](AsmOptions::keep_going) past errors, so every error in the
+/// file gets reported as an [`Error::Collected`]
+pub fn check(input_path: &Path, options: &AsmOptions) -> Result<(), Error> {
+	let src_file = input_path.to_string_lossy().to_string();
+	let contents = read_source(input_path)?;
+	let includes = Includes::load(&src_file, &contents, options.isa)?;
+	let options = AsmOptions { keep_going: true, ..options.clone() };
+
+	run_pipeline(&src_file, &contents, Some(&includes), false, &options)?.check_errors()
+}
+
+/// Get the symbol table of the file at the given input path
+///
+/// No binary is emitted
+pub fn symbols(input_path: &Path, options: &AsmOptions) -> Result<SymbolTable, Error> {
+	let src_file = input_path.to_string_lossy().to_string();
+	let contents = read_source(input_path)?;
+	let includes = Includes::load(&src_file, &contents, options.isa)?;
+
+	let mut assembled = run_pipeline(&src_file, &contents, Some(&includes), false, options)?;
+	assembled.check_errors()?;
+	let mut symbols = assembled.symbols;
+	symbols.sort_by_key(|symbol| symbol.addr);
+
+	Ok(SymbolTable { symbols, externs: assembled.externs })
+}
+
+/// Parse the file at the given input path and render its AST, either as an
+/// indented tree or, if `dot` is true, as a Graphviz DOT graph
+///
+/// No other passes are run
+pub fn dump_ast(input_path: &Path, dot: bool) -> Result<String, Error> {
 	let src_file = input_path.to_string_lossy().to_string();
+	let contents = read_source(input_path)?;
+	let includes = Includes::load(&src_file, &contents, Isa::default())?;
+
+	let lexer = includes.expand(Lexer::new(&src_file, &contents));
+	let ast_root = Parser::from_stream(&src_file, lexer).parse()?;
+	let node = Node::from(&ast_root);
+
+	Ok(if dot { node.to_dot() } else { node.to_string() })
+}
+
+/// Format the file at the given input path in its canonical layout, see
+/// [`format::format`]
+///
+/// Returns whether the file was already formatted. The file is rewritten
+/// with its formatted contents, unless `check` is true
+pub fn format_file(input_path: &Path, check: bool) -> Result<bool, Error> {
+	let src_file = input_path.to_string_lossy().to_string();
+	let contents = read_source(input_path)?;
+
+	let formatted = format::format(&src_file, &contents)?;
+	let unchanged = formatted == contents;
+	if !check && !unchanged {
+		std::fs::write(input_path, formatted)?;
+	}
+
+	Ok(unchanged)
+}
+
+/// Serialize an AST as JSON, including the location of every token
+///
+/// Only available with the `serde` feature
+#[cfg(feature = "serde")]
+pub fn ast_to_json(root: &Root) -> String {
+	// Unwrap is safe as the AST only contains plain data without any maps
+	serde_json::to_string(root).unwrap()
+}
+
+/// Read the contents of a source file
+fn read_source(input_path: &Path) -> Result<String, Error> {
 	let mut file = File::open(input_path)?;
 	let mut contents = String::new();
 	file.read_to_string(&mut contents)?;
 
-	info!("Lexing file {}", &src_file);
-	let lexer = Lexer::new(&src_file, &contents);
-	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+	Ok(contents)
+}
+
+/// Everything produced by running the assembler passes on a file
+struct Assembled<'s> {
+	/// The folded AST
+	root:    Root<'s>,
+	/// The generated image, [`None`] if no code was generated
+	image:   Option<Image>,
+	/// The symbols declared in the file
+	symbols: Vec<Symbol>,
+	/// The symbols declared with `#EXTERN` in the file
+	externs: Vec<String>,
+	/// How long each pass took
+	timings: Timings,
+	/// The errors recovered from while keeping going, see
+	/// [`AsmOptions::keep_going`]
+	errors:  Vec<Error>,
+}
+
+impl Assembled<'_> {
+	/// Fail with every error recovered from while keeping going, if any
+	fn check_errors(&mut self) -> Result<(), Error> {
+		match std::mem::take(&mut self.errors) {
+			errors if errors.is_empty() => Ok(()),
+			errors => Err(Error::Collected(errors)),
+		}
+	}
+}
+
+/// Run all assembler passes on the contents of the file named `src_file`
+///
+/// `#INCLUDE` directives are expanded if the included files are given. Code
+/// generation is only performed if `generate` is true
+fn run_pipeline<'s>(
+	src_file: &'s str,
+	contents: &'s str,
+	includes: Option<&'s Includes>,
+	generate: bool,
+	options: &'s AsmOptions,
+) -> Result<Assembled<'s>, Error> {
+	let mut timings = Timings::default();
+	let lex_time = Rc::new(Cell::new(Duration::ZERO));
+	let token_count = Rc::new(Cell::new(0));
+
+	// Tokens are parsed as they get lexed to avoid keeping all of them in
+	// memory at once
+	info!("Lexing and parsing file {}", src_file);
+	debug!("Lexemes for file {}:", src_file);
+	let lexer =
+		Lexer::new(src_file, contents).with_isa(options.isa).with_keep_going(options.keep_going);
+	let mut lexer: Box<dyn Iterator<Item = _>> = match includes {
+		Some(includes) => Box::new(includes.expand(lexer)),
+		None => Box::new(lexer),
+	};
+	let lexer = {
+		let lex_time = Rc::clone(&lex_time);
+		let token_count = Rc::clone(&token_count);
+
+		std::iter::from_fn(move || {
+			let start = Instant::now();
+			let token = lexer.next();
+			lex_time.set(lex_time.get() + start.elapsed());
 
-	debug!("Lexemes for file {}:", &src_file);
-	debug!("{}", tokens.iter().map(|t| t.to_string()).collect::<Vec<String>>().join("\n"));
+			if let Some(Ok(token)) = &token {
+				token_count.set(token_count.get() + 1);
+				debug!("{}", token);
+			}
 
-	info!("Parsing file {}", &src_file);
-	let mut parser = Parser::new(&src_file, &tokens);
-	let ast_root = parser.parse()?;
+			token
+		})
+	};
+
+	let start = Instant::now();
+	let mut parser =
+		Parser::from_stream(src_file, lexer).with_trailing_commas(options.trailing_commas);
+	let mut errors = vec![];
+	let mut ast_root = if options.keep_going {
+		let (ast_root, parse_errors) = parser.parse_partial()?;
+		errors.extend(parse_errors);
+		ast_root
+	} else {
+		parser.parse()?
+	};
+	timings.lex = lex_time.get();
+	timings.parse = start.elapsed().saturating_sub(timings.lex);
+	timings.tokens = token_count.get();
+	timings.statements = ast_root.preamble.iter().filter(|l| l.statement.is_some()).count()
+		+ ast_root.sections.iter().map(|s| count_statements(&s.lines)).sum::<usize>();
+
+	if ast_root.sections.iter().all(|s| count_statements(&s.lines) == 0) {
+		warn!("nothing to assemble in file {}", src_file);
+	}
+
+	info!("Linting file {}", src_file);
+	let start = Instant::now();
+	let mut linter = Linter::new(src_file);
+	for name in &options.allowed {
+		linter = linter.allow(name);
+	}
+	let mut warnings = linter.lint(&ast_root);
+	timings.lint = start.elapsed();
+
+	info!("Folding constants in file {}", src_file);
+	let start = Instant::now();
+	let mut folder = Folder::new(src_file).with_base_addr(options.base_addr);
+	for (id, value) in &options.defines {
+		folder = folder.with_constant(id, *value);
+	}
+	if options.keep_going {
+		errors.extend(folder.fold_partial(&mut ast_root)?.into_iter().map(Error::from));
+	} else {
+		folder.fold(&mut ast_root)?;
+	}
+	warnings.extend(
+		folder
+			.warnings()
+			.into_iter()
+			.filter(|warning| !options.allowed.iter().any(|name| name == warning.name())),
+	);
+	timings.fold = start.elapsed();
+
+	for warning in &warnings {
+		warn!("{}", warning);
+	}
+	if options.warnings_as_errors && !warnings.is_empty() {
+		if !options.keep_going {
+			return Err(Error::DeniedWarnings(warnings.len()));
+		}
+		errors.push(Error::DeniedWarnings(warnings.len()));
+	}
 
 	debug!("{}", Node::from(&ast_root));
 
-	Ok(())
+	let mut image = None;
+	if generate {
+		info!("Generating code for file {}", src_file);
+		let start = Instant::now();
+		// The source map tells which words are instructions
+		let generator = Generator::new(src_file)
+			.with_base_addr(options.base_addr)
+			.with_source_map(options.source_map || options.dump_encoding)
+			.with_endian(options.endian);
+		let mut generated = if options.keep_going {
+			let (generated, codegen_errors) = generator.generate_partial(&ast_root)?;
+			errors.extend(codegen_errors.into_iter().map(Error::from));
+			generated
+		} else {
+			generator.generate(&ast_root)?
+		};
+		timings.codegen = start.elapsed();
+
+		if options.dump_encoding {
+			print_encodings(&generated);
+		}
+		if !options.source_map {
+			generated.source_map.clear();
+		}
+		image = Some(generated);
+	}
+
+	info!("Timings for file {}:", src_file);
+	info!("    lexing:          {:?} ({} tokens)", timings.lex, timings.tokens);
+	info!("    parsing:         {:?} ({} statements)", timings.parse, timings.statements);
+	info!("    linting:         {:?}", timings.lint);
+	info!("    folding:         {:?}", timings.fold);
+	info!("    code generation: {:?}", timings.codegen);
+
+	let symbols = folder.symbols().to_vec();
+	let externs = folder.externs().iter().map(|id| id.to_string()).collect();
+
+	Ok(Assembled { root: ast_root, image, symbols, externs, timings, errors })
+}
+
+/// Print the address, encoding, and fields of every instruction in an image
+/// generated with a source map to stdout
+fn print_encodings(image: &Image) {
+	for location in &image.source_map {
+		let word = image.sections.iter().find_map(|section| {
+			let offset = location.addr.checked_sub(section.addr)? as usize;
+			section.data.get(offset..offset + 4)
+		});
+
+		if let Some(&[a, b, c, d]) = word {
+			let word = u32::from_le_bytes([a, b, c, d]);
+			println!(
+				"{:#010x}: {:#010x}  {}",
+				location.addr,
+				word,
+				codegen::describe_encoding(word)
+			);
+		}
+	}
+}
+
+/// Recursively count the statements in a list of [`Line`]s, including the
+/// statements inside of labeled blocks and both branches of conditionals
+fn count_statements(lines: &[Line]) -> usize {
+	lines
+		.iter()
+		.map(|line| {
+			match &line.statement {
+				Some(Statement::LabeledBlock(block)) => 1 + count_statements(&block.lines),
+				Some(Statement::Conditional(cond)) => {
+					1 + count_statements(&cond.then_lines) + count_statements(&cond.else_lines)
+				},
+				Some(_) => 1,
+				None => 0,
+			}
+		})
+		.sum()
+}
+
+/// Resolve the path of a file included by the source file at `source_file`
+///
+/// Relative paths are resolved relative to the directory containing the
+/// source file
+pub(crate) fn include_path(source_file: &str, path: &str) -> PathBuf {
+	match Path::new(source_file).parent() {
+		Some(dir) => dir.join(path),
+		None => PathBuf::from(path),
+	}
 }
@@ -0,0 +1,237 @@
+//! # Linter
+//!
+//! The linter looks for likely mistakes in the AST (see
+//! [`ast`](crate::parse::ast)) that are not errors, and reports them as
+//! [`Warning`]s
+//!
+//! The following warnings are currently produced:
+//!  - `unreachable-code`: an instruction following an unconditional jump (`jal r0, ...` or `jalr
+//!    r0, ...`) without a label in between can never be executed. Conditional branches and macro
+//!    invocations are assumed to possibly fall through
+//!  - `dead-write`: an instruction writes a register that is overwritten by a later instruction
+//!    before it is ever read. Only straight-line runs of instructions are considered, labels,
+//!    jumps, branches, system instructions, and any other statement may read every register
+//!
+//! Any warning can be allowed by passing its name to [`Linter::allow`]
+
+use std::collections::HashSet;
+
+use crate::error::{LocationInfo, Warning};
+use crate::lex::RegToken;
+use crate::parse::ast::{Instruction, Line, Root, Statement};
+
+/// Main linter type
+///
+/// Wraps all internal state during linting and provides a namespace for all
+/// linter-related functions
+#[derive(Clone, Debug)]
+pub struct Linter<'s> {
+	/// The name of the file being linted (used for warning messages)
+	source_file: &'s str,
+
+	/// The names of all allowed warnings
+	allowed: HashSet<String>,
+}
+
+impl<'s> Linter<'s> {
+	/// Create a new linter given a source file name
+	pub fn new(source_file: &'s str) -> Self { Self { source_file, allowed: HashSet::new() } }
+
+	/// Allow a warning, preventing it from being reported
+	pub fn allow(mut self, name: &str) -> Self {
+		self.allowed.insert(name.to_string());
+		self
+	}
+
+	/// Lint the given AST [`Root`] and return all warnings that are not
+	/// allowed
+	pub fn lint(&self, root: &Root<'s>) -> Vec<Warning> {
+		let mut warnings = vec![];
+
+		for section in &root.sections {
+			self.lint_unreachable(&section.lines, &mut warnings);
+			self.lint_dead_writes(&section.lines, &mut warnings);
+		}
+
+		warnings.retain(|warning| !self.allowed.contains(warning.name()));
+
+		warnings
+	}
+
+	/// Warn about every instruction in the given lines that can not be
+	/// reached
+	///
+	/// Returns whether execution can fall through past the last line
+	fn lint_unreachable(&self, lines: &[Line<'s>], warnings: &mut Vec<Warning>) -> bool {
+		let mut reachable = true;
+
+		for line in lines {
+			match &line.statement {
+				// A label makes its block reachable again, code after the
+				// block is reachable if the end of the block is
+				Some(Statement::LabeledBlock(block)) => {
+					reachable = self.lint_unreachable(&block.lines, warnings);
+				},
+				// Either branch of a conditional may be assembled, code after it
+				// is reachable if the end of either branch is
+				Some(Statement::Conditional(cond)) => {
+					let then_reachable = self.lint_unreachable(&cond.then_lines, warnings);
+					let else_reachable = self.lint_unreachable(&cond.else_lines, warnings);
+
+					reachable = then_reachable || else_reachable;
+				},
+				Some(Statement::MacroInvocation(_) | Statement::LocalLabel(_)) => reachable = true,
+				Some(Statement::Instruction(inst)) => {
+					if !reachable {
+						warnings.push(Warning::UnreachableCode {
+							src_file: self.source_file.to_string(),
+							location: Box::new(LocationInfo::from(&line.start)),
+						});
+					}
+
+					if is_unconditional_jump(inst) {
+						reachable = false;
+					}
+				},
+				_ => (),
+			}
+		}
+
+		reachable
+	}
+
+	/// Warn about every instruction in the given lines whose destination
+	/// register is overwritten before it is read
+	///
+	/// This is a backward liveness analysis, walking the lines from last to
+	/// first while tracking which registers are written before being read by
+	/// the instructions that follow
+	fn lint_dead_writes(&self, lines: &[Line<'s>], warnings: &mut Vec<Warning>) {
+		let mut dead_writes = vec![];
+		// Nothing is known about the registers read after the last line
+		let mut overwritten: HashSet<RegToken> = HashSet::new();
+
+		for line in lines.iter().rev() {
+			let statement = match &line.statement {
+				Some(statement) => statement,
+				None => continue,
+			};
+
+			let registers = match statement {
+				Statement::Instruction(inst) => registers(inst),
+				_ => None,
+			};
+			let Some((dest, sources)) = registers else {
+				// Every register may be read from here on
+				overwritten.clear();
+
+				match statement {
+					Statement::LabeledBlock(block) => self.lint_dead_writes(&block.lines, warnings),
+					Statement::Conditional(cond) => {
+						self.lint_dead_writes(&cond.then_lines, warnings);
+						self.lint_dead_writes(&cond.else_lines, warnings);
+					},
+					_ => (),
+				}
+
+				continue;
+			};
+
+			if let Some(dest) = dest && dest != RegToken::R0 && !overwritten.insert(dest) {
+				dead_writes.push(Warning::DeadWrite {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(&line.start)),
+					reg:      dest.to_string(),
+				});
+			}
+			for src in sources {
+				overwritten.remove(&src);
+			}
+		}
+
+		// Report the warnings in source order
+		warnings.extend(dead_writes.into_iter().rev());
+	}
+}
+
+/// Get the register an instruction writes, if any, along with the registers
+/// it reads
+///
+/// Returns [`None`] for instructions that transfer control or interact with
+/// the system, after which any register may be read
+fn registers(inst: &Instruction) -> Option<(Option<RegToken>, Vec<RegToken>)> {
+	match *inst {
+		Instruction::Addi { dest, src, .. }
+		| Instruction::Slti { dest, src, .. }
+		| Instruction::Sltiu { dest, src, .. }
+		| Instruction::Andi { dest, src, .. }
+		| Instruction::Ori { dest, src, .. }
+		| Instruction::Xori { dest, src, .. }
+		| Instruction::Lsli { dest, src, .. }
+		| Instruction::Lsri { dest, src, .. }
+		| Instruction::Asri { dest, src, .. } => Some((Some(dest), vec![src])),
+
+		Instruction::Add { dest, src1, src2 }
+		| Instruction::Slt { dest, src1, src2 }
+		| Instruction::Sltu { dest, src1, src2 }
+		| Instruction::And { dest, src1, src2 }
+		| Instruction::Or { dest, src1, src2 }
+		| Instruction::Xor { dest, src1, src2 }
+		| Instruction::Lsl { dest, src1, src2 }
+		| Instruction::Lsr { dest, src1, src2 }
+		| Instruction::Asr { dest, src1, src2 }
+		| Instruction::Sub { dest, src1, src2 }
+		| Instruction::Mul { dest, src1, src2 }
+		| Instruction::Mulh { dest, src1, src2 }
+		| Instruction::Mulhu { dest, src1, src2 }
+		| Instruction::Mulhsu { dest, src1, src2 }
+		| Instruction::Div { dest, src1, src2 }
+		| Instruction::Divu { dest, src1, src2 }
+		| Instruction::Rem { dest, src1, src2 }
+		| Instruction::Remu { dest, src1, src2 } => Some((Some(dest), vec![src1, src2])),
+
+		Instruction::Lui { dest, .. } | Instruction::Auipc { dest, .. } => {
+			Some((Some(dest), vec![]))
+		},
+
+		Instruction::Lb { dest, ref addr }
+		| Instruction::Lbu { dest, ref addr }
+		| Instruction::Lh { dest, ref addr }
+		| Instruction::Lhu { dest, ref addr }
+		| Instruction::Lw { dest, ref addr } => Some((Some(dest), vec![addr.base])),
+
+		Instruction::Sb { ref dest, src }
+		| Instruction::Sh { ref dest, src }
+		| Instruction::Sw { ref dest, src } => Some((None, vec![dest.base, src])),
+
+		Instruction::Fence { .. } | Instruction::FenceTso { .. } | Instruction::Fencei => {
+			Some((None, vec![]))
+		},
+
+		Instruction::Jal { .. }
+		| Instruction::Jalr { .. }
+		| Instruction::Beq { .. }
+		| Instruction::Bne { .. }
+		| Instruction::Blt { .. }
+		| Instruction::Bltu { .. }
+		| Instruction::Bge { .. }
+		| Instruction::Bgeu { .. }
+		| Instruction::Ecall
+		| Instruction::Ebreak
+		| Instruction::Csrrw { .. }
+		| Instruction::Csrrs { .. }
+		| Instruction::Csrrc { .. }
+		| Instruction::Csrrwi { .. }
+		| Instruction::Csrrsi { .. }
+		| Instruction::Csrrci { .. } => None,
+	}
+}
+
+/// Check if an instruction always transfers control without linking, ie.
+/// execution never continues with the next instruction
+fn is_unconditional_jump(inst: &Instruction) -> bool {
+	matches!(
+		inst,
+		Instruction::Jal { dest: RegToken::R0, .. } | Instruction::Jalr { dest: RegToken::R0, .. }
+	)
+}
@@ -0,0 +1,53 @@
+//! # Map Files
+//!
+//! A human-readable report of the memory used by an assembled image, listing
+//! the address and size of every section and label, followed by the total
+//! size of the image
+//!
+//! ```text
+//! Sections:
+//!   0x00000000  0x0000000c  .text
+//!   0x0000000c  0x00000004  .data
+//!
+//! Symbols:
+//!   0x00000000  0x00000008  _start
+//!   0x00000004  0x00000004  _start.nested
+//!   0x0000000c  0x00000004  message
+//!
+//! Total: 16 bytes
+//! ```
+//!
+//! The size of a label is the size of the block it is attached to, labels of
+//! empty blocks have a size of 0
+
+use crate::fold::Symbol;
+use crate::wpibin::Image;
+
+/// Render the map file of an image and the labels declared in its source
+///
+/// Symbols are listed by address, labels declared at the same address keep
+/// their order of declaration
+pub fn render(image: &Image, symbols: &[Symbol]) -> String {
+	let mut map = String::from("Sections:\n");
+	for section in &image.sections {
+		map.push_str(&format!(
+			"  {:#010x}  {:#010x}  {}\n",
+			section.addr,
+			section.data.len(),
+			section.name
+		));
+	}
+
+	let mut symbols: Vec<&Symbol> = symbols.iter().collect();
+	symbols.sort_by_key(|symbol| symbol.addr);
+
+	map.push_str("\nSymbols:\n");
+	for symbol in symbols {
+		map.push_str(&format!("  {:#010x}  {:#010x}  {}\n", symbol.addr, symbol.size, symbol.name));
+	}
+
+	let total: usize = image.sections.iter().map(|section| section.data.len()).sum();
+	map.push_str(&format!("\nTotal: {} bytes\n", total));
+
+	map
+}
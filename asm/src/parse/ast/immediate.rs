@@ -2,7 +2,7 @@
 
 use std::fmt::{Display, Formatter, Result};
 
-use crate::lex::Token;
+use crate::lex::{Token, TokenType};
 
 /// An immediate value
 ///
@@ -11,12 +11,35 @@ use crate::lex::Token;
 ///
 /// *EBNF not given as it is too chonky, look at the docs folder for grammar*
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Immediate<'s> {
 	/// The tokens making up this immediate, parsed into
 	/// [Reverse Polish notation](https://en.wikipedia.org/wiki/Reverse_Polish_notation)
 	pub rpn_tokens: Vec<Token<'s>>,
 }
 
+impl<'s> Immediate<'s> {
+	/// Get the value of this immediate if it has been folded into a single
+	/// numeric literal
+	pub fn value(&self) -> Option<i32> {
+		match self.rpn_tokens[..] {
+			[Token { t: TokenType::LitNum(n), .. }] => Some(n),
+			_ => None,
+		}
+	}
+}
+
+/// Immediates are equal if their tokens are of the same types, the locations
+/// of the tokens are ignored
+impl<'s> PartialEq for Immediate<'s> {
+	fn eq(&self, other: &Self) -> bool {
+		self.rpn_tokens.len() == other.rpn_tokens.len()
+			&& self.rpn_tokens.iter().zip(&other.rpn_tokens).all(|(a, b)| a.t == b.t)
+	}
+}
+
+impl<'s> Eq for Immediate<'s> {}
+
 impl<'s> Display for Immediate<'s> {
 	fn fmt(&self, f: &mut Formatter<'_>) -> Result {
 		let repr =
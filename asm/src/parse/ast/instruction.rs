@@ -1,5 +1,8 @@
 //! AST instruction type definitions
 
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 use bitflags::bitflags;
 
 use super::Immediate;
@@ -13,7 +16,8 @@ use crate::lex::{OpToken, RegToken};
 /// Fence instructions use [`OrderingTarget`]s
 ///
 /// *EBNF not given as it is too chonky, look at the docs folder for grammar*
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(missing_docs)]
 pub enum Instruction<'s> {
 	// Integer Register Immediate
@@ -102,6 +106,13 @@ pub enum Instruction<'s> {
 	Remu { dest: RegToken, src1: RegToken, src2: RegToken },
 }
 
+impl<'s> Instruction<'s> {
+	/// Get the amount of bytes this instruction will be encoded into
+	///
+	/// All instructions are encoded as a single word
+	pub fn size_bytes(&self) -> u32 { 4 }
+}
+
 /// An address calculation for use in load/store instructions
 ///
 /// Contains a base [register](RegToken) and an optional [offset](AddrOffset)
@@ -109,7 +120,8 @@ pub enum Instruction<'s> {
 /// ```ebnf
 /// address_calculation = "[", register, [ address_offset ] "]";
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Address<'s> {
 	/// The base register of the address
 	pub base:   RegToken,
@@ -121,10 +133,16 @@ pub struct Address<'s> {
 ///
 /// Contains an [operator](OffsetOperator) and some [offset](Immediate)
 ///
+/// A leading `-` is part of the offset expression, so `[r1 - a + b]` has an
+/// offset of `(-a) + b`. Offsets that are negated as a whole, such as
+/// `[r1 - 4]` or `[r1 - (a + b)]`, use the [`Minus`](OffsetOperator::Minus)
+/// operator
+///
 /// ```ebnf
 /// address_offset = "+" | "-", immediate;
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AddrOffset<'s> {
 	/// The operator of the offset (+ or -)
 	pub op:     OffsetOperator,
@@ -137,7 +155,8 @@ pub struct AddrOffset<'s> {
 /// Can be either "+" or "-"
 ///
 /// See [`AddrOffset`] for grammar
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[allow(missing_docs)]
 pub enum OffsetOperator {
 	Plus,
@@ -169,6 +188,7 @@ bitflags! {
 	/// ```ebnf
 	/// ordering_operation = [ "i" ], [ "o" ], [ "r" ], [ "w" ];
 	/// ```
+	#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 	pub struct OrderingTarget: u8 {
 		/// An input instruction
 		const I = 0b0000_0001;
@@ -181,6 +201,19 @@ bitflags! {
 	}
 }
 
+impl OrderingTarget {
+	/// Iterate over the set flags as lowercase characters, in canonical
+	/// `iorw` order
+	pub fn iter_flags(&self) -> impl Iterator<Item = char> {
+		let target = *self;
+
+		[(Self::I, 'i'), (Self::O, 'o'), (Self::R, 'r'), (Self::W, 'w')]
+			.into_iter()
+			.filter(move |(flag, _)| target.contains(*flag))
+			.map(|(_, c)| c)
+	}
+}
+
 impl From<&str> for OrderingTarget {
 	fn from(value: &str) -> Self {
 		let mut flags = Self::empty();
@@ -201,3 +234,45 @@ impl From<&str> for OrderingTarget {
 		flags
 	}
 }
+
+impl Display for OrderingTarget {
+	/// Write the set flags in canonical `IORW` order
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		self.iter_flags().try_for_each(|c| write!(f, "{}", c.to_ascii_uppercase()))
+	}
+}
+
+impl FromStr for OrderingTarget {
+	type Err = InvalidOrderingTarget;
+
+	/// Parse a string of flags in any order
+	///
+	/// Unlike the `From<&str>` impl, any character other than `I`, `O`, `R`,
+	/// or `W` is an error
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut flags = Self::empty();
+
+		for c in s.chars() {
+			match c {
+				'I' => flags.set(Self::I, true),
+				'O' => flags.set(Self::O, true),
+				'R' => flags.set(Self::R, true),
+				'W' => flags.set(Self::W, true),
+				_ => return Err(InvalidOrderingTarget(c)),
+			}
+		}
+
+		Ok(flags)
+	}
+}
+
+/// The error returned when parsing an [`OrderingTarget`] containing a
+/// character that is not a valid flag
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidOrderingTarget(pub char);
+
+impl Display for InvalidOrderingTarget {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "invalid ordering flag `{}`, expected one of `I`, `O`, `R`, or `W`", self.0)
+	}
+}
@@ -12,7 +12,8 @@ use crate::lex::TokenType;
 ///     | ( "{", { macro_rules }, "}" );
 /// macro_rules = macro_rule, { comma, macro_rule };
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MacroDefinition<'s> {
 	/// The name of the macro getting defined
 	pub id:    &'s str,
@@ -23,7 +24,7 @@ pub struct MacroDefinition<'s> {
 /// One of the rules making up a specific macro
 ///
 /// Contains a list of [`MacroMatch`]es to specify an argument pattern to
-/// detect and a transcriber containing one or more [`Statement`]s which will
+/// detect and a transcriber containing one or more [`Statement`](super::Statement)s which will
 /// get substituted into the AST when the macro gets called
 ///
 /// ```ebnf
@@ -35,7 +36,8 @@ pub struct MacroDefinition<'s> {
 ///     | ( "[", { line }, "]" )
 ///     | ( "{", { line }, "}" );
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MacroRule<'s> {
 	/// The pattern matching this rule
 	pub matcher:     Vec<MacroMatch<'s>>,
@@ -56,9 +58,13 @@ pub struct MacroRule<'s> {
 /// macro_arg_type = "inst", "reg", "dir", "ident", "imm", "stmt";
 /// macro_var_type = "?" | "+" | "*";
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MacroMatch<'s> {
 	/// A literal string of characters
+	///
+	/// Arguments only match if they are equal (`==`) to the token, including
+	/// its value, see [`TokenType::matches_pattern`] to match on kind alone
 	Raw(TokenType<'s>),
 	/// A single argument
 	Typed {
@@ -79,8 +85,37 @@ pub enum MacroMatch<'s> {
 	},
 }
 
+impl<'s> MacroRule<'s> {
+	/// Check if this rule matches exactly the same arguments as another rule,
+	/// in which case expanding the macro would be ambiguous
+	///
+	/// Matchers are compared structurally, the names of arguments are ignored
+	pub fn is_ambiguous_with(&self, other: &Self) -> bool {
+		same_shape(&self.matcher, &other.matcher)
+	}
+}
+
+/// Check if two lists of [`MacroMatch`]es match the same arguments
+fn same_shape(left: &[MacroMatch], right: &[MacroMatch]) -> bool {
+	left.len() == right.len()
+		&& left.iter().zip(right).all(|pair| {
+			match pair {
+				(MacroMatch::Raw(l), MacroMatch::Raw(r)) => l == r,
+				(MacroMatch::Typed { arg_type: l, .. }, MacroMatch::Typed { arg_type: r, .. }) => {
+					l == r
+				},
+				(
+					MacroMatch::Variadic { matches: lm, rep_sep: ls, var_type: lv },
+					MacroMatch::Variadic { matches: rm, rep_sep: rs, var_type: rv },
+				) => ls == rs && lv == rv && same_shape(lm, rm),
+				_ => false,
+			}
+		})
+}
+
 /// The possible type specifiers that can be used in a [`MacroMatch`]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MacroArgType {
 	/// Any instruction keyword
 	Inst,
@@ -98,7 +133,8 @@ pub enum MacroArgType {
 
 /// The possible types of 'variadicity' that a variadic [`MacroMatch`] can
 /// detect
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MacroVarType {
 	/// 0 or 1 matches
 	Optional,
@@ -117,7 +153,8 @@ pub enum MacroVarType {
 ///     | ( "[", { token }, "]" )
 ///     | ( "{", { token }, "}" );
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MacroInvocation<'s> {
 	/// The name of the macro getting called
 	pub id:   &'s str,
@@ -5,6 +5,10 @@
 //! It provides an easier way of interacting with, modifying, and reasoning
 //! about source code as compared to a stream of [`Token`](crate::lex::Token)s
 //! or a raw string
+//!
+//! All AST types implement [`PartialEq`] structurally, the locations of any
+//! tokens are ignored so that differently formatted but otherwise equivalent
+//! source code produces equal ASTs
 
 // #![allow(missing_docs)]
 
@@ -16,6 +20,8 @@ pub use immediate::*;
 pub use instruction::*;
 pub use r#macro::*;
 
+use crate::lex::{unescape, Token};
+
 /// The root of the AST
 ///
 /// Contains a [`preamble`](PreambleLine) and a list of [`Section`]s
@@ -23,7 +29,8 @@ pub use r#macro::*;
 /// ```ebnf
 /// root = [ preamble ], { section };
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Root<'s> {
 	/// The preamble of the file (see [`PreambleLine`] for more info)
 	pub preamble: Vec<PreambleLine<'s>>,
@@ -31,6 +38,53 @@ pub struct Root<'s> {
 	pub sections: Vec<Section<'s>>,
 }
 
+impl<'s> Root<'s> {
+	/// Iterate over every [`Instruction`] in the sections of this AST in
+	/// program order, along with the first token of its line
+	///
+	/// Descends into [`LabeledBlock`]s, and into both branches of any
+	/// [`Conditional`] that has not been resolved by the
+	/// [`Folder`](crate::fold::Folder) yet
+	pub fn instructions(&self) -> Instructions<'_, 's> {
+		Instructions { stack: self.sections.iter().rev().map(|s| s.lines.iter()).collect() }
+	}
+}
+
+/// Iterator over every [`Instruction`] in an AST, see [`Root::instructions`]
+#[derive(Clone, Debug)]
+pub struct Instructions<'a, 's> {
+	/// The lines left to visit at every level of nesting, innermost last
+	stack: Vec<std::slice::Iter<'a, Line<'s>>>,
+}
+
+impl<'a, 's> Iterator for Instructions<'a, 's> {
+	type Item = (&'a Instruction<'s>, &'a Token<'s>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while let Some(lines) = self.stack.last_mut() {
+			let line = match lines.next() {
+				Some(line) => line,
+				None => {
+					self.stack.pop();
+					continue;
+				},
+			};
+
+			match &line.statement {
+				Some(Statement::Instruction(inst)) => return Some((inst, &line.start)),
+				Some(Statement::LabeledBlock(block)) => self.stack.push(block.lines.iter()),
+				Some(Statement::Conditional(cond)) => {
+					self.stack.push(cond.else_lines.iter());
+					self.stack.push(cond.then_lines.iter());
+				},
+				_ => (),
+			}
+		}
+
+		None
+	}
+}
+
 /// A single line in the preamble of some source code
 ///
 /// The preamble will not be emitted to any named sections, and so cannot
@@ -45,20 +99,34 @@ pub struct Root<'s> {
 ///     newline;
 /// ```
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PreambleLine<'s> {
 	/// The optional [`PreambleStatement`] in this line
 	pub statement: Option<PreambleStatement<'s>>,
 	/// The optional comment in this line
 	pub comment:   Option<&'s str>,
+	/// The first token of this line (used for errors)
+	pub start:     Token<'s>,
 }
 
+/// Lines are equal if their contents are equal, the location of the line is
+/// ignored
+impl<'s> PartialEq for PreambleLine<'s> {
+	fn eq(&self, other: &Self) -> bool {
+		self.statement == other.statement && self.comment == other.comment
+	}
+}
+
+impl<'s> Eq for PreambleLine<'s> {}
+
 /// A statement that is allowed within the source file preamble,
 /// can contain either [`MacroDefinition`]s or [`ConstDirective`]s
 ///
 /// ```ebnf
 /// preamble_statement = const_directive | macro_definition;
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PreambleStatement<'s> {
 	/// A macro definition
 	MacroDefinition(MacroDefinition<'s>),
@@ -68,17 +136,23 @@ pub enum PreambleStatement<'s> {
 
 /// A directive to declare assemble-time constants
 ///
-/// Sets the prefixed [`LabelId`] equal to its [`value`](Literal)
+/// Sets the prefixed identifier equal to its [`value`](Literal). A constant
+/// declared with `#CONST` can only be declared once, one declared with `#SET`
+/// can be given a new value later on, which is used from that point onwards
 ///
 /// ```ebnf
-/// const_directive = "#CONST", literal;
+/// const_directive = ( "#CONST" | "#SET" ), identifier, literal;
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ConstDirective<'s> {
 	/// The identifier defining the name of this constant
-	pub id:    &'s str,
+	pub id:           &'s str,
 	/// The value of this constant
-	pub value: Literal<'s>,
+	pub value:        Literal<'s>,
+	/// Whether this constant was declared with `#SET`, and so may be
+	/// declared again
+	pub reassignable: bool,
 }
 
 /// A single assembler
@@ -96,7 +170,8 @@ pub struct ConstDirective<'s> {
 /// section_header = "#SECTION", section_name, newline;
 /// section_name = ".TEXT" | ".DATA" | ".BSS";
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Section<'s> {
 	/// The name of the section
 	pub name:  &'s str,
@@ -117,27 +192,43 @@ pub struct Section<'s> {
 ///     newline;
 /// ```
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Line<'s> {
 	/// The optional content in this line
 	pub statement: Option<Statement<'s>>,
 	/// The optional comment in this line
 	pub comment:   Option<&'s str>,
+	/// The first token of this line (used for warnings)
+	pub start:     Token<'s>,
 }
 
+/// Lines are equal if their contents are equal, the location of the line is
+/// ignored
+impl<'s> PartialEq for Line<'s> {
+	fn eq(&self, other: &Self) -> bool {
+		self.statement == other.statement && self.comment == other.comment
+	}
+}
+
+impl<'s> Eq for Line<'s> {}
+
 /// A single assembly statement
 ///
 /// Can be a [`MacroDefinition`], [`MacroInvocation`], [`LabeledBlock`],
-/// [`Directive`], or an [`Instruction`]
+/// [`Conditional`], local label, [`Directive`], or an [`Instruction`]
 ///
 /// ```ebnf
 /// statement =
 ///     macro_definition
 ///     | macro_invocation
 ///     | labeled_block
+///     | conditional
+///     | local_label
 ///     | directive
 ///     | instruction;
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Statement<'s> {
 	/// A macro definition
 	MacroDefinition(MacroDefinition<'s>),
@@ -145,6 +236,11 @@ pub enum Statement<'s> {
 	MacroInvocation(MacroInvocation<'s>),
 	/// A labeled block of code
 	LabeledBlock(LabeledBlock<'s>),
+	/// A block of code that is only assembled if a condition holds
+	Conditional(Conditional<'s>),
+	/// A numeric local label, which can be referenced using `1b` or `1f` to
+	/// get the address of the closest preceding or following definition
+	LocalLabel(u32),
 	/// A directive
 	Directive(Directive<'s>),
 	/// An instruction
@@ -156,7 +252,8 @@ pub enum Statement<'s> {
 /// ```ebnf
 /// labeled_block = identifier, "{", { line }, "}";
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LabeledBlock<'s> {
 	/// The label naming this block
 	pub label: &'s str,
@@ -164,24 +261,61 @@ pub struct LabeledBlock<'s> {
 	pub lines: Vec<Line<'s>>,
 }
 
+/// A block of code that is only assembled if its condition is nonzero,
+/// optionally followed by a block that is assembled otherwise
+///
+/// Conditionals are resolved by the [`Folder`](crate::fold::Folder), which
+/// replaces them by the lines of the taken branch
+///
+/// ```ebnf
+/// conditional =
+///     "#IF", immediate, [ comment ], newline,
+///     { line },
+///     [ "#ELSE", [ comment ], newline, { line } ],
+///     "#ENDIF";
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Conditional<'s> {
+	/// The condition deciding which branch is taken
+	pub condition:  Immediate<'s>,
+	/// The lines assembled if the condition is nonzero
+	pub then_lines: Vec<Line<'s>>,
+	/// The lines assembled if the condition is zero
+	pub else_lines: Vec<Line<'s>>,
+}
+
 /// A directive that creates or otherwise manipulates data
 ///
 /// Directives can:
 ///  - define initialised data as bytes, halves, or words
+///  - pack values of arbitrary bit widths into bytes
 ///  - reserve a given number bytes, halves, or words
 ///  - declare an identifier as a constant
+///  - embed the contents of a binary file
+///  - declare a symbol defined outside of the source file
+///  - pad the location counter to a given boundary
+///  - pad the location counter to an absolute address
 ///
 /// ```ebnf
 /// directive =
 ///     bytes_directive
 ///     | halves_directive
 ///     | words_directive
+///     | bits_directive
 ///     | res_bytes_directive
 ///     | res_halves_directive
 ///     | res_words_directive
-///     | const_directive;
+///     | const_directive
+///     | include_bin_directive
+///     | extern_directive
+///     | global_directive
+///     | weak_directive
+///     | align_directive
+///     | org_directive;
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Directive<'s> {
 	/// Encodes data as bytes
 	Bytes(Vec<Literal<'s>>),
@@ -189,6 +323,9 @@ pub enum Directive<'s> {
 	Halves(Vec<Literal<'s>>),
 	/// Encodes data as words
 	Words(Vec<Literal<'s>>),
+	/// Packs values into the given amount of bits each, most significant bit
+	/// first, padding the last byte with zero bits
+	Bits(Vec<BitField<'s>>),
 
 	/// Reserve a given amount of bytes
 	ResBytes(Vec<Literal<'s>>),
@@ -199,6 +336,121 @@ pub enum Directive<'s> {
 
 	/// Declare some identifier to be a constant
 	Const(ConstDirective<'s>),
+
+	/// Embed the bytes of a file
+	IncludeBin {
+		/// The **UNESCAPED** path of the file, relative to the source file
+		path: &'s str,
+	},
+
+	/// Declare a symbol defined outside of the source file, references to it
+	/// are left for the loader or linker to resolve
+	Extern {
+		/// The name of the symbol
+		id: &'s str,
+	},
+
+	/// Give a label [global](crate::fold::SymbolBinding::Global) binding
+	Global {
+		/// The name of the label
+		id: &'s str,
+	},
+
+	/// Give a label [weak](crate::fold::SymbolBinding::Weak) binding, a weak
+	/// label that is never declared resolves to address 0
+	Weak {
+		/// The name of the label
+		id: &'s str,
+	},
+
+	/// Pad with zero bytes up to the next multiple of a power of two
+	Align {
+		/// The boundary to align to
+		boundary: Immediate<'s>,
+	},
+
+	/// Pad with zero bytes up to an absolute address, which may not lie
+	/// before the current address
+	Org {
+		/// The address to continue at
+		addr: Immediate<'s>,
+	},
+}
+
+impl<'s> Directive<'s> {
+	/// Get the amount of bytes this directive will be encoded into
+	///
+	/// Strings are encoded as their UTF-8 bytes in byte directives, and as
+	/// one value per character in wider directives
+	///
+	/// Returns [`None`] if a reservation count or bit field width has not
+	/// been folded into a single value yet, for `#INCLUDE_BIN` directives as
+	/// their size depends on the included file, and for `#ALIGN` and `#ORG`
+	/// directives as their size depends on their address
+	///
+	/// Negative reservation counts are treated as their unsigned
+	/// reinterpretation, the [`Folder`](crate::fold::Folder) rejects them
+	pub fn size_bytes(&self) -> Option<u64> {
+		let (lits, width, reserve) = match self {
+			Self::Bytes(lits) => (lits, 1, false),
+			Self::Halves(lits) => (lits, 2, false),
+			Self::Words(lits) => (lits, 4, false),
+			Self::Bits(fields) => {
+				let mut bits = 0u64;
+				for field in fields {
+					bits = bits.saturating_add(field.width.value()? as u32 as u64);
+				}
+
+				return Some(bits.saturating_add(7) / 8);
+			},
+			Self::ResBytes(lits) => (lits, 1, true),
+			Self::ResHalves(lits) => (lits, 2, true),
+			Self::ResWords(lits) => (lits, 4, true),
+			Self::Const(_) | Self::Extern { .. } | Self::Global { .. } | Self::Weak { .. } => {
+				return Some(0);
+			},
+			Self::IncludeBin { .. } | Self::Align { .. } | Self::Org { .. } => return None,
+		};
+
+		let mut count = 0u64;
+		for lit in lits {
+			count = count.saturating_add(match lit {
+				Literal::Immediate(imm) if reserve => imm.value()? as u32 as u64,
+				Literal::Char(c, _) if reserve => *c as u64,
+				Literal::String(s, _) if width == 1 && !reserve => unescape(s).len() as u64,
+				Literal::String(s, _) => unescape(s).chars().count() as u64,
+				Literal::Immediate(_) | Literal::Char(..) => 1,
+			});
+		}
+
+		Some(count.saturating_mul(width))
+	}
+
+	/// Check if this directive emits data with a given value, rather than
+	/// reserving or padding space
+	pub fn initializes_data(&self) -> bool {
+		matches!(
+			self,
+			Self::Bytes(_)
+				| Self::Halves(_)
+				| Self::Words(_) | Self::Bits(_)
+				| Self::IncludeBin { .. }
+		)
+	}
+}
+
+/// A value packed into a fixed amount of bits by a `#BITS` directive
+///
+/// ```ebnf
+/// bit_field = "(", immediate, ":", immediate, ")";
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BitField<'s> {
+	/// The value to pack
+	pub value: Immediate<'s>,
+	/// The amount of bits to pack the value into
+	pub width: Immediate<'s>,
 }
 
 /// A literal value
@@ -208,12 +460,13 @@ pub enum Directive<'s> {
 /// ```ebnf
 /// literal = string | char | immediate;
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Literal<'s> {
-	/// A string literal
-	String(&'s str),
-	/// A character literal
-	Char(char),
+	/// A string literal, along with its token
+	String(&'s str, Token<'s>),
+	/// A character literal, along with its token
+	Char(char, Token<'s>),
 	/// An immediate (a number, label, or arithmetic expression)
 	Immediate(Immediate<'s>),
 }
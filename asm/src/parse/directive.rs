@@ -1,19 +1,27 @@
 //! [`Parser`] functions to parse [`Directive`] expressions
 
-use super::ast::{Directive, Literal};
+use super::ast::{BitField, Directive, Literal};
 use super::Parser;
 use crate::error::ParseError;
-use crate::lex::{DirToken, RegularDirective, TokenType};
+use crate::lex::{DirToken, OpToken, RegularDirective, TokenType};
 
 impl<'s> Parser<'s> {
 	/// Parse any of the following [`Directive`]s:
 	///  - [`#BYTES`](RegularDirective::Bytes)
 	///  - [`#HALVES`](RegularDirective::Halves)
 	///  - [`#WORDS`](RegularDirective::Words)
+	///  - [`#BITS`](RegularDirective::Bits)
 	///  - [`#RES_BYTES`](RegularDirective::ResBytes)
 	///  - [`#RES_HALVES`](RegularDirective::ResHalves)
 	///  - [`#RES_WORDS`](RegularDirective::ResWords)
 	///  - [`#CONST`](RegularDirective::Const)
+	///  - [`#SET`](RegularDirective::Set)
+	///  - [`#INCLUDE_BIN`](RegularDirective::IncludeBin)
+	///  - [`#EXTERN`](RegularDirective::Extern)
+	///  - [`#GLOBAL`](RegularDirective::Global)
+	///  - [`#WEAK`](RegularDirective::Weak)
+	///  - [`#ALIGN`](RegularDirective::Align)
+	///  - [`#ORG`](RegularDirective::Org)
 	///
 	/// Assumes the current [`Token`](crate::lex::Token) has [`TokenType`]
 	/// [`TokenType::Dir`]
@@ -22,9 +30,73 @@ impl<'s> Parser<'s> {
 		let directive_token = self.peek().unwrap();
 
 		match &directive_token.t {
-			TokenType::Dir(DirToken::Regular(RegularDirective::Const)) => {
+			TokenType::Dir(DirToken::Regular(RegularDirective::Const | RegularDirective::Set)) => {
 				self.parse_const_directive().map(Directive::Const)
 			},
+			TokenType::Dir(DirToken::Regular(RegularDirective::IncludeBin)) => {
+				// unwrap is safe as peek must've existed
+				self.next().unwrap();
+				let path_token =
+					self.expect_described(TokenType::LitStr(""), "`STRING`".to_string())?;
+
+				match path_token.t {
+					TokenType::LitStr(path) => Ok(Directive::IncludeBin { path }),
+					_ => unreachable!(),
+				}
+			},
+			TokenType::Dir(DirToken::Regular(
+				dir
+				@ (RegularDirective::Extern | RegularDirective::Global | RegularDirective::Weak),
+			)) => {
+				let dir = *dir;
+
+				// unwrap is safe as peek must've existed
+				self.next().unwrap();
+				let id_token = self.expect(TokenType::Identifier(""))?;
+
+				let id = match id_token.t {
+					TokenType::Identifier(id) => id,
+					_ => unreachable!(),
+				};
+
+				match dir {
+					RegularDirective::Extern => Ok(Directive::Extern { id }),
+					RegularDirective::Global => Ok(Directive::Global { id }),
+					_ => Ok(Directive::Weak { id }),
+				}
+			},
+			TokenType::Dir(DirToken::Regular(RegularDirective::Align)) => {
+				// unwrap is safe as peek must've existed
+				self.next().unwrap();
+				let boundary = self.parse_immediate()?;
+
+				Ok(Directive::Align { boundary })
+			},
+			TokenType::Dir(DirToken::Regular(RegularDirective::Bits)) => {
+				// unwrap is safe as peek must've existed
+				self.next().unwrap();
+
+				let mut fields = vec![self.parse_bit_field()?];
+				while let Ok(peek) = self.peek() && peek.t == TokenType::SymComma {
+					// Unwrap is safe as peek is Ok
+					self.next().unwrap();
+
+					if self.trailing_commas.lists && self.ends_list() {
+						break;
+					}
+
+					fields.push(self.parse_bit_field()?);
+				}
+
+				Ok(Directive::Bits(fields))
+			},
+			TokenType::Dir(DirToken::Regular(RegularDirective::Org)) => {
+				// unwrap is safe as peek must've existed
+				self.next().unwrap();
+				let addr = self.parse_immediate()?;
+
+				Ok(Directive::Org { addr })
+			},
 			TokenType::Dir(DirToken::Regular(dir)) => {
 				// unwrap is safe as peek must've existed
 				self.next().unwrap();
@@ -44,6 +116,18 @@ impl<'s> Parser<'s> {
 		}
 	}
 
+	/// Parse a single `(value : width)` pair of a
+	/// [`#BITS`](RegularDirective::Bits) directive
+	fn parse_bit_field<'r>(&'r mut self) -> Result<BitField<'s>, ParseError> {
+		self.expect(TokenType::SymLeftParen)?;
+		let value = self.parse_immediate_until(TokenType::Op(OpToken::Colon))?;
+		self.expect(TokenType::Op(OpToken::Colon))?;
+		let width = self.parse_immediate_until(TokenType::SymRightParen)?;
+		self.expect(TokenType::SymRightParen)?;
+
+		Ok(BitField { value, width })
+	}
+
 	/// Parse a value list for any of the following [`DataDirective`]s:
 	///  - [`#BYTES`](DirToken::Bytes)
 	///  - [`#HALVES`](DirToken::Halves)
@@ -62,6 +146,10 @@ impl<'s> Parser<'s> {
 			// Unwrap is safe as peek is Ok
 			self.next().unwrap();
 
+			if self.trailing_commas.lists && self.ends_list() {
+				break;
+			}
+
 			let value = self.parse_literal()?;
 			data.push(value);
 		}
@@ -4,6 +4,7 @@ use super::Node;
 use crate::lex::RegToken;
 use crate::parse::ast::{
 	Address,
+	Conditional,
 	ConstDirective,
 	Directive,
 	Immediate,
@@ -90,7 +91,9 @@ impl<'s> From<&ConstDirective<'s>> for Node {
 
 		children.push(Node::from(&value.value).add_prefix("Value"));
 
-		Node { prefixes: vec!["Directive".to_string()], repr: "Const".to_string(), children }
+		let repr = if value.reassignable { "Set" } else { "Const" };
+
+		Node { prefixes: vec!["Directive".to_string()], repr: repr.to_string(), children }
 	}
 }
 
@@ -143,6 +146,14 @@ impl<'s> From<&Statement<'s>> for Node {
 			Statement::MacroDefinition(m_def) => Node::from(m_def).add_prefix("Statement"),
 			Statement::MacroInvocation(m_invoc) => Node::from(m_invoc).add_prefix("Statement"),
 			Statement::LabeledBlock(l_block) => Node::from(l_block).add_prefix("Statement"),
+			Statement::Conditional(cond) => Node::from(cond).add_prefix("Statement"),
+			Statement::LocalLabel(label) => {
+				Node {
+					prefixes: vec!["Statement".to_string()],
+					repr:     format!("LocalLabel {}", label),
+					children: vec![],
+				}
+			},
 			Statement::Directive(dir) => Node::from(dir).add_prefix("Statement"),
 			Statement::Instruction(inst) => Node::from(inst).add_prefix("Statement"),
 		}
@@ -295,6 +306,26 @@ impl<'s> From<&LabeledBlock<'s>> for Node {
 	}
 }
 
+impl<'s> From<&Conditional<'s>> for Node {
+	fn from(value: &Conditional<'s>) -> Self {
+		let children = vec![
+			Node::from(&value.condition).add_prefix("Condition"),
+			Node {
+				prefixes: vec![],
+				repr:     "Then".to_string(),
+				children: value.then_lines.iter().map(|l| l.into()).collect(),
+			},
+			Node {
+				prefixes: vec![],
+				repr:     "Else".to_string(),
+				children: value.else_lines.iter().map(|l| l.into()).collect(),
+			},
+		];
+
+		Node { prefixes: vec![], repr: "Conditional".to_string(), children }
+	}
+}
+
 impl<'s> From<&Directive<'s>> for Node {
 	fn from(value: &Directive) -> Self {
 		match value {
@@ -319,6 +350,25 @@ impl<'s> From<&Directive<'s>> for Node {
 					children: data.iter().map(|d| d.into()).collect(),
 				}
 			},
+			Directive::Bits(fields) => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "Bits".to_string(),
+					children: fields
+						.iter()
+						.map(|field| {
+							Node {
+								prefixes: vec![],
+								repr:     "BitField".to_string(),
+								children: vec![
+									Node::from(&field.value).add_prefix("Value"),
+									Node::from(&field.width).add_prefix("Width"),
+								],
+							}
+						})
+						.collect(),
+				}
+			},
 			Directive::ResBytes(data) => {
 				Node {
 					prefixes: vec!["Directive".to_string()],
@@ -341,6 +391,64 @@ impl<'s> From<&Directive<'s>> for Node {
 				}
 			},
 			Directive::Const(const_dir) => Node::from(const_dir),
+			Directive::Extern { id } => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "Extern".to_string(),
+					children: vec![Node {
+						prefixes: vec!["Id".to_string()],
+						repr:     id.to_string(),
+						children: vec![],
+					}],
+				}
+			},
+			Directive::Global { id } => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "Global".to_string(),
+					children: vec![Node {
+						prefixes: vec!["Id".to_string()],
+						repr:     id.to_string(),
+						children: vec![],
+					}],
+				}
+			},
+			Directive::Weak { id } => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "Weak".to_string(),
+					children: vec![Node {
+						prefixes: vec!["Id".to_string()],
+						repr:     id.to_string(),
+						children: vec![],
+					}],
+				}
+			},
+			Directive::Align { boundary } => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "Align".to_string(),
+					children: vec![Node::from(boundary).add_prefix("Boundary")],
+				}
+			},
+			Directive::Org { addr } => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "Org".to_string(),
+					children: vec![Node::from(addr).add_prefix("Address")],
+				}
+			},
+			Directive::IncludeBin { path } => {
+				Node {
+					prefixes: vec!["Directive".to_string()],
+					repr:     "IncludeBin".to_string(),
+					children: vec![Node {
+						prefixes: vec!["Path".to_string()],
+						repr:     format!("{:?}", path),
+						children: vec![],
+					}],
+				}
+			},
 		}
 	}
 }
@@ -348,14 +456,14 @@ impl<'s> From<&Directive<'s>> for Node {
 impl<'s> From<&Literal<'s>> for Node {
 	fn from(value: &Literal) -> Self {
 		match value {
-			Literal::String(s) => {
+			Literal::String(s, _) => {
 				Node {
 					prefixes: vec!["Literal".to_string(), "String".to_string()],
 					repr:     format!("{:?}", s),
 					children: vec![],
 				}
 			},
-			Literal::Char(c) => {
+			Literal::Char(c, _) => {
 				Node {
 					prefixes: vec!["Literal".to_string(), "Char".to_string()],
 					repr:     format!("{:?}", c),
@@ -1011,21 +1119,6 @@ impl From<&OffsetOperator> for Node {
 
 impl From<&OrderingTarget> for Node {
 	fn from(value: &OrderingTarget) -> Self {
-		let mut repr = String::new();
-
-		if value.contains(OrderingTarget::I) {
-			repr.push('I');
-		}
-		if value.contains(OrderingTarget::O) {
-			repr.push('O');
-		}
-		if value.contains(OrderingTarget::R) {
-			repr.push('R');
-		}
-		if value.contains(OrderingTarget::W) {
-			repr.push('W');
-		}
-
-		Node { prefixes: vec![], repr, children: vec![] }
+		Node { prefixes: vec![], repr: value.to_string(), children: vec![] }
 	}
 }
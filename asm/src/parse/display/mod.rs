@@ -1,5 +1,5 @@
 //! Types and implementations to allow the AST to be printed as a structured
-//! tree
+//! tree, or exported as a Graphviz DOT graph
 
 use std::fmt::{Display, Formatter, Write};
 
@@ -36,6 +36,24 @@ impl Node {
 		Node { prefixes, ..self }
 	}
 
+	/// Export this node and all its children as a Graphviz DOT `digraph`
+	///
+	/// Every node becomes a vertex labeled with its prefixes and
+	/// representation, with an edge to each of its children
+	pub fn to_dot(&self) -> String {
+		let mut dot = String::from("digraph ast {\n\tnode [shape=box];\n");
+		let mut next_id = 0;
+		write_dot(&mut dot, self, &mut next_id);
+		dot.push_str("}\n");
+
+		dot
+	}
+
+	/// The label of this node, ie. its prefixes followed by its representation
+	fn label(&self) -> String {
+		self.prefixes.iter().map(|p| format!("({}) ", p)).collect::<String>() + &self.repr
+	}
+
 	/// Print this node as a string
 	fn write_self<W: Write>(&self, f: &mut W) -> std::fmt::Result {
 		if !(self.prefixes.is_empty()) {
@@ -51,6 +69,25 @@ impl Node {
 	}
 }
 
+/// Recursively write the vertices and edges of a [`Node`] and its children,
+/// returning the id of the node's vertex
+///
+/// Vertices are numbered in pre-order so every id is unique
+fn write_dot(dot: &mut String, t: &Node, next_id: &mut usize) -> usize {
+	let id = *next_id;
+	*next_id += 1;
+
+	let label = t.label().replace('\\', "\\\\").replace('"', "\\\"");
+	dot.push_str(&format!("\tn{} [label=\"{}\"];\n", id, label));
+
+	for c in &t.children {
+		let child_id = write_dot(dot, c, next_id);
+		dot.push_str(&format!("\tn{} -> n{};\n", id, child_id));
+	}
+
+	id
+}
+
 /// Recursively write a [`Node`] and its children
 fn print_item(f: &mut Formatter<'_>, t: &Node, level: usize) -> std::fmt::Result {
 	t.write_self(f)?;
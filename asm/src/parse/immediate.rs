@@ -16,18 +16,25 @@ struct ImmediateParser<'i, 's> {
 
 impl<'i, 's> ImmediateParser<'i, 's> {
 	/// Creates a new parser for the given token slice
-	///
-	/// Assumes the token slice only contains valid immediate tokens
 	fn new(imm_slice: &'i [Token<'s>], parser: &'i Parser<'s>) -> Self {
 		Self { parser, imm_slice }
 	}
 
-	/// Assert that the parentheses in the slice of tokens are balanced
+	/// Assert that the parentheses in the slice of tokens are balanced, and
+	/// not nested deeper than the maximum depth of the parser
 	fn check_parens_balanced(&self) -> Result<(), ParseError> {
 		let mut paren_stack: Vec<Token> = vec![];
 
 		for &token in self.imm_slice {
 			if token.t == TokenType::SymLeftParen {
+				if paren_stack.len() >= self.parser.max_depth {
+					return Err(ParseError::NestingTooDeep {
+						src_file: self.parser.source_file.to_string(),
+						location: Box::new(LocationInfo::from(&token)),
+						limit:    self.parser.max_depth,
+					});
+				}
+
 				paren_stack.push(token);
 			} else if token.t == TokenType::SymRightParen {
 				if paren_stack.is_empty() {
@@ -89,7 +96,12 @@ impl<'i, 's> ImmediateParser<'i, 's> {
 
 		for &token in self.imm_slice {
 			match &token.t {
-				TokenType::LitNum(_) | TokenType::Identifier(_) => {
+				TokenType::LitNum(_)
+				| TokenType::LitChar(_)
+				| TokenType::Identifier(_)
+				| TokenType::LocalLabelBackward(_)
+				| TokenType::LocalLabelForward(_)
+				| TokenType::SymDot => {
 					prev_was_operator = false;
 					rpn_stack.push(token);
 				},
@@ -146,9 +158,20 @@ impl<'i, 's> ImmediateParser<'i, 's> {
 
 					prev_was_operator = true;
 					op_stack.push(operator);
-					op_stack_.push(token);
+					// Store the (possibly unary) operator in the token so it ends up in the RPN
+					op_stack_.push(Token { t: TokenType::Op(operator), ..token });
+				},
+				// Operators that can not appear in expressions (eg. `$`) still
+				// end up in the slice
+				_ => {
+					return Err(ParseError::UnexpectedToken {
+						src_file:  self.parser.source_file.to_string(),
+						location:  Box::new(LocationInfo::from(&token)),
+						found:     token.t.to_string(),
+						expected:  "number, identifier, operator, or parenthesis".to_string(),
+						statement: None,
+					});
 				},
-				_ => unreachable!(),
 			}
 		}
 
@@ -168,25 +191,96 @@ impl<'s> Parser<'s> {
 	/// Parse an immediate expression into a list of tokens encoding the same
 	/// immediate but in
 	/// [Reverse Polish notation](https://en.wikipedia.org/wiki/Reverse_Polish_notation)
+	///
+	/// The immediate ends at the first token that can not be part of it, or
+	/// at an operand directly following another operand as the latter can
+	/// never be valid
 	pub(super) fn parse_immediate<'r>(&'r mut self) -> Result<Immediate<'s>, ParseError> {
-		let start = self.idx;
+		let mut imm_tokens = vec![];
+		let mut prev_was_operand = false;
 		while let Ok(peek) = self.peek() {
 			match &peek.t {
 				TokenType::LitNum(_)
+				| TokenType::LitChar(_)
 				| TokenType::Identifier(_)
+				| TokenType::LocalLabelBackward(_)
+				| TokenType::LocalLabelForward(_)
+				| TokenType::SymDot
 				| TokenType::SymLeftParen
-				| TokenType::SymRightParen
-				| TokenType::Op(_) => {
+					if prev_was_operand =>
+				{
+					break;
+				},
+				TokenType::LitNum(_)
+				| TokenType::LitChar(_)
+				| TokenType::Identifier(_)
+				| TokenType::LocalLabelBackward(_)
+				| TokenType::LocalLabelForward(_)
+				| TokenType::SymDot
+				| TokenType::SymRightParen => {
+					prev_was_operand = true;
+					// Unwrap is safe as peek is Ok
+					imm_tokens.push(self.next().unwrap());
+				},
+				TokenType::SymLeftParen | TokenType::Op(_) => {
+					prev_was_operand = false;
 					// Unwrap is safe as peek is Ok
-					self.next().unwrap();
+					imm_tokens.push(self.next().unwrap());
 				},
 				_ => break,
 			}
 		}
-		let end = self.idx;
 
-		let imm_slice = &self.stream[start..end];
-		let mut imm_parser = ImmediateParser::new(imm_slice, self);
+		let mut imm_parser = ImmediateParser::new(&imm_tokens, self);
+		let rpn_tokens = imm_parser.parse()?;
+
+		Ok(Immediate { rpn_tokens })
+	}
+
+	/// Parse an immediate expression that ends at the first `end` token
+	/// outside of any parentheses, without consuming that token
+	///
+	/// This allows an immediate to be followed by a `:` or `)` that
+	/// [`parse_immediate`](Self::parse_immediate) would consider part of it.
+	/// A `:` completing a `?` conditional in the immediate does not end it
+	pub(super) fn parse_immediate_until<'r>(
+		&'r mut self,
+		end: TokenType<'s>,
+	) -> Result<Immediate<'s>, ParseError> {
+		let mut imm_tokens = vec![];
+		let mut depth = 0usize;
+		let mut conditionals = 0usize;
+		while let Ok(peek) = self.peek() {
+			match peek.t {
+				t if t == end
+					&& depth == 0 && (t != TokenType::Op(OpToken::Colon) || conditionals == 0) =>
+				{
+					break;
+				},
+				TokenType::SymLeftParen => depth += 1,
+				TokenType::SymRightParen => depth = depth.saturating_sub(1),
+				TokenType::Op(OpToken::Question) => conditionals += 1,
+				TokenType::Op(OpToken::Colon) => conditionals = conditionals.saturating_sub(1),
+				TokenType::LitNum(_)
+				| TokenType::LitChar(_)
+				| TokenType::Identifier(_)
+				| TokenType::LocalLabelBackward(_)
+				| TokenType::LocalLabelForward(_)
+				| TokenType::SymDot
+				| TokenType::Op(_) => (),
+				_ => break,
+			}
+
+			// Unwrap is safe as peek is Ok
+			imm_tokens.push(self.next().unwrap());
+		}
+
+		if imm_tokens.is_empty() {
+			// The next token can not start an immediate, so this always errors
+			self.expect_described(TokenType::LitNum(0), "`IMMEDIATE`".to_string())?;
+		}
+
+		let mut imm_parser = ImmediateParser::new(&imm_tokens, self);
 		let rpn_tokens = imm_parser.parse()?;
 
 		Ok(Immediate { rpn_tokens })
@@ -2,12 +2,13 @@
 
 use super::ast::{AddrOffset, Address, Immediate, Instruction, OffsetOperator, OrderingTarget};
 use super::Parser;
-use crate::error::{LocationInfo, ParseError};
+use crate::error::{Error, LocationInfo, ParseError};
 use crate::lex::{
 	BranchInstruction,
 	CsrInstruction,
 	CsriInstruction,
 	InstToken,
+	Lexer,
 	LoadInstruction,
 	MdrInstruction,
 	OpToken,
@@ -15,18 +16,168 @@ use crate::lex::{
 	RriInstruction,
 	RrrInstruction,
 	StoreInstruction,
+	Token,
 	TokenType,
 };
 
+/// Describes where commas are expected in error messages
+const OPERAND_SEPARATOR: &str = "between instruction operands";
+
+/// Describes the operands of instructions only taking registers
+const REGISTER_OPERANDS: &str = "register operands";
+
+/// Describes the operands of all other instructions
+const OPERANDS: &str = "operands";
+
+/// The source file name used in errors for instructions parsed on their own
+const INSTRUCTION_SOURCE_FILE: &str = "<instruction>";
+
+impl<'s> Instruction<'s> {
+	/// Parse a single instruction, eg. `addi r1, r0, 5`, without running the
+	/// rest of the assembler
+	///
+	/// The instruction may be followed by a comment and a newline, any other
+	/// source code is an error. Immediates are not folded, so labels and
+	/// constants are kept as is
+	pub fn parse(source: &'s str) -> Result<Self, Error> {
+		let lexer = Lexer::new(INSTRUCTION_SOURCE_FILE, source);
+		let mut parser = Parser::from_stream(INSTRUCTION_SOURCE_FILE, lexer);
+
+		let instruction = parser.parse_lone_instruction();
+
+		match parser.lex_error.take() {
+			Some(err) => Err(err),
+			None => Ok(instruction?),
+		}
+	}
+}
+
 impl<'s> Parser<'s> {
+	/// Parse a token stream consisting of a single [`Instruction`], see
+	/// [`Instruction::parse`]
+	fn parse_lone_instruction<'r>(&'r mut self) -> Result<Instruction<'s>, ParseError> {
+		let first = self.peek()?;
+		if !matches!(first.t, TokenType::Inst(_)) {
+			return Err(ParseError::UnexpectedToken {
+				src_file:  self.source_file.to_string(),
+				location:  Box::new(LocationInfo::from(&first)),
+				found:     first.t.to_string(),
+				expected:  "instruction".to_string(),
+				statement: None,
+			});
+		}
+
+		let instruction = self.parse_instruction()?;
+		self.optional(TokenType::Comment(""));
+		self.optional(TokenType::SymNewline);
+
+		if let Ok(peek) = self.peek() {
+			return Err(ParseError::UnexpectedToken {
+				src_file:  self.source_file.to_string(),
+				location:  Box::new(LocationInfo::from(&peek)),
+				found:     peek.t.to_string(),
+				expected:  "end of input".to_string(),
+				statement: None,
+			});
+		}
+
+		Ok(instruction)
+	}
+
 	/// Parse any valid [`Instruction`]
 	///
 	/// Assumes the current [`Token`](crate::lex::Token) has [`TokenType`]
 	/// [`TokenType::Inst`]
+	///
+	/// Errors if the operands are followed by anything other than a comment
+	/// or the end of the line
 	pub(super) fn parse_instruction<'r>(&'r mut self) -> Result<Instruction<'s>, ParseError> {
 		// Unwrap is assumed to be safe
 		let instruction_token = self.next().unwrap();
 
+		if let Ok(peek) = self.peek() && peek.t == TokenType::SymLeftBrace {
+			return Err(self.reserved_name(&instruction_token, "label"));
+		}
+
+		self.statement = Some(instruction_token);
+		let instruction = self.parse_operands(&instruction_token);
+		self.statement = None;
+
+		let instruction = instruction?;
+		if self.trailing_commas.operands
+			&& self.peek().is_ok_and(|peek| peek.t == TokenType::SymComma)
+		{
+			// Unwrap is safe as peek is Ok
+			self.next().unwrap();
+		}
+		self.expect_end_of_instruction()?;
+
+		Ok(instruction)
+	}
+
+	/// Check that the next token terminates the current instruction
+	///
+	/// Does not consume the terminating token, a missing token is left for
+	/// the caller to report
+	fn expect_end_of_instruction(&mut self) -> Result<(), ParseError> {
+		let Ok(peek) = self.peek() else { return Ok(()) };
+
+		match peek.t {
+			TokenType::Comment(_) | TokenType::SymNewline => Ok(()),
+			_ => {
+				Err(ParseError::UnexpectedToken {
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					found:     peek.t.to_string(),
+					expected:  "end of instruction".to_string(),
+					statement: None,
+				})
+			},
+		}
+	}
+
+	/// Check that the line of the current instruction does not end before its
+	/// next operand, and take the comma separating that operand from the
+	/// previous one
+	///
+	/// `found` is the amount of operands parsed so far, and `expected` the
+	/// amount of `kind` operands the instruction takes, these are reported if
+	/// the line ends early
+	fn expect_operand(
+		&mut self,
+		found: usize,
+		expected: usize,
+		kind: &str,
+	) -> Result<(), ParseError> {
+		let line_ended = match self.peek() {
+			Ok(peek) => matches!(peek.t, TokenType::SymNewline | TokenType::Comment(_)),
+			Err(_) => true,
+		};
+
+		if line_ended && let Some(instruction) = self.statement {
+			return Err(ParseError::MissingOperands {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(&instruction)),
+				instruction: instruction.t.to_string(),
+				kind: kind.to_string(),
+				expected,
+				found,
+			});
+		}
+
+		if found > 0 {
+			self.expect_msg(TokenType::SymComma, OPERAND_SEPARATOR)?;
+		}
+
+		Ok(())
+	}
+
+	/// Parse the operands of the instruction represented by the given
+	/// [`Token`]
+	fn parse_operands<'r>(
+		&'r mut self,
+		instruction_token: &Token<'s>,
+	) -> Result<Instruction<'s>, ParseError> {
 		match &instruction_token.t {
 			TokenType::Inst(InstToken::Rri(rri_inst)) => {
 				let (dest, src, imm) = self.parse_rri()?;
@@ -163,12 +314,13 @@ impl<'s> Parser<'s> {
 
 	/// Parse 2 [`RegToken`]s, followed by an [`Immediate`]
 	fn parse_rri<'r>(&'r mut self) -> Result<(RegToken, RegToken, Immediate<'s>), ParseError> {
+		self.expect_operand(0, 3, OPERANDS)?;
 		let reg1 = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 3, OPERANDS)?;
 		let reg2 = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(2, 3, OPERANDS)?;
 		let imm = self.parse_immediate()?;
 
 		Ok((reg1, reg2, imm))
@@ -176,12 +328,13 @@ impl<'s> Parser<'s> {
 
 	/// Parse 3 [`RegToken`]s
 	fn parse_rrr(&mut self) -> Result<(RegToken, RegToken, RegToken), ParseError> {
+		self.expect_operand(0, 3, REGISTER_OPERANDS)?;
 		let reg1 = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 3, REGISTER_OPERANDS)?;
 		let reg2 = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(2, 3, REGISTER_OPERANDS)?;
 		let reg3 = self.parse_register()?;
 
 		Ok((reg1, reg2, reg3))
@@ -189,9 +342,10 @@ impl<'s> Parser<'s> {
 
 	/// Parse a [`RegToken`] followed by an [`Immediate`]
 	fn parse_ri<'r>(&'r mut self) -> Result<(RegToken, Immediate<'s>), ParseError> {
+		self.expect_operand(0, 2, OPERANDS)?;
 		let reg = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 2, OPERANDS)?;
 		let imm = self.parse_immediate()?;
 
 		Ok((reg, imm))
@@ -199,32 +353,77 @@ impl<'s> Parser<'s> {
 
 	/// Parse a [`RegToken`] followed by an [`Address`]
 	fn parse_ra<'r>(&'r mut self) -> Result<(RegToken, Address<'s>), ParseError> {
+		self.expect_operand(0, 2, OPERANDS)?;
 		let reg = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 2, OPERANDS)?;
 		let addr = self.parse_address()?;
 
 		Ok((reg, addr))
 	}
 
 	/// Parse an [`Address`] followed by a [`RegToken`]
+	///
+	/// Operands given in the order of a load (register first) are reported
+	/// with a suggestion to swap them
 	fn parse_ar<'r>(&'r mut self) -> Result<(Address<'s>, RegToken), ParseError> {
+		self.expect_operand(0, 2, OPERANDS)?;
+		if let TokenType::Reg(_) = self.peek()?.t {
+			// Unwrap is safe as the next token is not a left bracket
+			let err =
+				self.expect_msg(TokenType::SymLeftBracket, "to start an address").unwrap_err();
+			return Err(self.swapped_operands().unwrap_or(err));
+		}
+
 		let addr = self.parse_address()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 2, OPERANDS)?;
 		let reg = self.parse_register()?;
 
 		Ok((addr, reg))
 	}
 
+	/// Check whether the remaining operands are a register followed by an
+	/// [`Address`], and if so return an error suggesting to swap them
+	///
+	/// Assumes the current [`Token`] has [`TokenType`] [`TokenType::Reg`]
+	fn swapped_operands(&mut self) -> Option<ParseError> {
+		// Unwrap is assumed to be safe
+		let reg = self.next().unwrap();
+		self.optional(TokenType::SymComma)?;
+
+		let open = self.peek().ok()?;
+		if open.t != TokenType::SymLeftBracket {
+			return None;
+		}
+		self.parse_address().ok()?;
+		let close = *self.prev();
+
+		let instruction = self.statement?;
+		// The operands are shown as they were written
+		let source = |first: &Token, last: &Token| -> String {
+			let len = last.col + last.span - first.col;
+			first.source_line.chars().skip(first.col - 1).take(len).collect()
+		};
+
+		Some(ParseError::SwappedOperands {
+			src_file:    self.source_file.to_string(),
+			location:    Box::new(LocationInfo::spanning(&instruction, &close)),
+			instruction: instruction.t.to_string(),
+			register:    source(&reg, &reg),
+			address:     source(&open, &close),
+		})
+	}
+
 	/// Parse a [`RegToken`], followed by 2 [`Immediate`]s
 	fn parse_rii<'r>(&'r mut self) -> Result<(RegToken, Immediate<'s>, Immediate<'s>), ParseError> {
+		self.expect_operand(0, 3, OPERANDS)?;
 		let reg1 = self.parse_register()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 3, OPERANDS)?;
 		let imm1 = self.parse_immediate()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(2, 3, OPERANDS)?;
 		let imm2 = self.parse_immediate()?;
 
 		Ok((reg1, imm1, imm2))
@@ -232,9 +431,10 @@ impl<'s> Parser<'s> {
 
 	/// Parse 2 [`OrderingTarget`]s
 	fn parse_oo(&mut self) -> Result<(OrderingTarget, OrderingTarget), ParseError> {
+		self.expect_operand(0, 2, OPERANDS)?;
 		let ord1 = self.parse_ordering_target()?;
-		self.expect(TokenType::SymComma)?;
 
+		self.expect_operand(1, 2, OPERANDS)?;
 		let ord2 = self.parse_ordering_target()?;
 
 		Ok((ord1, ord2))
@@ -247,20 +447,34 @@ impl<'s> Parser<'s> {
 	///  - A right bracket
 	fn parse_address<'r>(&'r mut self) -> Result<Address<'s>, ParseError> {
 		let open_peek = self.peek()?;
-		self.expect(TokenType::SymLeftBracket)?;
+		self.expect_msg(TokenType::SymLeftBracket, "to start an address")?;
 
 		let base = self.parse_register()?;
 
 		let op_peek = self.peek()?;
 		let offset = match &op_peek.t {
-			TokenType::Op(o @ OpToken::Plus | o @ OpToken::Minus) => {
+			TokenType::Op(OpToken::Plus) => {
 				// Take operator token
 				// Unwrap is safe as peek is Ok
 				self.next().unwrap();
 
 				let offset = self.parse_immediate()?;
 
-				Some(AddrOffset { op: OffsetOperator::from(o), offset })
+				Some(AddrOffset { op: OffsetOperator::Plus, offset })
+			},
+			// The minus is parsed as part of the offset so it only negates
+			// the first operand, eg. `- a + b` is `(-a) + b`
+			TokenType::Op(OpToken::Minus) => {
+				let mut offset = self.parse_immediate()?;
+
+				// A negated expression keeps the `-` as its operator
+				match offset.rpn_tokens.last() {
+					Some(Token { t: TokenType::Op(OpToken::UnaryMinus), .. }) => {
+						offset.rpn_tokens.pop();
+						Some(AddrOffset { op: OffsetOperator::Minus, offset })
+					},
+					_ => Some(AddrOffset { op: OffsetOperator::Plus, offset }),
+				}
 			},
 			_ => None,
 		};
@@ -271,8 +485,8 @@ impl<'s> Parser<'s> {
 				src_file:       self.source_file.to_string(),
 				delim_type:     "bracket".to_string(),
 				found:          close_token.t.to_string(),
-				close_location: Box::new(LocationInfo::from(close_token)),
-				open_location:  Box::new(LocationInfo::from(open_peek)),
+				close_location: Box::new(LocationInfo::from(&close_token)),
+				open_location:  Box::new(LocationInfo::from(&open_peek)),
 			});
 		}
 
@@ -286,10 +500,11 @@ impl<'s> Parser<'s> {
 			TokenType::Reg(reg) => Ok(reg),
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(next)),
-					found:    next.t.to_string(),
-					expected: "REGISTER".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&next)),
+					found:     next.t.to_string(),
+					expected:  "`REGISTER`".to_string(),
+					statement: None,
 				})
 			},
 		}
@@ -300,24 +515,24 @@ impl<'s> Parser<'s> {
 		let ord_raw = self.next()?;
 		match ord_raw.t {
 			TokenType::Identifier(id) => {
-				let flags = OrderingTarget::from(id);
-
-				if flags.is_empty() {
-					return Err(ParseError::InvalidOrderingSpecifier {
-						src_file: self.source_file.to_string(),
-						location: Box::new(LocationInfo::from(ord_raw)),
-						spec:     id.to_string(),
-					});
+				match id.parse::<OrderingTarget>() {
+					Ok(flags) if !flags.is_empty() => Ok(flags),
+					_ => {
+						Err(ParseError::InvalidOrderingSpecifier {
+							src_file: self.source_file.to_string(),
+							location: Box::new(LocationInfo::from(&ord_raw)),
+							spec:     id.to_string(),
+						})
+					},
 				}
-
-				Ok(flags)
 			},
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(ord_raw)),
-					found:    ord_raw.t.to_string(),
-					expected: "ORDERING_TARGET".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&ord_raw)),
+					found:     ord_raw.t.to_string(),
+					expected:  "`ORDERING_TARGET`".to_string(),
+					statement: None,
 				})
 			},
 		}
@@ -32,16 +32,30 @@ impl<'s> Parser<'s> {
 		let id_token = self.next()?;
 		let id = match id_token.t {
 			TokenType::Identifier(id) => id,
+			TokenType::Inst(_) | TokenType::Reg(_) => {
+				return Err(self.reserved_name(&id_token, "macro"));
+			},
 			_ => {
 				return Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(id_token)),
-					found:    id_token.t.to_string(),
-					expected: "IDENTIFIER".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&id_token)),
+					found:     id_token.t.to_string(),
+					expected:  "`IDENTIFIER`".to_string(),
+					statement: None,
 				});
 			},
 		};
 
+		if let Some(original) = self.macros.get(id) {
+			return Err(ParseError::MacroRedefinition {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(&id_token)),
+				original: Box::new(LocationInfo::from(original)),
+				id:       id.to_string(),
+			});
+		}
+		self.macros.insert(id, id_token);
+
 		let body_open = self.paren_match_start()?;
 		self.optional(TokenType::SymNewline);
 
@@ -55,7 +69,21 @@ impl<'s> Parser<'s> {
 			self.next().unwrap();
 			self.optional(TokenType::SymNewline);
 
+			if self.trailing_commas.lists && self.ends_list() {
+				break;
+			}
+
+			let rule_start = self.peek()?;
 			let extra_rule = self.parse_macro_rule()?;
+
+			if rules.iter().any(|rule| rule.is_ambiguous_with(&extra_rule)) {
+				return Err(ParseError::AmbiguousMacroRules {
+					src_file:   self.source_file.to_string(),
+					location:   Box::new(LocationInfo::from(&rule_start)),
+					macro_name: id.to_string(),
+				});
+			}
+
 			rules.push(extra_rule);
 		}
 
@@ -110,7 +138,7 @@ impl<'s> Parser<'s> {
 					let mut matches = vec![];
 					matches.push(self.parse_macro_match()?);
 
-					while self.paren_match_end(*id_or_paren).is_err() {
+					while self.paren_match_end(id_or_paren).is_err() {
 						matches.push(self.parse_macro_match()?);
 					}
 
@@ -131,10 +159,11 @@ impl<'s> Parser<'s> {
 				},
 				_ => {
 					Err(ParseError::UnexpectedToken {
-						src_file: self.source_file.to_string(),
-						location: Box::new(LocationInfo::from(id_or_paren)),
-						found:    id_or_paren.t.to_string(),
-						expected: "IDENTIFIER or (".to_string(),
+						src_file:  self.source_file.to_string(),
+						location:  Box::new(LocationInfo::from(&id_or_paren)),
+						found:     id_or_paren.t.to_string(),
+						expected:  "`IDENTIFIER or (`".to_string(),
+						statement: None,
 					})
 				},
 			}
@@ -156,10 +185,11 @@ impl<'s> Parser<'s> {
 			TokenType::Identifier("stmt") => Ok(MacroArgType::Stmt),
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(specifier)),
-					found:    specifier.t.to_string(),
-					expected: "IDENTIFIER".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&specifier)),
+					found:     specifier.t.to_string(),
+					expected:  "`IDENTIFIER`".to_string(),
+					statement: None,
 				})
 			},
 		}
@@ -175,10 +205,11 @@ impl<'s> Parser<'s> {
 			TokenType::Op(OpToken::Star) => Ok(MacroVarType::Any),
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(specifier)),
-					found:    specifier.t.to_string(),
-					expected: "? or + or *".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&specifier)),
+					found:     specifier.t.to_string(),
+					expected:  "`? or + or *`".to_string(),
+					statement: None,
 				})
 			},
 		}
@@ -218,22 +249,23 @@ impl<'s> Parser<'s> {
 		match peek.t {
 			TokenType::SymLeftParen => {
 				self.next().unwrap();
-				Ok(*peek)
+				Ok(peek)
 			},
 			TokenType::SymLeftBracket => {
 				self.next().unwrap();
-				Ok(*peek)
+				Ok(peek)
 			},
 			TokenType::SymLeftBrace => {
 				self.next().unwrap();
-				Ok(*peek)
+				Ok(peek)
 			},
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(peek)),
-					found:    peek.t.to_string(),
-					expected: "( or [ or {".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					found:     peek.t.to_string(),
+					expected:  "`( or [ or {`".to_string(),
+					statement: None,
 				})
 			},
 		}
@@ -255,7 +287,7 @@ impl<'s> Parser<'s> {
 							src_file:       self.source_file.to_string(),
 							delim_type:     "parenthesis".to_string(),
 							found:          peek.t.to_string(),
-							close_location: Box::new(LocationInfo::from(peek)),
+							close_location: Box::new(LocationInfo::from(&peek)),
 							open_location:  Box::new(LocationInfo::from(&open)),
 						})
 					},
@@ -273,7 +305,7 @@ impl<'s> Parser<'s> {
 							src_file:       self.source_file.to_string(),
 							delim_type:     "bracket".to_string(),
 							found:          peek.t.to_string(),
-							close_location: Box::new(LocationInfo::from(peek)),
+							close_location: Box::new(LocationInfo::from(&peek)),
 							open_location:  Box::new(LocationInfo::from(&open)),
 						})
 					},
@@ -291,7 +323,7 @@ impl<'s> Parser<'s> {
 							src_file:       self.source_file.to_string(),
 							delim_type:     "brace".to_string(),
 							found:          peek.t.to_string(),
-							close_location: Box::new(LocationInfo::from(peek)),
+							close_location: Box::new(LocationInfo::from(&peek)),
 							open_location:  Box::new(LocationInfo::from(&open)),
 						})
 					},
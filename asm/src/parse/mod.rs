@@ -33,8 +33,29 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! Tokens can also be parsed straight from the [`Lexer`](crate::lex::Lexer)
+//! without collecting them first, which avoids keeping every token of a
+//! large file in memory at once
+//!
+//! ```rust
+//! use asm::error::Error;
+//! use asm::lex::Lexer;
+//! use asm::parse::Parser;
+//!
+//! fn streaming_parser_example(contents: &str) -> Result<(), Error> {
+//!     let lexer = Lexer::new("/foo/bar/baz.asm", contents);
+//!
+//!     let mut parser = Parser::from_stream("/foo/bar/baz.asm", lexer);
+//!     let ast_root = parser.parse()?;
+//!
+//!     Ok(())
+//! }
+//! ```
 
-use std::assert_matches::assert_matches;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::iter::Peekable;
 use std::mem::discriminant;
 
 use crate::error::{Error, LocationInfo, ParseError};
@@ -49,7 +70,9 @@ mod r#macro;
 pub use display::Node;
 
 use self::ast::{
+	Conditional,
 	ConstDirective,
+	Instruction,
 	LabeledBlock,
 	Line,
 	Literal,
@@ -61,6 +84,30 @@ use self::ast::{
 };
 use crate::lex::{DirToken, OpToken, RegularDirective, Token, TokenType};
 
+/// The default maximum nesting depth of labeled blocks, conditionals, and
+/// parentheses, see [`Parser::with_max_depth`]
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Where a comma may follow the last element of a comma separated list,
+/// see [`Parser::with_trailing_commas`]
+///
+/// By default trailing commas are allowed in data and macro lists, which
+/// makes them easier to generate, but not after the operands of an
+/// instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrailingCommas {
+	/// Whether the literals of a data directive (eg. `#WORDS 1, 2, 3,`) and
+	/// the rules of a macro definition may end on a comma
+	pub lists:    bool,
+	/// Whether the operands of an instruction (eg. `addi r1, r0, 5,`) may
+	/// end on a comma
+	pub operands: bool,
+}
+
+impl Default for TrailingCommas {
+	fn default() -> Self { Self { lists: true, operands: false } }
+}
+
 /// Main parser type
 ///
 /// Wraps all internal state during parsing and provides a namespace for all
@@ -69,86 +116,313 @@ use crate::lex::{DirToken, OpToken, RegularDirective, Token, TokenType};
 /// ### Lifetimes
 ///  - `'s`: The lifetime of the reference to the source code string, needed as (most) tokens
 ///    containing string literals will contain references instead of owned data
-#[derive(Clone, Copy, Debug)]
 pub struct Parser<'s> {
 	/// The stream of lexemes
-	stream: &'s [Token<'s>],
+	///
+	/// Tokens are pulled from the stream one at a time, the grammar never
+	/// needs to look more than one token ahead
+	stream:    Peekable<TokenStream<'s>>,
+	/// The first error produced by the stream, if any
+	///
+	/// The stream ends as soon as it produces an error
+	lex_error: Option<Error>,
 
 	/// The name of the file being parsed (used for error messages)
-	source_file: &'s str,
-	/// The length of the token stream
-	len:         usize,
-	/// The current index into the token stream
-	idx:         usize,
+	source_file:     &'s str,
+	/// The most recently consumed token
+	prev:            Option<Token<'s>>,
+	/// The first token of the instruction currently being parsed, if any
+	///
+	/// Used to underline the whole instruction in errors about its operands
+	statement:       Option<Token<'s>>,
+	/// All consumed opening braces, brackets, and parentheses that have not
+	/// been closed yet, in order
+	open_delims:     Vec<Token<'s>>,
+	/// The amount of labeled blocks and conditionals enclosing the line
+	/// being parsed
+	depth:           usize,
+	/// The maximum allowed value of `depth`
+	max_depth:       usize,
+	/// Where trailing commas are allowed
+	trailing_commas: TrailingCommas,
+	/// Whether to keep parsing after an error, see
+	/// [`Parser::parse_partial`]
+	keep_going:      bool,
+	/// The errors recovered from while keeping going
+	errors:          Vec<Error>,
+	/// The name token of every macro defined so far, by name
+	///
+	/// A macro can only be defined once, see [`ParseError::MacroRedefinition`]
+	macros:          HashMap<&'s str, Token<'s>>,
+}
+
+/// A boxed stream of (possibly erroneous) [`Token`]s
+type TokenStream<'s> = Box<dyn Iterator<Item = Result<Token<'s>, Error>> + 's>;
+
+impl<'s> Debug for Parser<'s> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Parser")
+			.field("source_file", &self.source_file)
+			.field("prev", &self.prev)
+			.finish_non_exhaustive()
+	}
 }
 
 impl<'s> Parser<'s> {
 	/// Create a new parser given a source file name and a stream of [`Token`]s
 	pub fn new(source_file: &'s str, stream: &'s [Token<'s>]) -> Self {
-		Self { stream, source_file, len: stream.len(), idx: 0 }
+		Self::from_stream(source_file, stream.iter().map(|t| Ok(*t)))
+	}
+
+	/// Create a new parser given a source file name and an iterator over
+	/// [`Token`]s, such as a [`Lexer`](crate::lex::Lexer)
+	///
+	/// Tokens are only pulled from the iterator when they are needed. If the
+	/// iterator produces an error parsing stops and the error is returned
+	/// from [`Parser::parse`]
+	pub fn from_stream<I>(source_file: &'s str, stream: I) -> Self
+	where
+		I: IntoIterator<Item = Result<Token<'s>, Error>>,
+		I::IntoIter: 's,
+	{
+		let stream: TokenStream<'s> = Box::new(stream.into_iter());
+
+		Self {
+			stream: stream.peekable(),
+			lex_error: None,
+			source_file,
+			prev: None,
+			statement: None,
+			open_delims: vec![],
+			depth: 0,
+			max_depth: DEFAULT_MAX_DEPTH,
+			trailing_commas: TrailingCommas::default(),
+			keep_going: false,
+			errors: vec![],
+			macros: HashMap::new(),
+		}
+	}
+
+	/// Set the maximum depth labeled blocks, conditionals, and the
+	/// parentheses in an immediate can be nested to
+	///
+	/// Parsing nested blocks is recursive, so the limit keeps deeply nested
+	/// input from overflowing the stack. Defaults to [`DEFAULT_MAX_DEPTH`]
+	pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = max_depth;
+		self
+	}
+
+	/// Set where a comma may follow the last element of a list, defaults to
+	/// [`TrailingCommas::default`]
+	pub fn with_trailing_commas(mut self, trailing_commas: TrailingCommas) -> Self {
+		self.trailing_commas = trailing_commas;
+		self
+	}
+
+	/// Make an error for declaring a label, constant, or macro with the name
+	/// of the given instruction or register token
+	///
+	/// These names are lexed as instructions and registers before they can
+	/// ever be identifiers, so they can not be declared. `declared` says
+	/// what was being declared, eg. `label`
+	fn reserved_name(&self, token: &Token<'s>, declared: &str) -> ParseError {
+		let reserved = match token.t {
+			TokenType::Inst(_) => "an instruction",
+			_ => "a register",
+		};
+
+		ParseError::ReservedName {
+			src_file: self.source_file.to_string(),
+			location: Box::new(LocationInfo::from(token)),
+			name:     token.raw().to_string(),
+			reserved: reserved.to_string(),
+			declared: declared.to_string(),
+		}
+	}
+
+	/// Check whether the token after a comma ends a list instead of starting
+	/// another element
+	///
+	/// Lists end on a newline, a comment, a closing delimiter, or the end of
+	/// the token stream
+	fn ends_list(&mut self) -> bool {
+		match self.peek() {
+			Ok(peek) => {
+				matches!(
+					peek.t,
+					TokenType::SymNewline
+						| TokenType::Comment(_) | TokenType::SymRightParen
+						| TokenType::SymRightBracket
+						| TokenType::SymRightBrace
+				)
+			},
+			Err(_) => true,
+		}
+	}
+
+	/// Enter a labeled block or conditional opened by the given token
+	///
+	/// Errors if this nests deeper than the maximum depth
+	fn enter_nested(&mut self, open: &Token<'s>) -> Result<(), ParseError> {
+		if self.depth >= self.max_depth {
+			return Err(ParseError::NestingTooDeep {
+				src_file: self.source_file.to_string(),
+				location: Box::new(LocationInfo::from(open)),
+				limit:    self.max_depth,
+			});
+		}
+
+		self.depth += 1;
+		Ok(())
 	}
 
 	/// Return the next token in the stream
 	///
 	/// Returns [`ParseError::UnexpectedEof`] if the next token is [`None`]
-	fn next<'r>(&'r mut self) -> Result<&'s Token<'s>, ParseError> {
-		if self.idx < self.len {
-			self.idx += 1;
-			Ok(&self.stream[self.idx - 1])
-		} else {
-			let srcf = self.source_file.to_string();
-			let prev = self.prev();
+	fn next(&mut self) -> Result<Token<'s>, ParseError> {
+		let token = self.peek()?;
 
-			Err(ParseError::UnexpectedEof {
-				src_file: srcf,
-				location: Box::new(LocationInfo::from(prev)),
-			})
+		self.stream.next();
+		self.prev = Some(token);
+		self.track_delimiter(token);
+
+		Ok(token)
+	}
+
+	/// Keep track of the opening delimiters that have not been closed yet
+	///
+	/// A closing delimiter closes the most recent opening delimiter of the
+	/// same type, even if a different type of delimiter was opened after it
+	fn track_delimiter(&mut self, token: Token<'s>) {
+		let opener = match token.t {
+			TokenType::SymLeftBrace | TokenType::SymLeftBracket | TokenType::SymLeftParen => {
+				self.open_delims.push(token);
+				return;
+			},
+			TokenType::SymRightBrace => TokenType::SymLeftBrace,
+			TokenType::SymRightBracket => TokenType::SymLeftBracket,
+			TokenType::SymRightParen => TokenType::SymLeftParen,
+			_ => return,
+		};
+
+		if let Some(idx) = self.open_delims.iter().rposition(|t| t.t == opener) {
+			self.open_delims.remove(idx);
 		}
 	}
 
+	/// Consume the rest of the token stream and check that every opening
+	/// delimiter got closed
+	///
+	/// Returns a [`ParseError::UnclosedDelimiters`] pointing at the first
+	/// unclosed delimiter, along with how many delimiters of its type were
+	/// left unclosed
+	fn check_delimiters(&mut self) -> Result<(), ParseError> {
+		while self.next().is_ok() {}
+
+		let first = match self.open_delims.first() {
+			Some(first) => *first,
+			None => return Ok(()),
+		};
+
+		Err(ParseError::UnclosedDelimiters {
+			src_file:  self.source_file.to_string(),
+			location:  Box::new(LocationInfo::from(&first)),
+			delimiter: first.t.to_string(),
+			count:     self.open_delims.iter().filter(|t| t.t == first.t).count(),
+		})
+	}
+
 	/// Peek at the next token in the stream
 	///
-	/// Returns [`ParseError::UnexpectedEof`] if the next token is [`None`]
-	fn peek(&self) -> Result<&'s Token<'s>, ParseError> {
-		if self.idx < self.len {
-			Ok(&self.stream[self.idx])
-		} else {
-			let srcf = self.source_file.to_string();
-			let prev = self.prev();
+	/// Returns [`ParseError::UnexpectedEof`] if the next token is [`None`],
+	/// which also happens immediately for an empty stream
+	fn peek(&mut self) -> Result<Token<'s>, ParseError> {
+		while let Some(Err(_)) = self.stream.peek() {
+			// Unwrap is safe as peek is Some(Err)
+			let err = self.stream.next().unwrap().err();
+			if !self.keep_going {
+				self.lex_error = err;
+				break;
+			}
+
+			self.errors.extend(err);
+		}
 
-			Err(ParseError::UnexpectedEof {
-				src_file: srcf,
-				location: Box::new(LocationInfo::from(prev)),
-			})
+		match self.stream.peek() {
+			Some(Ok(token)) if self.lex_error.is_none() => Ok(*token),
+			_ => {
+				// An empty stream has no previous token to point at
+				let location = self.prev.as_ref().map(LocationInfo::from).unwrap_or_default();
+
+				Err(ParseError::UnexpectedEof {
+					src_file: self.source_file.to_string(),
+					location: Box::new(location),
+				})
+			},
 		}
 	}
 
 	/// Return the previous token in the stream
-	fn prev(&self) -> &Token { &self.stream[self.idx - 1] }
+	///
+	/// Panics if no tokens have been consumed yet
+	fn prev(&self) -> &Token<'s> { self.prev.as_ref().unwrap() }
 
 	/// Returns [`Ok`] if the next token matches the given
 	/// [`TokenType`](crate::lex::TokenType), else returns [`Err`]
 	///
 	/// Will only consume the next token if its type matches the argument
-	///
-	/// TODO: make this accept a custom error return type for better errors
-	/// (might need a closure)
 	fn expect<'r>(&'r mut self, t: TokenType<'s>) -> Result<Token<'s>, ParseError> {
+		let expected = format!("`{}`", t);
+		self.expect_described(t, expected)
+	}
+
+	/// Like [`expect`](Self::expect), but describes where the token was
+	/// expected in the error
+	///
+	/// ```text
+	/// found unexpected token `REGISTER`, expected `COMMA` between instruction operands
+	/// ```
+	fn expect_msg<'r>(
+		&'r mut self,
+		t: TokenType<'s>,
+		context: &str,
+	) -> Result<Token<'s>, ParseError> {
+		let expected = format!("`{}` {}", t, context);
+		self.expect_described(t, expected)
+	}
+
+	/// Consume the next token if it matches the given
+	/// [`TokenType`](crate::lex::TokenType), else return an
+	/// [`UnexpectedToken`](ParseError::UnexpectedToken) error with the given
+	/// description of what was expected
+	fn expect_described<'r>(
+		&'r mut self,
+		t: TokenType<'s>,
+		expected: String,
+	) -> Result<Token<'s>, ParseError> {
 		let peek = self.peek()?;
 
 		if discriminant(&peek.t) == discriminant(&t) {
 			// Unwrap is safe as peek is [`Ok`]
-			Ok(*self.next().unwrap())
+			Ok(self.next().unwrap())
 		} else {
 			let repr = peek.t.to_string();
 			let srcf = self.source_file.to_string();
 			let prev = self.prev();
 
+			// Newlines are underlined past the end of the line, so stop at the
+			// last real token instead
+			let last = if peek.t == TokenType::SymNewline { prev } else { &peek };
+			let statement =
+				self.statement.map(|start| Box::new(LocationInfo::spanning(&start, last)));
+
 			Err(ParseError::UnexpectedToken {
 				src_file: srcf,
 				location: Box::new(LocationInfo::from(prev)),
-				found:    repr,
-				expected: t.to_string(),
+				found: repr,
+				expected,
+				statement,
 			})
 		}
 	}
@@ -161,7 +435,7 @@ impl<'s> Parser<'s> {
 
 		if discriminant(&peek.t) == discriminant(&t) {
 			// Unwrap is safe as peek is [`Ok`]
-			Some(*self.next().unwrap())
+			Some(self.next().unwrap())
 		} else {
 			None
 		}
@@ -170,7 +444,45 @@ impl<'s> Parser<'s> {
 	/// Parse the token stream into an AST [`Root`]
 	///
 	/// Assumes the token stream ends on a newline
-	pub fn parse(&'s mut self) -> Result<Root<'s>, Error> {
+	///
+	/// Errors produced by the token stream take precedence over any parse
+	/// errors, as the latter are likely caused by the stream ending early.
+	/// Unclosed delimiters are reported in a single summary instead of the
+	/// parse error they caused
+	pub fn parse(&mut self) -> Result<Root<'s>, Error> {
+		let mut root = self.parse_root();
+
+		if root.is_err() && let Err(err) = self.check_delimiters() {
+			root = Err(err.into());
+		}
+
+		match self.lex_error.take() {
+			Some(err) => Err(err),
+			None => root,
+		}
+	}
+
+	/// Parse the token stream into an AST [`Root`], continuing past errors
+	///
+	/// Errors produced by the token stream are recorded and the erroneous
+	/// token is skipped, this requires a stream that continues after an
+	/// error like a [keep-going](crate::lex::Lexer::with_keep_going) lexer.
+	/// A line that fails to parse is recorded and skipped up to and
+	/// including its newline, an instruction is replaced by an `ebreak` so
+	/// the addresses of all later lines stay the same
+	///
+	/// Returns the AST along with every recorded error, in the order they
+	/// were found. Errors the parser can not continue after, like an
+	/// unexpected end of the file, are returned like in [`parse`](Self::parse)
+	pub fn parse_partial(&mut self) -> Result<(Root<'s>, Vec<Error>), Error> {
+		self.keep_going = true;
+		let root = self.parse()?;
+
+		Ok((root, std::mem::take(&mut self.errors)))
+	}
+
+	/// Parse the token stream into an AST [`Root`]
+	fn parse_root(&mut self) -> Result<Root<'s>, Error> {
 		let mut preamble = vec![];
 		let mut sections = vec![];
 
@@ -196,6 +508,7 @@ impl<'s> Parser<'s> {
 	///
 	/// Consumes the trailing newline
 	fn parse_preambleline<'r>(&'r mut self) -> Result<PreambleLine<'s>, ParseError> {
+		let start = self.peek()?;
 		let statement = self.tryparse_preamble_statement()?;
 
 		let comment = if let TokenType::Comment(c) = self.peek()?.t {
@@ -211,10 +524,11 @@ impl<'s> Parser<'s> {
 			// Unwrap is safe as peek is [`Ok`]
 			self.next().unwrap();
 			return Err(ParseError::UnexpectedToken {
-				src_file: self.source_file.to_string(),
-				location: Box::new(LocationInfo::from(nl)),
-				found:    nl.t.to_string(),
-				expected: "CONST DIRECTIVE or NEWLINE".to_string(),
+				src_file:  self.source_file.to_string(),
+				location:  Box::new(LocationInfo::from(&nl)),
+				found:     nl.t.to_string(),
+				expected:  "`CONST DIRECTIVE or NEWLINE`".to_string(),
+				statement: None,
 			});
 		}
 
@@ -222,7 +536,7 @@ impl<'s> Parser<'s> {
 		// comment is found
 		self.expect(TokenType::SymNewline)?;
 
-		Ok(PreambleLine { statement, comment })
+		Ok(PreambleLine { statement, comment, start })
 	}
 
 	/// Try to parse a [`PreambleStatement`]
@@ -236,55 +550,61 @@ impl<'s> Parser<'s> {
 			TokenType::Identifier("define_macro") => {
 				Ok(Some(PreambleStatement::MacroDefinition(self.parse_macro_definition()?)))
 			},
-			TokenType::Dir(DirToken::Regular(RegularDirective::Const)) => {
+			TokenType::Dir(DirToken::Regular(RegularDirective::Const | RegularDirective::Set)) => {
 				Ok(Some(PreambleStatement::ConstDirective(self.parse_const_directive()?)))
 			},
 			TokenType::SymNewline => Ok(None),
 			TokenType::Comment(_) => Ok(None),
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(peek)),
-					found:    peek.t.to_string(),
-					expected: "CONST DIRECTIVE or MACRO DEFINITION or COMMENT or NEWLINE"
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					found:     peek.t.to_string(),
+					expected:  "`CONST DIRECTIVE or MACRO DEFINITION or COMMENT or NEWLINE`"
 						.to_string(),
+					statement: None,
 				})
 			},
 		}
 	}
 
-	/// Parse a `#CONST` directive consisting of:
-	///  - The [`#CONST`](DirToken::Const) keyword
+	/// Parse a `#CONST` or `#SET` directive consisting of:
+	///  - The [`#CONST`](RegularDirective::Const) or [`#SET`](RegularDirective::Set) keyword
 	///  - An [`Identifier`] name
 	///  - A [`Literal`] value
 	///
 	/// Assumes the current [`Token`] has [`TokenType`]
-	/// [`TokenType::Dir(DirToken::Const)`]
+	/// [`TokenType::Dir(DirToken::Const)`] or [`TokenType::Dir(DirToken::Set)`]
 	fn parse_const_directive<'r>(&'r mut self) -> Result<ConstDirective<'s>, ParseError> {
-		// Consume the #CONST token
+		// Consume the #CONST or #SET token
 		// Unwrap is assumed to be safe
-		assert_matches!(
-			self.next().unwrap().t,
-			TokenType::Dir(DirToken::Regular(RegularDirective::Const))
-		);
+		let reassignable = match self.next().unwrap().t {
+			TokenType::Dir(DirToken::Regular(RegularDirective::Const)) => false,
+			TokenType::Dir(DirToken::Regular(RegularDirective::Set)) => true,
+			_ => unreachable!(),
+		};
 
 		let id_token = self.next()?;
 
 		let id = match &id_token.t {
 			TokenType::Identifier(id) => id,
+			TokenType::Inst(_) | TokenType::Reg(_) => {
+				return Err(self.reserved_name(&id_token, "constant"));
+			},
 			_ => {
 				return Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(id_token)),
-					found:    id_token.t.to_string(),
-					expected: "IDENTIFIER".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&id_token)),
+					found:     id_token.t.to_string(),
+					expected:  "`IDENTIFIER`".to_string(),
+					statement: None,
 				});
 			},
 		};
 
 		let value = self.parse_literal()?;
 
-		Ok(ConstDirective { id, value })
+		Ok(ConstDirective { id, value, reassignable })
 	}
 
 	/// Parse a [`Literal`] consisting of either:
@@ -296,26 +616,38 @@ impl<'s> Parser<'s> {
 
 		// Unwrap is safe as peek is Ok
 		let lit = match &peek.t {
-			TokenType::LitStr(s) => {
-				self.next().unwrap();
-				Literal::String(s)
-			},
-			TokenType::LitChar(c) => {
-				self.next().unwrap();
-				Literal::Char(*c)
+			TokenType::LitStr(s) => Literal::String(s, self.next().unwrap()),
+			// Characters can be used as operands in immediates, a character on
+			// its own is still a character literal
+			TokenType::LitChar(_) => {
+				let imm = self.parse_immediate()?;
+
+				match imm.rpn_tokens[..] {
+					[token @ Token { t: TokenType::LitChar(c), .. }] => Literal::Char(c, token),
+					_ => Literal::Immediate(imm),
+				}
 			},
 			TokenType::Op(
-				OpToken::Plus | OpToken::Minus | OpToken::BitNot | OpToken::Exclamation,
+				OpToken::Plus
+				| OpToken::Minus
+				| OpToken::BitNot
+				| OpToken::Exclamation
+				| OpToken::Hi
+				| OpToken::Lo,
 			)
 			| TokenType::SymLeftParen
+			| TokenType::SymDot
 			| TokenType::LitNum(_)
-			| TokenType::Identifier(_) => Literal::Immediate(self.parse_immediate()?),
+			| TokenType::Identifier(_)
+			| TokenType::LocalLabelBackward(_)
+			| TokenType::LocalLabelForward(_) => Literal::Immediate(self.parse_immediate()?),
 			_ => {
 				return Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(peek)),
-					found:    peek.t.to_string(),
-					expected: "STRING or CHAR or IMMEDIATE".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					found:     peek.t.to_string(),
+					expected:  "`STRING or CHAR or IMMEDIATE`".to_string(),
+					statement: None,
 				});
 			},
 		};
@@ -343,10 +675,11 @@ impl<'s> Parser<'s> {
 			},
 			_ => {
 				return Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(peek)),
-					found:    peek.t.to_string(),
-					expected: ".TEXT or .DATA or .BSS".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					found:     peek.t.to_string(),
+					expected:  "`.TEXT or .DATA or .BSS`".to_string(),
+					statement: None,
 				});
 			},
 		};
@@ -366,15 +699,56 @@ impl<'s> Parser<'s> {
 		Ok(Section { name, lines })
 	}
 
-	/// Parse a (section) [`Line`] consisting of:
+	/// Parse a (section) [`Line`], recovering from errors when parsing
+	/// [partially](Self::parse_partial)
+	fn parse_line<'r>(&'r mut self) -> Result<Line<'s>, ParseError> {
+		let start = self.peek()?;
+		let (prev, depth, open_delims) = (self.prev, self.depth, self.open_delims.len());
+
+		match self.parse_line_contents(start) {
+			Err(err) if self.keep_going && !matches!(err, ParseError::UnexpectedEof { .. }) => {
+				self.errors.push(err.into());
+				self.statement = None;
+				self.depth = depth;
+				self.open_delims.truncate(open_delims);
+
+				// Skip the rest of the line, unless its newline was already
+				// consumed
+				let ended = self.prev != prev && self.prev().t == TokenType::SymNewline;
+				if !ended {
+					while let Ok(token) = self.next() && token.t != TokenType::SymNewline {}
+				}
+
+				let statement = match start.t {
+					TokenType::Inst(_) => Some(Statement::Instruction(Instruction::Ebreak)),
+					_ => None,
+				};
+
+				Ok(Line { statement, comment: None, start })
+			},
+			line => line,
+		}
+	}
+
+	/// Parse a (section) [`Line`] starting at the given token, consisting
+	/// of:
 	///  - An optional [`Statement`]
 	///  - An optional comment
 	///  - A newline
 	///
-	/// Consumes the final newline
-	fn parse_line<'r>(&'r mut self) -> Result<Line<'s>, ParseError> {
+	/// Consumes the final newline, unless the statement is a local label
+	/// followed by another statement on the same line, that statement is
+	/// then parsed as the next line
+	fn parse_line_contents<'r>(&'r mut self, start: Token<'s>) -> Result<Line<'s>, ParseError> {
 		let statement = self.tryparse_statement()?;
 
+		if let Some(Statement::LocalLabel(_)) = statement {
+			match self.peek()?.t {
+				TokenType::Comment(_) | TokenType::SymNewline => (),
+				_ => return Ok(Line { statement, comment: None, start }),
+			}
+		}
+
 		let comment = if let TokenType::Comment(c) = self.peek()?.t {
 			// Unwrap is safe as peek is Ok
 			self.next().unwrap();
@@ -385,7 +759,7 @@ impl<'s> Parser<'s> {
 
 		self.expect(TokenType::SymNewline)?;
 
-		Ok(Line { statement, comment })
+		Ok(Line { statement, comment, start })
 	}
 
 	/// Try to parse a [`Statement`]
@@ -410,24 +784,70 @@ impl<'s> Parser<'s> {
 					},
 					_ => {
 						Err(ParseError::UnexpectedToken {
-							src_file: self.source_file.to_string(),
-							location: Box::new(LocationInfo::from(peek)),
-							found:    peek.t.to_string(),
-							expected: "! or {".to_string(),
+							src_file:  self.source_file.to_string(),
+							location:  Box::new(LocationInfo::from(&peek)),
+							found:     peek.t.to_string(),
+							expected:  "`! or {`".to_string(),
+							statement: None,
 						})
 					},
 				}
 			},
+			TokenType::LocalLabel(label) => {
+				let label = *label;
+				// Unwrap is safe as peek is Ok
+				self.next().unwrap();
+
+				Ok(Some(Statement::LocalLabel(label)))
+			},
+			TokenType::Dir(DirToken::If) => {
+				Ok(Some(Statement::Conditional(self.parse_conditional()?)))
+			},
+			TokenType::Dir(DirToken::Else | DirToken::Endif) => {
+				Err(ParseError::UnmatchedConditional {
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					directive: peek.t.to_string(),
+				})
+			},
+			// Included files are spliced in by the lexer, see `Includes`
+			TokenType::Dir(DirToken::Include) => {
+				Err(ParseError::UnexpandedInclude {
+					src_file: self.source_file.to_string(),
+					location: Box::new(LocationInfo::from(&peek)),
+				})
+			},
 			TokenType::Dir(_) => Ok(Some(Statement::Directive(self.parse_directive()?))),
 			TokenType::Inst(_) => Ok(Some(Statement::Instruction(self.parse_instruction()?))),
 			TokenType::SymNewline => Ok(None),
 			TokenType::Comment(_) => Ok(None),
+			TokenType::Reg(_) => {
+				// Unwrap is safe as peek is Ok
+				let reg = self.next().unwrap();
+
+				match self.peek() {
+					Ok(next) if next.t == TokenType::SymLeftBrace => {
+						Err(self.reserved_name(&reg, "label"))
+					},
+					_ => {
+						Err(ParseError::UnexpectedToken {
+							src_file:  self.source_file.to_string(),
+							location:  Box::new(LocationInfo::from(&reg)),
+							found:     reg.t.to_string(),
+							expected:  "`DIRECTIVE or INSTRUCTION or COMMENT or NEWLINE`"
+								.to_string(),
+							statement: None,
+						})
+					},
+				}
+			},
 			_ => {
 				Err(ParseError::UnexpectedToken {
-					src_file: self.source_file.to_string(),
-					location: Box::new(LocationInfo::from(peek)),
-					found:    peek.t.to_string(),
-					expected: "DIRECTIVE or INSTRUCTION or COMMENT or NEWLINE".to_string(),
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&peek)),
+					found:     peek.t.to_string(),
+					expected:  "`DIRECTIVE or INSTRUCTION or COMMENT or NEWLINE`".to_string(),
+					statement: None,
 				})
 			},
 		}
@@ -446,12 +866,14 @@ impl<'s> Parser<'s> {
 		label: &'s str,
 	) -> Result<LabeledBlock<'s>, ParseError> {
 		let open = self.expect(TokenType::SymLeftBrace)?;
+		self.enter_nested(&open)?;
 
 		let mut lines = vec![];
 		while let Ok(peek) = self.peek() && peek.t != TokenType::SymRightBrace {
 			let line = self.parse_line()?;
 			lines.push(line);
 		}
+		self.depth -= 1;
 
 		let close = self.next()?;
 		if close.t != TokenType::SymRightBrace {
@@ -459,11 +881,85 @@ impl<'s> Parser<'s> {
 				src_file:       self.source_file.to_string(),
 				delim_type:     "brace".to_string(),
 				found:          close.t.to_string(),
-				close_location: Box::new(LocationInfo::from(close)),
+				close_location: Box::new(LocationInfo::from(&close)),
 				open_location:  Box::new(LocationInfo::from(&open)),
 			});
 		}
 
 		Ok(LabeledBlock { label, lines })
 	}
+
+	/// Parse a [`Conditional`] consisting of:
+	///  - The [`#IF`](DirToken::If) keyword and a condition [`Immediate`](ast::Immediate)
+	///  - A block of [`Line`]s
+	///  - Optionally the [`#ELSE`](DirToken::Else) keyword and another block of [`Line`]s
+	///  - The [`#ENDIF`](DirToken::Endif) keyword
+	///
+	/// Nested conditionals are parsed as a [`Statement`] within one of the
+	/// blocks
+	///
+	/// Assumes the current [`Token`] has [`TokenType`]
+	/// [`TokenType::Dir(DirToken::If)`]
+	fn parse_conditional<'r>(&'r mut self) -> Result<Conditional<'s>, ParseError> {
+		// Unwrap is assumed to be safe
+		let open = self.next().unwrap();
+		self.enter_nested(&open)?;
+
+		let condition = self.parse_immediate()?;
+		self.optional(TokenType::Comment(""));
+		self.expect(TokenType::SymNewline)?;
+
+		let then_lines = self.parse_conditional_block(&open)?;
+
+		// Unwrap is safe as the block only ends at an #ELSE or #ENDIF
+		let else_lines = if self.next().unwrap().t == TokenType::Dir(DirToken::Else) {
+			self.optional(TokenType::Comment(""));
+			self.expect(TokenType::SymNewline)?;
+
+			let else_lines = self.parse_conditional_block(&open)?;
+
+			let close = self.next().unwrap();
+			if close.t != TokenType::Dir(DirToken::Endif) {
+				return Err(ParseError::UnmatchedConditional {
+					src_file:  self.source_file.to_string(),
+					location:  Box::new(LocationInfo::from(&close)),
+					directive: close.t.to_string(),
+				});
+			}
+
+			else_lines
+		} else {
+			vec![]
+		};
+		self.depth -= 1;
+
+		Ok(Conditional { condition, then_lines, else_lines })
+	}
+
+	/// Parse the [`Line`]s of a branch of the [`Conditional`] opened by the
+	/// given `#IF` token, up to the next [`#ELSE`](DirToken::Else) or
+	/// [`#ENDIF`](DirToken::Endif)
+	///
+	/// Errors if the enclosing block, section, or file ends first
+	fn parse_conditional_block<'r>(
+		&'r mut self,
+		open: &Token<'s>,
+	) -> Result<Vec<Line<'s>>, ParseError> {
+		let mut lines = vec![];
+
+		loop {
+			match self.peek().map(|t| t.t) {
+				Ok(TokenType::Dir(DirToken::Else | DirToken::Endif)) => return Ok(lines),
+				Ok(TokenType::Dir(DirToken::Section) | TokenType::SymRightBrace)
+				| Err(ParseError::UnexpectedEof { .. }) => {
+					return Err(ParseError::UnclosedConditional {
+						src_file: self.source_file.to_string(),
+						location: Box::new(LocationInfo::from(open)),
+					});
+				},
+				Err(err) => return Err(err),
+				Ok(_) => lines.push(self.parse_line()?),
+			}
+		}
+	}
 }
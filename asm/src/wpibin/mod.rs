@@ -0,0 +1,505 @@
+//! # WPIBIN
+//!
+//! The binary format produced by the assembler and loaded by the simulator
+//!
+//! A wpibin file consists of a header followed by a list of sections, a list
+//! of relocations, a list of symbols, and a source map, all multi-byte values
+//! of the format itself are stored in little-endian order
+//!
+//! ```text
+//! header =
+//!     magic:         "WPIB"
+//!     version:       u8
+//!     endian:        u8
+//!     base:          u32
+//!     entry:         u32
+//!     section_count: u32
+//!
+//! section =
+//!     name_len: u8
+//!     name:     [u8; name_len]
+//!     flags:    u8
+//!     addr:     u32
+//!     size:     u32
+//!     data:     [u8; size], absent if the section is uninitialized
+//!
+//! relocations =
+//!     relocation_count: u32
+//!     relocation:       [relocation; relocation_count]
+//!
+//! relocation =
+//!     addr:     u32
+//!     kind:     u8
+//!     name_len: u8
+//!     name:     [u8; name_len]
+//!
+//! symbols =
+//!     symbol_count: u32
+//!     symbol:       [symbol; symbol_count]
+//!
+//! symbol =
+//!     addr:     u32
+//!     name_len: u8
+//!     name:     [u8; name_len]
+//!
+//! source_map =
+//!     location_count: u32
+//!     location:       [location; location_count]
+//!
+//! location =
+//!     addr:     u32
+//!     line:     u32
+//!     file_len: u16
+//!     file:     [u8; file_len]
+//! ```
+//!
+//! The byte order of the data in the sections is encoded as `0` for
+//! [`Little`](Endian::Little) and `1` for [`Big`](Endian::Big), instructions
+//! are always little-endian
+//!
+//! Section flags are encoded as a bit set, with bit 0 set for
+//! [`exec`](SectionFlags::exec), bit 1 for [`write`](SectionFlags::write),
+//! and bit 2 for [`uninit`](SectionFlags::uninit)
+//!
+//! Relocation kinds are encoded as `0` for [`Branch`](RelocationKind::Branch),
+//! `1` for [`Jal`](RelocationKind::Jal), and `2` for
+//! [`Absolute`](RelocationKind::Absolute)
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The magic bytes every wpibin file starts with
+pub const MAGIC: &[u8; 4] = b"WPIB";
+
+/// The version of the format written by [`Image::to_bytes`]
+pub const VERSION: u8 = 7;
+
+/// An executable image, the in-memory representation of a wpibin file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Image {
+	/// The byte order of halves and words in the data of the image
+	pub endian:      Endian,
+	/// The address the image was assembled to be loaded at
+	pub base:        u32,
+	/// The address of the first instruction to execute
+	pub entry:       u32,
+	/// All the sections contained in the image
+	pub sections:    Vec<Section>,
+	/// All the references to external symbols that still need to be resolved
+	pub relocations: Vec<Relocation>,
+	/// All the labels declared in the image
+	pub symbols:     Vec<Symbol>,
+	/// The source code location of every instruction, empty unless the image
+	/// was generated with a source map
+	pub source_map:  Vec<SourceLocation>,
+}
+
+/// The order in which the bytes of a multi-byte value are stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endian {
+	/// The least significant byte is stored first
+	#[default]
+	Little,
+	/// The most significant byte is stored first
+	Big,
+}
+
+impl Endian {
+	/// Get the `width` least significant bytes of a value in this byte order
+	pub fn bytes(self, value: u32, width: usize) -> Vec<u8> {
+		match self {
+			Self::Little => value.to_le_bytes()[..width].to_vec(),
+			Self::Big => value.to_be_bytes()[4 - width..].to_vec(),
+		}
+	}
+
+	/// Combine up to 4 bytes stored in this byte order into a value
+	pub fn value(self, bytes: &[u8]) -> u32 {
+		let combine = |value: u32, &byte: &u8| (value << 8) | byte as u32;
+
+		match self {
+			Self::Little => bytes.iter().rev().fold(0, combine),
+			Self::Big => bytes.iter().fold(0, combine),
+		}
+	}
+
+	/// The byte identifying this byte order in a wpibin file
+	fn to_byte(self) -> u8 {
+		match self {
+			Self::Little => 0,
+			Self::Big => 1,
+		}
+	}
+
+	/// Get the byte order identified by a byte in a wpibin file
+	fn from_byte(byte: u8) -> Result<Self, FormatError> {
+		match byte {
+			0 => Ok(Self::Little),
+			1 => Ok(Self::Big),
+			_ => Err(FormatError::InvalidEndian(byte)),
+		}
+	}
+}
+
+impl FromStr for Endian {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"little" => Ok(Self::Little),
+			"big" => Ok(Self::Big),
+			_ => Err(format!("invalid byte order '{}', expected little or big", s)),
+		}
+	}
+}
+
+impl Display for Endian {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Little => write!(f, "little"),
+			Self::Big => write!(f, "big"),
+		}
+	}
+}
+
+/// A named block of bytes to be loaded at a given address
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Section {
+	/// The name of the section
+	pub name:  String,
+	/// How the section may be accessed once loaded
+	pub flags: SectionFlags,
+	/// The address the section should be loaded at
+	pub addr:  u32,
+	/// The contents of the section, all zeroes if the section is
+	/// [uninitialized](SectionFlags::uninit)
+	pub data:  Vec<u8>,
+}
+
+/// The properties of a [`Section`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SectionFlags {
+	/// The section contains instructions
+	pub exec:   bool,
+	/// The section may be written to
+	pub write:  bool,
+	/// The section only reserves space, its contents are not stored in the
+	/// file and are zeroed when loaded
+	pub uninit: bool,
+}
+
+impl SectionFlags {
+	/// Get the flags of a section from its conventional name, `.text`
+	/// sections are executable, `.data` sections writable, and `.bss`
+	/// sections writable and uninitialized
+	pub fn from_name(name: &str) -> Self {
+		match name.to_lowercase().as_str() {
+			".text" => Self { exec: true, ..Default::default() },
+			".data" => Self { write: true, ..Default::default() },
+			".bss" => Self { write: true, uninit: true, ..Default::default() },
+			_ => Self::default(),
+		}
+	}
+
+	/// The byte identifying these flags in a wpibin file
+	fn to_byte(self) -> u8 { self.exec as u8 | (self.write as u8) << 1 | (self.uninit as u8) << 2 }
+
+	/// Get the flags identified by a byte in a wpibin file
+	fn from_byte(byte: u8) -> Result<Self, FormatError> {
+		if byte & !0b111 != 0 {
+			return Err(FormatError::InvalidSectionFlags(byte));
+		}
+
+		Ok(Self { exec: byte & 1 != 0, write: byte & 2 != 0, uninit: byte & 4 != 0 })
+	}
+}
+
+/// A reference to an external symbol that should be patched in once the
+/// symbol's address is known
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+	/// The address of the word to patch
+	pub addr:   u32,
+	/// The name of the referenced symbol
+	pub symbol: String,
+	/// How the symbol's address should be encoded into the word
+	pub kind:   RelocationKind,
+}
+
+/// A label declared in an image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+	/// The name of the label, prefixed by the names of all enclosing labels
+	/// separated by dots (eg. `_start.nested`)
+	pub name: String,
+	/// The address of the label
+	pub addr: u32,
+}
+
+/// The location in the source code an instruction was assembled from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+	/// The address of the instruction
+	pub addr: u32,
+	/// The name of the source file
+	pub file: String,
+	/// The line of the instruction in the source file
+	pub line: u32,
+}
+
+impl Display for SourceLocation {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}", self.file, self.line)
+	}
+}
+
+/// The ways a symbol's address can be encoded into a [`Relocation`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelocationKind {
+	/// The pc-relative offset of a conditional branch
+	Branch,
+	/// The pc-relative offset of a `jal` instruction
+	Jal,
+	/// The absolute address, stored as a full word
+	Absolute,
+}
+
+impl RelocationKind {
+	/// The byte identifying this kind in a wpibin file
+	fn to_byte(self) -> u8 {
+		match self {
+			Self::Branch => 0,
+			Self::Jal => 1,
+			Self::Absolute => 2,
+		}
+	}
+
+	/// Get the kind identified by a byte in a wpibin file
+	fn from_byte(byte: u8) -> Result<Self, FormatError> {
+		match byte {
+			0 => Ok(Self::Branch),
+			1 => Ok(Self::Jal),
+			2 => Ok(Self::Absolute),
+			_ => Err(FormatError::InvalidRelocationKind(byte)),
+		}
+	}
+}
+
+impl Display for RelocationKind {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Branch => write!(f, "branch"),
+			Self::Jal => write!(f, "jal"),
+			Self::Absolute => write!(f, "absolute"),
+		}
+	}
+}
+
+/// Any error produced while reading a wpibin file
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatError {
+	/// The file does not start with the wpibin [magic bytes](MAGIC)
+	InvalidMagic,
+	/// The file was written with an unknown version of the format
+	UnsupportedVersion(u8),
+	/// The file ended in the middle of a header or section
+	UnexpectedEof,
+	/// A section name is not valid UTF-8
+	InvalidSectionName,
+	/// A section has unknown flags set
+	InvalidSectionFlags(u8),
+	/// A relocation or symbol name is not valid UTF-8
+	InvalidSymbolName,
+	/// A source file name in the source map is not valid UTF-8
+	InvalidSourceFile,
+	/// A relocation has an unknown kind
+	InvalidRelocationKind(u8),
+	/// The header has an unknown byte order
+	InvalidEndian(u8),
+}
+
+impl Display for FormatError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::InvalidMagic => write!(f, "not a wpibin file"),
+			Self::UnsupportedVersion(v) => {
+				write!(f, "unsupported wpibin version {}, expected version {}", v, VERSION)
+			},
+			Self::UnexpectedEof => write!(f, "unexpected end of wpibin file"),
+			Self::InvalidSectionName => write!(f, "wpibin section name is not valid UTF-8"),
+			Self::InvalidSectionFlags(s) => write!(f, "unknown wpibin section flags {:#04x}", s),
+			Self::InvalidSymbolName => write!(f, "wpibin symbol name is not valid UTF-8"),
+			Self::InvalidSourceFile => write!(f, "wpibin source file name is not valid UTF-8"),
+			Self::InvalidRelocationKind(k) => write!(f, "unknown wpibin relocation kind {}", k),
+			Self::InvalidEndian(e) => write!(f, "unknown wpibin byte order {}", e),
+		}
+	}
+}
+
+impl std::error::Error for FormatError {}
+
+impl Image {
+	/// Serialize this image into the bytes of a wpibin file
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::new();
+
+		bytes.extend_from_slice(MAGIC);
+		bytes.push(VERSION);
+		bytes.push(self.endian.to_byte());
+		bytes.extend_from_slice(&self.base.to_le_bytes());
+		bytes.extend_from_slice(&self.entry.to_le_bytes());
+		bytes.extend_from_slice(&(self.sections.len() as u32).to_le_bytes());
+
+		for section in &self.sections {
+			bytes.push(section.name.len() as u8);
+			bytes.extend_from_slice(section.name.as_bytes());
+			bytes.push(section.flags.to_byte());
+			bytes.extend_from_slice(&section.addr.to_le_bytes());
+			bytes.extend_from_slice(&(section.data.len() as u32).to_le_bytes());
+			if !section.flags.uninit {
+				bytes.extend_from_slice(&section.data);
+			}
+		}
+
+		bytes.extend_from_slice(&(self.relocations.len() as u32).to_le_bytes());
+
+		for relocation in &self.relocations {
+			bytes.extend_from_slice(&relocation.addr.to_le_bytes());
+			bytes.push(relocation.kind.to_byte());
+			bytes.push(relocation.symbol.len() as u8);
+			bytes.extend_from_slice(relocation.symbol.as_bytes());
+		}
+
+		bytes.extend_from_slice(&(self.symbols.len() as u32).to_le_bytes());
+
+		for symbol in &self.symbols {
+			bytes.extend_from_slice(&symbol.addr.to_le_bytes());
+			bytes.push(symbol.name.len() as u8);
+			bytes.extend_from_slice(symbol.name.as_bytes());
+		}
+
+		bytes.extend_from_slice(&(self.source_map.len() as u32).to_le_bytes());
+
+		for location in &self.source_map {
+			bytes.extend_from_slice(&location.addr.to_le_bytes());
+			bytes.extend_from_slice(&location.line.to_le_bytes());
+			bytes.extend_from_slice(&(location.file.len() as u16).to_le_bytes());
+			bytes.extend_from_slice(location.file.as_bytes());
+		}
+
+		bytes
+	}
+
+	/// Deserialize an image from the bytes of a wpibin file
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+		let mut reader = Reader { bytes, pos: 0 };
+
+		if reader.take(MAGIC.len())? != MAGIC {
+			return Err(FormatError::InvalidMagic);
+		}
+
+		let version = reader.read_u8()?;
+		if version != VERSION {
+			return Err(FormatError::UnsupportedVersion(version));
+		}
+
+		let endian = Endian::from_byte(reader.read_u8()?)?;
+		let base = reader.read_u32()?;
+		let entry = reader.read_u32()?;
+		let section_count = reader.read_u32()?;
+
+		let mut sections = Vec::new();
+		for _ in 0..section_count {
+			let name_len = reader.read_u8()? as usize;
+			let name = std::str::from_utf8(reader.take(name_len)?)
+				.map_err(|_| FormatError::InvalidSectionName)?
+				.to_string();
+			let flags = SectionFlags::from_byte(reader.read_u8()?)?;
+			let addr = reader.read_u32()?;
+			let size = reader.read_u32()? as usize;
+			let data = match flags.uninit {
+				true => vec![0; size],
+				false => reader.take(size)?.to_vec(),
+			};
+
+			sections.push(Section { name, flags, addr, data });
+		}
+
+		let relocation_count = reader.read_u32()?;
+
+		let mut relocations = Vec::new();
+		for _ in 0..relocation_count {
+			let addr = reader.read_u32()?;
+			let kind = RelocationKind::from_byte(reader.read_u8()?)?;
+			let name_len = reader.read_u8()? as usize;
+			let symbol = std::str::from_utf8(reader.take(name_len)?)
+				.map_err(|_| FormatError::InvalidSymbolName)?
+				.to_string();
+
+			relocations.push(Relocation { addr, symbol, kind });
+		}
+
+		let symbol_count = reader.read_u32()?;
+
+		let mut symbols = Vec::new();
+		for _ in 0..symbol_count {
+			let addr = reader.read_u32()?;
+			let name_len = reader.read_u8()? as usize;
+			let name = std::str::from_utf8(reader.take(name_len)?)
+				.map_err(|_| FormatError::InvalidSymbolName)?
+				.to_string();
+
+			symbols.push(Symbol { name, addr });
+		}
+
+		let location_count = reader.read_u32()?;
+
+		let mut source_map = Vec::new();
+		for _ in 0..location_count {
+			let addr = reader.read_u32()?;
+			let line = reader.read_u32()?;
+			let file_len = reader.read_u16()? as usize;
+			let file = std::str::from_utf8(reader.take(file_len)?)
+				.map_err(|_| FormatError::InvalidSourceFile)?
+				.to_string();
+
+			source_map.push(SourceLocation { addr, file, line });
+		}
+
+		Ok(Self { endian, base, entry, sections, relocations, symbols, source_map })
+	}
+
+	/// Get the source code location of the instruction at the given address
+	pub fn source_location(&self, addr: u32) -> Option<&SourceLocation> {
+		self.source_map.iter().find(|location| location.addr == addr)
+	}
+}
+
+/// Cursor over the bytes of a wpibin file
+struct Reader<'b> {
+	bytes: &'b [u8],
+	pos:   usize,
+}
+
+impl<'b> Reader<'b> {
+	/// Take the next `len` bytes
+	fn take(&mut self, len: usize) -> Result<&'b [u8], FormatError> {
+		let end = self.pos.checked_add(len).ok_or(FormatError::UnexpectedEof)?;
+		let slice = self.bytes.get(self.pos..end).ok_or(FormatError::UnexpectedEof)?;
+		self.pos = end;
+
+		Ok(slice)
+	}
+
+	fn read_u8(&mut self) -> Result<u8, FormatError> { Ok(self.take(1)?[0]) }
+
+	fn read_u16(&mut self) -> Result<u16, FormatError> {
+		let bytes = self.take(2)?;
+		Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+	}
+
+	fn read_u32(&mut self) -> Result<u32, FormatError> {
+		let bytes = self.take(4)?;
+		Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+}
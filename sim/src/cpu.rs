@@ -0,0 +1,468 @@
+//! # CPU
+//!
+//! The register file and execution logic of the simulated processor
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use asm::lex::{
+	BranchInstruction,
+	CsrInstruction,
+	CsriInstruction,
+	InstToken,
+	LoadInstruction,
+	MdrInstruction,
+	RegToken,
+	RriInstruction,
+	RrrInstruction,
+	StoreInstruction,
+};
+
+use crate::decode::{decode, Instruction};
+use crate::error::Error;
+use crate::memory::Memory;
+
+/// The amount of addressable control and status registers
+const CSR_COUNT: usize = 4096;
+
+/// The lower 32 bits of the cycle counter
+const CSR_CYCLE: u16 = 0xC00;
+/// The lower 32 bits of the retired instruction counter
+const CSR_INSTRET: u16 = 0xC02;
+/// The upper 32 bits of the cycle counter
+const CSR_CYCLEH: u16 = 0xC80;
+/// The upper 32 bits of the retired instruction counter
+const CSR_INSTRETH: u16 = 0xC82;
+
+/// The register holding the `ecall` number (`a7`)
+const ECALL_NUMBER_REG: usize = 17;
+/// The register holding the first `ecall` argument (`a0`)
+const ECALL_ARG_REG: usize = 10;
+
+/// `ecall` number used to exit the program
+const ECALL_EXIT: u32 = 93;
+
+/// The reason the simulated program stopped executing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitReason {
+	/// The program performed an exit `ecall` with the given status code
+	Exit(i32),
+	/// The program executed an `ebreak` instruction
+	Break,
+	/// An instruction changed a watched register or wrote to a watched
+	/// address
+	Watchpoint(Watchpoint),
+}
+
+/// A watched register or memory address that was changed by an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+	/// What was being watched
+	pub target: WatchTarget,
+	/// The value before the instruction executed
+	pub old:    u32,
+	/// The value after the instruction executed
+	pub new:    u32,
+	/// The address of the instruction
+	pub pc:     u32,
+	/// The instruction responsible for the change
+	pub inst:   Instruction,
+}
+
+/// Something that can be watched for changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchTarget {
+	/// A register, by index
+	Reg(usize),
+	/// A memory address, any store overlapping it counts as a change even if
+	/// the stored value is the same
+	Mem(u32),
+}
+
+impl Display for Watchpoint {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self.target {
+			WatchTarget::Reg(idx) => {
+				// Unwrap is safe as there are exactly 32 registers
+				let reg = RegToken::try_from(idx as u8).unwrap();
+				write!(f, "watched register {} ({})", reg, reg.abi())?;
+			},
+			WatchTarget::Mem(addr) => write!(f, "watched address {:#010x}", addr)?,
+		}
+
+		write!(
+			f,
+			" changed from {:#010x} to {:#010x} at pc {:#010x}: {}",
+			self.old, self.new, self.pc, self.inst
+		)
+	}
+}
+
+/// The state of the simulated processor
+#[derive(Clone, Debug)]
+pub struct Cpu {
+	/// The program counter
+	pub pc:      u32,
+	regs:        [u32; 32],
+	csrs:        Vec<u32>,
+	cycles:      u64,
+	/// A bitmask of the registers written since reset, `r0` counts as
+	/// written
+	written:     u32,
+	/// Whether reading an unwritten register traps
+	trap_uninit: bool,
+	/// How many times each kind of instruction was executed, if profiling
+	profile:     Option<HashMap<InstToken, u64>>,
+}
+
+impl Cpu {
+	/// Create a new CPU that will start executing at the given address
+	pub fn new(pc: u32) -> Self {
+		Self {
+			pc,
+			regs: [0; 32],
+			csrs: vec![0; CSR_COUNT],
+			cycles: 0,
+			written: 1,
+			trap_uninit: false,
+			profile: None,
+		}
+	}
+
+	/// Make instructions trap with an [`Error::UninitializedRegister`] when
+	/// they use a source register that has not been written since reset
+	pub fn with_uninit_trap(mut self, trap_uninit: bool) -> Self {
+		self.trap_uninit = trap_uninit;
+		self
+	}
+
+	/// Count how many times each kind of instruction gets executed, see
+	/// [`profile`](Self::profile)
+	pub fn with_profiling(mut self, profile: bool) -> Self {
+		self.profile = profile.then(HashMap::new);
+		self
+	}
+
+	/// Read the register with the given index
+	///
+	/// `r0` always reads as zero
+	pub fn reg(&self, idx: usize) -> u32 { self.regs[idx] }
+
+	/// Write the register with the given index
+	///
+	/// Writes to `r0` are ignored
+	pub fn set_reg(&mut self, idx: usize, value: u32) {
+		if idx != 0 {
+			self.regs[idx] = value;
+			self.written |= 1 << idx;
+		}
+	}
+
+	/// Read a source register of the instruction being executed
+	///
+	/// Errors if [uninitialized register traps](Self::with_uninit_trap) are
+	/// enabled and the register has not been written yet
+	fn src(&self, idx: usize) -> Result<u32, Error> {
+		if self.trap_uninit && self.written & (1 << idx) == 0 {
+			return Err(Error::UninitializedRegister { reg: idx, pc: self.pc });
+		}
+
+		Ok(self.regs[idx])
+	}
+
+	/// The amount of instructions executed so far
+	///
+	/// Every instruction takes a single cycle, so this is both the `cycle` and
+	/// `instret` counter
+	pub fn cycles(&self) -> u64 { self.cycles }
+
+	/// Read the control and status register with the given address
+	///
+	/// The `cycle` and `instret` counters (and their upper halves) read as
+	/// the amount of instructions executed so far
+	///
+	/// CSR addresses are 12 bits, any higher bits of `csr` are ignored
+	pub fn csr(&self, csr: u16) -> u32 {
+		match csr & 0xFFF {
+			CSR_CYCLE | CSR_INSTRET => self.cycles as u32,
+			CSR_CYCLEH | CSR_INSTRETH => (self.cycles >> 32) as u32,
+			csr => self.csrs[csr as usize],
+		}
+	}
+
+	/// Write the control and status register with the given address
+	///
+	/// Writes to the read-only counters are ignored. CSR addresses are 12
+	/// bits, any higher bits of `csr` are ignored
+	pub fn set_csr(&mut self, csr: u16, value: u32) {
+		match csr & 0xFFF {
+			CSR_CYCLE | CSR_INSTRET | CSR_CYCLEH | CSR_INSTRETH => (),
+			csr => self.csrs[csr as usize] = value,
+		}
+	}
+
+	/// How many times each kind of instruction was executed, most executed
+	/// first, or [`None`] if [profiling](Self::with_profiling) is disabled
+	///
+	/// Instructions executed equally often are sorted by name
+	pub fn profile(&self) -> Option<Vec<(InstToken, u64)>> {
+		let mut counts: Vec<_> = self.profile.as_ref()?.iter().map(|(&k, &v)| (k, v)).collect();
+		counts.sort_by_key(|&(inst, count)| (std::cmp::Reverse(count), inst.to_string()));
+
+		Some(counts)
+	}
+
+	/// Format the [profile](Self::profile) as a histogram, one instruction
+	/// per line, or [`None`] if profiling is disabled
+	///
+	/// ```text
+	/// addi      12  ############
+	/// ```
+	pub fn profile_histogram(&self) -> Option<String> {
+		let counts = self.profile()?;
+		let max = counts.first().map_or(1, |&(_, count)| count);
+
+		Some(
+			counts
+				.iter()
+				.map(|&(inst, count)| {
+					// Bars are scaled to at most 40 characters
+					let bar = "#".repeat(((count * 40 + max - 1) / max) as usize);
+					format!("{:<8} {:>8}  {}\n", inst.to_string(), count, bar)
+				})
+				.collect(),
+		)
+	}
+
+	/// Format the program counter and all registers, one per line
+	///
+	/// Registers are shown using both their number and ABI name, with their
+	/// value in hexadecimal and signed decimal
+	///
+	/// ```text
+	/// r5/t0     = 0x0000dead (57005)
+	/// ```
+	pub fn register_dump(&self) -> String {
+		let mut dump = format!("{:<9} = {:#010x}\n", "pc", self.pc);
+
+		for idx in 0..self.regs.len() {
+			dump.push_str(&self.register_line(idx));
+		}
+
+		dump
+	}
+
+	/// Format a single register the same way as a
+	/// [register dump](Self::register_dump)
+	pub fn register_line(&self, idx: usize) -> String {
+		// Unwrap is safe as there are exactly 32 registers
+		let reg = RegToken::try_from(idx as u8).unwrap();
+		let name = format!("{}/{}", reg, reg.abi());
+		let value = self.regs[idx];
+
+		format!("{:<9} = {:#010x} ({})\n", name, value, value as i32)
+	}
+
+	/// Fetch, decode, and execute a single instruction
+	///
+	/// Returns the reason the program stopped, or [`None`] if execution
+	/// should continue. Only instructions that execute without trapping are
+	/// counted towards the [cycle count](Self::cycles)
+	pub fn step(&mut self, memory: &mut Memory) -> Result<Option<ExitReason>, Error> {
+		if self.pc % 4 != 0 {
+			return Err(Error::MisalignedAccess { pc: self.pc, addr: self.pc });
+		}
+
+		let word = memory.fetch(self.pc)?;
+		let inst = decode(self.pc, word)?;
+
+		let reason = self.execute(inst, memory)?;
+		self.cycles += 1;
+		if let Some(profile) = &mut self.profile {
+			*profile.entry(inst.token()).or_default() += 1;
+		}
+
+		Ok(reason)
+	}
+
+	/// Execute a single decoded instruction and advance the program counter
+	fn execute(
+		&mut self,
+		inst: Instruction,
+		memory: &mut Memory,
+	) -> Result<Option<ExitReason>, Error> {
+		let mut next_pc = self.pc.wrapping_add(4);
+
+		match inst {
+			Instruction::Rri { op, rd, rs1, imm } => {
+				let a = self.src(rs1)?;
+				let b = imm as u32;
+
+				let value = match op {
+					RriInstruction::Addi => a.wrapping_add(b),
+					RriInstruction::Slti => ((a as i32) < imm) as u32,
+					RriInstruction::Sltiu => (a < b) as u32,
+					RriInstruction::Andi => a & b,
+					RriInstruction::Ori => a | b,
+					RriInstruction::Xori => a ^ b,
+					RriInstruction::Lsli => a << (b & 0x1F),
+					RriInstruction::Lsri => a >> (b & 0x1F),
+					RriInstruction::Asri => ((a as i32) >> (b & 0x1F)) as u32,
+				};
+
+				self.set_reg(rd, value);
+			},
+			Instruction::Rrr { op, rd, rs1, rs2 } => {
+				let a = self.src(rs1)?;
+				let b = self.src(rs2)?;
+
+				let value = match op {
+					RrrInstruction::Add => a.wrapping_add(b),
+					RrrInstruction::Sub => a.wrapping_sub(b),
+					RrrInstruction::And => a & b,
+					RrrInstruction::Or => a | b,
+					RrrInstruction::Xor => a ^ b,
+					RrrInstruction::Lsl => a << (b & 0x1F),
+					RrrInstruction::Lsr => a >> (b & 0x1F),
+					RrrInstruction::Asr => ((a as i32) >> (b & 0x1F)) as u32,
+					RrrInstruction::Slt => ((a as i32) < (b as i32)) as u32,
+					RrrInstruction::Sltu => (a < b) as u32,
+				};
+
+				self.set_reg(rd, value);
+			},
+			Instruction::Mdr { op, rd, rs1, rs2 } => {
+				let a = self.src(rs1)?;
+				let b = self.src(rs2)?;
+
+				let value = match op {
+					MdrInstruction::Mul => a.wrapping_mul(b),
+					MdrInstruction::Mulh => ((a as i32 as i64 * b as i32 as i64) >> 32) as u32,
+					MdrInstruction::Mulhu => ((a as u64 * b as u64) >> 32) as u32,
+					MdrInstruction::Mulhsu => ((a as i32 as i64 * b as i64) >> 32) as u32,
+					// Division by zero and overflow follow the RISC-V spec instead of trapping
+					MdrInstruction::Div => {
+						match b {
+							0 => u32::MAX,
+							_ => (a as i32).wrapping_div(b as i32) as u32,
+						}
+					},
+					MdrInstruction::Divu => a.checked_div(b).unwrap_or(u32::MAX),
+					MdrInstruction::Rem => {
+						match b {
+							0 => a,
+							_ => (a as i32).wrapping_rem(b as i32) as u32,
+						}
+					},
+					MdrInstruction::Remu => a.checked_rem(b).unwrap_or(a),
+				};
+
+				self.set_reg(rd, value);
+			},
+			Instruction::Branch { op, rs1, rs2, offset } => {
+				let a = self.src(rs1)?;
+				let b = self.src(rs2)?;
+
+				let taken = match op {
+					BranchInstruction::Beq => a == b,
+					BranchInstruction::Bne => a != b,
+					BranchInstruction::Blt => (a as i32) < (b as i32),
+					BranchInstruction::Bltu => a < b,
+					BranchInstruction::Bge => (a as i32) >= (b as i32),
+					BranchInstruction::Bgeu => a >= b,
+				};
+
+				if taken {
+					next_pc = self.pc.wrapping_add(offset as u32);
+				}
+			},
+			Instruction::Load { op, rd, rs1, offset } => {
+				let addr = self.src(rs1)?.wrapping_add(offset as u32);
+
+				let value = match op {
+					LoadInstruction::Lb => memory.read_u8(addr)? as i8 as u32,
+					LoadInstruction::Lbu => memory.read_u8(addr)? as u32,
+					LoadInstruction::Lh => memory.read_u16(self.aligned(addr, 2)?)? as i16 as u32,
+					LoadInstruction::Lhu => memory.read_u16(self.aligned(addr, 2)?)? as u32,
+					LoadInstruction::Lw => memory.read_u32(self.aligned(addr, 4)?)?,
+				};
+
+				self.set_reg(rd, value);
+			},
+			Instruction::Store { op, rs1, rs2, offset } => {
+				let addr = self.src(rs1)?.wrapping_add(offset as u32);
+				let value = self.src(rs2)?;
+
+				match op {
+					StoreInstruction::Sb => memory.write_u8(addr, value as u8)?,
+					StoreInstruction::Sh => {
+						memory.write_u16(self.aligned(addr, 2)?, value as u16)?
+					},
+					StoreInstruction::Sw => memory.write_u32(self.aligned(addr, 4)?, value)?,
+				}
+			},
+			Instruction::Csr { op, rd, rs1, csr } => {
+				let old = self.csr(csr);
+				let src = self.src(rs1)?;
+
+				let new = match op {
+					CsrInstruction::Csrrw => src,
+					CsrInstruction::Csrrs => old | src,
+					CsrInstruction::Csrrc => old & !src,
+				};
+
+				self.set_csr(csr, new);
+				self.set_reg(rd, old);
+			},
+			Instruction::Csri { op, rd, uimm, csr } => {
+				let old = self.csr(csr);
+
+				let new = match op {
+					CsriInstruction::Csrrwi => uimm,
+					CsriInstruction::Csrrsi => old | uimm,
+					CsriInstruction::Csrrci => old & !uimm,
+				};
+
+				self.set_csr(csr, new);
+				self.set_reg(rd, old);
+			},
+			Instruction::Lui { rd, imm } => self.set_reg(rd, imm),
+			Instruction::Auipc { rd, imm } => self.set_reg(rd, self.pc.wrapping_add(imm)),
+			Instruction::Jal { rd, offset } => {
+				self.set_reg(rd, next_pc);
+				next_pc = self.pc.wrapping_add(offset as u32);
+			},
+			Instruction::Jalr { rd, rs1, offset } => {
+				let target = self.src(rs1)?.wrapping_add(offset as u32) & !1;
+
+				self.set_reg(rd, next_pc);
+				next_pc = target;
+			},
+			// The simulator executes in order on a single hart, so fences have no effect
+			Instruction::Fence { .. } | Instruction::FenceTso | Instruction::Fencei => (),
+			Instruction::Ecall => {
+				let number = self.src(ECALL_NUMBER_REG)?;
+
+				match number {
+					ECALL_EXIT => {
+						return Ok(Some(ExitReason::Exit(self.src(ECALL_ARG_REG)? as i32)));
+					},
+					_ => return Err(Error::UnknownEcall { pc: self.pc, number }),
+				}
+			},
+			Instruction::Ebreak => return Ok(Some(ExitReason::Break)),
+		}
+
+		self.pc = next_pc;
+
+		Ok(None)
+	}
+
+	/// Check that `addr` is aligned to `align` bytes
+	fn aligned(&self, addr: u32, align: u32) -> Result<u32, Error> {
+		match addr % align {
+			0 => Ok(addr),
+			_ => Err(Error::MisalignedAccess { pc: self.pc, addr }),
+		}
+	}
+}
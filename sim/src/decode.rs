@@ -0,0 +1,346 @@
+//! # Decoder
+//!
+//! Converts raw 32-bit instruction words into [`Instruction`]s
+//!
+//! Registers are stored as their index into the register file, immediates
+//! are sign-extended (where applicable) and already shifted into place
+
+use std::fmt::{Display, Formatter};
+
+use asm::lex::{
+	BranchInstruction,
+	CsrInstruction,
+	CsriInstruction,
+	InstToken,
+	LoadInstruction,
+	MdrInstruction,
+	RriInstruction,
+	RrrInstruction,
+	StoreInstruction,
+};
+use asm::parse::ast::OrderingTarget;
+
+use crate::error::Error;
+
+/// A decoded instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum Instruction {
+	Rri { op: RriInstruction, rd: usize, rs1: usize, imm: i32 },
+	Rrr { op: RrrInstruction, rd: usize, rs1: usize, rs2: usize },
+	Mdr { op: MdrInstruction, rd: usize, rs1: usize, rs2: usize },
+	Branch { op: BranchInstruction, rs1: usize, rs2: usize, offset: i32 },
+	Load { op: LoadInstruction, rd: usize, rs1: usize, offset: i32 },
+	Store { op: StoreInstruction, rs1: usize, rs2: usize, offset: i32 },
+	Csr { op: CsrInstruction, rd: usize, rs1: usize, csr: u16 },
+	Csri { op: CsriInstruction, rd: usize, uimm: u32, csr: u16 },
+	Lui { rd: usize, imm: u32 },
+	Auipc { rd: usize, imm: u32 },
+	Jal { rd: usize, offset: i32 },
+	Jalr { rd: usize, rs1: usize, offset: i32 },
+	Fence { pred: OrderingTarget, succ: OrderingTarget },
+	FenceTso,
+	Fencei,
+	Ecall,
+	Ebreak,
+}
+
+impl Instruction {
+	/// Get the instruction token this instruction was assembled from,
+	/// without its operands
+	pub fn token(&self) -> InstToken {
+		match *self {
+			Self::Rri { op, .. } => InstToken::Rri(op),
+			Self::Rrr { op, .. } => InstToken::Rrr(op),
+			Self::Mdr { op, .. } => InstToken::Mdr(op),
+			Self::Branch { op, .. } => InstToken::Branch(op),
+			Self::Load { op, .. } => InstToken::Load(op),
+			Self::Store { op, .. } => InstToken::Store(op),
+			Self::Csr { op, .. } => InstToken::Csr(op),
+			Self::Csri { op, .. } => InstToken::Csri(op),
+			Self::Lui { .. } => InstToken::Lui,
+			Self::Auipc { .. } => InstToken::Auipc,
+			Self::Jal { .. } => InstToken::Jal,
+			Self::Jalr { .. } => InstToken::Jalr,
+			Self::Fence { .. } => InstToken::Fence,
+			Self::FenceTso => InstToken::FenceTso,
+			Self::Fencei => InstToken::Fencei,
+			Self::Ecall => InstToken::Ecall,
+			Self::Ebreak => InstToken::Ebreak,
+		}
+	}
+}
+
+/// Formats the instruction as assembly source that assembles back into the
+/// same word
+impl Display for Instruction {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match *self {
+			Self::Rri { op, rd, rs1, imm } => {
+				write!(f, "{} r{}, r{}, {}", InstToken::Rri(op), rd, rs1, imm)
+			},
+			Self::Rrr { op, rd, rs1, rs2 } => {
+				write!(f, "{} r{}, r{}, r{}", InstToken::Rrr(op), rd, rs1, rs2)
+			},
+			Self::Mdr { op, rd, rs1, rs2 } => {
+				write!(f, "{} r{}, r{}, r{}", InstToken::Mdr(op), rd, rs1, rs2)
+			},
+			Self::Branch { op, rs1, rs2, offset } => {
+				write!(f, "{} r{}, r{}, {}", InstToken::Branch(op), rs1, rs2, offset)
+			},
+			Self::Load { op, rd, rs1, offset } => {
+				write!(f, "{} r{}, [r{} + {}]", InstToken::Load(op), rd, rs1, offset)
+			},
+			Self::Store { op, rs1, rs2, offset } => {
+				write!(f, "{} [r{} + {}], r{}", InstToken::Store(op), rs1, offset, rs2)
+			},
+			Self::Csr { op, rd, rs1, csr } => {
+				write!(f, "{} r{}, r{}, {}", InstToken::Csr(op), rd, rs1, csr)
+			},
+			Self::Csri { op, rd, uimm, csr } => {
+				write!(f, "{} r{}, {}, {}", InstToken::Csri(op), rd, uimm, csr)
+			},
+			Self::Lui { rd, imm } => write!(f, "lui r{}, {}", rd, imm >> 12),
+			Self::Auipc { rd, imm } => write!(f, "auipc r{}, {}", rd, imm as i32 >> 12),
+			Self::Jal { rd, offset } => write!(f, "jal r{}, {}", rd, offset),
+			Self::Jalr { rd, rs1, offset } => write!(f, "jalr r{}, r{}, {}", rd, rs1, offset),
+			Self::Fence { pred, succ } => write!(f, "fence {}, {}", pred, succ),
+			Self::FenceTso => write!(f, "fence.tso RW, RW"),
+			Self::Fencei => write!(f, "fence.i"),
+			Self::Ecall => write!(f, "ecall"),
+			Self::Ebreak => write!(f, "ebreak"),
+		}
+	}
+}
+
+const OP_LOAD: u32 = 0b000_0011;
+const OP_MISC_MEM: u32 = 0b000_1111;
+const OP_IMM: u32 = 0b001_0011;
+const OP_AUIPC: u32 = 0b001_0111;
+const OP_STORE: u32 = 0b010_0011;
+const OP_OP: u32 = 0b011_0011;
+const OP_LUI: u32 = 0b011_0111;
+const OP_BRANCH: u32 = 0b110_0011;
+const OP_JALR: u32 = 0b110_0111;
+const OP_JAL: u32 = 0b110_1111;
+const OP_SYSTEM: u32 = 0b111_0011;
+
+/// Decode a single instruction word located at address `pc`
+///
+/// Returns an [`Error::IllegalInstruction`] describing the offending field
+/// if the word does not encode a known instruction, reserved encodings are
+/// rejected the same way. Decoding never panics, and every word that does
+/// decode is exactly the word the assembler would encode the instruction
+/// into
+pub fn decode(pc: u32, word: u32) -> Result<Instruction, Error> {
+	let opcode = word & 0x7F;
+	let rd = ((word >> 7) & 0x1F) as usize;
+	let funct3 = (word >> 12) & 0x7;
+	let rs1 = ((word >> 15) & 0x1F) as usize;
+	let rs2 = ((word >> 20) & 0x1F) as usize;
+	let funct7 = word >> 25;
+
+	let illegal = |detail: String| Error::IllegalInstruction { pc, word, detail };
+
+	let inst = match opcode {
+		OP_IMM => {
+			let imm = imm_i(word);
+			let op = match (funct3, funct7) {
+				(0b000, _) => RriInstruction::Addi,
+				(0b010, _) => RriInstruction::Slti,
+				(0b011, _) => RriInstruction::Sltiu,
+				(0b100, _) => RriInstruction::Xori,
+				(0b110, _) => RriInstruction::Ori,
+				(0b111, _) => RriInstruction::Andi,
+				(0b001, 0b000_0000) => RriInstruction::Lsli,
+				(0b101, 0b000_0000) => RriInstruction::Lsri,
+				(0b101, 0b010_0000) => RriInstruction::Asri,
+				_ => {
+					return Err(illegal(format!(
+						"unknown OP-IMM funct3={:#05b} funct7={:#09b}",
+						funct3, funct7
+					)));
+				},
+			};
+
+			// Shifts only use the lower 5 bits of the immediate as the shift amount
+			let imm = match op {
+				RriInstruction::Lsli | RriInstruction::Lsri | RriInstruction::Asri => rs2 as i32,
+				_ => imm,
+			};
+
+			Instruction::Rri { op, rd, rs1, imm }
+		},
+		OP_OP if funct7 == 0b000_0001 => {
+			let op = match funct3 {
+				0b000 => MdrInstruction::Mul,
+				0b001 => MdrInstruction::Mulh,
+				0b010 => MdrInstruction::Mulhsu,
+				0b011 => MdrInstruction::Mulhu,
+				0b100 => MdrInstruction::Div,
+				0b101 => MdrInstruction::Divu,
+				0b110 => MdrInstruction::Rem,
+				_ => MdrInstruction::Remu,
+			};
+
+			Instruction::Mdr { op, rd, rs1, rs2 }
+		},
+		OP_OP => {
+			let op = match (funct3, funct7) {
+				(0b000, 0b000_0000) => RrrInstruction::Add,
+				(0b000, 0b010_0000) => RrrInstruction::Sub,
+				(0b001, 0b000_0000) => RrrInstruction::Lsl,
+				(0b010, 0b000_0000) => RrrInstruction::Slt,
+				(0b011, 0b000_0000) => RrrInstruction::Sltu,
+				(0b100, 0b000_0000) => RrrInstruction::Xor,
+				(0b101, 0b000_0000) => RrrInstruction::Lsr,
+				(0b101, 0b010_0000) => RrrInstruction::Asr,
+				(0b110, 0b000_0000) => RrrInstruction::Or,
+				(0b111, 0b000_0000) => RrrInstruction::And,
+				_ => {
+					return Err(illegal(format!(
+						"unknown OP funct3={:#05b} funct7={:#09b}",
+						funct3, funct7
+					)));
+				},
+			};
+
+			Instruction::Rrr { op, rd, rs1, rs2 }
+		},
+		OP_LUI => Instruction::Lui { rd, imm: word & 0xFFFF_F000 },
+		OP_AUIPC => Instruction::Auipc { rd, imm: word & 0xFFFF_F000 },
+		OP_JAL => Instruction::Jal { rd, offset: imm_j(word) },
+		OP_JALR if funct3 == 0b000 => Instruction::Jalr { rd, rs1, offset: imm_i(word) },
+		OP_BRANCH => {
+			let op = match funct3 {
+				0b000 => BranchInstruction::Beq,
+				0b001 => BranchInstruction::Bne,
+				0b100 => BranchInstruction::Blt,
+				0b101 => BranchInstruction::Bge,
+				0b110 => BranchInstruction::Bltu,
+				0b111 => BranchInstruction::Bgeu,
+				_ => return Err(illegal(format!("unknown BRANCH funct3={:#05b}", funct3))),
+			};
+
+			Instruction::Branch { op, rs1, rs2, offset: imm_b(word) }
+		},
+		OP_LOAD => {
+			let op = match funct3 {
+				0b000 => LoadInstruction::Lb,
+				0b001 => LoadInstruction::Lh,
+				0b010 => LoadInstruction::Lw,
+				0b100 => LoadInstruction::Lbu,
+				0b101 => LoadInstruction::Lhu,
+				_ => return Err(illegal(format!("unknown LOAD funct3={:#05b}", funct3))),
+			};
+
+			Instruction::Load { op, rd, rs1, offset: imm_i(word) }
+		},
+		OP_STORE => {
+			let op = match funct3 {
+				0b000 => StoreInstruction::Sb,
+				0b001 => StoreInstruction::Sh,
+				0b010 => StoreInstruction::Sw,
+				_ => return Err(illegal(format!("unknown STORE funct3={:#05b}", funct3))),
+			};
+
+			Instruction::Store { op, rs1, rs2, offset: imm_s(word) }
+		},
+		OP_MISC_MEM => {
+			let fm = word >> 28;
+			let pred = ordering_target((word >> 24) & 0xF);
+			let succ = ordering_target((word >> 20) & 0xF);
+			let rw = OrderingTarget::R | OrderingTarget::W;
+
+			// The register fields of all fences are reserved and must be 0
+			if rd != 0 || rs1 != 0 {
+				return Err(illegal(format!(
+					"reserved MISC-MEM fields rd={} rs1={} are not 0",
+					rd, rs1
+				)));
+			}
+
+			match (funct3, fm) {
+				(0b000, 0b0000) if !pred.is_empty() && !succ.is_empty() => {
+					Instruction::Fence { pred, succ }
+				},
+				(0b000, 0b1000) if pred == rw && succ == rw => Instruction::FenceTso,
+				(0b001, _) if word >> 20 == 0 => Instruction::Fencei,
+				_ => {
+					return Err(illegal(format!(
+						"unknown MISC-MEM funct3={:#05b} fm={:#06b} pred={:#06b} succ={:#06b}",
+						funct3,
+						fm,
+						(word >> 24) & 0xF,
+						(word >> 20) & 0xF
+					)));
+				},
+			}
+		},
+		OP_SYSTEM => {
+			let csr = (word >> 20) as u16;
+
+			match funct3 {
+				0b000 => {
+					match word {
+						0x0000_0073 => Instruction::Ecall,
+						0x0010_0073 => Instruction::Ebreak,
+						_ => return Err(illegal(format!("unknown SYSTEM funct12={:#014b}", csr))),
+					}
+				},
+				0b001 => Instruction::Csr { op: CsrInstruction::Csrrw, rd, rs1, csr },
+				0b010 => Instruction::Csr { op: CsrInstruction::Csrrs, rd, rs1, csr },
+				0b011 => Instruction::Csr { op: CsrInstruction::Csrrc, rd, rs1, csr },
+				0b101 => {
+					Instruction::Csri { op: CsriInstruction::Csrrwi, rd, uimm: rs1 as u32, csr }
+				},
+				0b110 => {
+					Instruction::Csri { op: CsriInstruction::Csrrsi, rd, uimm: rs1 as u32, csr }
+				},
+				0b111 => {
+					Instruction::Csri { op: CsriInstruction::Csrrci, rd, uimm: rs1 as u32, csr }
+				},
+				_ => return Err(illegal(format!("unknown SYSTEM funct3={:#05b}", funct3))),
+			}
+		},
+		OP_JALR => return Err(illegal(format!("unknown JALR funct3={:#05b}", funct3))),
+		_ => return Err(illegal(format!("unknown opcode {:#09b}", opcode))),
+	};
+
+	Ok(inst)
+}
+
+/// Sign-extended I-type immediate
+fn imm_i(word: u32) -> i32 { (word as i32) >> 20 }
+
+/// Sign-extended S-type immediate
+fn imm_s(word: u32) -> i32 { ((word as i32) >> 25 << 5) | ((word >> 7) & 0x1F) as i32 }
+
+/// Sign-extended B-type immediate
+fn imm_b(word: u32) -> i32 {
+	((word as i32) >> 31 << 12)
+		| (((word >> 7) & 0x1) << 11) as i32
+		| (((word >> 25) & 0x3F) << 5) as i32
+		| (((word >> 8) & 0xF) << 1) as i32
+}
+
+/// Sign-extended J-type immediate
+fn imm_j(word: u32) -> i32 {
+	((word as i32) >> 31 << 20)
+		| (word & 0x000F_F000) as i32
+		| (((word >> 20) & 0x1) << 11) as i32
+		| (((word >> 21) & 0x3FF) << 1) as i32
+}
+
+/// Convert the 4 bit predecessor/successor field of a fence into an
+/// [`OrderingTarget`]
+fn ordering_target(bits: u32) -> OrderingTarget {
+	let mut target = OrderingTarget::empty();
+
+	target.set(OrderingTarget::I, bits & 0b1000 != 0);
+	target.set(OrderingTarget::O, bits & 0b0100 != 0);
+	target.set(OrderingTarget::R, bits & 0b0010 != 0);
+	target.set(OrderingTarget::W, bits & 0b0001 != 0);
+
+	target
+}
@@ -2,17 +2,123 @@
 
 use std::fmt::{Display, Formatter};
 
+use asm::error::Error as AssemblerError;
+use asm::lex::RegToken;
+use asm::wpibin::FormatError;
+
 /// Any possible error produced during simulation
 #[derive(Debug)]
+#[allow(missing_docs)]
 pub enum Error {
 	/// Wrapper around [`std::io::Error`]
 	Io(std::io::Error),
+	/// The binary is not a valid wpibin file
+	Format(FormatError),
+	/// An access to memory outside of the simulated memory space
+	OutOfBounds { addr: u32, len: u32 },
+	/// The image does not contain any code or data to execute
+	EmptyImage,
+	/// The image still references an external symbol, it needs to be linked
+	/// before it can be simulated
+	UnresolvedSymbol { symbol: String, addr: u32 },
+	/// The symbol to start executing at is not declared in the image
+	UnknownSymbol(String),
+	/// Two sections of the image would be loaded at overlapping addresses
+	OverlappingSections { first: String, second: String, addr: u32 },
+	/// The image, along with its stack, does not fit below the end of the
+	/// 32-bit address space, `end` is the address its memory would end at
+	AddressSpaceExceeded { end: u64 },
+	/// A memory access or jump to an address that is not correctly aligned
+	MisalignedAccess { pc: u32, addr: u32 },
+	/// An instruction word that could not be decoded, `detail` describes
+	/// which part of the word is invalid
+	IllegalInstruction { pc: u32, word: u32, detail: String },
+	/// An instruction read a register that has not been written since reset,
+	/// only produced when [`trap_uninit`](crate::SimOptions::trap_uninit) is
+	/// enabled
+	UninitializedRegister { reg: usize, pc: u32 },
+	/// An `ecall` with an unknown number in `a7`
+	UnknownEcall { pc: u32, number: u32 },
+	/// The program did not stop within the maximum amount of steps
+	StepLimitReached { steps: u64 },
+	/// A line entered in the [REPL](crate::repl) failed to assemble
+	Assembler(AssemblerError),
+	/// A line entered in the [REPL](crate::repl) is not an instruction
+	NotAnInstruction(String),
+	/// An unknown [REPL](crate::repl) meta-command
+	UnknownCommand(String),
+	/// The target of a [REPL](crate::repl) `.watch` command is neither a
+	/// register nor an address
+	InvalidWatchTarget(String),
 }
 
 impl Display for Error {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		match self {
 			Self::Io(err) => write!(f, "{}", err),
+			Self::Format(err) => write!(f, "{}", err),
+			Self::OutOfBounds { addr, len } => {
+				write!(f, "memory access of {} bytes at {:#010x} is out of bounds", len, addr)
+			},
+			Self::EmptyImage => write!(f, "image is empty, there is nothing to execute"),
+			Self::UnresolvedSymbol { symbol, addr } => {
+				write!(f, "unresolved external symbol `{}` referenced at {:#010x}", symbol, addr)
+			},
+			Self::UnknownSymbol(symbol) => {
+				write!(f, "symbol `{}` is not declared in the image", symbol)
+			},
+			Self::OverlappingSections { first, second, addr } => {
+				write!(f, "section {} overlaps section {} at {:#010x}", second, first, addr)
+			},
+			Self::AddressSpaceExceeded { end } => {
+				write!(
+					f,
+					"memory would end at {:#x}, past the end of the 32-bit address space",
+					end
+				)
+			},
+			Self::MisalignedAccess { pc, addr } => {
+				write!(f, "misaligned access to {:#010x} at pc {:#010x}", addr, pc)
+			},
+			Self::IllegalInstruction { pc, word, detail } => {
+				write!(f, "illegal instruction {:#010x} at pc {:#010x}: {}", word, pc, detail)
+			},
+			Self::UninitializedRegister { reg, pc } => {
+				// Unwrap is safe as register indices are always below 32
+				let reg = RegToken::try_from(*reg as u8).unwrap();
+				write!(
+					f,
+					"read of uninitialized register {} ({}) at pc {:#010x}",
+					reg,
+					reg.abi(),
+					pc
+				)
+			},
+			Self::UnknownEcall { pc, number } => {
+				write!(f, "unknown ecall number {} at pc {:#010x}", number, pc)
+			},
+			Self::StepLimitReached { steps } => {
+				write!(f, "program did not stop within {} steps", steps)
+			},
+			Self::Assembler(err) => write!(f, "{}", err),
+			Self::NotAnInstruction(line) => {
+				write!(f, "only instructions can be executed, found `{}`", line)
+			},
+			Self::UnknownCommand(cmd) => write!(f, "unknown command `{}`", cmd),
+			Self::InvalidWatchTarget(target) => {
+				write!(f, "can only watch a register or an address, found `{}`", target)
+			},
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(err) => Some(err),
+			// The assembler error is displayed as is, so only expose what it wraps
+			Self::Assembler(err) => std::error::Error::source(err),
+			_ => None,
 		}
 	}
 }
@@ -20,3 +126,11 @@ impl Display for Error {
 impl From<std::io::Error> for Error {
 	fn from(value: std::io::Error) -> Self { Self::Io(value) }
 }
+
+impl From<FormatError> for Error {
+	fn from(value: FormatError) -> Self { Self::Format(value) }
+}
+
+impl From<AssemblerError> for Error {
+	fn from(value: AssemblerError) -> Self { Self::Assembler(value) }
+}
@@ -1,16 +1,377 @@
 //! # Simulator
 //!
-//! TODO: everything
+//! The simulator executes binaries produced by the assembler <br>
+//! A simulation consists of the following steps:
+//!  - Loading: The [`wpibin`](asm::wpibin) image is read and all of its sections are copied into
+//!    [`Memory`] at their load address
+//!  - Execution: The [`Cpu`] repeatedly fetches, decodes, and executes instructions until the
+//!    program exits via `ecall` or hits an `ebreak`
+//!
+//! Programs can exit by performing an `ecall` with `93` in `a7` and the exit
+//! status in `a0`
+//!
+//! Execution can also be halted whenever a watched register changes or a
+//! watched memory address is written to, see [`SimOptions`]
+//!
+//! Source code can also be assembled and executed one line at a time using
+//! the [`repl`]
 
 #![warn(missing_docs)]
+#![feature(let_chains)]
+
+#[macro_use]
+extern crate log;
 
+use std::collections::HashMap;
 use std::path::Path;
 
+use asm::lex::StoreInstruction;
+use asm::wpibin::{Image, SourceLocation};
+
+pub mod cpu;
+pub mod decode;
 pub mod error;
+pub mod memory;
+pub mod repl;
 
+use cpu::{Cpu, ExitReason, WatchTarget, Watchpoint};
+use decode::{decode, Instruction};
 use error::Error;
+use memory::Memory;
+
+/// The default size of the simulated memory in bytes
+pub const DEFAULT_MEMORY_SIZE: usize = 0x10_0000;
+
+/// The amount of memory left free for the stack when the memory is grown to
+/// fit an image
+pub const STACK_SIZE: usize = 0x1_0000;
+
+/// The index of the stack pointer register (`sp`)
+const STACK_POINTER_REG: usize = 2;
+
+/// Options controlling how a binary gets simulated
+#[derive(Clone, Debug)]
+pub struct SimOptions {
+	/// The size of the simulated memory in bytes, grown to fit the image and
+	/// a [stack](STACK_SIZE) if the image does not fit
+	pub memory_size:  usize,
+	/// The maximum amount of instructions to execute before giving up, or
+	/// [`None`] to run until the program stops
+	pub max_steps:    Option<u64>,
+	/// The address to start executing at instead of the entry point of the
+	/// image
+	pub entry:        Option<u32>,
+	/// The name of a symbol in the image to start executing at, takes
+	/// precedence over [`entry`](Self::entry)
+	pub entry_symbol: Option<String>,
+	/// Print every executed instruction to stdout, along with its source
+	/// location if the image has a source map
+	pub trace:        bool,
+	/// Trap when an instruction reads a register that has not been written
+	/// since reset, `r0` and the stack pointer always count as written
+	pub trap_uninit:  bool,
+	/// Count how many times each kind of instruction gets executed, see
+	/// [`Cpu::profile`]
+	pub profile:      bool,
+	/// The indices of registers to watch, execution halts after any
+	/// instruction that changes their value
+	pub watch_regs:   Vec<usize>,
+	/// The memory addresses to watch, execution halts after any store that
+	/// writes to them
+	pub watch_mem:    Vec<u32>,
+	/// The initial value of registers, given as pairs of an index and a
+	/// value, writes to `r0` are ignored
+	pub set_regs:     Vec<(usize, u32)>,
+}
+
+impl Default for SimOptions {
+	fn default() -> Self {
+		Self {
+			memory_size:  DEFAULT_MEMORY_SIZE,
+			max_steps:    None,
+			entry:        None,
+			entry_symbol: None,
+			trace:        false,
+			trap_uninit:  false,
+			profile:      false,
+			watch_regs:   Vec::new(),
+			watch_mem:    Vec::new(),
+			set_regs:     Vec::new(),
+		}
+	}
+}
+
+/// A simulated machine, consisting of a [`Cpu`] and its [`Memory`]
+#[derive(Clone, Debug)]
+pub struct Machine {
+	/// The processor of the machine
+	pub cpu:    Cpu,
+	/// The memory of the machine
+	pub memory: Memory,
+	max_steps:  Option<u64>,
+	trace:      bool,
+	/// The source location of every instruction, by address
+	source_map: HashMap<u32, SourceLocation>,
+	watch_regs: Vec<usize>,
+	watch_mem:  Vec<u32>,
+}
+
+impl Machine {
+	/// Create a new machine with the given image loaded into its memory
+	///
+	/// Every section is copied to its own load address, uninitialized sections
+	/// are zeroed instead. If the highest section does not fit in the
+	/// configured memory size the memory is grown to fit it along with a
+	/// stack. If even the lowest section starts past the configured memory
+	/// size, the memory starts at that section instead of at address 0
+	///
+	/// The program counter is set to the entry point of the image (unless
+	/// overridden by the options) and the stack pointer to the end of memory
+	///
+	/// Errors if two sections overlap, if the memory would not end below the
+	/// end of the address space, or if the options name an entry symbol that
+	/// is not in the image
+	pub fn load(image: &Image, options: &SimOptions) -> Result<Self, Error> {
+		info!("Loading image assembled for base address {:#010x}", image.base);
+
+		if let Some(relocation) = image.relocations.first() {
+			return Err(Error::UnresolvedSymbol {
+				symbol: relocation.symbol.clone(),
+				addr:   relocation.addr,
+			});
+		}
+
+		let mut sections: Vec<_> = image.sections.iter().filter(|s| !s.data.is_empty()).collect();
+		sections.sort_by_key(|section| section.addr);
+
+		for pair in sections.windows(2) {
+			let [first, second] = pair else { unreachable!() };
+			if first.addr as usize + first.data.len() > second.addr as usize {
+				return Err(Error::OverlappingSections {
+					first:  first.name.clone(),
+					second: second.name.clone(),
+					addr:   second.addr,
+				});
+			}
+		}
+
+		// Images loaded past the configured memory size get a memory starting
+		// at their lowest section, instead of one covering every address below
+		let start = sections.first().map_or(0, |s| s.addr as u64);
+		let base = match start >= options.memory_size as u64 {
+			true => start & !15,
+			false => 0,
+		};
+
+		let end = sections.iter().map(|s| s.addr as u64 + s.data.len() as u64).max().unwrap_or(0);
+		let memory_size = match end - base > options.memory_size as u64 {
+			// Keep the stack pointer 16 byte aligned
+			true => (end - base + STACK_SIZE as u64 + 15) & !15,
+			false => options.memory_size as u64,
+		};
+
+		// The stack pointer starts at the end of memory
+		let memory_end = base + memory_size;
+		if memory_end > u32::MAX as u64 {
+			return Err(Error::AddressSpaceExceeded { end: memory_end });
+		}
+
+		let mut memory =
+			Memory::new(memory_size as usize).with_base(base as u32).with_endian(image.endian);
+		for section in sections {
+			info!("Loading section {} at {:#010x}", section.name, section.addr);
+			match section.flags.uninit {
+				true => memory.write(section.addr, &vec![0; section.data.len()])?,
+				false => memory.write(section.addr, &section.data)?,
+			}
+		}
+
+		let entry = match &options.entry_symbol {
+			Some(name) => {
+				let symbol = image.symbols.iter().find(|symbol| &symbol.name == name);
+				symbol.ok_or_else(|| Error::UnknownSymbol(name.clone()))?.addr
+			},
+			None => options.entry.unwrap_or(image.entry),
+		};
+
+		let mut cpu =
+			Cpu::new(entry).with_uninit_trap(options.trap_uninit).with_profiling(options.profile);
+		cpu.set_reg(STACK_POINTER_REG, memory_end as u32);
+		for &(idx, value) in &options.set_regs {
+			cpu.set_reg(idx, value);
+		}
+
+		let source_map =
+			image.source_map.iter().map(|location| (location.addr, location.clone())).collect();
+
+		Ok(Self {
+			cpu,
+			memory,
+			max_steps: options.max_steps,
+			trace: options.trace,
+			source_map,
+			watch_regs: options.watch_regs.clone(),
+			watch_mem: options.watch_mem.clone(),
+		})
+	}
+
+	/// Halt execution after any instruction that changes the watched
+	/// register, or writes to the watched address
+	pub fn watch(&mut self, target: WatchTarget) {
+		match target {
+			WatchTarget::Reg(idx) => self.watch_regs.push(idx),
+			WatchTarget::Mem(addr) => self.watch_mem.push(addr),
+		}
+	}
+
+	/// Get the source location of the instruction at the given address, if
+	/// the image was loaded with a source map
+	pub fn source_location(&self, addr: u32) -> Option<&SourceLocation> {
+		self.source_map.get(&addr)
+	}
+
+	/// Execute instructions until the program stops
+	///
+	/// Errors if the program does not stop within the maximum amount of
+	/// steps
+	pub fn run(&mut self) -> Result<ExitReason, Error> {
+		let mut steps = 0;
+
+		loop {
+			if let Some(max_steps) = self.max_steps && steps >= max_steps {
+				return Err(Error::StepLimitReached { steps });
+			}
+
+			if self.trace {
+				self.trace_instruction();
+			}
+
+			if let Some(reason) = self.step()? {
+				return Ok(reason);
+			}
+
+			steps += 1;
+		}
+	}
+
+	/// Execute a single instruction
+	///
+	/// Returns [`ExitReason::Watchpoint`] if the instruction changed a
+	/// watched register or wrote to a watched address
+	fn step(&mut self) -> Result<Option<ExitReason>, Error> {
+		if self.watch_regs.is_empty() && self.watch_mem.is_empty() {
+			return self.cpu.step(&mut self.memory);
+		}
+
+		let pc = self.cpu.pc;
+		let inst = self.memory.fetch(pc).and_then(|word| decode(pc, word)).ok();
+		let regs: Vec<u32> = self.watch_regs.iter().map(|&idx| self.cpu.reg(idx)).collect();
+		let store = inst.and_then(|inst| self.watched_store(inst));
+
+		if let Some(reason) = self.cpu.step(&mut self.memory)? {
+			return Ok(Some(reason));
+		}
+
+		// Unwrap is safe as the instruction was executed, so it was decoded
+		let inst = inst.unwrap();
+		let watchpoint = |target, old, new| {
+			Ok(Some(ExitReason::Watchpoint(Watchpoint { target, old, new, pc, inst })))
+		};
+
+		for (&idx, &old) in self.watch_regs.iter().zip(&regs) {
+			let new = self.cpu.reg(idx);
+			if new != old {
+				return watchpoint(WatchTarget::Reg(idx), old, new);
+			}
+		}
+
+		if let Some((watched, addr, len, old)) = store {
+			let new = self.read_value(addr, len).unwrap_or(old);
+			return watchpoint(WatchTarget::Mem(watched), old, new);
+		}
+
+		Ok(None)
+	}
+
+	/// Get the watched address a store instruction is about to write to, if
+	/// any, along with the address, length, and current value of the bytes it
+	/// writes
+	fn watched_store(&self, inst: Instruction) -> Option<(u32, u32, u32, u32)> {
+		let Instruction::Store { op, rs1, offset, .. } = inst else { return None };
+
+		let addr = self.cpu.reg(rs1).wrapping_add(offset as u32);
+		let len = match op {
+			StoreInstruction::Sb => 1,
+			StoreInstruction::Sh => 2,
+			StoreInstruction::Sw => 4,
+		};
+
+		let watched = *self.watch_mem.iter().find(|&&watched| watched.wrapping_sub(addr) < len)?;
+		Some((watched, addr, len, self.read_value(addr, len)?))
+	}
+
+	/// Read up to 4 bytes as a value in the byte order of the memory
+	fn read_value(&self, addr: u32, len: u32) -> Option<u32> {
+		let bytes = self.memory.read(addr, len).ok()?;
+		Some(self.memory.endian().value(bytes))
+	}
+
+	/// Print the instruction that is about to be executed to stdout
+	///
+	/// If the image has a source map, the source location of the instruction
+	/// is printed as well, or `<no source>` if it has none
+	///
+	/// Instructions that can not be fetched or decoded are not printed, the
+	/// error is reported when executing them instead
+	fn trace_instruction(&self) {
+		let pc = self.cpu.pc;
+
+		if let Ok(word) = self.memory.fetch(pc) && let Ok(inst) = decode(pc, word) {
+			let disassembly = format!("{:#010x}: {:08x}  {}", pc, word, inst);
+
+			if self.source_map.is_empty() {
+				println!("{}", disassembly);
+			} else {
+				let location = match self.source_location(pc) {
+					Some(location) => location.to_string(),
+					None => "<no source>".to_string(),
+				};
+
+				println!("{}  {}", disassembly, location);
+			}
+		}
+	}
+}
 
 /// Simulate the execution of a binary file located at the given input path
 ///
-/// See the [module level documentation](self) for more info
-pub fn simulate(_input_path: &Path) -> Result<(), Error> { Ok(()) }
+/// Returns the state of the machine once the program stops, along with the
+/// reason it stopped
+///
+/// See [`simulate_bytes`] and the [module level documentation](self) for
+/// more info
+pub fn simulate(input_path: &Path, options: &SimOptions) -> Result<(Machine, ExitReason), Error> {
+	let bytes = std::fs::read(input_path)?;
+
+	simulate_bytes(&bytes, options)
+}
+
+/// Simulate the execution of the bytes of a wpibin file
+///
+/// Returns the state of the machine once the program stops, along with the
+/// reason it stopped
+///
+/// Images that do not contain any code or data are refused with an
+/// [`Error::EmptyImage`], as there would be nothing to execute
+pub fn simulate_bytes(bytes: &[u8], options: &SimOptions) -> Result<(Machine, ExitReason), Error> {
+	let image = Image::from_bytes(bytes)?;
+	if image.sections.iter().all(|s| s.data.is_empty()) {
+		return Err(Error::EmptyImage);
+	}
+
+	let mut machine = Machine::load(&image, options)?;
+	let reason = machine.run()?;
+	info!("Program stopped: {:?}", reason);
+
+	Ok((machine, reason))
+}
@@ -0,0 +1,153 @@
+//! # Memory
+//!
+//! A flat, byte-addressable memory space
+//!
+//! The memory starts at address 0 unless it is created with another
+//! [base address](Memory::with_base), accesses below the base address are
+//! out of bounds
+//!
+//! Halves and words are little-endian unless the memory is created with
+//! another [byte order](Memory::with_endian), instructions are always
+//! fetched as little-endian words
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use asm::wpibin::Endian;
+
+use crate::error::Error;
+
+/// The amount of bytes shown on a single line of a [hexdump](Memory::hexdump)
+const HEXDUMP_WIDTH: usize = 16;
+
+/// The simulated memory space
+///
+/// All accesses are bounds checked, reading or writing outside of the memory
+/// produces an [`Error::OutOfBounds`]
+#[derive(Clone, Debug)]
+pub struct Memory {
+	bytes:  Vec<u8>,
+	/// The address of the first byte
+	base:   u32,
+	endian: Endian,
+}
+
+impl Memory {
+	/// Create a new zero-initialised memory of `size` bytes
+	pub fn new(size: usize) -> Self {
+		Self { bytes: vec![0; size], base: 0, endian: Endian::default() }
+	}
+
+	/// Start the memory at the given address instead of at 0
+	pub fn with_base(mut self, base: u32) -> Self {
+		self.base = base;
+		self
+	}
+
+	/// Store halves and words in the given byte order
+	pub fn with_endian(mut self, endian: Endian) -> Self {
+		self.endian = endian;
+		self
+	}
+
+	/// The byte order halves and words are stored in
+	pub fn endian(&self) -> Endian { self.endian }
+
+	/// The address of the first byte of this memory
+	pub fn base(&self) -> u32 { self.base }
+
+	/// The size of this memory in bytes
+	pub fn size(&self) -> usize { self.bytes.len() }
+
+	/// Get the range of bytes `addr..addr + len`
+	pub fn read(&self, addr: u32, len: u32) -> Result<&[u8], Error> {
+		self.offsets(addr, len as usize)
+			.and_then(|range| self.bytes.get(range))
+			.ok_or(Error::OutOfBounds { addr, len })
+	}
+
+	/// Overwrite the bytes starting at `addr` with the given data
+	pub fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Error> {
+		self.offsets(addr, data.len())
+			.and_then(|range| self.bytes.get_mut(range))
+			.ok_or(Error::OutOfBounds { addr, len: data.len() as u32 })?
+			.copy_from_slice(data);
+
+		Ok(())
+	}
+
+	/// Get the offsets into the bytes of this memory of the range
+	/// `addr..addr + len`, or [`None`] if it starts below the base address
+	fn offsets(&self, addr: u32, len: usize) -> Option<Range<usize>> {
+		let start = addr.checked_sub(self.base)? as usize;
+		Some(start..start + len)
+	}
+
+	/// Read a single byte
+	pub fn read_u8(&self, addr: u32) -> Result<u8, Error> { Ok(self.read(addr, 1)?[0]) }
+
+	/// Read a half
+	pub fn read_u16(&self, addr: u32) -> Result<u16, Error> {
+		Ok(self.endian.value(self.read(addr, 2)?) as u16)
+	}
+
+	/// Read a word
+	pub fn read_u32(&self, addr: u32) -> Result<u32, Error> {
+		Ok(self.endian.value(self.read(addr, 4)?))
+	}
+
+	/// Read the little-endian word of an instruction, regardless of the byte
+	/// order of the memory
+	pub fn fetch(&self, addr: u32) -> Result<u32, Error> {
+		Ok(Endian::Little.value(self.read(addr, 4)?))
+	}
+
+	/// Write a single byte
+	pub fn write_u8(&mut self, addr: u32, value: u8) -> Result<(), Error> {
+		self.write(addr, &[value])
+	}
+
+	/// Write a half
+	pub fn write_u16(&mut self, addr: u32, value: u16) -> Result<(), Error> {
+		self.write(addr, &self.endian.bytes(value as u32, 2))
+	}
+
+	/// Write a word
+	pub fn write_u32(&mut self, addr: u32, value: u32) -> Result<(), Error> {
+		self.write(addr, &self.endian.bytes(value, 4))
+	}
+
+	/// Format the range of bytes `addr..addr + len` as a hexdump
+	///
+	/// Every line contains the address of its first byte, the bytes
+	/// themselves in hexadecimal, and their printable ASCII representation
+	///
+	/// ```text
+	/// 00000100  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 00 00 00  |Hello, world!...|
+	/// ```
+	pub fn hexdump(&self, addr: u32, len: u32) -> Result<String, Error> {
+		let bytes = self.read(addr, len)?;
+		let mut dump = String::new();
+
+		for (i, chunk) in bytes.chunks(HEXDUMP_WIDTH).enumerate() {
+			let offset = addr as usize + i * HEXDUMP_WIDTH;
+
+			let mut hex = String::new();
+			for (j, byte) in chunk.iter().enumerate() {
+				if j == HEXDUMP_WIDTH / 2 {
+					hex.push(' ');
+				}
+				write!(hex, "{:02x} ", byte).unwrap();
+			}
+
+			let ascii: String = chunk
+				.iter()
+				.map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+				.collect();
+
+			writeln!(dump, "{:08x}  {:<49} |{}|", offset, hex, ascii).unwrap();
+		}
+
+		Ok(dump)
+	}
+}
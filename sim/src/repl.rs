@@ -0,0 +1,148 @@
+//! # REPL
+//!
+//! An interactive mode that assembles and executes one line of source code
+//! at a time against a persistent [`Machine`]
+//!
+//! Every line is assembled at the current program counter, written to memory,
+//! and executed straight away. Only instructions can be entered, labels,
+//! directives, and macros are not supported
+//!
+//! Lines starting with a `.` are meta-commands:
+//!  - `.regs`: show the value of every register
+//!  - `.reset`: clear all registers, memory, and watchpoints
+//!  - `.watch TARGET`: stop executing a line once it changes the register or writes to the address
+//!    `TARGET`, and show the change
+
+use asm::codegen::Generator;
+use asm::fold::Folder;
+use asm::lex::{keyword, parse_number, Lexer, TokenType};
+use asm::parse::ast::Statement;
+use asm::parse::Parser;
+use asm::wpibin::Image;
+
+use crate::cpu::{Cpu, ExitReason, WatchTarget};
+use crate::error::Error;
+use crate::{Machine, SimOptions};
+
+/// The name used for the source file in diagnostics
+const REPL_SOURCE_FILE: &str = "<repl>";
+
+/// The state of a REPL session
+#[derive(Clone, Debug)]
+pub struct Repl {
+	/// The machine executing all entered instructions
+	pub machine: Machine,
+}
+
+impl Default for Repl {
+	fn default() -> Self { Self::new() }
+}
+
+impl Repl {
+	/// Create a new REPL session with cleared registers and memory
+	pub fn new() -> Self {
+		// Unwrap is safe as an empty image always fits in memory
+		Self { machine: Machine::load(&Image::default(), &SimOptions::default()).unwrap() }
+	}
+
+	/// Evaluate a single line of input and return the text to show for it
+	///
+	/// After executing an instruction, every register whose value changed is
+	/// shown. Lines that fail to assemble leave the state of the machine
+	/// unchanged
+	pub fn eval(&mut self, line: &str) -> Result<String, Error> {
+		let line = line.trim();
+
+		match line {
+			"" => Ok(String::new()),
+			".regs" => Ok(self.machine.cpu.register_dump()),
+			".reset" => {
+				*self = Self::new();
+				Ok(String::new())
+			},
+			cmd if cmd.split_whitespace().next() == Some(".watch") => {
+				let target = cmd[".watch".len()..].trim();
+				self.machine.watch(parse_watch_target(target)?);
+				Ok(String::new())
+			},
+			cmd if cmd.starts_with('.') => Err(Error::UnknownCommand(cmd.to_string())),
+			_ => {
+				let code = self.assemble(line)?;
+				self.execute(&code)
+			},
+		}
+	}
+
+	/// Assemble a single instruction at the current program counter
+	fn assemble(&self, line: &str) -> Result<Vec<u8>, Error> {
+		let source = format!("#SECTION .text\n{}\n", line);
+		let pc = self.machine.cpu.pc;
+
+		let lexer = Lexer::new(REPL_SOURCE_FILE, &source);
+		let mut parser = Parser::from_stream(REPL_SOURCE_FILE, lexer);
+		let mut root = parser.parse()?;
+
+		let statements =
+			root.sections.iter().flat_map(|s| &s.lines).filter_map(|l| l.statement.as_ref());
+		for statement in statements {
+			if !matches!(statement, Statement::Instruction(_)) {
+				return Err(Error::NotAnInstruction(line.to_string()));
+			}
+		}
+
+		Folder::new(REPL_SOURCE_FILE).with_base_addr(pc).fold(&mut root)?;
+		let image = Generator::new(REPL_SOURCE_FILE).with_base_addr(pc).generate(&root)?;
+
+		Ok(image.sections.into_iter().flat_map(|s| s.data).collect())
+	}
+
+	/// Write assembled instructions at the current program counter and
+	/// execute them
+	fn execute(&mut self, code: &[u8]) -> Result<String, Error> {
+		let before = self.machine.cpu.clone();
+		self.machine.memory.write(self.machine.cpu.pc, code)?;
+
+		let mut reason = None;
+		for _ in 0..code.len() / 4 {
+			reason = self.machine.step()?;
+			if reason.is_some() {
+				break;
+			}
+		}
+
+		let mut output = changed_registers(&before, &self.machine.cpu);
+		match reason {
+			Some(ExitReason::Exit(status)) => {
+				output.push_str(&format!("program exited with status {}\n", status))
+			},
+			Some(ExitReason::Break) => output.push_str("program stopped at an ebreak\n"),
+			Some(ExitReason::Watchpoint(watchpoint)) => {
+				output.push_str(&format!("{}\n", watchpoint))
+			},
+			None => (),
+		}
+
+		Ok(output)
+	}
+}
+
+/// Parse the target of a `.watch` command, either a register name or a
+/// memory address
+fn parse_watch_target(target: &str) -> Result<WatchTarget, Error> {
+	if let Some(TokenType::Reg(reg)) = keyword(target) {
+		return Ok(WatchTarget::Reg(reg.index() as usize));
+	}
+
+	match parse_number(target) {
+		Some(addr) => Ok(WatchTarget::Mem(addr as u32)),
+		None => Err(Error::InvalidWatchTarget(target.to_string())),
+	}
+}
+
+/// Format every register whose value differs between two CPU states
+fn changed_registers(before: &Cpu, after: &Cpu) -> String {
+	(0..32)
+		.filter(|&idx| before.reg(idx) != after.reg(idx))
+		.map(|idx| after.register_line(idx))
+		.collect()
+}
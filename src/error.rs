@@ -4,6 +4,7 @@ use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub(super) enum Error {
+	Io(std::io::Error),
 	WrongFileType { found: String, expected: String },
 	Assembler(super::AssemblerError),
 	Simulator(super::SimulatorError),
@@ -12,6 +13,7 @@ pub(super) enum Error {
 impl Display for Error {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		match self {
+			Self::Io(err) => write!(f, "{}", err),
 			Self::WrongFileType { found, expected } => {
 				write!(
 					f,
@@ -25,6 +27,22 @@ impl Display for Error {
 	}
 }
 
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(err) => Some(err),
+			// Wrapped errors are displayed as is, so only expose what they wrap
+			Self::Assembler(err) => std::error::Error::source(err),
+			Self::Simulator(err) => std::error::Error::source(err),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(value: std::io::Error) -> Self { Self::Io(value) }
+}
+
 impl From<super::AssemblerError> for Error {
 	fn from(value: super::AssemblerError) -> Self { Self::Assembler(value) }
 }
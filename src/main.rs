@@ -11,22 +11,58 @@
 //!
 //! Custom assembler and emulator for the
 //! [RISC-V](https://en.wikipedia.org/wiki/RISC-V) CPU architecture
+//!
+//! ## Exit codes
+//!
+//! When simulating a program the process exits with a status describing how
+//! the program stopped:
+//!  - `ecall` exit: the exit status in `a0`, masked to `0..=255`
+//!  - `ebreak`: 0
+//!  - Illegal instruction: 132
+//!  - Misaligned access: 135
+//!  - Out of bounds memory access: 139
+//!  - Unknown `ecall`: 159
+//!
+//! Traps use `128 + n`, where `n` is the number of the signal a native
+//! process would receive. Any other error exits with status 1
 
 #![warn(missing_docs)]
 
+use std::io::{IsTerminal, Write};
 use std::path::PathBuf;
 
 use ansi_term::Colour::{Blue, Red, Yellow};
 use asm::error::Error as AssemblerError;
+use asm::fold::SymbolBinding;
+use asm::lex::{keyword, Isa, TokenType};
+use asm::wpibin::Endian;
+use asm::AsmOptions;
 use clap::{Arg, ArgAction, ArgMatches, Command};
-use log::Level;
+use log::{warn, Level};
+use sim::cpu::ExitReason;
 use sim::error::Error as SimulatorError;
+use sim::repl::Repl;
+use sim::SimOptions;
 
 mod error;
 
 use error::Error;
 
-fn run(matches: &ArgMatches) -> Result<(), Error> {
+/// Exit status for programs stopped by an `ebreak`
+const EXIT_BREAK: i32 = 0;
+/// Exit status for errors that are not simulator traps
+const EXIT_ERROR: i32 = 1;
+/// Exit status for illegal instructions (`SIGILL`)
+const EXIT_ILLEGAL_INSTRUCTION: i32 = 128 + 4;
+/// Exit status for misaligned accesses (`SIGBUS`)
+const EXIT_MISALIGNED_ACCESS: i32 = 128 + 7;
+/// Exit status for out of bounds memory accesses (`SIGSEGV`)
+const EXIT_OUT_OF_BOUNDS: i32 = 128 + 11;
+/// Exit status for unknown `ecall`s (`SIGSYS`)
+const EXIT_UNKNOWN_ECALL: i32 = 128 + 31;
+
+/// Run the selected subcommand and return the status code to exit with
+fn run(matches: &ArgMatches) -> Result<i32, Error> {
 	if let Some(m) = matches.subcommand_matches("asm") {
 		let input_path = m.get_one::<String>("file").map(PathBuf::from).unwrap();
 		let output_path_raw = m.get_one::<String>("output_file").map(PathBuf::from);
@@ -39,7 +75,47 @@ fn run(matches: &ArgMatches) -> Result<(), Error> {
 			},
 		};
 
-		asm::assemble(&input_path, &output_path)?;
+		let options = AsmOptions {
+			source_map: m.get_flag("source_map"),
+			map: m.get_one::<String>("map").map(PathBuf::from),
+			dump_encoding: m.get_flag("dump_encoding"),
+			keep_going: m.get_flag("keep_going"),
+			..asm_options(m)
+		};
+
+		asm::assemble(&input_path, &output_path, &options)?;
+	} else if let Some(m) = matches.subcommand_matches("check") {
+		let input_path = m.get_one::<String>("file").map(PathBuf::from).unwrap();
+
+		asm::check(&input_path, &asm_options(m))?;
+	} else if let Some(m) = matches.subcommand_matches("fmt") {
+		let input_path = m.get_one::<String>("file").map(PathBuf::from).unwrap();
+
+		let check = m.get_flag("check");
+		if !asm::format_file(&input_path, check)? && check {
+			eprintln!("{} is not formatted", input_path.display());
+			return Ok(EXIT_ERROR);
+		}
+	} else if let Some(m) = matches.subcommand_matches("symbols") {
+		let input_path = m.get_one::<String>("file").map(PathBuf::from).unwrap();
+
+		let table = asm::symbols(&input_path, &asm_options(m))?;
+		// External symbols have no address, like undefined symbols in `nm`
+		for name in &table.externs {
+			println!("{:8} U {}", "", name);
+		}
+		for symbol in &table.symbols {
+			println!(
+				"{:08x} {} {}",
+				symbol.addr,
+				symbol_type(&symbol.section, symbol.binding),
+				symbol.name
+			);
+		}
+	} else if let Some(m) = matches.subcommand_matches("dump-ast") {
+		let input_path = m.get_one::<String>("file").map(PathBuf::from).unwrap();
+
+		print!("{}", asm::dump_ast(&input_path, m.get_flag("dot"))?);
 	} else if let Some(m) = matches.subcommand_matches("sim") {
 		let input_path = m.get_one::<String>("file").map(PathBuf::from).unwrap();
 		let ext = input_path.extension().map_or("", |ext| ext.to_str().unwrap());
@@ -50,10 +126,262 @@ fn run(matches: &ArgMatches) -> Result<(), Error> {
 			});
 		}
 
-		sim::simulate(&input_path)?;
+		let options = SimOptions {
+			entry_symbol: m.get_one::<String>("entry").cloned(),
+			trap_uninit: m.get_flag("trap_uninit"),
+			trace: m.get_flag("trace"),
+			profile: m.get_flag("profile"),
+			watch_regs: m.get_many::<usize>("watch_reg").into_iter().flatten().copied().collect(),
+			watch_mem: m.get_many::<u32>("watch_mem").into_iter().flatten().copied().collect(),
+			set_regs: m
+				.get_many::<(usize, u32)>("set_reg")
+				.into_iter()
+				.flatten()
+				.copied()
+				.collect(),
+			..Default::default()
+		};
+
+		let (machine, reason) = sim::simulate(&input_path, &options)?;
+
+		if m.get_flag("dump_regs") {
+			eprint!("{}", machine.cpu.register_dump());
+		}
+
+		if m.get_flag("print_cycles") {
+			eprintln!("cycles: {}", machine.cpu.cycles());
+		}
+
+		if let Some(histogram) = machine.cpu.profile_histogram() {
+			eprint!("{}", histogram);
+		}
+
+		for &(addr, len) in m.get_many::<(u32, u32)>("dump_mem").into_iter().flatten() {
+			match machine.memory.hexdump(addr, len) {
+				Ok(dump) => print!("{}", dump),
+				Err(err) => warn!("skipping memory dump of {:#x}:{:#x}: {}", addr, len, err),
+			}
+		}
+
+		return Ok(match reason {
+			ExitReason::Exit(status) => status & 0xFF,
+			ExitReason::Break => EXIT_BREAK,
+			ExitReason::Watchpoint(watchpoint) => {
+				eprintln!("{}", watchpoint);
+				EXIT_BREAK
+			},
+		});
+	} else if matches.subcommand_matches("repl").is_some() {
+		repl()?;
 	}
 
-	Ok(())
+	Ok(0)
+}
+
+/// Read lines from stdin and evaluate them in a [`Repl`] until stdin is closed
+///
+/// A prompt is only shown when stdin is a terminal
+fn repl() -> Result<(), Error> {
+	let stdin = std::io::stdin();
+	let interactive = stdin.is_terminal();
+	let mut repl = Repl::new();
+
+	loop {
+		if interactive {
+			print!("> ");
+			std::io::stdout().flush()?;
+		}
+
+		let mut line = String::new();
+		if stdin.read_line(&mut line)? == 0 {
+			return Ok(());
+		}
+
+		match repl.eval(&line) {
+			Ok(output) => print!("{}", output),
+			Err(err) => eprintln!("{}", err),
+		}
+	}
+}
+
+/// Get the `nm` style type letter of a symbol declared in the given section
+///
+/// Local symbols use lowercase letters and global symbols uppercase ones,
+/// weak symbols are always `W`
+fn symbol_type(section: &str, binding: SymbolBinding) -> char {
+	let letter = match section.to_ascii_lowercase().as_str() {
+		".text" => 't',
+		".data" => 'd',
+		".bss" => 'b',
+		_ => '?',
+	};
+
+	match binding {
+		SymbolBinding::Local => letter,
+		SymbolBinding::Global => letter.to_ascii_uppercase(),
+		SymbolBinding::Weak => 'W',
+	}
+}
+
+/// Get the status code to exit with after an error
+fn error_exit_code(err: &Error) -> i32 {
+	match err {
+		Error::Simulator(SimulatorError::IllegalInstruction { .. }) => EXIT_ILLEGAL_INSTRUCTION,
+		Error::Simulator(SimulatorError::MisalignedAccess { .. }) => EXIT_MISALIGNED_ACCESS,
+		Error::Simulator(SimulatorError::OutOfBounds { .. }) => EXIT_OUT_OF_BOUNDS,
+		Error::Simulator(SimulatorError::UnknownEcall { .. }) => EXIT_UNKNOWN_ECALL,
+		_ => EXIT_ERROR,
+	}
+}
+
+/// Parse a decimal, `0x` prefixed hexadecimal, or `0b` prefixed binary
+/// number
+fn parse_u32(s: &str) -> Result<u32, String> {
+	let parsed = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+		u32::from_str_radix(hex, 16)
+	} else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+		u32::from_str_radix(bin, 2)
+	} else {
+		s.parse()
+	};
+
+	parsed.map_err(|err| format!("invalid number '{}': {}", s, err))
+}
+
+/// Parse a register name, either its number (eg. `r10`) or its ABI name (eg.
+/// `a0`), into its index
+///
+/// Register names are case insensitive, like in assembly source
+fn parse_register(s: &str) -> Result<usize, String> {
+	match keyword(s) {
+		Some(TokenType::Reg(reg)) => Ok(reg.index() as usize),
+		_ => Err(format!("invalid register '{}'", s)),
+	}
+}
+
+/// Parse a `REG=VALUE` register assignment
+///
+/// Negative values are stored in two's complement
+fn parse_set_reg(s: &str) -> Result<(usize, u32), String> {
+	let (reg, raw) =
+		s.split_once('=').ok_or_else(|| format!("expected REG=VALUE, found '{}'", s))?;
+
+	let value = match raw.strip_prefix('-') {
+		Some(abs) => {
+			match parse_u32(abs)? {
+				abs if abs <= 1 << 31 => abs.wrapping_neg(),
+				_ => return Err(format!("invalid number '{}': out of range", raw)),
+			}
+		},
+		None => parse_u32(raw)?,
+	};
+
+	Ok((parse_register(reg)?, value))
+}
+
+/// Parse a word aligned base address
+fn parse_base_addr(s: &str) -> Result<u32, String> {
+	let addr = parse_u32(s)?;
+
+	match addr % 4 {
+		0 => Ok(addr),
+		_ => Err(format!("base address {:#x} is not aligned to a word boundary", addr)),
+	}
+}
+
+/// Parse a `NAME=VALUE` constant definition
+///
+/// Values use the same syntax as number literals in source code, and may be
+/// negative
+fn parse_define(s: &str) -> Result<(String, i32), String> {
+	let (name, raw) =
+		s.split_once('=').ok_or_else(|| format!("expected NAME=VALUE, found '{}'", s))?;
+	if name.is_empty() {
+		return Err(format!("missing constant name in '{}'", s));
+	}
+
+	let value = match raw.strip_prefix('-') {
+		Some(abs) => asm::lex::parse_number(abs).and_then(i32::checked_neg),
+		None => asm::lex::parse_number(raw),
+	};
+
+	match value {
+		Some(value) => Ok((name.to_string(), value)),
+		None => Err(format!("invalid number '{}'", raw)),
+	}
+}
+
+/// Parse an `ADDR:LEN` memory region
+fn parse_mem_region(s: &str) -> Result<(u32, u32), String> {
+	let (addr, len) =
+		s.split_once(':').ok_or_else(|| format!("expected ADDR:LEN, found '{}'", s))?;
+
+	Ok((parse_u32(addr)?, parse_u32(len)?))
+}
+
+/// The arguments shared by every subcommand that assembles a file, see
+/// [`asm_options`]
+fn asm_args() -> [Arg; 7] {
+	[
+		Arg::new("base_addr")
+			.help("The address the binary will be loaded at")
+			.long("base-addr")
+			.value_name("ADDR")
+			.value_parser(parse_base_addr)
+			.default_value("0x0"),
+		Arg::new("define")
+			.help("Declare a constant, a #CONST with the same name overrides it")
+			.short('D')
+			.long("define")
+			.value_name("NAME=VALUE")
+			.value_parser(parse_define)
+			.action(ArgAction::Append),
+		Arg::new("allow")
+			.help("Do not report the given warning")
+			.short('A')
+			.long("allow")
+			.value_name("WARNING")
+			.value_parser(["unreachable-code", "undefined-weak-symbol", "dead-write"])
+			.action(ArgAction::Append),
+		Arg::new("warnings_as_errors")
+			.help("Fail if any warning is reported")
+			.long("warnings-as-errors")
+			.action(ArgAction::SetTrue),
+		Arg::new("warn")
+			.help("Set to `error` to fail if any warning is reported")
+			.short('W')
+			.value_name("LEVEL")
+			.value_parser(["error"]),
+		Arg::new("isa")
+			.help("The instruction set to accept, eg. rv32i, rv32im, or rv32im_zicsr")
+			.long("isa")
+			.value_name("ISA")
+			.value_parser(|s: &str| s.parse::<Isa>())
+			.default_value("rv32im_zicsr"),
+		Arg::new("target_endian")
+			.help(
+				"The byte order of halves and words in data directives, instructions are always \
+				 little-endian",
+			)
+			.long("target-endian")
+			.value_name("ENDIAN")
+			.value_parser(|s: &str| s.parse::<Endian>())
+			.default_value("little"),
+	]
+}
+
+/// Get the [`AsmOptions`] set by the arguments of [`asm_args`]
+fn asm_options(m: &ArgMatches) -> AsmOptions {
+	AsmOptions {
+		base_addr: *m.get_one::<u32>("base_addr").unwrap(),
+		defines: m.get_many::<(String, i32)>("define").into_iter().flatten().cloned().collect(),
+		allowed: m.get_many::<String>("allow").into_iter().flatten().cloned().collect(),
+		isa: *m.get_one::<Isa>("isa").unwrap(),
+		warnings_as_errors: m.get_flag("warnings_as_errors")
+			|| m.get_one::<String>("warn").is_some_and(|level| level == "error"),
+		endian: *m.get_one::<Endian>("target_endian").unwrap(),
+		..Default::default()
+	}
 }
 
 fn main() {
@@ -73,6 +401,7 @@ fn main() {
 			Command::new("asm")
 				.about("Assemble a file into a binary")
 				.arg_required_else_help(true)
+				.args(asm_args())
 				.arg(
 					Arg::new("output_file")
 						.help("The file to write the binary to")
@@ -80,14 +409,162 @@ fn main() {
 						.long("output")
 						.action(ArgAction::Set),
 				)
+				.arg(
+					Arg::new("source_map")
+						.help("Include the source line of every instruction in the binary")
+						.long("source-map")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("dump_encoding")
+						.help("Print the encoding of every instruction, split into its fields")
+						.long("dump-encoding")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("keep_going")
+						.help(
+							"Report every error instead of stopping at the first one, and still \
+							 write the parts of the binary that assembled",
+						)
+						.long("keep-going")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("map")
+						.help("Write the size of every section and label to FILE")
+						.long("map")
+						.value_name("FILE"),
+				)
 				.arg(Arg::new("file").help("The file to assemble").index(1).required(true)),
 		)
+		.subcommand(
+			Command::new("check")
+				.about("Check that a file assembles without writing a binary")
+				.arg_required_else_help(true)
+				.args(asm_args())
+				.arg(Arg::new("file").help("The file to check").index(1).required(true)),
+		)
+		.subcommand(
+			Command::new("fmt")
+				.about("Rewrite a file in the canonical layout")
+				.arg_required_else_help(true)
+				.arg(
+					Arg::new("check")
+						.help("Fail if the file is not formatted instead of rewriting it")
+						.long("check")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(Arg::new("file").help("The file to format").index(1).required(true)),
+		)
+		.subcommand(
+			Command::new("symbols")
+				.about("List every label and external symbol in a file, nm style")
+				.arg_required_else_help(true)
+				.args(asm_args())
+				.arg(Arg::new("file").help("The file to list").index(1).required(true)),
+		)
+		.subcommand(
+			Command::new("dump-ast")
+				.about("Print the syntax tree of a file")
+				.arg_required_else_help(true)
+				.arg(
+					Arg::new("dot")
+						.help("Print the tree as a Graphviz DOT graph")
+						.long("dot")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(Arg::new("file").help("The file to parse").index(1).required(true)),
+		)
 		.subcommand(
 			Command::new("sim")
 				.about("Simulte the execution of a binary file")
 				.arg_required_else_help(true)
+				.arg(
+					Arg::new("entry")
+						.help("Start executing at the label SYMBOL instead of the entry point")
+						.long("entry")
+						.value_name("SYMBOL"),
+				)
+				.arg(
+					Arg::new("trap_uninit")
+						.help("Stop with an error when a register is read before it is written")
+						.long("trap-uninit")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("trace")
+						.help(
+							"Print every executed instruction, along with its source line if the \
+							 binary has a source map",
+						)
+						.long("trace")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("profile")
+						.help(
+							"Print how many times each instruction was executed to stderr once \
+							 the program stops",
+						)
+						.long("profile")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("dump_regs")
+						.help("Print the value of all registers to stderr once the program stops")
+						.long("dump-regs")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("print_cycles")
+						.help(
+							"Print the amount of executed instructions to stderr once the program \
+							 stops",
+						)
+						.long("print-cycles")
+						.action(ArgAction::SetTrue),
+				)
+				.arg(
+					Arg::new("dump_mem")
+						.help(
+							"Print a hexdump of LEN bytes of memory at ADDR once the program stops",
+						)
+						.long("dump-mem")
+						.value_name("ADDR:LEN")
+						.value_parser(parse_mem_region)
+						.action(ArgAction::Append),
+				)
+				.arg(
+					Arg::new("watch_reg")
+						.help("Stop as soon as an instruction changes the value of register REG")
+						.long("watch-reg")
+						.value_name("REG")
+						.value_parser(parse_register)
+						.action(ArgAction::Append),
+				)
+				.arg(
+					Arg::new("watch_mem")
+						.help("Stop as soon as an instruction writes to the byte at ADDR")
+						.long("watch-mem")
+						.value_name("ADDR")
+						.value_parser(parse_u32)
+						.action(ArgAction::Append),
+				)
+				.arg(
+					Arg::new("set_reg")
+						.help("Set register REG to VALUE before execution starts")
+						.long("set-reg")
+						.value_name("REG=VALUE")
+						.value_parser(parse_set_reg)
+						.action(ArgAction::Append),
+				)
 				.arg(Arg::new("file").help("The binary to simulate").index(1).required(true)),
 		)
+		.subcommand(
+			Command::new("repl")
+				.about("Assemble and execute instructions read from stdin one line at a time"),
+		)
 		.get_matches();
 
 	let verbosity = matches.get_count("verbosity");
@@ -117,10 +594,11 @@ fn main() {
 		});
 
 	match run(&matches) {
-		Ok(_) => (),
+		Ok(0) => (),
+		Ok(code) => std::process::exit(code),
 		Err(e) => {
 			eprintln!("{}", e);
-			std::process::exit(1);
+			std::process::exit(error_exit_code(&e));
 		},
 	}
 }
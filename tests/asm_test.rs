@@ -1,10 +1,74 @@
-use asm::error::Error;
-use asm::lex::{Lexer, Token};
-use asm::parse::{Node, Parser};
+use std::collections::{BTreeMap, HashSet};
+use std::process::Command;
+
+use asm::codegen::{describe_encoding, Generator};
+use asm::error::{
+	CodegenError,
+	Diagnostic,
+	Error,
+	FoldError,
+	LexError,
+	ParseError,
+	Severity,
+	Warning,
+};
+use asm::fold::{Folder, SymbolBinding};
+use asm::format::format;
+use asm::lex::{
+	read_cache,
+	DirToken,
+	Extension,
+	InstToken,
+	Isa,
+	Lexer,
+	Radix,
+	RegToken,
+	SourceChunk,
+	Token,
+	TokenType,
+};
+use asm::lint::Linter;
+use asm::parse::ast::{
+	Instruction as AstInstruction,
+	InvalidOrderingTarget,
+	OrderingTarget,
+	Statement,
+};
+use asm::parse::{Node, Parser, TrailingCommas, DEFAULT_MAX_DEPTH};
+use asm::wpibin::{Endian, Image, Relocation, RelocationKind};
+use asm::AsmOptions;
+use sim::decode::{decode, Instruction};
 
 mod common;
 use common::*;
 
+/// Lex, parse, and fold a string of source code
+fn fold(source: &str) -> Result<(), Error> {
+	let lexer = Lexer::new("test_file.asm", source);
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+
+	let mut parser = Parser::new("test_file.asm", &tokens);
+	let mut ast_root = parser.parse()?;
+
+	let mut folder = Folder::new("test_file.asm");
+	folder.fold(&mut ast_root)
+}
+
+/// Lex, parse, fold, and generate a string of source code
+fn generate(source: &str) -> Result<Image, Error> {
+	let lexer = Lexer::new("test_file.asm", source);
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+
+	let mut parser = Parser::new("test_file.asm", &tokens);
+	let mut ast_root = parser.parse()?;
+
+	let mut folder = Folder::new("test_file.asm");
+	folder.fold(&mut ast_root)?;
+
+	let generator = Generator::new("test_file.asm");
+	generator.generate(&ast_root)
+}
+
 #[test]
 fn lexer_test() -> Result<(), Error> {
 	let lexer = Lexer::new("test_file.asm", TEST_SOURCE_CODE);
@@ -31,3 +95,1983 @@ fn parser_test() -> Result<(), Error> {
 
 	Ok(())
 }
+
+#[test]
+fn ast_exports_as_dot() -> Result<(), Error> {
+	let source = "#SECTION .text\naddi r1, r0, 1\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let node = Node::from(&ast_root);
+	let dot = node.to_dot();
+
+	fn count(node: &Node) -> usize { 1 + node.children.iter().map(count).sum::<usize>() }
+	let nodes = count(&node);
+	let vertices: Vec<&str> = dot.lines().filter(|l| l.contains("[label=")).collect();
+	let edges = dot.lines().filter(|l| l.contains(" -> ")).count();
+
+	assert!(dot.starts_with("digraph ast {\n"));
+	assert!(dot.ends_with("}\n"));
+	assert_eq!(dot.matches("digraph").count(), 1);
+	assert_eq!(dot.matches('{').count(), 1);
+	assert_eq!(vertices.len(), nodes);
+	assert_eq!(edges, nodes - 1);
+	assert!(vertices.iter().any(|v| v.contains("label=\"(Dest) (Register) r1\"")));
+
+	let mut ids: Vec<&str> =
+		vertices.iter().map(|v| v.split_whitespace().next().unwrap()).collect();
+	ids.sort();
+	ids.dedup();
+	assert_eq!(ids.len(), nodes);
+
+	Ok(())
+}
+
+#[test]
+fn blank_lines_render_as_empty_everywhere() -> Result<(), Error> {
+	let source = "#SECTION .text\n\nblock {\n\n\tebreak\n}\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let section = &ast_root.sections[0];
+	let block = match &section.lines[1].statement {
+		Some(Statement::LabeledBlock(block)) => block,
+		stmt => panic!("expected a labeled block, found {:?}", stmt),
+	};
+	assert_eq!(section.lines[0].statement, None);
+	assert_eq!(block.lines[0].statement, None);
+
+	let section_line = Node::from(&section.lines[0]);
+	let block_line = Node::from(&block.lines[0]);
+	assert_eq!(section_line.repr, "Empty");
+	assert_eq!(block_line.repr, "Empty");
+	assert_eq!(section_line.to_string(), block_line.to_string());
+
+	Ok(())
+}
+
+#[test]
+fn leading_and_trailing_comments_keep_their_placement() -> Result<(), Error> {
+	let source = "#SECTION .text\n; leading\nebreak ; trailing\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let lines = &ast_root.sections[0].lines;
+	assert_eq!(lines.len(), 2);
+
+	assert_eq!(lines[0].statement, None);
+	assert!(lines[0].comment.is_some_and(|c| c.contains("leading")));
+
+	assert!(matches!(lines[1].statement, Some(Statement::Instruction(_))));
+	assert!(lines[1].comment.is_some_and(|c| c.contains("trailing")));
+
+	Ok(())
+}
+
+#[test]
+fn unclosed_braces_are_summarised() {
+	let source = "#SECTION .text\nouter {\n\tinner {\n\t\tebreak\n\tnested {\n\t\tebreak\n}\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>().unwrap();
+	let err = Parser::new("test_file.asm", &tokens).parse().unwrap_err();
+
+	assert!(matches!(
+		&err,
+		Error::Parse(ParseError::UnclosedDelimiters { delimiter, count: 2, .. }) if delimiter == "{"
+	));
+
+	let repr = strip_ansi(&err.to_string());
+	assert!(repr.contains("2 unclosed `{`"));
+	assert!(repr.contains("test_file.asm:2:7"));
+}
+
+#[test]
+fn instructions_iterate_in_program_order() -> Result<(), Error> {
+	let source = "#SECTION .text\naddi r1, r0, 1\nouter {\n\tinner {\n\t\taddi r2, r0, \
+	              2\n\t}\n\taddi r3, r0, 3\n}\n#SECTION .data\n#BYTES 1\n#SECTION .text\nebreak\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let found: Vec<_> = ast_root
+		.instructions()
+		.map(|(inst, start)| {
+			let dest = match inst {
+				AstInstruction::Addi { dest, .. } => Some(dest.index()),
+				_ => None,
+			};
+
+			(dest, start.line)
+		})
+		.collect();
+
+	assert_eq!(found, [(Some(1), 2), (Some(2), 5), (Some(3), 7), (None, 12)]);
+
+	Ok(())
+}
+
+#[test]
+fn ast_ignores_formatting() -> Result<(), Error> {
+	let reformatted = TEST_SOURCE_CODE.replace('\t', "    ").replace(", ", " ,  ");
+
+	let lexer = Lexer::new("test_file.asm", TEST_SOURCE_CODE);
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+	let mut parser = Parser::new("test_file.asm", &tokens);
+	let ast = parser.parse()?;
+
+	let lexer = Lexer::new("test_file.asm", &reformatted);
+	let reformatted_tokens: Vec<Token> =
+		lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+	let mut parser = Parser::new("test_file.asm", &reformatted_tokens);
+	let reformatted_ast = parser.parse()?;
+
+	assert_ast_eq(&ast, &reformatted_ast);
+
+	Ok(())
+}
+
+#[test]
+fn format_is_canonical_and_idempotent() -> Result<(), Error> {
+	let messy = format!(
+		"\n\n{}\n\n",
+		TEST_SOURCE_CODE
+			.replace('\t', "   ")
+			.replace(", ", " ,  ")
+			.replace("; Test", ";Test")
+			.replace("\n\n", "\n\n\n")
+			.replace("=>", "    =>")
+			.replace(" {\n", " {   \n")
+	);
+
+	let golden = "\
+; Test header comment
+;
+
+define_macro! movi {
+	($r:reg, $( $i:ident ),+) => {
+		addi    $r, r0, $( $i )++
+	}
+}
+
+#CONST large_immediate 0 ? (1_234 + 2 * 0o73) << (4 ^ 0x5) : (0b1011 & (8 % 5))
+
+#SECTION .text
+
+_start {
+	nested {
+		movi!(r1, 5, 6, 7)
+	}
+}
+";
+
+	let formatted = format("test_file.asm", &messy)?;
+	assert_eq!(formatted, golden);
+	assert_eq!(format("test_file.asm", &formatted)?, formatted);
+
+	Ok(())
+}
+
+#[test]
+fn streaming_parser_matches_slice_parser() -> Result<(), Error> {
+	let lexer = Lexer::new("test_file.asm", TEST_SOURCE_CODE);
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+	let mut parser = Parser::new("test_file.asm", &tokens);
+	let ast = parser.parse()?;
+
+	let lexer = Lexer::new("test_file.asm", TEST_SOURCE_CODE);
+	let mut parser = Parser::from_stream("test_file.asm", lexer);
+	let streamed_ast = parser.parse()?;
+
+	assert_ast_eq(&ast, &streamed_ast);
+
+	Ok(())
+}
+
+#[test]
+fn single_instructions_parse_from_str() {
+	let inst = AstInstruction::parse("addi r1, r0, 5").unwrap();
+	assert!(matches!(inst, AstInstruction::Addi { dest: RegToken::R1, src: RegToken::R0, .. }));
+	assert!(AstInstruction::parse("sw [r2 - 4], r5 ; store\n").is_ok());
+
+	assert!(matches!(
+		AstInstruction::parse("addi r1"),
+		Err(Error::Parse(ParseError::MissingOperands { .. }))
+	));
+	for source in ["addi r1, r0, 5\nebreak\n", "label {", "addi r1, r0, @"] {
+		assert!(AstInstruction::parse(source).is_err(), "{}", source);
+	}
+}
+
+#[test]
+fn partial_parse_recovers_from_errors() -> Result<(), Error> {
+	let source = "#SECTION .text\naddi r1, r0, 1 @\n#WORDS 1 2\nsub r2 r1, r1\nebreak\n";
+	let lexer = Lexer::new("test_file.asm", source).with_keep_going(true);
+
+	let (ast_root, errors) = Parser::from_stream("test_file.asm", lexer).parse_partial()?;
+
+	assert!(matches!(
+		errors[..],
+		[
+			Error::Lex(LexError::RawUnexpectedSymbol { line: 2, .. }),
+			Error::Parse(ParseError::UnexpectedToken { .. }),
+			Error::Parse(ParseError::UnexpectedToken { .. }),
+		]
+	));
+
+	// The failed directive is dropped, the failed instruction poisoned
+	let statements: Vec<&Statement> =
+		ast_root.sections[0].lines.iter().filter_map(|line| line.statement.as_ref()).collect();
+	assert!(matches!(
+		statements[..],
+		[
+			Statement::Instruction(AstInstruction::Addi { .. }),
+			Statement::Instruction(AstInstruction::Ebreak),
+			Statement::Instruction(AstInstruction::Ebreak),
+		]
+	));
+
+	Ok(())
+}
+
+#[test]
+fn streaming_parser_returns_lexer_errors() {
+	let lexer = Lexer::new("test_file.asm", "#SECTION .text\naddi r1, r0, @\n");
+	let mut parser = Parser::from_stream("test_file.asm", lexer);
+
+	let err = parser.parse().unwrap_err();
+
+	assert!(matches!(err, Error::Lex(LexError::RawUnexpectedSymbol { found: '@', .. })));
+}
+
+#[test]
+fn lone_equals_expects_equals_or_arrow() {
+	let lexer = Lexer::new("test_file.asm", "#IF 1 =a\n");
+	let err = lexer.into_iter().collect::<Result<Vec<Token>, Error>>().unwrap_err();
+
+	let Error::Lex(LexError::UnexpectedSymbol { found, expected, col, .. }) = &err else {
+		panic!("{:?}", err)
+	};
+	assert_eq!((*found, expected.as_slice(), *col), ('a', ['=', '>'].as_slice(), 8));
+	assert!(
+		strip_ansi(&err.to_string())
+			.contains("found unexpected symbol `'a'`, expected `'='` or `'>'`")
+	);
+
+	let lexer = Lexer::new("test_file.asm", "#IF 1 =");
+	let err = lexer.into_iter().collect::<Result<Vec<Token>, Error>>().unwrap_err();
+
+	assert!(matches!(err, Error::Lex(LexError::UnexpectedEof { col: 8, .. })));
+}
+
+#[test]
+fn lexer_error_on_last_line_without_newline() {
+	let source = "#CONST greeting \"hello";
+
+	let lexer = Lexer::new("test_file.asm", source);
+	let err = lexer.into_iter().collect::<Result<Vec<Token>, Error>>().unwrap_err();
+
+	assert!(matches!(err, Error::Lex(LexError::UnexpectedEof { .. })));
+	assert!(err.to_string().contains(source));
+}
+
+#[test]
+fn tokens_past_the_end_of_their_line_display() -> Result<(), Error> {
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", "ebreak\n").collect::<Result<_, _>>()?;
+	let newline = tokens.last().unwrap();
+	assert_eq!(newline.t, TokenType::SymNewline);
+	assert!(newline.to_string().starts_with("[001:007]"));
+
+	let eof = Token { col: 8, span: 1, source_line: "ebreak", ..*newline };
+	assert!(eof.to_string().contains("ebreak``````..."));
+	assert_eq!(eof.raw(), "");
+
+	let wide = Token { col: 2, span: 1, source_line: "\u{e9}", ..*newline };
+	assert!(wide.to_string().starts_with("[001:002]"));
+
+	Ok(())
+}
+
+#[test]
+fn every_instruction_encodes() -> Result<(), Error> {
+	let mut source = String::from("#SECTION .text\n");
+	for inst in InstToken::ALL {
+		let operands = match inst {
+			InstToken::Rri(_) => "r1, r2, 3",
+			InstToken::Rrr(_) | InstToken::Mdr(_) => "r1, r2, r3",
+			InstToken::Load(_) => "r1, [r2 + 4]",
+			InstToken::Store(_) => "[r2 - 4], r1",
+			InstToken::Lui | InstToken::Auipc => "r1, 0x12345",
+			InstToken::Branch(_) => "r1, r2, 8",
+			InstToken::Jal => "r1, -8",
+			InstToken::Jalr => "r1, r2, 4",
+			InstToken::Fence | InstToken::FenceTso => "RW, RW",
+			InstToken::Csr(_) => "r1, r2, 0x300",
+			InstToken::Csri(_) => "r1, 7, 0x300",
+			InstToken::Ecall | InstToken::Ebreak | InstToken::Fencei => "",
+		};
+		source.push_str(&format!("{} {}\n", inst, operands));
+	}
+
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", &source).collect::<Result<_, _>>()?;
+	let mut ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+	Folder::new("test_file.asm").fold(&mut ast_root)?;
+
+	let generator = Generator::new("test_file.asm");
+	let mut variants = HashSet::new();
+	for (inst, token) in ast_root.instructions() {
+		let word = generator.encode(inst).map_err(Error::from)?;
+		assert!(decode(0, word).is_ok(), "{} encodes to an invalid word {:#010x}", token, word);
+
+		variants.insert(std::mem::discriminant(inst));
+	}
+	assert_eq!(variants.len(), InstToken::ALL.len());
+
+	Ok(())
+}
+
+#[test]
+fn missing_operands_report_instruction_arity() {
+	let err = fold("#SECTION .text\nadd r1, r2\n").unwrap_err();
+	assert!(matches!(
+		&err,
+		Error::Parse(ParseError::MissingOperands { expected: 3, found: 2, .. })
+	));
+	let repr = strip_ansi(&err.to_string());
+	assert!(repr.contains("`add` expects 3 register operands, found 2"));
+	assert!(repr.contains("test_file.asm:2:1"));
+
+	let err = fold("#SECTION .text\naddi r1, r0 ; no immediate\n").unwrap_err();
+	assert!(strip_ansi(&err.to_string()).contains("`addi` expects 3 operands, found 2"));
+
+	let err = fold("#SECTION .text\nlw r1\n").unwrap_err();
+	assert!(strip_ansi(&err.to_string()).contains("`lw` expects 2 operands, found 1"));
+}
+
+#[test]
+fn csr_source_immediate_out_of_range() {
+	let err = fold("#SECTION .text\ncsrrwi r1, 32, 0x300\n").unwrap_err();
+
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::ImmediateOutOfRange { value: 32, min: 0, max: 31, .. })
+	));
+}
+
+#[test]
+fn csr_target_out_of_range() {
+	let err = fold("#SECTION .text\ncsrrw r1, r2, 0x1000\n").unwrap_err();
+
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::ImmediateOutOfRange { value: 0x1000, min: 0, max: 0xFFF, .. })
+	));
+	assert!(err.to_string().contains("test_file.asm:2:15"));
+}
+
+#[test]
+fn csr_immediates_in_range() -> Result<(), Error> {
+	fold("#SECTION .text\ncsrrwi r1, 31, 0xFFF\ncsrrs r1, r2, 0x300\n")
+}
+
+#[test]
+fn upper_immediates_out_of_range() {
+	let err = fold("#SECTION .text\nlui r1, 0x100000\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: 0x100000, .. })));
+
+	let err = fold("#SECTION .text\nlui r1, -1\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: -1, .. })));
+
+	let err = fold("#SECTION .text\nauipc r1, 0x80000\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: 0x80000, .. })));
+}
+
+#[test]
+fn alu_immediates_out_of_range() {
+	let err = fold("#SECTION .text\naddi r1, r0, 5000\n").unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::ImmediateOutOfRange { value: 5000, min: -2048, max: 2047, .. })
+	));
+	assert!(err.to_string().contains("test_file.asm:2:14"));
+
+	let err = fold("#SECTION .text\nandi r1, r1, -2049\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: -2049, .. })));
+}
+
+#[test]
+fn shift_amounts_out_of_range() {
+	let err = fold("#SECTION .text\nlsli r2, r1, 40\n").unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::ImmediateOutOfRange { value: 40, min: 0, max: 31, .. })
+	));
+
+	let err = fold("#SECTION .text\nasri r2, r1, -1\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: -1, .. })));
+}
+
+#[test]
+fn alu_immediates_in_range() -> Result<(), Error> {
+	fold("#SECTION .text\naddi r1, r0, -2048\nori r1, r1, 2047\nlsri r1, r1, 31\n")
+}
+
+#[test]
+fn memory_offsets_out_of_range() {
+	let err = fold("#SECTION .text\nlw r1, [r2 + 4096]\n").unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::ImmediateOutOfRange { value: 4096, min: -2048, max: 2047, .. })
+	));
+	assert!(err.to_string().contains("test_file.asm:2:14"));
+
+	let err = fold("#SECTION .text\nsw [r2 - 2049], r1\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: 2049, .. })));
+
+	let err = fold("#SECTION .text\njalr r1, r2, -2049\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: -2049, .. })));
+}
+
+#[test]
+fn memory_offsets_are_full_expressions() -> Result<(), Error> {
+	let image = generate(
+		"#CONST FRAME 16\n#SECTION .text\nlw r1, [r2 + (4 * 3)]\nsw [sp - (FRAME + 4)], r1\nlw \
+		 r1, [r2 - FRAME + 8]\nlw r1, [r2 - 4]\n",
+	)?;
+	let words: Vec<u32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+
+	assert!(matches!(decode(0, words[0]).unwrap(), Instruction::Load { offset: 12, .. }));
+	assert!(matches!(decode(4, words[1]).unwrap(), Instruction::Store { rs1: 2, offset: -20, .. }));
+	assert!(matches!(decode(8, words[2]).unwrap(), Instruction::Load { offset: -8, .. }));
+	assert!(matches!(decode(12, words[3]).unwrap(), Instruction::Load { offset: -4, .. }));
+
+	Ok(())
+}
+
+#[test]
+fn memory_offsets_in_range() -> Result<(), Error> {
+	fold("#SECTION .text\nlw r1, [r2 + 2047]\nsw [r2 - 2048], r1\njalr r1, r2, -2048\n")
+}
+
+#[test]
+fn set_constants_can_be_reassigned() -> Result<(), Error> {
+	let image = generate(
+		"#SET x 1\n#SECTION .data\n#WORDS x\n#SET x 2\n#WORDS x\n#SET x x + 1\n#WORDS x\n",
+	)?;
+
+	assert_eq!(image.sections[0].data, [1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0]);
+
+	Ok(())
+}
+
+#[test]
+fn const_constants_cannot_be_redefined() {
+	let err = fold("#CONST x 1\n#SECTION .data\n#WORDS x\n#CONST x 2\n").unwrap_err();
+	assert!(matches!(&err, Error::Fold(FoldError::ConstantRedefinition { id, .. }) if id == "x"));
+	assert!(err.to_string().contains("test_file.asm:4:1"));
+
+	let err = fold("#SET x 1\n#CONST x 2\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ConstantRedefinition { .. })));
+}
+
+#[test]
+fn current_address_minus_label() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\nstart {\n\taddi r1, r0, 1\n\taddi r1, r1, 1\n\t#CONST size . - \
+		 start\n\t#WORDS size, .\n}\n",
+	)?;
+
+	assert_eq!(image.sections[0].data[8..], [8, 0, 0, 0, 8, 0, 0, 0]);
+
+	Ok(())
+}
+
+#[test]
+fn current_address_outside_section() {
+	let err = fold("#CONST here .\n#SECTION .text\n").unwrap_err();
+
+	assert!(matches!(err, Error::Fold(FoldError::DotOutsideSection { .. })));
+}
+
+#[test]
+fn lexer_recovers_from_errors() {
+	let source = "addi r1, @foo r0\nsub r2, ` r3\n";
+
+	let lexer = Lexer::new("test_file.asm", source);
+	let (tokens, errors) = lexer.tokenize_with_recovery();
+
+	assert_eq!(errors.len(), 2);
+	assert!(matches!(
+		errors[0],
+		LexError::RawUnexpectedSymbol { line: 1, col: 10, found: '@', .. }
+	));
+	assert!(matches!(errors[1], LexError::RawUnexpectedSymbol { line: 2, col: 9, found: '`', .. }));
+
+	let repr: Vec<String> = tokens.iter().map(|t| t.t.to_string()).collect();
+	assert_eq!(
+		repr,
+		["addi", "r1", "COMMA", "r0", "NEWLINE", "sub", "r2", "COMMA", "r3", "NEWLINE"]
+	);
+	assert_eq!((tokens[3].line, tokens[3].col), (1, 15));
+	assert_eq!((tokens[8].line, tokens[8].col), (2, 11));
+}
+
+#[test]
+fn keywords_lex_case_insensitively() {
+	/// Lex the first token of a line
+	fn lex_one(line: &str) -> TokenType {
+		let mut lexer = Lexer::new("test_file.asm", line);
+		lexer.next().unwrap().unwrap().t
+	}
+
+	for inst in InstToken::ALL {
+		let mnemonic = format!("{}\n", inst);
+
+		assert_eq!(lex_one(&mnemonic), TokenType::Inst(inst));
+		assert_eq!(lex_one(&mnemonic.to_uppercase()), TokenType::Inst(inst));
+	}
+
+	let aliases = [
+		("zero", 0),
+		("ra", 1),
+		("sp", 2),
+		("gp", 3),
+		("tp", 4),
+		("t0", 5),
+		("t2", 7),
+		("fp", 8),
+		("s0", 8),
+		("s1", 9),
+		("a0", 10),
+		("a7", 17),
+		("s2", 18),
+		("s11", 27),
+		("t3", 28),
+		("t6", 31),
+	];
+	let names = (0..32).map(|idx| (format!("r{}\n", idx), idx));
+	let aliases = aliases.into_iter().map(|(alias, idx)| (format!("{}\n", alias), idx));
+
+	for (name, idx) in names.chain(aliases) {
+		let reg = RegToken::try_from(idx).unwrap();
+
+		assert_eq!(lex_one(&name), TokenType::Reg(reg));
+		assert_eq!(lex_one(&name.to_uppercase()), TokenType::Reg(reg));
+	}
+
+	assert_eq!(lex_one("Addi_loop\n"), TokenType::Identifier("Addi_loop"));
+	assert_eq!(lex_one("r32\n"), TokenType::Identifier("r32"));
+	assert_eq!(lex_one("#Section\n"), TokenType::Dir(DirToken::Section));
+	assert_eq!(lex_one(".TEXT\n"), TokenType::Section(".TEXT"));
+}
+
+#[test]
+fn token_cache_round_trips() -> Result<(), Error> {
+	let input_path = write_test_file("token_cache.asm", TEST_SOURCE_CODE);
+	let cache_path = input_path.with_extension("tokens");
+	let _ = std::fs::remove_file(&cache_path);
+	let name = input_path.to_str().unwrap();
+
+	let tokens: Vec<Token> = Lexer::new(name, TEST_SOURCE_CODE).collect::<Result<_, _>>()?;
+
+	// The first run misses the cache and writes it, the second one hits it
+	assert_eq!(read_cache(&input_path, TEST_SOURCE_CODE, Isa::default(), &cache_path), None);
+	assert_eq!(Lexer::new(name, TEST_SOURCE_CODE).tokenize_cached(&cache_path)?, tokens);
+	assert_eq!(
+		read_cache(&input_path, TEST_SOURCE_CODE, Isa::default(), &cache_path),
+		Some(tokens.clone())
+	);
+	assert_eq!(Lexer::new(name, TEST_SOURCE_CODE).tokenize_cached(&cache_path)?, tokens);
+
+	Ok(())
+}
+
+#[test]
+fn token_cache_invalidated_by_changes() -> Result<(), Error> {
+	let input_path = write_test_file("token_cache_changed.asm", "addi r1, r0, 1\n");
+	let cache_path = input_path.with_extension("tokens");
+	let _ = std::fs::remove_file(&cache_path);
+	let name = input_path.to_str().unwrap();
+
+	Lexer::new(name, "addi r1, r0, 1\n").tokenize_cached(&cache_path)?;
+	assert!(read_cache(&input_path, "addi r1, r0, 1\n", Isa::default(), &cache_path).is_some());
+
+	// Same length, different contents
+	let changed = "addi r2, r0, 1\n";
+	std::fs::write(&input_path, changed)?;
+	assert_eq!(read_cache(&input_path, changed, Isa::default(), &cache_path), None);
+
+	let tokens = Lexer::new(name, changed).tokenize_cached(&cache_path)?;
+	assert_eq!(tokens[1].t, TokenType::Reg(RegToken::R2));
+	assert!(read_cache(&input_path, changed, Isa::default(), &cache_path).is_some());
+
+	Ok(())
+}
+
+#[test]
+fn token_cache_keyed_by_isa() -> Result<(), Error> {
+	let source = "mul r1, r2, r3\n";
+	let input_path = write_test_file("token_cache_isa.asm", source);
+	let cache_path = input_path.with_extension("tokens");
+	let _ = std::fs::remove_file(&cache_path);
+	let name = input_path.to_str().unwrap();
+
+	Lexer::new(name, source).tokenize_cached(&cache_path)?;
+	assert!(read_cache(&input_path, source, Isa::default(), &cache_path).is_some());
+	assert_eq!(read_cache(&input_path, source, Isa::RV32I, &cache_path), None);
+
+	// A cache written for a larger instruction set does not hide errors
+	let err = Lexer::new(name, source).with_isa(Isa::RV32I).tokenize_cached(&cache_path);
+	assert!(matches!(err, Err(Error::Lex(LexError::UnsupportedInstruction { .. }))));
+
+	Ok(())
+}
+
+#[test]
+fn global_directive_binds_symbol() -> Result<(), Error> {
+	let source = "#SECTION .text\n#GLOBAL _start\n_start {\n\tebreak\n}\nhelper {\n\tebreak\n}\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let mut ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let mut folder = Folder::new("test_file.asm");
+	folder.fold(&mut ast_root)?;
+
+	let bindings: Vec<_> = folder.symbols().iter().map(|s| (s.name.as_str(), s.binding)).collect();
+	assert_eq!(bindings, [("_start", SymbolBinding::Global), ("helper", SymbolBinding::Local)]);
+
+	Ok(())
+}
+
+#[test]
+fn undefined_weak_symbol_warns() -> Result<(), Error> {
+	let source = "#SECTION .text\n#WEAK handler\naddi r1, r0, 1\njal r1, handler\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let mut ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let mut folder = Folder::new("test_file.asm");
+	folder.fold(&mut ast_root)?;
+
+	let warnings = folder.warnings();
+	assert_eq!(warnings.len(), 1);
+	assert!(matches!(&warnings[0], Warning::UndefinedWeakSymbol { id, .. } if id == "handler"));
+	assert_eq!(warnings[0].name(), "undefined-weak-symbol");
+
+	// The jump goes to address 0
+	let image = Generator::new("test_file.asm").generate(&ast_root)?;
+	let jal = u32::from_le_bytes(image.sections[0].data[4..8].try_into().unwrap());
+	assert_eq!(decode(4, jal).unwrap(), Instruction::Jal { rd: 1, offset: -4 });
+
+	Ok(())
+}
+
+#[test]
+fn registers_have_abi_names() {
+	let expected = [
+		"zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+		"a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+		"t5", "t6",
+	];
+
+	for (idx, name) in expected.into_iter().enumerate() {
+		let reg = RegToken::try_from(idx as u8).unwrap();
+
+		assert_eq!(reg.abi_name(), name);
+		assert_eq!(reg.abi().to_string(), name);
+
+		// The name lexes back to the same register
+		let line = format!("{}\n", name);
+		let token = Lexer::new("test_file.asm", &line).next().unwrap().unwrap();
+		assert_eq!(token.t, TokenType::Reg(reg));
+	}
+}
+
+#[test]
+fn registers_sort_by_index() {
+	let mut regs: Vec<RegToken> =
+		(0..32).map(|i| RegToken::try_from((i * 7 % 32) as u8).unwrap()).collect();
+	regs.sort();
+
+	let indices: Vec<u8> = regs.iter().map(|r| r.index()).collect();
+	assert_eq!(indices, (0..32).collect::<Vec<u8>>());
+
+	let uses: BTreeMap<RegToken, &str> =
+		[(RegToken::R31, "t6"), (RegToken::R2, "sp"), (RegToken::R10, "a0")].into();
+	assert_eq!(uses.into_values().collect::<Vec<_>>(), ["sp", "a0", "t6"]);
+}
+
+#[test]
+fn number_literals_keep_their_representation() {
+	let lexer = Lexer::new("test_file.asm", "addi r1, r0, 0x1F\n#WORDS 0b1_0, 0o17, 9\n");
+	let tokens: Vec<Token> = lexer.collect::<Result<_, _>>().unwrap();
+
+	let numbers: Vec<_> = tokens
+		.iter()
+		.filter_map(|token| {
+			match token.t {
+				TokenType::LitNum(value) => Some((value, Radix::of(token.raw()), token.raw())),
+				_ => None,
+			}
+		})
+		.collect();
+
+	assert_eq!(
+		numbers,
+		[
+			(31, Radix::Hex, "0x1F"),
+			(2, Radix::Binary, "0b1_0"),
+			(15, Radix::Octal, "0o17"),
+			(9, Radix::Decimal, "9"),
+		]
+	);
+	assert_eq!(TokenType::LitNum(31).to_string(), "31");
+}
+
+#[test]
+fn data_out_of_range() {
+	let err = fold("#SECTION .data\n#BYTES 1, 300\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::DataOutOfRange { value: 300, bits: 8, .. })));
+	assert!(err.to_string().contains("test_file.asm:2:11"));
+
+	let err = fold("#SECTION .data\n#BYTES -129\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::DataOutOfRange { value: -129, bits: 8, .. })));
+
+	let err = fold("#SECTION .data\n#BYTES 1, '€'\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::CharOutOfRange { value: '€', bits: 8, .. })));
+	assert!(strip_ansi(&err.to_string()).contains("test_file.asm:2:11"));
+
+	let err = fold("#SECTION .data\n#HALVES 'a', '😀'\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::CharOutOfRange { value: '😀', bits: 16, .. })));
+	assert!(strip_ansi(&err.to_string()).contains("test_file.asm:2:14"));
+}
+
+#[test]
+fn data_in_range() -> Result<(), Error> {
+	fold("#SECTION .data\n#BYTES -128, 255, 'a', \"abc\"\n#HALVES 65535, -32768\n")
+}
+
+#[test]
+fn base_addr_offsets_labels() {
+	let input_path = write_test_file(
+		"base_addr.asm",
+		"#SECTION .text\naddi r0, r0, 0\nlabel {\n\t#WORDS label\n}\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("asm")
+		.arg("--base-addr")
+		.arg("0x1000")
+		.arg(&input_path)
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+
+	let image = Image::from_bytes(&std::fs::read(output_path).unwrap()).unwrap();
+
+	assert_eq!((image.base, image.entry), (0x1000, 0x1000));
+	assert_eq!(image.sections[0].addr, 0x1000);
+	assert_eq!(image.sections[0].data[4..], 0x1004_u32.to_le_bytes());
+}
+
+#[test]
+fn branch_labels_resolve_to_offsets() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\nstart {\n\taddi r1, r1, 1\n\tbne r1, r2, start\n\tbeq r0, r0, end\n\tjal \
+		 r0, start\n}\nend {\n\tebreak\n}\n",
+	)?;
+	let words: Vec<u32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+
+	assert!(matches!(decode(4, words[1]).unwrap(), Instruction::Branch { offset: -4, .. }));
+	assert!(matches!(decode(8, words[2]).unwrap(), Instruction::Branch { offset: 8, .. }));
+	assert!(matches!(decode(12, words[3]).unwrap(), Instruction::Jal { offset: -12, .. }));
+
+	Ok(())
+}
+
+#[test]
+fn branch_offset_out_of_range() {
+	let err = fold("#SECTION .text\nbeq r0, r0, 0x1000\n").unwrap_err();
+
+	assert!(matches!(err, Error::Fold(FoldError::BranchOutOfRange { offset: 0x1000, .. })));
+}
+
+#[test]
+fn branch_to_distant_label_out_of_range() {
+	let err =
+		fold("#SECTION .text\nbeq r0, r0, far\n#RES_BYTES 4996\nfar {\n\tebreak\n}\n").unwrap_err();
+
+	assert!(matches!(err, Error::Fold(FoldError::BranchOutOfRange { offset: 5000, .. })));
+	assert!(
+		strip_ansi(&err.to_string())
+			.contains("branch target out of range: 5000 bytes, max \u{b1}4096")
+	);
+}
+
+#[test]
+fn backward_branch_in_range() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\nback {\n\taddi r1, r1, -1\n\t#RES_BYTES 4088\n}\nbne r1, r0, back\n",
+	)?;
+	let data = &image.sections[0].data;
+	let word = u32::from_le_bytes([data[4092], data[4093], data[4094], data[4095]]);
+
+	assert!(matches!(decode(4092, word).unwrap(), Instruction::Branch { offset: -4092, .. }));
+
+	Ok(())
+}
+
+#[test]
+fn misaligned_branch_offset() {
+	let err = fold("#SECTION .text\nbeq r0, r0, 7\n").unwrap_err();
+
+	assert!(matches!(err, Error::Fold(FoldError::MisalignedBranch { offset: 7, .. })));
+}
+
+#[test]
+fn misaligned_jump_offset() {
+	let err = fold("#SECTION .text\njal r0, 3\n").unwrap_err();
+
+	assert!(matches!(err, Error::Fold(FoldError::MisalignedBranch { offset: 3, .. })));
+	assert!(err.to_string().contains("test_file.asm:2:9"));
+}
+
+#[test]
+fn define_declares_constants() {
+	let input_path = write_test_file(
+		"define.asm",
+		"#SECTION .data\n#BYTES BUFSIZE, OFFSET\n#CONST OFFSET 3\n#BYTES OFFSET\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("asm")
+		.arg("--define")
+		.arg("BUFSIZE=0x2_0")
+		.arg("-D")
+		.arg("OFFSET=-1")
+		.arg(&input_path)
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+
+	let image = Image::from_bytes(&std::fs::read(output_path).unwrap()).unwrap();
+
+	assert_eq!(image.sections[0].data, [0x20, 0xFF, 3]);
+}
+
+#[test]
+fn statement_sizes() -> Result<(), Error> {
+	let source =
+		"#SECTION .data\n#WORDS 1, 2, 3\n#RES_BYTES 2 * 5\n#BYTES \"ab\", 'c'\naddi r0, r0, 0\n";
+
+	let lexer = Lexer::new("test_file.asm", source);
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+	let mut parser = Parser::new("test_file.asm", &tokens);
+	let mut ast_root = parser.parse()?;
+	Folder::new("test_file.asm").fold(&mut ast_root)?;
+
+	let sizes: Vec<Option<u64>> = ast_root.sections[0]
+		.lines
+		.iter()
+		.map(|line| {
+			match &line.statement {
+				Some(Statement::Directive(dir)) => dir.size_bytes(),
+				Some(Statement::Instruction(inst)) => Some(inst.size_bytes() as u64),
+				_ => None,
+			}
+		})
+		.collect();
+
+	assert_eq!(sizes, [Some(12), Some(10), Some(3), Some(4)]);
+
+	Ok(())
+}
+
+#[test]
+fn negative_reservations_are_rejected() {
+	let err = fold("#SECTION .data\n#RES_BYTES -1\n").unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::ImmediateOutOfRange { value: -1, min: 0, max: 0x7FFF_FFFF, .. })
+	));
+	assert!(err.to_string().contains("test_file.asm:2:12"));
+
+	let err = fold("#SECTION .data\n#RES_BYTES 1 - 2\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: -1, .. })));
+}
+
+#[test]
+fn statements_past_the_address_space_are_rejected() {
+	let err = fold("#SECTION .data\n#RES_WORDS 0x40000000\n").unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::AddressOverflow { addr: 0, size: 0x1_0000_0000, .. })
+	));
+	assert!(err.to_string().contains("test_file.asm:2:1"));
+
+	let err = fold("#SECTION .data\n#RES_BYTES 0x7FFFFFFF\n#RES_BYTES 0x7FFFFFFF\n#WORDS 1, 2\n")
+		.unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::AddressOverflow { addr: 0xFFFF_FFFE, size: 8, .. })
+	));
+}
+
+#[test]
+fn trailing_commas_follow_policy() -> Result<(), Error> {
+	let parse = |source: &str,
+	             trailing_commas: TrailingCommas|
+	 -> Result<Vec<Option<u64>>, Error> {
+		let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+		let ast_root =
+			Parser::new("test_file.asm", &tokens).with_trailing_commas(trailing_commas).parse()?;
+
+		Ok(ast_root.sections[0]
+			.lines
+			.iter()
+			.filter_map(|line| {
+				match &line.statement {
+					Some(Statement::Directive(dir)) => Some(dir.size_bytes()),
+					Some(Statement::Instruction(inst)) => Some(Some(inst.size_bytes() as u64)),
+					_ => None,
+				}
+			})
+			.collect())
+	};
+	let words = "#SECTION .data\n#WORDS 1, 2, 3,\n";
+	let operands = "#SECTION .text\naddi r1, r0, 5,\n";
+
+	assert_eq!(parse(words, TrailingCommas::default())?, [Some(12)]);
+	assert!(matches!(
+		parse(operands, TrailingCommas::default()),
+		Err(Error::Parse(ParseError::UnexpectedToken { .. }))
+	));
+
+	let strict = TrailingCommas { lists: false, operands: false };
+	assert!(parse(words, strict).is_err());
+
+	let lenient = TrailingCommas { lists: true, operands: true };
+	assert_eq!(parse(operands, lenient)?, [Some(4)]);
+
+	Ok(())
+}
+
+#[test]
+fn division_by_zero() {
+	let err = fold("#CONST x 1 / 0\n").unwrap_err();
+	assert!(matches!(&err, Error::Fold(FoldError::DivisionByZero { op, .. }) if op == "/"));
+	assert!(err.to_string().contains("test_file.asm:1:12"));
+
+	let err = fold("#CONST y 5 % (2 - 2)\n").unwrap_err();
+	assert!(matches!(&err, Error::Fold(FoldError::DivisionByZero { op, .. }) if op == "%"));
+}
+
+#[test]
+fn division_overflow() {
+	let err = fold("#CONST x (-2147483647 - 1) / -1\n").unwrap_err();
+
+	assert!(matches!(err, Error::Fold(FoldError::Overflow { .. })));
+}
+
+#[test]
+fn char_operands_in_immediates() -> Result<(), Error> {
+	let image = generate(
+		"#CONST delta 'z' - 'a'\n#SECTION .text\naddi r1, r0, 'A'\n#WORDS delta, '~' + 1\n",
+	)?;
+	let words: Vec<u32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+
+	assert!(matches!(decode(0, words[0]).unwrap(), Instruction::Rri { imm: 65, .. }));
+	assert_eq!(words[1..], [25, 127]);
+
+	Ok(())
+}
+
+#[test]
+fn logical_and_arithmetic_right_shifts() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .data\n#WORDS (-8) >> 1, (-8) >>> 1, -8 >>> 1, 8 >>> 1, 1 << 2 + 1, 16 >> 1 >> \
+		 1, -1 >> 31\n",
+	)?;
+	let words: Vec<i32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| i32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+
+	assert_eq!(words, [0x7FFF_FFFC, -4, -4, 4, 8, 4, 1]);
+
+	Ok(())
+}
+
+/// Lex, parse, and lint a string of source code
+fn lint(source: &str) -> Result<Vec<Warning>, Error> {
+	let lexer = Lexer::new("test_file.asm", source);
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+
+	let mut parser = Parser::new("test_file.asm", &tokens);
+	let ast_root = parser.parse()?;
+
+	Ok(Linter::new("test_file.asm").lint(&ast_root))
+}
+
+#[test]
+fn unreachable_after_jump() -> Result<(), Error> {
+	let warnings = lint("#SECTION .text\njal r0, end\n\taddi r1, r0, 1\nend {\n\tebreak\n}\n")?;
+
+	assert_eq!(warnings.len(), 1);
+	assert_eq!(warnings[0].name(), "unreachable-code");
+	assert!(warnings[0].to_string().contains("test_file.asm:3:2"));
+
+	Ok(())
+}
+
+#[test]
+fn reachable_after_label_or_branch() -> Result<(), Error> {
+	let warnings = lint(
+		"#SECTION .text\nbeq r1, r2, end\naddi r1, r0, 1\njal r0, end\nend {\n\taddi r2, r0, \
+		 2\n}\n",
+	)?;
+
+	assert!(warnings.is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn dead_writes_are_flagged() -> Result<(), Error> {
+	let warnings = lint("#SECTION .text\naddi r5, r0, 1\naddi r5, r0, 2\nsw [r2], r5\n")?;
+
+	assert_eq!(warnings.len(), 1);
+	assert_eq!(warnings[0].name(), "dead-write");
+	let message = strip_ansi(&warnings[0].to_string());
+	assert!(message.contains("value written to `r5` is never read"));
+	assert!(message.contains("test_file.asm:2:1"));
+
+	// Read in between, overwritten after a label or branch, or never
+	// overwritten at all
+	let warnings = lint(
+		"#SECTION .text\naddi r5, r0, 1\nadd r6, r5, r5\naddi r5, r0, 2\nfoo {\n\taddi r5, r0, \
+		 3\n}\naddi r6, r0, 4\nbeq r0, r0, foo\naddi r6, r0, 5\n",
+	)?;
+	assert!(warnings.is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn register_index_round_trip() {
+	for i in 0..32 {
+		let reg = RegToken::try_from(i).unwrap();
+
+		assert_eq!(reg.index(), i);
+		assert_eq!(reg.to_string(), format!("r{}", i));
+	}
+
+	assert_eq!(RegToken::try_from(32), Err(32));
+	assert_eq!(RegToken::try_from(u8::MAX), Err(u8::MAX));
+}
+
+#[test]
+fn include_bin_embeds_file() -> Result<(), Error> {
+	write_test_file("include_bin_blob.bin", [0xDE, 0xAD, 0xBE, 0xEF, 0x01]);
+	let input_path = write_test_file(
+		"include_bin.asm",
+		"#SECTION .data\n#BYTES 1\n#INCLUDE_BIN \"include_bin_blob.bin\"\n#WORDS .\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	asm::assemble(&input_path, &output_path, &Default::default())?;
+	let image = Image::from_bytes(&std::fs::read(&output_path)?).unwrap();
+
+	assert_eq!(image.sections[0].data, [1, 0xDE, 0xAD, 0xBE, 0xEF, 0x01, 6, 0, 0, 0]);
+
+	Ok(())
+}
+
+#[test]
+fn include_splices_tokens() -> Result<(), Error> {
+	write_test_file("include_consts.asm", "#CONST value 7\n");
+	write_test_file("include_body.asm", "addi r1, r0, value\nhelper {\n\tebreak\n}\n");
+	let input_path = write_test_file(
+		"include.asm",
+		"#INCLUDE \"include_consts.asm\"\n#SECTION .text\n_start {\n\t#INCLUDE \
+		 \"include_body.asm\"\n\tjal r0, helper\n}\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	asm::assemble(&input_path, &output_path, &Default::default())?;
+	let image = Image::from_bytes(&std::fs::read(&output_path)?).unwrap();
+	let names: Vec<String> = asm::symbols(&input_path, &Default::default())?
+		.symbols
+		.into_iter()
+		.map(|s| s.name)
+		.collect();
+
+	assert_eq!(image.sections[0].data.len(), 12);
+	assert_eq!(&image.sections[0].data[..4], 0x0070_0093u32.to_le_bytes());
+	assert_eq!(names, ["_start", "_start.helper"]);
+
+	// Errors in included code point at the included file
+	write_test_file("include_bad_body.asm", "addi r1, r0\n");
+	let input_path =
+		write_test_file("include_bad.asm", "#SECTION .text\n#INCLUDE \"include_bad_body.asm\"\n");
+	let err = asm::check(&input_path, &Default::default()).unwrap_err();
+
+	assert!(matches!(&err, Error::Collected(errors) if matches!(errors[..], [Error::Parse(_)])));
+	assert!(strip_ansi(&err.to_string()).contains("include_bad_body.asm:1:"));
+
+	Ok(())
+}
+
+#[test]
+fn check_reports_every_error() -> Result<(), Error> {
+	let input_path = write_test_file(
+		"check_every_error.asm",
+		"#SECTION .text\naddi r1, r0, 5000\nlw r1, [r2 + LIMIT]\nmul r1, r2, r3\n",
+	);
+	let options = AsmOptions { isa: "rv32i".parse().unwrap(), ..Default::default() };
+
+	let err = asm::check(&input_path, &options).unwrap_err();
+
+	assert!(matches!(err, Error::Collected(_)));
+	let message = strip_ansi(&err.to_string());
+	assert!(message.contains("check_every_error.asm:2:14"));
+	assert!(message.contains("check_every_error.asm:3:"));
+	assert!(message.contains("check_every_error.asm:4:1"));
+
+	// Defines and the instruction set are taken from the options
+	let input_path = write_test_file(
+		"check_options.asm",
+		"#SECTION .text\nlw r1, [r2 + LIMIT]\nmul r1, r2, r3\n",
+	);
+	let options = AsmOptions { defines: vec![("LIMIT".to_string(), 4)], ..Default::default() };
+
+	asm::check(&input_path, &options)
+}
+
+#[test]
+fn include_cycles_are_rejected() {
+	write_test_file("include_cycle_a.asm", "#SECTION .text\n#INCLUDE \"include_cycle_b.asm\"\n");
+	write_test_file("include_cycle_b.asm", "ebreak\n#INCLUDE \"include_cycle_a.asm\"\n");
+	let input_path = write_test_file("include_cycle.asm", "#INCLUDE \"include_cycle_a.asm\"\n");
+
+	let err = asm::check(&input_path, &Default::default()).unwrap_err();
+
+	assert!(matches!(err, Error::Lex(LexError::IncludeCycle { .. })));
+	let message = strip_ansi(&err.to_string());
+	assert!(message.contains("`include_cycle_a.asm` is included recursively"));
+	assert!(message.contains("include_cycle_b.asm:2:10"));
+
+	let source = "#SECTION .text\n#INCLUDE \"other.asm\"\n";
+	let err = asm::assemble_to_vec("test_file.asm", source, &Default::default()).unwrap_err();
+	assert!(matches!(err, Error::Parse(ParseError::UnexpandedInclude { .. })));
+}
+
+#[test]
+fn empty_files_assemble_to_empty_images() -> Result<(), Error> {
+	for (name, source) in [("empty", ""), ("blank", "  \n\t\n; just a comment\n\n")] {
+		let input_path = write_test_file(&format!("{}.asm", name), source);
+		let output_path = input_path.with_extension("wpibin");
+
+		asm::assemble(&input_path, &output_path, &Default::default())?;
+		let image = Image::from_bytes(&std::fs::read(&output_path)?).unwrap();
+
+		assert!(image.sections.iter().all(|s| s.data.is_empty()));
+		assert!(matches!(
+			sim::simulate_bytes(&image.to_bytes(), &Default::default()),
+			Err(sim::error::Error::EmptyImage)
+		));
+	}
+
+	Ok(())
+}
+
+#[test]
+fn assemble_to_vec_matches_assemble() -> Result<(), Error> {
+	let source = "#CONST count 3\n#SECTION .text\n_start {\n\taddi r1, r0, count\n\tjal r0, \
+	              _start\n}\n#SECTION .data\nmessage {\n\t#BYTES \"hi\", 0\n}\n";
+	let input_path = write_test_file("assemble_to_vec.asm", source);
+	let output_path = input_path.with_extension("wpibin");
+	let options = Default::default();
+
+	asm::assemble(&input_path, &output_path, &options)?;
+	let (root, bytes) = asm::assemble_to_vec("assemble_to_vec.asm", source, &options)?;
+
+	assert_eq!(bytes, std::fs::read(&output_path)?);
+	assert_eq!(root.sections.len(), 2);
+	assert_eq!(root.sections[1].name, ".data");
+
+	Ok(())
+}
+
+#[test]
+fn include_bin_missing_file() {
+	let input_path = write_test_file(
+		"include_bin_missing.asm",
+		"#SECTION .data\n#INCLUDE_BIN \"missing.bin\"\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	let err = asm::assemble(&input_path, &output_path, &Default::default()).unwrap_err();
+
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::IncludeFailed { ref path, .. }) if path == "missing.bin"
+	));
+	assert!(err.to_string().contains("include_bin_missing.asm:2:1"));
+}
+
+#[test]
+fn missing_operand_comma() {
+	let err = fold("#SECTION .text\naddi r1 r0, 5\n").unwrap_err();
+
+	assert!(
+		err.to_string()
+			.contains("found unexpected token `r0`, expected `COMMA` between instruction operands")
+	);
+}
+
+#[test]
+fn trailing_operand_tokens() {
+	let err = fold("#SECTION .text\naddi r1, r0, 5 garbage\n").unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	assert!(matches!(
+		err,
+		Error::Parse(ParseError::UnexpectedToken { ref expected, .. })
+			if expected == "end of instruction"
+	));
+	assert!(repr.contains("found unexpected token `garbage`, expected end of instruction"));
+	assert!(repr.contains("test_file.asm:2:16"));
+
+	let err = fold("#SECTION .text\nadd r1, r0, r2 r3 ; comment\n").unwrap_err();
+
+	assert!(strip_ansi(&err.to_string()).contains("test_file.asm:2:16"));
+}
+
+#[test]
+fn swapped_store_operands() {
+	let err = fold("#SECTION .text\nsw r1, [r2]\n").unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	assert!(matches!(err, Error::Parse(ParseError::SwappedOperands { .. })));
+	assert!(repr.contains("`sw` takes the address before the register"));
+	assert!(repr.contains("try `sw [r2], r1`"));
+
+	let err = fold("#SECTION .text\nsb t0, [sp - 4]\n").unwrap_err();
+
+	assert!(strip_ansi(&err.to_string()).contains("try `sb [sp - 4], t0`"));
+
+	// Without a following address there is nothing to swap
+	let err = fold("#SECTION .text\nsh r1, r2\n").unwrap_err();
+
+	assert!(matches!(err, Error::Parse(ParseError::UnexpectedToken { .. })));
+}
+
+#[test]
+fn missing_operand_comma_underlines_instruction() {
+	let err = fold("#SECTION .text\n\taddi r1 r0, 5\n").unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	// The caret line follows the source line, and should cover `addi r1 r0`
+	let lines: Vec<&str> = repr.lines().collect();
+	let src_idx = lines.iter().position(|l| l.ends_with("\taddi r1 r0, 5")).unwrap();
+	let carets = lines[src_idx + 1].split_once("| ").unwrap().1;
+
+	assert_eq!(carets, " ^^^^^^^^^^");
+	assert!(repr.contains("test_file.asm:2:7"));
+}
+
+#[test]
+fn ambiguous_macro_rules() {
+	let source = "define_macro! clear {\n\t($r:reg) => { addi $r, r0, 0 },\n\t($x:reg) => { add \
+	              $x, r0, r0 }\n}\n";
+	let err = fold(source).unwrap_err();
+
+	match err {
+		Error::Parse(ParseError::AmbiguousMacroRules { macro_name, .. }) => {
+			assert_eq!(macro_name, "clear")
+		},
+		_ => panic!("expected an ambiguous macro rules error, got {:?}", err),
+	}
+}
+
+#[test]
+fn errors_and_warnings_render_as_diagnostics() -> Result<(), Error> {
+	let Err(Error::Lex(lex_err)) = fold("#SECTION .text\naddi r1, r0, @\n") else { panic!() };
+	let Err(Error::Parse(parse_err)) = fold("#SECTION .text\naddi r1, (r0\n") else { panic!() };
+	let Err(Error::Parse(macro_err)) =
+		fold("define_macro! m {\n\t($a:reg) => { },\n\t($b:reg) => { }\n}\n")
+	else {
+		panic!()
+	};
+	let warning =
+		lint("#SECTION .text\njal r0, end\naddi r1, r0, 1\nend {\n\tebreak\n}\n")?.remove(0);
+
+	let rendered = [
+		(lex_err.to_string(), Diagnostic::from(lex_err)),
+		(parse_err.to_string(), Diagnostic::from(parse_err)),
+		(macro_err.to_string(), Diagnostic::from(macro_err)),
+		(warning.to_string(), Diagnostic::from(warning)),
+	];
+	for (repr, diagnostic) in &rendered {
+		assert_eq!(&diagnostic.to_string(), repr);
+		assert_eq!(diagnostic.file(), "test_file.asm");
+	}
+
+	let positions: Vec<(usize, usize)> = rendered.iter().map(|(_, d)| d.position()).collect();
+	assert_eq!(positions, [(2, 14), (2, 10), (3, 2), (3, 1)]);
+
+	let severities: Vec<Severity> = rendered.iter().map(|(_, d)| d.severity).collect();
+	assert_eq!(severities, [Severity::Error, Severity::Error, Severity::Error, Severity::Warning]);
+	assert_eq!(rendered[1].1.label.as_deref(), Some("first unclosed here"));
+	assert!(rendered[2].1.message.contains("macro `m`"));
+
+	Ok(())
+}
+
+#[test]
+fn multi_line_string_token() -> Result<(), Error> {
+	let lexer = Lexer::new("test_file.asm", "#BYTES \"ab\ncd\", 1\n#WORDS 2\n");
+	let tokens: Vec<Token> = lexer.into_iter().collect::<Result<Vec<Token>, Error>>()?;
+
+	assert_eq!(tokens[1].t, TokenType::LitStr("ab\ncd"));
+	assert_eq!(
+		tokens[1].to_string(),
+		r##"[001:008]: STRING     "ab\ncd"                        "#BYTES ```\"ab\n```...""##
+	);
+
+	// Tokens following the string continue on its last line
+	assert_eq!((tokens[2].line, tokens[2].col), (2, 4));
+	assert_eq!((tokens[5].line, tokens[5].col), (3, 1));
+
+	Ok(())
+}
+
+#[test]
+fn multi_line_token_errors_bracket_every_line() {
+	let err = fold("#SECTION .text\naddi r1, r0, \"ab\ncd\", 1\n").unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	assert!(repr.contains("test_file.asm:2:14"));
+	assert!(repr.contains(
+		"  |\n2 |   addi r1, r0, \"ab\n  |  ______________^\n3 | | cd\", 1\n  | |___^\n"
+	));
+}
+
+#[test]
+fn source_map_records_instruction_lines() -> Result<(), Error> {
+	let source = "#SECTION .text\naddi r1, r0, 1\n#WORDS 7\n\nebreak\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let mut ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+	Folder::new("test_file.asm").fold(&mut ast_root)?;
+
+	let image = Generator::new("test_file.asm").with_source_map(true).generate(&ast_root)?;
+
+	let locations: Vec<String> = image.source_map.iter().map(|l| l.to_string()).collect();
+	assert_eq!(locations, ["test_file.asm:2", "test_file.asm:5"]);
+	assert_eq!(image.source_location(8).map(|l| l.line), Some(5));
+	assert_eq!(image.source_location(4), None);
+	assert_eq!(Image::from_bytes(&image.to_bytes()), Ok(image));
+
+	assert!(generate(source)?.source_map.is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn labels_are_recorded_as_symbols() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\n_start {\n\taddi r1, r0, 1\n\tnested {\n\t\tebreak\n\t}\n}\n#SECTION \
+		 .data\nmessage {\n\t#BYTES 1\n}\n",
+	)?;
+
+	let symbols: Vec<(&str, u32)> =
+		image.symbols.iter().map(|symbol| (&symbol.name[..], symbol.addr)).collect();
+
+	assert_eq!(symbols, [("_start", 0), ("_start.nested", 4), ("message", 8)]);
+	assert_eq!(Image::from_bytes(&image.to_bytes()), Ok(image));
+
+	Ok(())
+}
+
+#[test]
+fn extern_references_are_relocated() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\n#EXTERN target\naddi r1, r0, 1\nbeq r1, r2, target\njal r0, \
+		 target\n#SECTION .data\n#WORDS 7, target\n",
+	)?;
+
+	// Relocated fields are encoded as 0, leaving only the opcode and registers
+	let text = &image.sections[0].data;
+	assert_eq!(text[4..12], [0x63, 0x80, 0x20, 0x00, 0x6F, 0x00, 0x00, 0x00]);
+	assert_eq!(image.sections[1].data, [7, 0, 0, 0, 0, 0, 0, 0]);
+	assert_eq!(
+		image.relocations,
+		[
+			Relocation { addr: 4, symbol: "target".to_string(), kind: RelocationKind::Branch },
+			Relocation { addr: 8, symbol: "target".to_string(), kind: RelocationKind::Jal },
+			Relocation {
+				addr:   16,
+				symbol: "target".to_string(),
+				kind:   RelocationKind::Absolute,
+			},
+		]
+	);
+	assert_eq!(Image::from_bytes(&image.to_bytes()), Ok(image));
+
+	Ok(())
+}
+
+#[test]
+fn align_pads_to_boundary() -> Result<(), Error> {
+	let image =
+		generate("#SECTION .data\n#BYTES 1, 2, 3\n#ALIGN 4\n#WORDS .\n#ALIGN 4\n#BYTES 4\n")?;
+
+	assert_eq!(image.sections[0].data, [1, 2, 3, 0, 4, 0, 0, 0, 4]);
+
+	let err = fold("#SECTION .data\n#ALIGN 6\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::InvalidAlignment { boundary: 6, .. })));
+
+	Ok(())
+}
+
+#[test]
+fn assemble_reports_timings() -> Result<(), Error> {
+	let input_path = write_test_file(
+		"timings.asm",
+		"#CONST limit 3\n#SECTION .text\n_start {\n\taddi r1, r0, limit\n\tecall\n}\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	let timings = asm::assemble(&input_path, &output_path, &Default::default())?;
+
+	assert_eq!(timings.tokens, 21);
+	// The constant, the section's label, and both instructions
+	assert_eq!(timings.statements, 4);
+	assert!(!timings.lex.is_zero());
+	assert!(!timings.parse.is_zero());
+	assert!(!timings.fold.is_zero());
+	assert!(!timings.codegen.is_zero());
+
+	Ok(())
+}
+
+/// Decode the instruction at the given address of a generated image's first
+/// section
+fn decode_at(image: &Image, addr: u32) -> Instruction {
+	let data = &image.sections[0].data[addr as usize..addr as usize + 4];
+	decode(addr, u32::from_le_bytes(data.try_into().unwrap())).unwrap()
+}
+
+#[test]
+fn local_label_backward_loop() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\naddi r1, r0, 3\n1: addi r1, r1, -1\nbne r1, r0, 1b\n1:\nbeq r0, r0, 1b\n",
+	)?;
+
+	// Each reference resolves to the closest preceding `1:`
+	match (decode_at(&image, 8), decode_at(&image, 12)) {
+		(Instruction::Branch { offset: bne, .. }, Instruction::Branch { offset: beq, .. }) => {
+			assert_eq!((bne, beq), (-4, 0));
+		},
+		other => panic!("expected two branches, got {:?}", other),
+	}
+
+	Ok(())
+}
+
+#[test]
+fn local_label_forward_reference() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\njal r0, 1f\naddi r1, r0, 1\n1:\nebreak\n#SECTION .data\n#WORDS 1b, \
+		 1f\n1:\n",
+	)?;
+
+	assert_eq!(decode_at(&image, 0), Instruction::Jal { rd: 0, offset: 8 });
+	assert_eq!(image.sections[1].data, [8, 0, 0, 0, 20, 0, 0, 0]);
+
+	let err = fold("#SECTION .text\n1:\njal r0, 2f\n").unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::UndefinedLocalLabel { ref label, .. }) if label == "2f"
+	));
+
+	Ok(())
+}
+
+#[test]
+fn isa_profiles_restrict_extensions() {
+	let source = "#SECTION .text\nmul r1, r2, r3\ncsrrw r1, r2, 0x300\n";
+	let lex = |isa: Isa| {
+		let lexer = Lexer::new("test_file.asm", source).with_isa(isa);
+		lexer.into_iter().collect::<Result<Vec<Token>, Error>>()
+	};
+
+	let err = lex(Isa::RV32I).unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Lex(LexError::UnsupportedInstruction { extension: Extension::M, .. })
+	));
+
+	let err = lex("rv32im".parse().unwrap()).unwrap_err();
+	assert!(matches!(
+		err,
+		Error::Lex(LexError::UnsupportedInstruction { extension: Extension::Zicsr, .. })
+	));
+
+	assert!(lex("rv32im_zicsr".parse().unwrap()).is_ok());
+	assert_eq!(Isa::default().to_string(), "rv32im_zicsr");
+	assert!("rv64i".parse::<Isa>().is_err());
+}
+
+#[test]
+fn ordering_target_round_trips() {
+	for bits in 0..16 {
+		let target = OrderingTarget::from_bits(bits).unwrap();
+
+		assert_eq!(target.to_string().parse::<OrderingTarget>(), Ok(target));
+	}
+
+	assert_eq!((OrderingTarget::W | OrderingTarget::I).to_string(), "IW");
+	assert_eq!((OrderingTarget::I | OrderingTarget::W).iter_flags().collect::<String>(), "iw");
+	assert_eq!(OrderingTarget::empty().iter_flags().next(), None);
+	assert_eq!("RX".parse::<OrderingTarget>(), Err(InvalidOrderingTarget('X')));
+	assert!(matches!(
+		fold("#SECTION .text\nfence RX, W\n").unwrap_err(),
+		Error::Parse(ParseError::InvalidOrderingSpecifier { .. })
+	));
+}
+
+#[test]
+fn conditional_assembly_selects_branch() {
+	let input_path = write_test_file(
+		"conditional.asm",
+		"#SECTION .data\n#IF DEBUG ; debug build\n#BYTES 1\n#IF DEBUG - 1\n#BYTES 		 \
+		 2\n#ENDIF\n#ELSE\n#BYTES 0\n#ENDIF\nend {\n\t#BYTES 9\n}\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+
+	for (debug, expected) in [("1", &[1, 9][..]), ("0", &[0, 9]), ("2", &[1, 2, 9])] {
+		let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+			.arg("asm")
+			.arg("-D")
+			.arg(format!("DEBUG={}", debug))
+			.arg(&input_path)
+			.output()
+			.unwrap();
+		assert!(output.status.success());
+
+		let image = Image::from_bytes(&std::fs::read(&output_path).unwrap()).unwrap();
+
+		assert_eq!(image.sections[0].data, expected);
+	}
+}
+
+#[test]
+fn unmatched_conditionals() {
+	let err = fold("#SECTION .text\nebreak\n#ENDIF\n").unwrap_err();
+	assert!(matches!(err, Error::Parse(ParseError::UnmatchedConditional { .. })));
+	assert!(err.to_string().contains("test_file.asm:3:1"));
+
+	let err = fold("#SECTION .text\n#IF 1\n#ELSE\n#ELSE\n#ENDIF\n").unwrap_err();
+	assert!(matches!(err, Error::Parse(ParseError::UnmatchedConditional { .. })));
+	assert!(err.to_string().contains("test_file.asm:4:1"));
+
+	let err = fold("#SECTION .text\n#IF 1\nblock {\n#IF 0\n}\n#ENDIF\n").unwrap_err();
+	assert!(matches!(err, Error::Parse(ParseError::UnclosedConditional { .. })));
+	assert!(err.to_string().contains("test_file.asm:4:1"));
+
+	let err = fold("#SECTION .text\nlabel {\n}\n#IF label\n#ENDIF\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::UnresolvedCondition { .. })));
+}
+
+#[test]
+fn dotted_identifiers_lex_as_one_token() -> Result<(), Error> {
+	let lex_one = |source| -> Result<TokenType, Error> {
+		let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+		assert_eq!(tokens.len(), 2, "{:?}", tokens);
+		assert!(matches!(tokens[1].t, TokenType::SymNewline));
+
+		Ok(tokens[0].t)
+	};
+
+	assert!(matches!(lex_one("fence.tso\n")?, TokenType::Inst(InstToken::FenceTso)));
+	assert!(matches!(lex_one("FENCE.I\n")?, TokenType::Inst(InstToken::Fencei)));
+	assert!(matches!(lex_one(".text\n")?, TokenType::Section(".text")));
+
+	Ok(())
+}
+
+#[test]
+fn adjacent_data_directives_are_packed_in_order() -> Result<(), Error> {
+	let image = generate("#SECTION .data\n#BYTES 1, 2\n#BYTES 3\n#BYTES \"ab\"\n#HALVES 0x1234\n")?;
+	assert_eq!(image.sections.len(), 1);
+	assert_eq!(image.sections[0].data, [1, 2, 3, b'a', b'b', 0x34, 0x12]);
+
+	let image = generate("#SECTION .data\n#BYTES 1\n#ALIGN 2\n#BYTES 2\n#HALVES 0x1234\n")?;
+	assert_eq!(image.sections[0].data, [1, 0, 2, 0x34, 0x12]);
+
+	Ok(())
+}
+
+#[test]
+fn raw_macro_tokens_match_by_value() -> Result<(), Error> {
+	let comma = TokenType::SymComma;
+	assert_eq!(comma, TokenType::SymComma);
+	assert!(comma.matches_pattern(&TokenType::SymComma));
+	assert!(!comma.matches_pattern(&TokenType::SymDot));
+
+	let five = TokenType::LitNum(5);
+	assert_eq!(five, TokenType::LitNum(5));
+	assert_ne!(five, TokenType::LitNum(6));
+	assert!(five.matches_pattern(&TokenType::LitNum(6)));
+	assert!(!five.matches_pattern(&comma));
+
+	// Raw tokens are compared by value, so these rules are not ambiguous
+	fold("define_macro! pick {\n\t(5) => { addi r1, r0, 5 },\n\t(6) => { addi r1, r0, 6 }\n}\n")?;
+
+	Ok(())
+}
+
+#[test]
+fn unexpected_operator_in_immediate() {
+	let err = fold("#SECTION .text\naddi r1, r0, 1 + $\n").unwrap_err();
+
+	match &err {
+		Error::Parse(ParseError::UnexpectedToken { found, expected, .. }) => {
+			assert_eq!(found, "$");
+			assert_eq!(expected, "number, identifier, operator, or parenthesis");
+		},
+		_ => panic!("expected an unexpected token error, got {:?}", err),
+	}
+	assert!(strip_ansi(&err.to_string()).contains("test_file.asm:2:18"));
+}
+
+#[test]
+fn org_moves_to_absolute_address() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\nebreak\n#ORG 0x100\nhandler {\n\tebreak\n}\n#SECTION .data\n#BYTES 1\n",
+	)?;
+
+	assert_eq!(image.symbols[0].name, "handler");
+	assert_eq!(image.symbols[0].addr, 0x100);
+	assert_eq!(image.sections[0].data.len(), 0x104);
+	assert!(image.sections[0].data[4..0x100].iter().all(|&b| b == 0));
+	assert_eq!(image.sections[1].addr, 0x104);
+
+	let err = fold("#SECTION .text\nebreak\nebreak\n#ORG 4\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::BackwardOrg { addr: 4, current: 8, .. })));
+	assert!(
+		strip_ansi(&err.to_string())
+			.contains("`#ORG` address 0x00000004 lies before the current address 0x00000008")
+	);
+
+	Ok(())
+}
+
+#[test]
+fn encodings_are_described_by_field() -> Result<(), Error> {
+	let image = generate("#SECTION .text\nadd r1, r2, r3\nsw [r2 - 4], r5\nbeq r1, r2, -8\n")?;
+	let words: Vec<u32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+
+	assert_eq!(
+		describe_encoding(words[0]),
+		"0b0000000_00011_00010_000_00001_0110011  (opcode=0b0110011, rd=1, funct3=0b000, rs1=2, \
+		 rs2=3, funct7=0b0000000)"
+	);
+	assert!(
+		describe_encoding(words[1])
+			.ends_with("(opcode=0b0100011, funct3=0b010, rs1=2, rs2=5, imm=-4)")
+	);
+	assert!(
+		describe_encoding(words[2])
+			.ends_with("(opcode=0b1100011, funct3=0b000, rs1=1, rs2=2, imm=-8)")
+	);
+
+	Ok(())
+}
+
+#[test]
+fn sections_are_emitted_separately() -> Result<(), Error> {
+	let image = generate(
+		"#SECTION .text\nebreak\n#SECTION .data\n#WORDS 0x12345678\n#SECTION .bss\n#RES_WORDS 2\n",
+	)?;
+
+	assert_eq!(image.sections.len(), 3);
+	assert_eq!(image.sections[0].name, ".text");
+	assert_eq!(image.sections[0].data, 0x0010_0073u32.to_le_bytes());
+	assert_eq!(image.sections[1].name, ".data");
+	assert_eq!(
+		(image.sections[1].addr, &image.sections[1].data[..]),
+		(4, &[0x78, 0x56, 0x34, 0x12][..])
+	);
+	assert_eq!(image.sections[2].name, ".bss");
+	assert_eq!((image.sections[2].addr, &image.sections[2].data[..]), (8, &[0; 8][..]));
+
+	let flags: Vec<(bool, bool, bool)> =
+		image.sections.iter().map(|s| (s.flags.exec, s.flags.write, s.flags.uninit)).collect();
+	assert_eq!(flags, [(true, false, false), (false, true, false), (false, true, true)]);
+	assert_eq!(Image::from_bytes(&image.to_bytes()), Ok(image));
+
+	for section in [".data", ".bss"] {
+		let err = generate(&format!("#SECTION {}\nebreak\n", section)).unwrap_err();
+		match &err {
+			Error::Codegen(CodegenError::InstructionOutsideText { section: found, .. }) => {
+				assert_eq!(found, section)
+			},
+			_ => panic!("expected an instruction outside text error, got {:?}", err),
+		}
+	}
+
+	let err = generate("#SECTION .bss\n#RES_BYTES 4\n#BYTES 1\n").unwrap_err();
+	assert!(matches!(err, Error::Codegen(CodegenError::DataInUninitSection { .. })));
+	assert!(err.to_string().contains("test_file.asm:3:1"));
+
+	Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ast_serializes_as_json() -> Result<(), Error> {
+	let tokens: Vec<Token> =
+		Lexer::new("test_file.asm", TEST_SOURCE_CODE).collect::<Result<_, _>>()?;
+	let ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+
+	let json: serde_json::Value = serde_json::from_str(&asm::ast_to_json(&ast_root)).unwrap();
+
+	assert!(json["preamble"].is_array());
+	let sections = json["sections"].as_array().unwrap();
+	assert_eq!(sections.len(), 1);
+	assert_eq!(sections[0]["name"], ".text");
+
+	let start = &sections[0]["lines"][1]["start"];
+	assert_eq!((start["line"].as_u64(), start["col"].as_u64()), (Some(14), Some(1)));
+
+	Ok(())
+}
+
+#[test]
+fn deep_nesting_is_rejected() -> Result<(), Error> {
+	let nested = |depth: usize| {
+		let open: String = (0..depth).map(|i| format!("l{} {{\n", i)).collect();
+		format!("#SECTION .text\n{}ebreak\n{}", open, "}\n".repeat(depth))
+	};
+
+	// Parsing at the full default depth needs more stack than a debug test thread has
+	let deep = std::thread::Builder::new()
+		.stack_size(64 << 20)
+		.spawn(move || -> Result<Error, Error> {
+			let source = nested(DEFAULT_MAX_DEPTH);
+			let tokens: Vec<Token> =
+				Lexer::new("test_file.asm", &source).collect::<Result<_, _>>()?;
+			Parser::new("test_file.asm", &tokens).parse()?;
+
+			Ok(fold(&nested(300)).unwrap_err())
+		})
+		.unwrap();
+	let err = deep.join().unwrap()?;
+	assert!(matches!(err, Error::Parse(ParseError::NestingTooDeep { limit: 256, .. })));
+	assert!(strip_ansi(&err.to_string()).contains("nesting exceeds the maximum depth of 256"));
+
+	let source = "#SECTION .text\naddi r1, r0, ((1))\n#IF 1\n#IF 1\nebreak\n#ENDIF\n#ENDIF\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	Parser::new("test_file.asm", &tokens).with_max_depth(2).parse()?;
+
+	let err = Parser::new("test_file.asm", &tokens).with_max_depth(1).parse().unwrap_err();
+	assert!(matches!(err, Error::Parse(ParseError::NestingTooDeep { limit: 1, .. })));
+
+	Ok(())
+}
+
+#[test]
+fn declarations_named_after_instructions_or_registers() {
+	let err = fold("#SECTION .text\nadd {\n\tebreak\n}\n").unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	assert!(matches!(
+		err,
+		Error::Parse(ParseError::ReservedName { ref declared, .. }) if declared == "label"
+	));
+	assert!(repr.contains("`add` is an instruction and can not be the name of a label"));
+	assert!(repr.contains("test_file.asm:2:1"));
+
+	let err = fold("#SECTION .text\nsp {\n\tebreak\n}\n").unwrap_err();
+
+	assert!(strip_ansi(&err.to_string()).contains("`sp` is a register"));
+
+	let err = fold("#CONST r1 5\n").unwrap_err();
+
+	assert!(matches!(
+		err,
+		Error::Parse(ParseError::ReservedName { ref declared, .. }) if declared == "constant"
+	));
+	assert!(strip_ansi(&err.to_string()).contains("rename the constant, eg. to `r1_`"));
+}
+
+#[test]
+fn parsed_root_outlives_parser() -> Result<(), Error> {
+	let source = "#SECTION .text\naddi r1, r0, 5\n".to_string();
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", &source).collect::<Result<_, _>>()?;
+
+	let root = {
+		let mut parser = Parser::new("test_file.asm", &tokens);
+		let root = parser.parse()?;
+
+		// The parser can still be used once it has produced a root
+		let rest = parser.parse()?;
+		assert!(rest.sections.is_empty());
+
+		root
+	};
+
+	// The root only borrows the tokens and source code, not the parser
+	assert_eq!(root.sections.len(), 1);
+	assert!(tokens[0].source_line.starts_with("#SECTION .text"));
+	assert!(source.starts_with(tokens[0].raw()));
+
+	Ok(())
+}
+
+#[test]
+fn bits_pack_values_most_significant_first() -> Result<(), Error> {
+	let image = generate("#SECTION .data\n#BITS (1:1), (0:1), (3:2)\n#BYTES 0xFF\n")?;
+
+	assert_eq!(image.sections[0].data, [0b1011_0000, 0xFF]);
+
+	let image = generate("#SECTION .data\n#BITS (0xABC : 12), (1 ? 5 : 6 : 4), (-1 : 9)\n")?;
+
+	assert_eq!(image.sections[0].data, [0xAB, 0xC5, 0xFF, 0x80]);
+
+	let err = fold("#SECTION .data\n#BITS (1:1), (4:2)\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::DataOutOfRange { value: 4, bits: 2, .. })));
+	assert!(strip_ansi(&err.to_string()).contains("test_file.asm:2:15"));
+
+	let err = fold("#SECTION .data\n#BITS (1:0)\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::ImmediateOutOfRange { value: 0, .. })));
+
+	Ok(())
+}
+
+#[test]
+fn reader_lexer_matches_borrowed_lexer() -> Result<(), Error> {
+	let sources = [
+		TEST_SOURCE_CODE,
+		"#SECTION .data\n#BYTES \"multi\nline\", 1 ; comment\n\n#BYTES 2",
+		"#SECTION .data\n#BYTES '\"', '\\'' ; \"\n#BYTES \"a\\\"\nb\", 3\n",
+	];
+
+	for source in sources {
+		let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+
+		let chunks: Vec<SourceChunk> =
+			Lexer::new_from_reader("test_file.asm", source.as_bytes()).collect::<Result<_, _>>()?;
+		let mut chunk_tokens = vec![];
+		for chunk in &chunks {
+			chunk_tokens.extend(chunk.tokens()?);
+		}
+
+		assert_eq!(tokens, chunk_tokens);
+	}
+
+	// The string literal spanning two lines is read as a single chunk
+	let source = "#BYTES \"a\nb\"\nebreak\n";
+	let chunks: Vec<SourceChunk> =
+		Lexer::new_from_reader("test_file.asm", source.as_bytes()).collect::<Result<_, _>>()?;
+	assert_eq!(chunks.len(), 2);
+	assert_eq!(chunks[1].first_line(), 3);
+	assert_eq!(chunks[1].source(), "ebreak\n");
+
+	// Quotes in comments, character literals, and escapes do not open or
+	// close a string
+	let source = "#BYTES '\"' ; \"\n#BYTES \"\\\"\n\"\nebreak\n";
+	let chunks: Vec<SourceChunk> =
+		Lexer::new_from_reader("test_file.asm", source.as_bytes()).collect::<Result<_, _>>()?;
+	let lines: Vec<usize> = chunks.iter().map(SourceChunk::first_line).collect();
+	assert_eq!(lines, [1, 2, 4]);
+
+	Ok(())
+}
+
+#[test]
+fn macro_redefinition_reports_both_definitions() {
+	let err = fold(
+		"define_macro! foo {\n\t() => { ebreak }\n}\n#SECTION .text\ndefine_macro! foo {\n\t() => \
+		 { ecall }\n}\n",
+	)
+	.unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	assert!(matches!(
+		err,
+		Error::Parse(ParseError::MacroRedefinition { ref id, .. }) if id == "foo"
+	));
+	assert!(repr.contains("macro `foo` is defined more than once"));
+	assert!(repr.contains("test_file.asm:5:15"));
+	assert!(repr.contains("`foo` was first defined here"));
+	assert!(repr.contains("`foo` was first defined here\n  |\n1 | define_macro! foo {"));
+}
+
+#[test]
+fn data_follows_target_endianness() -> Result<(), Error> {
+	let source =
+		"#SECTION .text\naddi r1, r0, 1\n#SECTION .data\n#WORDS 0x01020304\n#HALVES 0x0506\n";
+	let tokens: Vec<Token> = Lexer::new("test_file.asm", source).collect::<Result<_, _>>()?;
+	let mut ast_root = Parser::new("test_file.asm", &tokens).parse()?;
+	Folder::new("test_file.asm").fold(&mut ast_root)?;
+
+	let little = Generator::new("test_file.asm").generate(&ast_root)?;
+	let big = Generator::new("test_file.asm").with_endian(Endian::Big).generate(&ast_root)?;
+
+	assert_eq!(little.sections[1].data, [0x04, 0x03, 0x02, 0x01, 0x06, 0x05]);
+	assert_eq!(big.sections[1].data, [0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+	// Instructions are always little-endian
+	assert_eq!(little.sections[0].data, big.sections[0].data);
+
+	assert_eq!(Image::from_bytes(&big.to_bytes()).unwrap().endian, Endian::Big);
+
+	Ok(())
+}
+
+#[test]
+fn csr_targets_resolve_by_name() -> Result<(), Error> {
+	let named = generate("#SECTION .text\ncsrrs r1, r0, cycle\ncsrrwi r0, 5, mstatus\n")?;
+	let numeric = generate("#SECTION .text\ncsrrs r1, r0, 0xC00\ncsrrwi r0, 5, 0x300\n")?;
+
+	assert_eq!(named.sections[0].data, numeric.sections[0].data);
+
+	// Constants and labels take precedence over CSR names
+	let shadowed = generate("#CONST cycle 0x300\n#SECTION .text\ncsrrs r1, r0, cycle\n")?;
+	let expected = generate("#SECTION .text\ncsrrs r1, r0, 0x300\n")?;
+	assert_eq!(shadowed.sections[0].data, expected.sections[0].data);
+
+	let err = fold("#SECTION .text\ncsrrs r1, r0, cycel\n").unwrap_err();
+	let repr = strip_ansi(&err.to_string());
+
+	assert!(matches!(
+		err,
+		Error::Fold(FoldError::UnknownCsr { ref suggestion, .. })
+			if suggestion.as_deref() == Some("cycle")
+	));
+	assert!(repr.contains("unknown CSR `cycel`"));
+	assert!(repr.contains("did you mean `cycle`?"));
+	assert!(repr.contains("test_file.asm:2:15"));
+
+	let err = fold("#SECTION .text\ncsrrs r1, r0, nonsense\n").unwrap_err();
+	assert!(matches!(err, Error::Fold(FoldError::UnknownCsr { suggestion: None, .. })));
+
+	Ok(())
+}
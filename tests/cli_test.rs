@@ -0,0 +1,337 @@
+use std::process::Command;
+
+mod common;
+use common::*;
+
+#[test]
+fn check_valid_file() {
+	let path = write_test_file("check_valid.asm", TEST_SOURCE_CODE);
+
+	let output =
+		Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("check").arg(&path).output().unwrap();
+
+	assert!(output.status.success());
+	assert!(output.stderr.is_empty());
+	assert!(!path.with_extension("wpibin").exists());
+}
+
+#[test]
+fn check_invalid_file() {
+	let path = write_test_file("check_invalid.asm", "#SECTION .text\naddi r1, r0, @\n");
+
+	let output =
+		Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("check").arg(&path).output().unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(!output.status.success());
+	assert!(stderr.contains("found unexpected symbol `'@'`"));
+}
+
+#[test]
+fn check_accepts_assembler_options() {
+	let path =
+		write_test_file("check_cli_options.asm", "#SECTION .text\naddi r1, r0, LIMIT\nebreak\n");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["check", "-D", "LIMIT=4", "--isa", "rv32i"])
+		.arg(&path)
+		.output()
+		.unwrap();
+
+	assert!(output.status.success());
+
+	let output =
+		Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("check").arg(&path).output().unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(!output.status.success());
+	assert!(stderr.contains("use of undefined identifier `LIMIT`"));
+}
+
+#[test]
+fn fmt_rewrites_unformatted_files() {
+	let path = write_test_file("fmt.asm", "#SECTION .text\n_start   {\n  addi r1,r0,1\n}\n");
+	let fmt = |check: bool| {
+		let mut command = Command::new(env!("CARGO_BIN_EXE_wpi32"));
+		command.arg("fmt");
+		if check {
+			command.arg("--check");
+		}
+
+		command.arg(&path).output().unwrap()
+	};
+
+	let output = fmt(true);
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("is not formatted"));
+
+	assert!(fmt(false).status.success());
+	assert_eq!(
+		std::fs::read_to_string(&path).unwrap(),
+		"#SECTION .text\n_start {\n\taddi    r1, r0, 1\n}\n"
+	);
+	assert!(fmt(true).status.success());
+}
+
+#[test]
+fn symbols_lists_labels() {
+	let path = write_test_file(
+		"symbols.asm",
+		"#SECTION .text\n#EXTERN print\n_start {\n\taddi r1, r0, 1\n\tnested {\n\t\tjal r1, \
+		 print\n\t}\n}\n#SECTION .data\nmessage {\n\t#BYTES \"hi\"\n}\n",
+	);
+
+	let output =
+		Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("symbols").arg(&path).output().unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	assert!(output.status.success());
+	assert_eq!(
+		stdout,
+		"         U print\n00000000 t _start\n00000004 t _start.nested\n00000008 d message\n"
+	);
+
+	let path = write_test_file(
+		"symbols_options.asm",
+		"#SECTION .data\n#RES_BYTES SIZE\nmessage {\n\t#BYTES \"hi\"\n}\n",
+	);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["symbols", "--base-addr", "0x100", "-D", "SIZE=8"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	assert!(output.status.success());
+	assert_eq!(stdout, "00000108 d message\n");
+}
+
+#[test]
+fn asm_isa_rejects_disabled_extensions() {
+	let path = write_test_file("isa.asm", "#SECTION .text\nmul r1, r2, r3\n");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["asm", "--isa", "rv32i"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(!output.status.success());
+	assert!(
+		stderr
+			.contains("instruction `mul` requires the M extension, which is not enabled in rv32i")
+	);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["asm", "--isa", "rv32im"])
+		.arg(&path)
+		.output()
+		.unwrap();
+
+	assert!(output.status.success());
+	assert!(path.with_extension("wpibin").exists());
+}
+
+#[test]
+fn dump_ast_as_dot() {
+	let path = write_test_file("dump_ast.asm", "#SECTION .text\nebreak\n");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("dump-ast")
+		.arg("--dot")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	assert!(output.status.success());
+	assert!(stdout.starts_with("digraph ast {"));
+	assert!(stdout.contains("n0 [label=\"Root\"];"));
+	assert!(stdout.contains("n0 -> n1;"));
+}
+
+#[test]
+fn asm_warnings_as_errors() {
+	let path = write_test_file("warnings.asm", "#SECTION .text\njal r0, 0\naddi r1, r0, 1\n");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("asm").arg(&path).output().unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success());
+	assert!(stderr.contains("unreachable instruction"));
+
+	for args in [["--warnings-as-errors"].as_slice(), ["-W", "error"].as_slice()] {
+		let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+			.arg("asm")
+			.args(args)
+			.arg(&path)
+			.output()
+			.unwrap();
+		let stderr = String::from_utf8_lossy(&output.stderr);
+
+		assert!(!output.status.success());
+		assert!(stderr.contains("aborting due to 1 warning treated as an error"));
+	}
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["asm", "--warnings-as-errors", "--allow", "unreachable-code"])
+		.arg(&path)
+		.output()
+		.unwrap();
+
+	assert!(output.status.success());
+}
+
+#[test]
+fn sim_trace_shows_source_lines() {
+	let path = write_test_file(
+		"source_map.asm",
+		"#SECTION .text\naddi r10, r0, 3\n\naddi r17, r0, 93\n#WORDS 0x00000073\n",
+	);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["asm", "--source-map"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["sim", "--trace"])
+		.arg(path.with_extension("wpibin"))
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let trace: Vec<&str> = stdout.lines().collect();
+
+	assert_eq!(output.status.code(), Some(3));
+	assert_eq!(trace.len(), 3);
+	assert!(trace[0].starts_with("0x00000000: 00300513  addi r10, r0, 3"));
+	assert!(trace[0].contains(&format!("{}:2", path.display())));
+	assert!(trace[1].contains("0x00000004") && trace[1].contains(&format!("{}:4", path.display())));
+	assert!(trace[2].contains("0x00000008") && trace[2].contains("<no source>"));
+}
+
+#[test]
+fn sim_trace_shows_included_source_lines() {
+	let included = write_test_file(
+		"source_map_body.asm",
+		"addi r17, r0, 93
+ecall
+",
+	);
+	let path = write_test_file(
+		"source_map_include.asm",
+		"#SECTION .text
+addi r10, r0, 3
+#INCLUDE \"source_map_body.asm\"
+",
+	);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["asm", "--source-map"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["sim", "--trace"])
+		.arg(path.with_extension("wpibin"))
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let trace: Vec<&str> = stdout.lines().collect();
+
+	assert_eq!(output.status.code(), Some(3));
+	assert_eq!(trace.len(), 3);
+	assert!(trace[0].contains(&format!("{}:2", path.display())));
+	assert!(trace[1].contains(&format!("{}:1", included.display())));
+	assert!(trace[2].contains(&format!("{}:2", included.display())));
+}
+
+#[test]
+fn asm_writes_map_file() {
+	let path = write_test_file(
+		"map.asm",
+		"#SECTION .text\n_start {\n\taddi r1, r0, 1\n\tnested {\n\t\tebreak\n\t}\n}\n#SECTION \
+		 .data\nmessage {\n\t#BYTES \"hi\"\n}\nend {\n}\n",
+	);
+	let map_path = path.with_extension("map");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("asm")
+		.arg("--map")
+		.arg(&map_path)
+		.arg(&path)
+		.output()
+		.unwrap();
+
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	assert_eq!(
+		std::fs::read_to_string(&map_path).unwrap(),
+		"Sections:\n  0x00000000  0x00000008  .text\n  0x00000008  0x00000002  \
+		 .data\n\nSymbols:\n  0x00000000  0x00000008  _start\n  0x00000004  0x00000004  \
+		 _start.nested\n  0x00000008  0x00000002  message\n  0x0000000a  0x00000000  \
+		 end\n\nTotal: 10 bytes\n"
+	);
+}
+
+#[test]
+fn asm_keep_going_writes_partial_output() {
+	let path = write_test_file(
+		"keep_going.asm",
+		"#SECTION .text\ngood {\n\taddi r1, r0, 1\n\tebreak\n}\nbad {\n\tlui r2, 0x100000\n\tadd \
+		 r3 r1, r2\n}\n",
+	);
+	let output_path = path.with_extension("wpibin");
+	let _ = std::fs::remove_file(&output_path);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("asm")
+		.arg("--keep-going")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = strip_ansi(&String::from_utf8_lossy(&output.stderr));
+
+	assert!(!output.status.success());
+	assert!(stderr.contains("keep_going.asm:7:10"));
+	assert!(stderr.contains("keep_going.asm:8:6"));
+	assert!(stderr.contains("aborting due to 2 previous errors"));
+
+	// Both failed instructions are replaced by an `ebreak`
+	let bytes = std::fs::read(output_path).unwrap();
+	let image = asm::wpibin::Image::from_bytes(&bytes).unwrap();
+	let words: Vec<u32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+	assert_eq!(words, [0x0010_0093, 0x0010_0073, 0x0010_0073, 0x0010_0073]);
+}
+
+#[test]
+fn asm_dumps_encodings() {
+	let path = write_test_file("dump_encoding.asm", "#SECTION .text\nebreak\nadd r1, r2, r3\n");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("asm")
+		.arg("--dump-encoding")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	assert!(output.status.success());
+	assert!(stdout.contains(
+		"0x00000004: 0x003100b3  0b0000000_00011_00010_000_00001_0110011  (opcode=0b0110011, \
+		 rd=1, funct3=0b000, rs1=2, rs2=3, funct7=0b0000000)"
+	));
+
+	// The source map is only used to find the instructions, it is not written
+	let bytes = std::fs::read(path.with_extension("wpibin")).unwrap();
+	assert!(asm::wpibin::Image::from_bytes(&bytes).unwrap().source_map.is_empty());
+}
@@ -1,3 +1,45 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use asm::parse::ast::Root;
+use asm::parse::Node;
+
+/// Write some source code or binary contents to a file in the test scratch
+/// directory and return its path
+pub(super) fn write_test_file(name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+	let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+	std::fs::write(&path, contents).unwrap();
+
+	path
+}
+
+/// Remove all ANSI colour codes from rendered output
+pub(super) fn strip_ansi(repr: &str) -> String {
+	let mut stripped = String::new();
+	let mut chars = repr.chars();
+
+	while let Some(c) = chars.next() {
+		if c == '\x1b' {
+			chars.by_ref().find(|&c| c == 'm');
+		} else {
+			stripped.push(c);
+		}
+	}
+
+	stripped
+}
+
+/// Assert that two ASTs are structurally equal, ignoring source locations
+///
+/// Both ASTs are printed as a tree if they differ
+#[track_caller]
+pub(super) fn assert_ast_eq(left: &Root, right: &Root) {
+	if left != right {
+		panic!("ASTs are not equal\nleft:\n{}\nright:\n{}", Node::from(left), Node::from(right));
+	}
+}
+
 #[rustfmt::skip]
 pub(super) static TEST_SOURCE_CODE: &str = "\
 ; Test header comment
@@ -0,0 +1,700 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use asm::lex::{BranchInstruction, InstToken, RriInstruction};
+use asm::parse::ast::OrderingTarget;
+use asm::wpibin::{Endian, Image, Section, SectionFlags};
+use asm::AsmOptions;
+use sim::cpu::{Cpu, ExitReason, WatchTarget};
+use sim::decode::{decode, Instruction};
+use sim::error::Error;
+use sim::memory::Memory;
+use sim::repl::Repl;
+use sim::{Machine, SimOptions};
+
+mod common;
+use common::*;
+
+/// Encode an I-type instruction
+fn encode_i(opcode: u32, rd: u32, funct3: u32, rs1: u32, imm: i32) -> u32 {
+	((imm as u32) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+/// Encode an S-type instruction
+fn encode_s(opcode: u32, funct3: u32, rs1: u32, rs2: u32, imm: i32) -> u32 {
+	let imm = imm as u32;
+	((imm >> 5) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | ((imm & 0x1F) << 7) | opcode
+}
+
+fn addi(rd: u32, rs1: u32, imm: i32) -> u32 { encode_i(0b001_0011, rd, 0b000, rs1, imm) }
+
+fn sb(rs2: u32, rs1: u32, imm: i32) -> u32 { encode_s(0b010_0011, 0b000, rs1, rs2, imm) }
+
+fn lui(rd: u32, imm: u32) -> u32 { (imm << 12) | (rd << 7) | 0b011_0111 }
+
+const ECALL: u32 = 0x0000_0073;
+
+/// Make the bytes of a wpibin file containing the given instructions at
+/// address 0
+fn program_bytes(program: &[u32]) -> Vec<u8> {
+	let data = program.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+	let image = Image {
+		endian:      Endian::Little,
+		base:        0,
+		entry:       0,
+		sections:    vec![Section {
+			name: ".text".to_string(),
+			flags: SectionFlags::from_name(".text"),
+			addr: 0,
+			data,
+		}],
+		relocations: vec![],
+		symbols:     vec![],
+		source_map:  vec![],
+	};
+
+	image.to_bytes()
+}
+
+/// Write a wpibin file containing the given instructions at address 0
+fn write_program(name: &str, program: &[u32]) -> PathBuf {
+	write_test_file(name, program_bytes(program))
+}
+
+/// Assemble some source code into a wpibin file
+fn assemble_program(name: &str, source: &str) -> PathBuf {
+	let input_path = write_test_file(&format!("{}.asm", name), source);
+	let output_path = input_path.with_extension("wpibin");
+	asm::assemble(&input_path, &output_path, &Default::default()).unwrap();
+
+	output_path
+}
+
+/// A program storing "WPI!" at address 0x100 before exiting
+fn store_bytes_program() -> Vec<u32> {
+	let mut program = Vec::new();
+	for (i, byte) in b"WPI!".iter().enumerate() {
+		program.push(addi(5, 0, *byte as i32));
+		program.push(sb(5, 0, 0x100 + i as i32));
+	}
+	program.extend([addi(17, 0, 93), ECALL]);
+
+	program
+}
+
+#[test]
+fn dump_mem_prints_hexdump() {
+	let path = write_program("dump_mem.wpibin", &store_bytes_program());
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("sim")
+		.arg("--dump-mem")
+		.arg("0x100:0x14")
+		.arg("--dump-mem")
+		.arg("256:4")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	assert!(output.status.success());
+	assert_eq!(
+		stdout,
+		"00000100  57 50 49 21 00 00 00 00  00 00 00 00 00 00 00 00  |WPI!............|\n\
+		 00000110  00 00 00 00                                       |....|\n\
+		 00000100  57 50 49 21                                       |WPI!|\n"
+	);
+}
+
+#[test]
+fn dump_mem_skips_out_of_range_regions() {
+	let path = write_program("dump_mem_out_of_range.wpibin", &store_bytes_program());
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("sim")
+		.arg("--dump-mem")
+		.arg("0xFFFFFFF0:0x20")
+		.arg("--dump-mem")
+		.arg("0x100:4")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success());
+	assert!(stderr.contains("skipping memory dump of 0xfffffff0:0x20"));
+	assert_eq!(stdout, "00000100  57 50 49 21                                       |WPI!|\n");
+}
+
+#[test]
+fn dump_regs_prints_registers() {
+	// r5 = 0xE000 - 0x153 = 0xDEAD
+	let program = [lui(5, 0xE), addi(5, 5, -0x153), addi(17, 0, 93), ECALL];
+	let path = write_program("dump_regs.wpibin", &program);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("sim")
+		.arg("--dump-regs")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success());
+	assert!(stdout.is_empty());
+	assert!(stderr.contains("pc        = 0x0000000c\n"));
+	assert!(stderr.contains("r0/zero   = 0x00000000 (0)\n"));
+	assert!(stderr.contains("r5/t0     = 0x0000dead (57005)\n"));
+	assert!(stderr.contains("r17/a7    = 0x0000005d (93)\n"));
+	assert!(stderr.contains("r31/t6    = 0x00000000 (0)\n"));
+}
+
+#[test]
+fn set_reg_seeds_registers() {
+	let path = assemble_program("set_reg", "#SECTION .text\nadd r12, r10, r11\nebreak\n");
+	let options = SimOptions { set_regs: vec![(10, 7), (11, 8), (0, 1)], ..Default::default() };
+
+	let (machine, _) = sim::simulate(&path, &options).unwrap();
+
+	assert_eq!(machine.cpu.reg(0), 0);
+	assert_eq!(machine.cpu.reg(12), 15);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["sim", "--dump-regs", "--set-reg", "R10=0b111", "--set-reg", "a1=-8"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success());
+	assert!(stderr.contains("r12/a2    = 0xffffffff (-1)\n"));
+}
+
+#[test]
+fn cycle_counter_counts_instructions() {
+	let path = assemble_program(
+		"cycles",
+		"#SECTION .text\naddi r5, r0, 1\naddi r5, r5, 1\ncsrrs r1, r0, 0xC00\ncsrrs r2, r0, \
+		 0xC02\ncsrrw r0, r5, 0xC00\ncsrrs r3, r0, 0xC00\naddi r17, r0, 93\necall\n",
+	);
+
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+
+	assert_eq!(machine.cpu.reg(1), 2);
+	assert_eq!(machine.cpu.reg(2), 3);
+	// Writes to the counters are ignored
+	assert_eq!(machine.cpu.reg(3), 5);
+	assert_eq!(machine.cpu.cycles(), 8);
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("sim")
+		.arg("--print-cycles")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success());
+	assert!(stderr.contains("cycles: 8\n"));
+}
+
+#[test]
+fn entry_symbol_selects_routine() {
+	let path = assemble_program(
+		"entry_symbol",
+		"#SECTION .text\nfirst {\n\taddi r10, r0, 1\n\tjal r0, exit\n}\nsecond {\n\taddi r10, r0, \
+		 2\n}\nexit {\n\taddi r17, r0, 93\n\tecall\n}\n",
+	);
+
+	let (_, reason) = sim::simulate(&path, &Default::default()).unwrap();
+	assert_eq!(reason, ExitReason::Exit(1));
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("sim")
+		.arg("--entry")
+		.arg("second")
+		.arg("--dump-regs")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert_eq!(output.status.code(), Some(2));
+	assert!(stderr.contains("r10/a0    = 0x00000002 (2)\n"));
+
+	let options = SimOptions { entry_symbol: Some("missing".to_string()), ..Default::default() };
+	let err = sim::simulate(&path, &options).unwrap_err();
+
+	assert!(matches!(err, Error::UnknownSymbol(ref symbol) if symbol == "missing"));
+}
+
+#[test]
+fn trap_on_uninitialized_register() {
+	let path = assemble_program(
+		"uninit",
+		"#SECTION .text\naddi r6, r0, 1\naddi r6, r5, 1\naddi r17, r0, 93\necall\n",
+	);
+
+	// Without the trap uninitialized registers read as 0
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+	assert_eq!(machine.cpu.reg(6), 1);
+
+	let options = SimOptions { trap_uninit: true, ..Default::default() };
+	let err = sim::simulate(&path, &options).unwrap_err();
+	assert!(matches!(err, Error::UninitializedRegister { reg: 5, pc: 4 }));
+	assert_eq!(err.to_string(), "read of uninitialized register r5 (t0) at pc 0x00000004");
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["sim", "--trap-uninit"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	assert!(String::from_utf8_lossy(&output.stderr).contains("uninitialized register r5"));
+}
+
+#[test]
+fn fences_execute_as_no_ops() {
+	let path = assemble_program(
+		"fences",
+		"#SECTION .text\nfence RW, RW\nfence.tso RW, RW\nfence.i\naddi r5, r0, 42\naddi r17, r0, \
+		 93\necall\n",
+	);
+
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+
+	assert_eq!(machine.cpu.reg(5), 42);
+	assert_eq!(machine.cpu.pc, 0x14);
+}
+
+#[test]
+fn decode_distinguishes_fences() {
+	let rw = OrderingTarget::R | OrderingTarget::W;
+
+	assert_eq!(decode(0, 0x0330_000F).unwrap(), Instruction::Fence { pred: rw, succ: rw });
+	assert_eq!(decode(0, 0x8330_000F).unwrap(), Instruction::FenceTso);
+	assert_eq!(decode(0, 0x0000_100F).unwrap(), Instruction::Fencei);
+}
+
+#[test]
+fn lui_places_immediate_in_upper_bits() {
+	let path =
+		assemble_program("lui", "#SECTION .text\nlui r1, 0x12345\nlui r2, 0xFFFFF\nebreak\n");
+
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+
+	assert_eq!(machine.cpu.reg(1), 0x1234_5000);
+	assert_eq!(machine.cpu.reg(2), 0xFFFF_F000);
+}
+
+#[test]
+fn auipc_adds_immediate_to_pc() {
+	let path = assemble_program(
+		"auipc",
+		"#SECTION .text\naddi r0, r0, 0\naddi r0, r0, 0\nauipc r1, 0x10\nauipc r2, -1\nebreak\n",
+	);
+
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+
+	assert_eq!(machine.cpu.reg(1), 0x8 + (0x10 << 12));
+	assert_eq!(machine.cpu.reg(2), 0xC_u32.wrapping_sub(0x1000));
+}
+
+#[test]
+fn hi_lo_reconstruct_addresses() {
+	let path = assemble_program(
+		"hi_lo",
+		"#SECTION .text\nlui r1, %hi(0x12345678)\naddi r1, r1, %lo(0x12345678)\nlui r2, \
+		 %hi(0x12345878)\naddi r2, r2, %lo(0x12345878)\nlui r3, %HI(-2048)\naddi r3, r3, \
+		 %LO(-2048)\nlui r4, %hi(message)\naddi r4, r4, %lo(message) % 4096\nebreak\n#SECTION \
+		 .data\nmessage {\n\t#BYTES \"hi\"\n}\n",
+	);
+
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+
+	assert_eq!(machine.cpu.reg(1), 0x1234_5678);
+	assert_eq!(machine.cpu.reg(2), 0x1234_5878);
+	assert_eq!(machine.cpu.reg(3), -2048_i32 as u32);
+	assert_eq!(machine.cpu.reg(4), 0x24);
+}
+
+#[test]
+fn csr_addresses_ignore_upper_bits() {
+	let mut cpu = Cpu::new(0);
+
+	cpu.set_csr(0xF340, 42);
+
+	assert_eq!(cpu.csr(0x340), 42);
+	assert_eq!(cpu.csr(0xFFFF), 0);
+	assert_eq!(cpu.csr(0x1C00), cpu.csr(0xC00));
+}
+
+#[test]
+fn illegal_instruction_reports_location() {
+	// `slli` with a non-zero funct7
+	let word = 0x0200_1013;
+
+	let mut memory = Memory::new(0x100);
+	memory.write_u32(0x10, word).unwrap();
+	let mut cpu = Cpu::new(0x10);
+
+	let err = cpu.step(&mut memory).unwrap_err();
+
+	match err {
+		Error::IllegalInstruction { pc, word: found, detail } => {
+			assert_eq!(pc, 0x10);
+			assert_eq!(found, word);
+			assert_eq!(detail, "unknown OP-IMM funct3=0b001 funct7=0b0000001");
+		},
+		_ => panic!("expected an illegal instruction error, got {:?}", err),
+	}
+}
+
+#[test]
+fn exit_code_is_program_status() {
+	let program = [addi(10, 0, 42), addi(17, 0, 93), ECALL];
+	let path = write_program("exit_code.wpibin", &program);
+
+	let status = Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("sim").arg(&path).status().unwrap();
+
+	assert_eq!(status.code(), Some(42));
+}
+
+#[test]
+fn exit_code_reports_traps() {
+	let path = write_program("exit_code_trap.wpibin", &[0xFFFF_FFFF]);
+
+	let status = Command::new(env!("CARGO_BIN_EXE_wpi32")).arg("sim").arg(&path).status().unwrap();
+
+	assert_eq!(status.code(), Some(132));
+}
+
+#[test]
+fn repl_executes_lines() {
+	let mut repl = Repl::new();
+
+	assert_eq!(repl.eval("addi r1, r0, 5\n").unwrap(), "r1/ra     = 0x00000005 (5)\n");
+
+	let regs = repl.eval(".regs").unwrap();
+	assert!(regs.contains("pc        = 0x00000004\n"));
+	assert!(regs.contains("r1/ra     = 0x00000005 (5)\n"));
+
+	// Failed lines leave the state unchanged
+	assert!(matches!(repl.eval("addi r1, r0"), Err(Error::Assembler(_))));
+	assert!(matches!(repl.eval("#WORDS 5"), Err(Error::NotAnInstruction(_))));
+	assert_eq!(repl.machine.cpu.pc, 4);
+
+	assert_eq!(repl.eval("add r2, r1, r1").unwrap(), "r2/sp     = 0x0000000a (10)\n");
+
+	repl.eval(".reset").unwrap();
+	assert_eq!(repl.machine.cpu.pc, 0);
+	assert_eq!(repl.machine.cpu.reg(1), 0);
+}
+
+#[test]
+fn repl_sets_watchpoints() {
+	let mut repl = Repl::new();
+
+	repl.eval(".watch t0").unwrap();
+	repl.eval(".watch 0x102").unwrap();
+	assert!(matches!(repl.eval(".watch pc"), Err(Error::InvalidWatchTarget(_))));
+
+	let output = repl.eval("addi r5, r0, 3").unwrap();
+	assert!(output.contains("watched register r5 (t0) changed from 0x00000000 to 0x00000003"));
+	assert!(output.contains("addi r5, r0, 3"));
+
+	let output = repl.eval("sw [r0 + 0x100], r5").unwrap();
+	assert!(output.contains("watched address 0x00000102 changed from 0x00000000 to 0x00000003"));
+
+	// Writes elsewhere do not stop anything
+	assert_eq!(repl.eval("sw [r0 + 0x104], r5").unwrap(), "");
+}
+
+#[test]
+fn repl_reads_stdin() {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("repl")
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(b"addi r1, r0, 5\n.regs\n").unwrap();
+	let output = child.wait_with_output().unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+
+	assert!(output.status.success());
+	assert!(stdout.starts_with("r1/ra     = 0x00000005 (5)\npc        = 0x00000004\n"));
+}
+
+#[test]
+fn decode_is_total_and_round_trips() {
+	// xorshift32, so the words are the same on every run
+	let mut state: u32 = 0x2545_F491;
+	let mut next_word = || {
+		state ^= state << 13;
+		state ^= state >> 17;
+		state ^= state << 5;
+		state
+	};
+
+	let mut words = Vec::new();
+	let mut source = String::from("#SECTION .text\n");
+	for _ in 0..100_000 {
+		let word = next_word();
+
+		if let Ok(inst) = decode(0, word) {
+			words.push(word);
+			source.push_str(&inst.to_string());
+			source.push('\n');
+		}
+	}
+
+	let path = assemble_program("decode_round_trip", &source);
+	let image = Image::from_bytes(&std::fs::read(path).unwrap()).unwrap();
+	let encoded: Vec<u32> = image.sections[0]
+		.data
+		.chunks(4)
+		.map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+		.collect();
+
+	assert!(!words.is_empty());
+	for (word, reencoded) in words.iter().zip(&encoded) {
+		assert_eq!(word, reencoded, "{:#010x} re-encoded as {:#010x}", word, reencoded);
+	}
+	assert_eq!(words.len(), encoded.len());
+}
+
+#[test]
+fn simulate_bytes_runs_in_memory() {
+	let bytes = program_bytes(&[addi(10, 0, 7), addi(17, 0, 93), ECALL]);
+
+	let (machine, reason) = sim::simulate_bytes(&bytes, &Default::default()).unwrap();
+
+	assert_eq!(reason, ExitReason::Exit(7));
+	assert_eq!(machine.cpu.pc, 8);
+	assert_eq!(machine.memory.size(), sim::DEFAULT_MEMORY_SIZE);
+}
+
+#[test]
+fn simulate_bytes_applies_options() {
+	// Starting at the second instruction skips setting the exit status
+	let bytes = program_bytes(&[addi(10, 0, 7), addi(17, 0, 93), ECALL]);
+	let options = SimOptions { memory_size: 0x100, entry: Some(4), ..Default::default() };
+
+	let (machine, reason) = sim::simulate_bytes(&bytes, &options).unwrap();
+
+	assert_eq!(reason, ExitReason::Exit(0));
+	assert_eq!(machine.cpu.reg(2), 0x100);
+
+	// `jal r0, 0` loops forever
+	let bytes = program_bytes(&[0x0000_006F]);
+	let options = SimOptions { max_steps: Some(1000), trace: true, ..Default::default() };
+
+	let err = sim::simulate_bytes(&bytes, &options).unwrap_err();
+	assert!(matches!(err, Error::StepLimitReached { steps: 1000 }));
+}
+
+#[test]
+fn errors_box_and_expose_io_source() {
+	use std::error::Error as StdError;
+
+	let io = || std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+	let is_io = |err: &dyn StdError| {
+		err.source().and_then(|e| e.downcast_ref::<std::io::Error>()).is_some()
+	};
+
+	let lex_err = match asm::lex::Lexer::new("test_file.asm", "@\n").next().unwrap() {
+		Err(asm::error::Error::Lex(err)) => err,
+		res => panic!("expected a lex error, found {:?}", res),
+	};
+	let boxed: Vec<Box<dyn StdError>> = vec![
+		Box::new(asm::error::Error::from(io())),
+		Box::new(Error::from(io())),
+		Box::new(Error::Assembler(asm::error::Error::from(io()))),
+		Box::new(lex_err),
+		Box::new(asm::wpibin::FormatError::InvalidMagic),
+		Box::new(Error::EmptyImage),
+	];
+
+	let sources: Vec<bool> = boxed.iter().map(|err| is_io(err.as_ref())).collect();
+	assert_eq!(sources, [true, true, true, false, false, false]);
+	assert_eq!(boxed[0].to_string(), "missing");
+}
+
+#[test]
+fn register_watchpoints_stop_on_change() {
+	// Increment r5 forever, `jal r0, -4` jumps back to the `addi`
+	let bytes = program_bytes(&[addi(5, 5, 1), 0xFFDF_F06F]);
+	let options = SimOptions { watch_regs: vec![5], max_steps: Some(100), ..Default::default() };
+
+	let (machine, reason) = sim::simulate_bytes(&bytes, &options).unwrap();
+
+	let ExitReason::Watchpoint(watchpoint) = reason else { panic!("{:?}", reason) };
+	assert_eq!(watchpoint.target, WatchTarget::Reg(5));
+	assert_eq!((watchpoint.old, watchpoint.new, watchpoint.pc), (0, 1, 0));
+	assert!(matches!(watchpoint.inst, Instruction::Rri { rd: 5, rs1: 5, imm: 1, .. }));
+	assert_eq!(machine.cpu.pc, 4);
+
+	let path = write_program("watch_reg.wpibin", &[addi(5, 5, 1), 0xFFDF_F06F]);
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.arg("sim")
+		.arg("--watch-reg")
+		.arg("T0")
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(output.status.success());
+	assert!(stderr.starts_with(
+		"watched register r5 (t0) changed from 0x00000000 to 0x00000001 at pc 0x00000000"
+	));
+}
+
+#[test]
+fn memory_watchpoints_stop_on_store() {
+	let bytes = program_bytes(&store_bytes_program());
+	let options = SimOptions { watch_mem: vec![0x102], ..Default::default() };
+
+	let (machine, reason) = sim::simulate_bytes(&bytes, &options).unwrap();
+
+	let ExitReason::Watchpoint(watchpoint) = reason else { panic!("{:?}", reason) };
+	assert_eq!(watchpoint.target, WatchTarget::Mem(0x102));
+	assert_eq!((watchpoint.old, watchpoint.new, watchpoint.pc), (0, b'I' as u32, 20));
+	assert_eq!(machine.memory.read(0x100, 4).unwrap(), b"WPI\0");
+}
+
+#[test]
+fn sections_load_at_their_addresses() {
+	let text = [addi(10, 0, 7), addi(17, 0, 93), ECALL];
+	let image = Image {
+		endian:      Endian::Little,
+		base:        0x1000,
+		entry:       0x1000,
+		sections:    vec![
+			Section {
+				name:  ".text".to_string(),
+				flags: SectionFlags::from_name(".text"),
+				addr:  0x1000,
+				data:  text.iter().flat_map(|inst| inst.to_le_bytes()).collect(),
+			},
+			Section {
+				name:  ".data".to_string(),
+				flags: SectionFlags::from_name(".data"),
+				addr:  0x2000,
+				data:  b"data".to_vec(),
+			},
+		],
+		relocations: vec![],
+		symbols:     vec![],
+		source_map:  vec![],
+	};
+
+	let (machine, reason) = sim::simulate_bytes(&image.to_bytes(), &Default::default()).unwrap();
+	assert_eq!(reason, ExitReason::Exit(7));
+	assert_eq!(machine.memory.read_u32(0x1000).unwrap(), text[0]);
+	assert_eq!(machine.memory.read(0x2000, 4).unwrap(), b"data");
+	assert!(machine.memory.read(0, 0x1000).unwrap().iter().all(|&b| b == 0));
+
+	// Memory grows to fit the highest section and a stack
+	let options = SimOptions { memory_size: 0x1800, ..Default::default() };
+	let (machine, _) = sim::simulate_bytes(&image.to_bytes(), &options).unwrap();
+	assert_eq!(machine.memory.size(), 0x2010 + sim::STACK_SIZE);
+	assert_eq!(machine.memory.read(0x2000, 4).unwrap(), b"data");
+	assert_eq!(machine.cpu.reg(2), machine.memory.size() as u32);
+
+	// Images far from address 0 get a memory starting at their lowest section
+	let mut high = image.clone();
+	high.entry = 0x8000_1000;
+	high.sections[0].addr = 0x8000_1000;
+	high.sections[1].addr = 0x8000_2000;
+	let (machine, _) = sim::simulate_bytes(&high.to_bytes(), &options).unwrap();
+	assert_eq!(machine.memory.base(), 0x8000_1000);
+	assert_eq!(machine.memory.size(), 0x1800);
+	assert_eq!(machine.memory.read(0x8000_2000, 4).unwrap(), b"data");
+	assert!(matches!(machine.memory.read(0x8000_0FFC, 4), Err(Error::OutOfBounds { .. })));
+	assert_eq!(machine.cpu.reg(2), 0x8000_2800);
+
+	high.sections[1].addr = 0xFFFF_FF00;
+	let err = sim::simulate_bytes(&high.to_bytes(), &options).unwrap_err();
+	assert!(matches!(err, Error::AddressSpaceExceeded { end: 0x1_0000_FF10 }));
+
+	let mut overlapping = image;
+	overlapping.sections[1].addr = 0x1008;
+	let err = sim::simulate_bytes(&overlapping.to_bytes(), &Default::default()).unwrap_err();
+	assert!(matches!(err, Error::OverlappingSections { addr: 0x1008, .. }));
+	assert_eq!(err.to_string(), "section .data overlaps section .text at 0x00001008");
+}
+
+#[test]
+fn uninit_sections_are_zeroed() {
+	let mut image = Image::from_bytes(&program_bytes(&[addi(17, 0, 93), ECALL])).unwrap();
+	image.sections.push(Section {
+		name:  ".bss".to_string(),
+		flags: SectionFlags::from_name(".bss"),
+		addr:  0x100,
+		data:  vec![0xAA; 8],
+	});
+
+	let machine = Machine::load(&image, &Default::default()).unwrap();
+	assert_eq!(machine.memory.read(0x100, 8).unwrap(), [0; 8]);
+
+	// The contents of uninitialized sections are not stored in the file
+	let bytes = image.to_bytes();
+	assert_eq!(bytes.len(), program_bytes(&[addi(17, 0, 93), ECALL]).len() + 1 + 4 + 1 + 4 + 4);
+	assert_eq!(Image::from_bytes(&bytes).unwrap().sections[1].data, [0; 8]);
+}
+
+#[test]
+fn profile_counts_executed_instructions() {
+	let path = assemble_program(
+		"profile",
+		"#SECTION .text\naddi r5, r0, 10\nloop {\n\taddi r5, r5, -1\n\tbne r5, r0, loop\n}\naddi \
+		 r17, r0, 93\necall\n",
+	);
+
+	let options = SimOptions { profile: true, ..Default::default() };
+	let (machine, _) = sim::simulate(&path, &options).unwrap();
+
+	assert_eq!(
+		machine.cpu.profile().unwrap(),
+		vec![
+			(InstToken::Rri(RriInstruction::Addi), 12),
+			(InstToken::Branch(BranchInstruction::Bne), 10),
+			(InstToken::Ecall, 1),
+		]
+	);
+
+	let (machine, _) = sim::simulate(&path, &Default::default()).unwrap();
+	assert!(machine.cpu.profile().is_none());
+
+	let output = Command::new(env!("CARGO_BIN_EXE_wpi32"))
+		.args(["sim", "--profile"])
+		.arg(&path)
+		.output()
+		.unwrap();
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	assert!(stderr.starts_with("addi           12  ########################################\nbne"));
+}
+
+#[test]
+fn big_endian_loads_and_stores() {
+	let input_path = write_test_file(
+		"big_endian.asm",
+		"#SECTION .text\nlw r1, [r0 + value]\nlb r2, [r0 + value]\nsh [r0 + value], r1\nlbu r3, \
+		 [r0 + value + 1]\nebreak\n#SECTION .data\nvalue {\n\t#WORDS 0x01020304\n}\n",
+	);
+	let output_path = input_path.with_extension("wpibin");
+	let options = AsmOptions { endian: Endian::Big, ..Default::default() };
+	asm::assemble(&input_path, &output_path, &options).unwrap();
+
+	let (machine, _) = sim::simulate(&output_path, &Default::default()).unwrap();
+
+	assert_eq!(machine.cpu.reg(1), 0x0102_0304);
+	assert_eq!(machine.cpu.reg(2), 0x01);
+	assert_eq!(machine.cpu.reg(3), 0x04);
+}